@@ -0,0 +1,47 @@
+//! Times controller updates over synthetic rigs of growing size.
+//! Run with `cargo bench --features bench`.
+
+use bevy::prelude::*;
+use bevy_animator_controller::prelude::*;
+use std::{
+    hint::black_box,
+    time::{Duration, Instant},
+};
+
+/// Frames simulated per case, after one frame of warm up
+const FRAMES: u32 = 120;
+
+fn bench(name: &str, animators: SyntheticAnimators) {
+    let mut controllers = animators.controllers().expect("synthetic rig");
+    let mut time = Time::<()>::default();
+    time.advance_by(Duration::from_secs_f64(1.0 / 60.0));
+    for controller in &mut controllers {
+        controller.update(&time).expect("update");
+    }
+
+    let start = Instant::now();
+    for _ in 0..FRAMES {
+        time.advance_by(Duration::from_secs_f64(1.0 / 60.0));
+        for controller in &mut controllers {
+            controller.update(black_box(&time)).expect("update");
+        }
+        black_box(&controllers);
+    }
+    let frame = start.elapsed() / FRAMES;
+    let per_controller = frame / animators.count.max(1) as u32;
+    println!("{name:<40} {frame:>12.2?}/frame {per_controller:>10.2?}/controller");
+}
+
+fn main() {
+    bench("100 x 60 joints", SyntheticAnimators::new(100, 60));
+    bench("100 x 200 joints", SyntheticAnimators::new(100, 200));
+    bench("500 x 60 joints", SyntheticAnimators::new(500, 60));
+    bench(
+        "100 x 60 joints, 4 clips, 2 layers",
+        SyntheticAnimators::new(100, 60).with_clips(4).with_layers(2),
+    );
+    bench(
+        "100 x 60 joints, no crossfades",
+        SyntheticAnimators::new(100, 60).with_clips(1),
+    );
+}
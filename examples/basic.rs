@@ -23,7 +23,7 @@ pub struct PlayerAnimationAssets {
 
 fn main() {
     App::new()
-        .add_plugins((DefaultPlugins, OzzAnimationPlugin))
+        .add_plugins((DefaultPlugins, OzzAnimationPlugin::default()))
         .init_state::<GameState>()
         .add_loading_state(
             LoadingState::new(GameState::Loading)
@@ -47,9 +47,11 @@ fn setup_scene(
         SceneRoot(player_animations.player_mesh.clone()),
     ));
 
-    let player_anim_controller = build_player_animation_controller(player_animations, ozz_assets);
-    if let Some(controller) = player_anim_controller {
-        player.insert(controller);
+    match build_player_animation_controller(player_animations, ozz_assets) {
+        Ok(controller) => {
+            player.insert(controller);
+        }
+        Err(err) => error!("Failed to build animator controller: {err}"),
     }
 
     commands.spawn((
@@ -75,39 +77,24 @@ fn setup_scene(
 pub(crate) fn build_player_animation_controller(
     player_animations: Res<PlayerAnimationAssets>,
     mut ozz_assets: ResMut<Assets<OzzAsset>>,
-) -> Option<AnimatorController> {
-    let skeleton = ozz_assets.get_mut(&player_animations.skeleton)?;
-    let Ok(skeleton) = Skeleton::from_archive(&mut skeleton.archive) else {
-        return None;
-    };
-    let skeleton = Arc::new(skeleton);
+) -> Result<AnimatorController, Box<dyn std::error::Error>> {
+    let skeleton = ozz_assets
+        .get_mut(&player_animations.skeleton)
+        .ok_or("Skeleton asset is not loaded")?;
+    let skeleton = Arc::new(Skeleton::from_archive(&mut skeleton.archive)?);
 
-    let idle_anim = ozz_assets.get_mut(&player_animations.player_idle)?;
-    let Ok(idle_anim) = Animation::from_archive(&mut idle_anim.archive) else {
-        return None;
-    };
-    let idle_anim = Arc::new(idle_anim);
-
-    let mut animation_layer = AnimationLayer::new(
-        "Base Layer".to_string(),
-        LayerBlendType::Override,
-        1.0,
-        &skeleton,
-        "greatsword_idle".to_string(),
-    );
+    let idle_anim = ozz_assets
+        .get_mut(&player_animations.player_idle)
+        .ok_or("Idle animation asset is not loaded")?;
+    let idle_anim = Arc::new(Animation::from_archive(&mut idle_anim.archive)?);
 
     let idle_state = SimpleState::new(idle_anim, skeleton.num_soa_joints());
-    animation_layer.add_state(
-        "greatsword_idle".to_string(),
-        AnimationState::Simple(idle_state),
-    );
-
-    let mut parameters = Parameters::new();
-    parameters.set_float("speed", 0.0);
 
-    Some(AnimatorController::new(
-        skeleton.clone(),
-        vec![animation_layer],
-        parameters,
-    ))
+    Ok(AnimatorControllerBuilder::new(skeleton)
+        .with_parameter("speed", 0.0)
+        .with_layer(
+            LayerBuilder::new("Base Layer")
+                .with_state(StateBuilder::new("greatsword_idle", idle_state)),
+        )
+        .build()?)
 }
@@ -1,7 +1,4 @@
-use bevy::{
-    prelude::*,
-    render::{mesh::PrimitiveTopology, render_asset::RenderAssetUsages},
-};
+use bevy::prelude::*;
 use bevy_animator_controller::{OzzAnimationPlugin, prelude::*};
 use bevy_asset_loader::prelude::*;
 use ozz_animation_rs::*;
@@ -28,7 +25,7 @@ pub struct PlayerAnimationAssets {
 
 fn main() {
     App::new()
-        .add_plugins((DefaultPlugins, OzzAnimationPlugin))
+        .add_plugins((DefaultPlugins, OzzAnimationPlugin::default()))
         .init_state::<GameState>()
         .add_loading_state(
             LoadingState::new(GameState::Loading)
@@ -63,8 +60,11 @@ fn setup_scene(
         SceneRoot(player_animations.player_mesh.clone()),
     ));
 
-    if let Some(controller) = build_blend_tree_controller(&player_animations, ozz_assets) {
-        player.insert(controller);
+    match build_blend_tree_controller(&player_animations, ozz_assets) {
+        Ok(controller) => {
+            player.insert(controller);
+        }
+        Err(err) => error!("Failed to build animator controller: {err}"),
     }
 
     commands.spawn((
@@ -97,24 +97,21 @@ fn setup_scene(
 fn build_blend_tree_controller(
     assets: &PlayerAnimationAssets,
     mut ozz_assets: ResMut<Assets<OzzAsset>>,
-) -> Option<AnimatorController> {
-    let skeleton = ozz_assets.get_mut(&assets.skeleton)?;
-    let Ok(skeleton) = Skeleton::from_archive(&mut skeleton.archive) else {
-        return None;
-    };
-    let skeleton = Arc::new(skeleton);
-
-    let idle_anim = ozz_assets.get_mut(&assets.idle)?;
-    let Ok(idle_anim) = Animation::from_archive(&mut idle_anim.archive) else {
-        return None;
-    };
-    let idle_anim = Arc::new(idle_anim);
-
-    let run_anim = ozz_assets.get_mut(&assets.run)?;
-    let Ok(run_anim) = Animation::from_archive(&mut run_anim.archive) else {
-        return None;
-    };
-    let run_anim = Arc::new(run_anim);
+) -> Result<AnimatorController, Box<dyn std::error::Error>> {
+    let skeleton = ozz_assets
+        .get_mut(&assets.skeleton)
+        .ok_or("Skeleton asset is not loaded")?;
+    let skeleton = Arc::new(Skeleton::from_archive(&mut skeleton.archive)?);
+
+    let idle_anim = ozz_assets
+        .get_mut(&assets.idle)
+        .ok_or("Idle animation asset is not loaded")?;
+    let idle_anim = Arc::new(Animation::from_archive(&mut idle_anim.archive)?);
+
+    let run_anim = ozz_assets
+        .get_mut(&assets.run)
+        .ok_or("Run animation asset is not loaded")?;
+    let run_anim = Arc::new(Animation::from_archive(&mut run_anim.archive)?);
 
     // Create Blend Tree
     let idle_state = SimpleState::new(idle_anim.clone(), skeleton.num_soa_joints());
@@ -138,25 +135,11 @@ fn build_blend_tree_controller(
     );
 
     // Create Layer containing the Blend Tree
-    let mut animation_layer = AnimationLayer::new(
-        "Locomotion".to_string(),
-        LayerBlendType::Override,
-        1.0,
-        &skeleton,
-        "blend_tree".to_string(),
-    );
-
-    animation_layer.add_state(
-        "blend_tree".to_string(),
-        AnimationState::Blend(BlendState::new(blend_tree)),
-    );
-
-    let mut parameters = Parameters::new();
-    parameters.set_float("speed", 0.0);
-
-    Some(AnimatorController::new(
-        skeleton.clone(),
-        vec![animation_layer],
-        parameters,
-    ))
+    Ok(AnimatorControllerBuilder::new(skeleton)
+        .with_parameter("speed", 0.0)
+        .with_layer(
+            LayerBuilder::new("Locomotion")
+                .with_state(StateBuilder::new("blend_tree", BlendState::new(blend_tree))),
+        )
+        .build()?)
 }
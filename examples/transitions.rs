@@ -1,11 +1,8 @@
-use bevy::{
-    prelude::*,
-    render::{mesh::PrimitiveTopology, render_asset::RenderAssetUsages},
-};
+use bevy::prelude::*;
 use bevy_animator_controller::{OzzAnimationPlugin, prelude::*};
 use bevy_asset_loader::prelude::*;
 use ozz_animation_rs::*;
-use std::sync::{Arc, RwLock};
+use std::sync::Arc;
 
 #[derive(States, Default, Clone, Eq, PartialEq, Debug, Hash)]
 pub enum GameState {
@@ -28,7 +25,7 @@ pub struct PlayerAnimationAssets {
 
 fn main() {
     App::new()
-        .add_plugins((DefaultPlugins, OzzAnimationPlugin))
+        .add_plugins((DefaultPlugins, OzzAnimationPlugin::default()))
         .init_state::<GameState>()
         .add_loading_state(
             LoadingState::new(GameState::Loading)
@@ -63,8 +60,11 @@ fn setup_scene(
         SceneRoot(player_animations.player_mesh.clone()),
     ));
 
-    if let Some(controller) = build_transition_controller(&player_animations, ozz_assets) {
-        player.insert(controller);
+    match build_transition_controller(&player_animations, ozz_assets) {
+        Ok(controller) => {
+            player.insert(controller);
+        }
+        Err(err) => error!("Failed to build animator controller: {err}"),
     }
 
     commands.spawn((
@@ -97,76 +97,51 @@ fn setup_scene(
 fn build_transition_controller(
     assets: &PlayerAnimationAssets,
     mut ozz_assets: ResMut<Assets<OzzAsset>>,
-) -> Option<AnimatorController> {
-    let skeleton = ozz_assets.get_mut(&assets.skeleton)?;
-    let Ok(skeleton) = Skeleton::from_archive(&mut skeleton.archive) else {
-        return None;
-    };
-    let skeleton = Arc::new(skeleton);
-
-    let idle_anim = ozz_assets.get_mut(&assets.idle)?;
-    let Ok(idle_anim) = Animation::from_archive(&mut idle_anim.archive) else {
-        return None;
-    };
-    let idle_anim = Arc::new(idle_anim);
-
-    let run_anim = ozz_assets.get_mut(&assets.run)?;
-    let Ok(run_anim) = Animation::from_archive(&mut run_anim.archive) else {
-        return None;
-    };
-    let run_anim = Arc::new(run_anim);
-
-    // Create Layer
-    let mut animation_layer = AnimationLayer::new(
-        "Base Layer".to_string(),
-        LayerBlendType::Override,
-        1.0,
-        &skeleton,
-        "Idle".to_string(),
-    );
-
-    // Add States
-    let idle_state = SimpleState::new(idle_anim.clone(), skeleton.num_soa_joints());
-    animation_layer.add_state(
-        "Idle".to_string(),
-        AnimationState::Simple(idle_state),
-    );
-
-    let run_state = SimpleState::new(run_anim.clone(), skeleton.num_soa_joints());
-    animation_layer.add_state(
-        "Run".to_string(),
-        AnimationState::Simple(run_state),
-    );
-
-    // Add Transitions
-    animation_layer.add_transition(
-        "Idle".to_string(),
-        Transition {
-            to_state: "Run".to_string(),
-            duration: 0.5,
-            conditions: vec![TransitionCondition::Bool("is_running".to_string(), true)],
-            has_exit_time: false,
-            exit_time: 0.0,
-        },
-    );
-
-    animation_layer.add_transition(
-        "Run".to_string(),
-        Transition {
-            to_state: "Idle".to_string(),
-            duration: 0.5,
-            conditions: vec![TransitionCondition::Bool("is_running".to_string(), false)],
-            has_exit_time: false,
-            exit_time: 0.0,
-        },
-    );
-
-    let mut parameters = Parameters::new();
-    parameters.set_bool("is_running", false);
-
-    Some(AnimatorController::new(
-        skeleton.clone(),
-        vec![animation_layer],
-        parameters,
-    ))
+) -> Result<AnimatorController, Box<dyn std::error::Error>> {
+    let skeleton = ozz_assets
+        .get_mut(&assets.skeleton)
+        .ok_or("Skeleton asset is not loaded")?;
+    let skeleton = Arc::new(Skeleton::from_archive(&mut skeleton.archive)?);
+
+    let idle_anim = ozz_assets
+        .get_mut(&assets.idle)
+        .ok_or("Idle animation asset is not loaded")?;
+    let idle_anim = Arc::new(Animation::from_archive(&mut idle_anim.archive)?);
+
+    let run_anim = ozz_assets
+        .get_mut(&assets.run)
+        .ok_or("Run animation asset is not loaded")?;
+    let run_anim = Arc::new(Animation::from_archive(&mut run_anim.archive)?);
+
+    // States with the transitions leaving them
+    let idle_state = StateBuilder::new(
+        "Idle",
+        SimpleState::new(idle_anim, skeleton.num_soa_joints()),
+    )
+    .with_transition(Transition {
+        to_state: "Run".to_string(),
+        duration: 0.5,
+        conditions: vec![TransitionCondition::Bool("is_running".to_string(), true)],
+        ..default()
+    });
+
+    let run_state = StateBuilder::new(
+        "Run",
+        SimpleState::new(run_anim, skeleton.num_soa_joints()),
+    )
+    .with_transition(Transition {
+        to_state: "Idle".to_string(),
+        duration: 0.5,
+        conditions: vec![TransitionCondition::Bool("is_running".to_string(), false)],
+        ..default()
+    });
+
+    Ok(AnimatorControllerBuilder::new(skeleton)
+        .with_parameter("is_running", false)
+        .with_layer(
+            LayerBuilder::new("Base Layer")
+                .with_state(idle_state)
+                .with_state(run_state),
+        )
+        .build()?)
 }
@@ -0,0 +1,112 @@
+use super::{AnimatorController, LayerBlendType};
+
+/// How a one-off additive clip like weapon recoil is played, see
+/// [`AnimatorController::fire_additive`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AdditiveShot {
+    /// Seconds to reach full weight
+    pub blend_in: f32,
+    /// Seconds to fade back out before the shot ends
+    pub blend_out: f32,
+    /// Length of the shot in seconds, None plays the layer's current clip once
+    pub length: Option<f32>,
+    /// Layer weight at the peak of the shot
+    pub weight: f32,
+}
+
+impl Default for AdditiveShot {
+    fn default() -> Self {
+        Self {
+            blend_in: 0.0,
+            blend_out: 0.15,
+            length: None,
+            weight: 1.0,
+        }
+    }
+}
+
+/// An additive shot in progress
+#[derive(Debug, Clone)]
+pub(crate) struct ActiveAdditiveShot {
+    layer: usize,
+    shot: AdditiveShot,
+    length: f32,
+    elapsed: f32,
+}
+
+impl ActiveAdditiveShot {
+    /// Layer weight at the current point of the shot
+    #[inline]
+    fn weight(&self) -> f32 {
+        let fade_in = if self.shot.blend_in > 0.0 {
+            self.elapsed / self.shot.blend_in
+        } else {
+            1.0
+        };
+        let fade_out = if self.shot.blend_out > 0.0 {
+            (self.length - self.elapsed) / self.shot.blend_out
+        } else {
+            1.0
+        };
+        fade_in.min(fade_out).clamp(0.0, 1.0) * self.shot.weight
+    }
+}
+
+impl AnimatorController {
+    /// Play an additive layer's current clip once from the start, blending it
+    /// out automatically. Firing again while it plays restarts it. Returns false
+    /// if there is no additive layer with that name.
+    /// ## Example
+    /// ```ignore
+    /// if mouse.just_pressed(MouseButton::Left) {
+    ///     controller.fire_additive("recoil");
+    /// }
+    /// ```
+    #[inline]
+    pub fn fire_additive(&mut self, layer: &str) -> bool {
+        self.fire_additive_with(layer, AdditiveShot::default())
+    }
+
+    /// Like [`fire_additive`](Self::fire_additive) with custom blend times
+    pub fn fire_additive_with(&mut self, layer: &str, shot: AdditiveShot) -> bool {
+        let Some(index) = self
+            .layers
+            .iter()
+            .position(|l| l.name == layer && l.layer_blend_type == LayerBlendType::Additive)
+        else {
+            return false;
+        };
+
+        let layer = &mut self.layers[index];
+        layer.seek(0.0);
+        let length = shot
+            .length
+            .or_else(|| layer.current_state_duration())
+            .unwrap_or(shot.blend_in + shot.blend_out);
+
+        let active = ActiveAdditiveShot {
+            layer: index,
+            shot,
+            length,
+            elapsed: 0.0,
+        };
+        layer.set_weight(active.weight());
+        self.additive_shots.retain(|a| a.layer != index);
+        self.additive_shots.push(active);
+        true
+    }
+
+    /// Advance every additive shot, driving the weight of its layer
+    pub(crate) fn update_additive_shots(&mut self, delta_secs: f32) {
+        let layers = &mut self.layers;
+        self.additive_shots.retain_mut(|active| {
+            active.elapsed += delta_secs;
+            let Some(layer) = layers.get_mut(active.layer) else {
+                return false;
+            };
+            let finished = active.elapsed >= active.length;
+            layer.set_weight(if finished { 0.0 } else { active.weight() });
+            !finished
+        });
+    }
+}
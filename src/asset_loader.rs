@@ -6,14 +6,66 @@ use bevy::{
     },
     prelude::*,
 };
-use ozz_animation_rs::{Archive, OzzError};
+use ozz_animation_rs::{Animation, Archive, OzzError, Skeleton};
 use std::io::Cursor;
+use std::sync::Arc;
 use thiserror::Error;
 
 /// An asset representing a loaded Ozz animation file
 #[derive(TypePath, Asset)]
 pub struct OzzAsset {
     pub archive: Archive<Cursor<Vec<u8>>>,
+    /// Metadata of the clip, if the file holds an animation
+    clip_metadata: Option<ClipMetadata>,
+    /// The animation, if the file holds one
+    animation: Option<Arc<Animation>>,
+    /// The skeleton, if the file holds one
+    skeleton: Option<Arc<Skeleton>>,
+}
+
+impl OzzAsset {
+    /// Duration, track count and name of the clip without building a sampling
+    /// job, or None if the file doesn't hold an animation
+    #[inline]
+    pub fn clip_metadata(&self) -> Option<&ClipMetadata> {
+        self.clip_metadata.as_ref()
+    }
+
+    /// The animation held by the file, parsed once when it loaded so it can be
+    /// shared without reading the archive again
+    #[inline]
+    pub fn animation(&self) -> Option<&Arc<Animation>> {
+        self.animation.as_ref()
+    }
+
+    /// The skeleton held by the file, parsed once when it loaded
+    #[inline]
+    pub fn skeleton(&self) -> Option<&Arc<Skeleton>> {
+        self.skeleton.as_ref()
+    }
+}
+
+/// Information about an animation clip
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClipMetadata {
+    /// Name of the clip, if it was exported with one
+    pub name: Option<String>,
+    /// Duration in seconds
+    pub duration: f32,
+    /// Number of joint tracks
+    pub num_tracks: usize,
+}
+
+impl ClipMetadata {
+    #[inline]
+    pub fn from_animation(animation: &Animation) -> Self {
+        let name = animation.name();
+        Self {
+            name: (!name.is_empty()).then(|| name.to_string()),
+            duration: animation.duration(),
+            num_tracks: animation.num_tracks(),
+        }
+    }
 }
 
 /// Plugin to load Ozz animation files
@@ -69,8 +121,28 @@ impl AssetLoader for OzzAssetLoader {
     ) -> Result<Self::Asset, Self::Error> {
         let mut bytes = Vec::new();
         reader.read_to_end(&mut bytes).await?;
+
+        // Skeletons and other archives simply have no clip metadata
+        let animation = Archive::from_vec(bytes.clone())
+            .and_then(|mut archive| Animation::from_archive(&mut archive))
+            .ok()
+            .map(Arc::new);
+        let clip_metadata = animation.as_deref().map(ClipMetadata::from_animation);
+        let skeleton = match animation {
+            Some(_) => None,
+            None => Archive::from_vec(bytes.clone())
+                .and_then(|mut archive| Skeleton::from_archive(&mut archive))
+                .ok()
+                .map(Arc::new),
+        };
+
         let archive = Archive::from_vec(bytes)?;
-        Ok(OzzAsset { archive })
+        Ok(OzzAsset {
+            archive,
+            clip_metadata,
+            animation,
+            skeleton,
+        })
     }
 
     fn extensions(&self) -> &[&str] {
@@ -0,0 +1,209 @@
+use super::budget::advance_controller;
+use super::{
+    AnimationPriorities, AnimationPriority, AnimatorClock, AnimatorController, AnimatorError,
+    AnimatorEventWriters, AnimatorStepLimits, Parameters, PrioritySettings, ScaledTime,
+};
+use bevy::ecs::component::HookContext;
+use bevy::ecs::world::DeferredWorld;
+use bevy::prelude::*;
+use bevy::tasks::{AsyncComputeTaskPool, Task, block_on};
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Updates the `AnimatorController` of its entity as a task on the
+/// `AsyncComputeTaskPool`, for characters with so many joints that sampling
+/// and blending them would push the main schedule over budget. The task starts
+/// in `Last` with the parameters gameplay set this frame and is finished in
+/// `PreUpdate` of the next one, so the pose shows a frame late and its events
+/// arrive a frame late. Gameplay sees the controller as usual in between
+/// `PreUpdate` and `PostUpdate`.
+///
+/// Async controllers update once per frame, outside the `AnimationBudget`,
+/// and should use `AnimatorUpdateMode::Frame`. Tasks can't be waited on in
+/// the browser, on wasm32 the controller is updated right away in `Last`
+/// instead, its pose and events still handed back in the next `PreUpdate`.
+/// ## Example
+/// ```ignore
+/// commands.spawn((SceneRoot(dragon), controller, AsyncAnimation::default()));
+/// ```
+#[derive(Component, Default)]
+#[component(on_remove = return_controller)]
+pub struct AsyncAnimation {
+    update: Option<InFlight>,
+    /// Empty controller swapped onto the entity while a task runs, kept
+    /// between tasks so it is only built once
+    placeholder: Option<AnimatorController>,
+}
+
+impl AsyncAnimation {
+    /// If the controller is being updated by a task
+    #[inline]
+    pub fn is_in_flight(&self) -> bool {
+        self.update.is_some()
+    }
+}
+
+impl fmt::Debug for AsyncAnimation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AsyncAnimation")
+            .field("in_flight", &self.is_in_flight())
+            .finish()
+    }
+}
+
+/// A controller handed back by its task
+struct AsyncUpdate {
+    controller: AnimatorController,
+    result: Result<(), AnimatorError>,
+}
+
+/// An update started in `Last`, handed back in the next `PreUpdate`
+enum InFlight {
+    Task(Task<AsyncUpdate>),
+    /// Already updated, on targets that can't wait for tasks
+    Done(Box<AsyncUpdate>),
+}
+
+impl InFlight {
+    /// Update `controller` on the pool, or right away if `inline` is set
+    fn start(
+        mut controller: AnimatorController,
+        settings: PrioritySettings,
+        delta: Duration,
+        limits: (Option<Duration>, Option<Duration>),
+        inline: bool,
+    ) -> Self {
+        let (max_delta, sub_step) = limits;
+        let update = move || {
+            let result = advance_controller(&mut controller, settings, delta, max_delta, sub_step);
+            AsyncUpdate { controller, result }
+        };
+        if inline {
+            InFlight::Done(Box::new(update()))
+        } else {
+            InFlight::Task(AsyncComputeTaskPool::get().spawn(async move { update() }))
+        }
+    }
+
+    #[inline]
+    fn finish(self) -> AsyncUpdate {
+        match self {
+            InFlight::Task(task) => block_on(task),
+            InFlight::Done(update) => *update,
+        }
+    }
+}
+
+/// The browser runs tasks on its event loop, which never gets to run them
+/// while the schedule blocks on one
+const INLINE_UPDATES: bool = cfg!(target_arch = "wasm32");
+
+/// Move every async controller into a task updating it, leaving an empty
+/// controller on the entity until the task is finished
+pub fn start_async_animations(
+    mut controllers: Query<(
+        &mut AnimatorController,
+        &mut AsyncAnimation,
+        Option<&AnimationPriority>,
+        Option<&mut AnimatorClock>,
+    )>,
+    time: ScaledTime,
+    priorities: Option<Res<AnimationPriorities>>,
+    limits: AnimatorStepLimits,
+) {
+    let fallback = AnimationPriorities::default();
+    let priorities = priorities.as_deref().unwrap_or(&fallback);
+    let limits = (limits.max_delta(), limits.sub_step());
+
+    for (mut controller, mut async_animation, priority, clock) in &mut controllers {
+        if async_animation.is_in_flight() {
+            continue;
+        }
+        let delta = clock.map_or_else(|| time.delta(), |mut clock| clock.0.delta(time.time()));
+        let settings = priorities.get(priority.copied().unwrap_or_default());
+        // Rebuilt if the controller was replaced by one on another skeleton
+        let placeholder = async_animation
+            .placeholder
+            .take()
+            .filter(|placeholder| Arc::ptr_eq(&placeholder.skeleton, &controller.skeleton));
+        let placeholder = match placeholder {
+            Some(placeholder) => placeholder,
+            // A controller without layers can't fail to build
+            None => match AnimatorController::new(
+                controller.skeleton.clone(),
+                Vec::new(),
+                Parameters::new(),
+            ) {
+                Ok(placeholder) => placeholder,
+                Err(_) => continue,
+            },
+        };
+        let controller = std::mem::replace(&mut *controller, placeholder);
+        async_animation.update =
+            Some(InFlight::start(controller, settings, delta, limits, INLINE_UPDATES));
+    }
+}
+
+/// Wait for the updates started last frame, put their controllers back and
+/// send their events
+pub fn finish_async_animations(
+    mut controllers: Query<(Entity, &mut AnimatorController, &mut AsyncAnimation)>,
+    mut events: AnimatorEventWriters,
+) {
+    for (entity, mut controller, mut async_animation) in &mut controllers {
+        let Some(update) = async_animation.update.take() else {
+            continue;
+        };
+        let AsyncUpdate {
+            controller: mut updated,
+            result,
+        } = update.finish();
+        events.send(entity, &mut updated, result);
+        async_animation.placeholder = Some(std::mem::replace(&mut *controller, updated));
+    }
+}
+
+/// Put the controller back if the component is removed while an update is in flight
+fn return_controller(mut world: DeferredWorld, context: HookContext) {
+    let Some(update) = world
+        .get_mut::<AsyncAnimation>(context.entity)
+        .and_then(|mut async_animation| async_animation.update.take())
+    else {
+        return;
+    };
+    let update = update.finish();
+    if let Some(mut controller) = world.get_mut::<AnimatorController>(context.entity) {
+        *controller = update.controller;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::SyntheticAnimators;
+    use bevy::tasks::TaskPool;
+
+    #[test]
+    fn inline_updates_match_task_updates() {
+        AsyncComputeTaskPool::get_or_init(TaskPool::default);
+        let animators = SyntheticAnimators::new(1, 8);
+        let (skeleton, clips) = animators.rig().unwrap();
+        let update = |inline: bool| {
+            let controller = animators.controller(&skeleton, &clips, 0).unwrap();
+            let in_flight = InFlight::start(
+                controller,
+                PrioritySettings::default(),
+                Duration::from_millis(250),
+                (None, None),
+                inline,
+            );
+            assert_eq!(matches!(in_flight, InFlight::Done(_)), inline);
+            let AsyncUpdate { controller, result } = in_flight.finish();
+            result.unwrap();
+            assert_eq!(controller.local_pose().len(), 8);
+            controller
+        };
+        assert_eq!(update(true).local_pose(), update(false).local_pose());
+    }
+}
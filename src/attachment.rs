@@ -0,0 +1,102 @@
+use super::skeleton::joint_parent;
+use super::{AnimatorController, AnimatorUpdateMode};
+use bevy::prelude::*;
+
+/// Keeps an entity, like a weapon, hat or particle emitter, glued to a joint
+/// of a controller's animated pose every frame. The entity's transforms are
+/// overwritten after transform propagation, so its own children follow a
+/// frame later.
+/// ## Example
+/// ```ignore
+/// commands.spawn((
+///     SceneRoot(sword),
+///     BoneAttachment::new(player, "RightHand")
+///         .with_offset(Transform::from_rotation(Quat::from_rotation_x(FRAC_PI_2))),
+/// ));
+/// ```
+#[derive(Component, Debug, Clone, PartialEq)]
+pub struct BoneAttachment {
+    /// Entity with the `AnimatorController`
+    pub controller: Entity,
+    /// Name of the joint to follow
+    pub bone: String,
+    /// Transform relative to the joint
+    pub offset: Transform,
+}
+
+impl BoneAttachment {
+    #[inline]
+    pub fn new(controller: Entity, bone: impl Into<String>) -> Self {
+        Self {
+            controller,
+            bone: bone.into(),
+            offset: Transform::IDENTITY,
+        }
+    }
+
+    #[inline]
+    pub fn with_offset(mut self, offset: Transform) -> Self {
+        self.offset = offset;
+        self
+    }
+}
+
+/// Move every attached entity to its joint's world transform
+pub fn update_bone_attachments(
+    mut attachments: Query<(
+        &BoneAttachment,
+        &mut Transform,
+        &mut GlobalTransform,
+        Option<&ChildOf>,
+    )>,
+    controllers: Query<(&AnimatorController, &GlobalTransform), Without<BoneAttachment>>,
+    parents: Query<&GlobalTransform, Without<BoneAttachment>>,
+    update_mode: Res<AnimatorUpdateMode>,
+    fixed_time: Res<Time<Fixed>>,
+) {
+    // Follow the same interpolated pose as the bones
+    let alpha = match *update_mode {
+        AnimatorUpdateMode::Frame => 1.0,
+        AnimatorUpdateMode::Fixed => fixed_time.overstep_fraction(),
+    };
+
+    for (attachment, mut transform, mut global_transform, child_of) in &mut attachments {
+        let Ok((controller, controller_transform)) = controllers.get(attachment.controller) else {
+            continue;
+        };
+        let Some(joint) = controller.find_joint(&attachment.bone) else {
+            continue;
+        };
+        let Some(model) = interpolated_model_bone(controller, joint, alpha) else {
+            continue;
+        };
+
+        let world =
+            controller_transform.compute_matrix() * model * attachment.offset.compute_matrix();
+        *global_transform = GlobalTransform::from(world);
+        // Keep the local transform in step, so propagation lands on the same spot
+        let local = match child_of.and_then(|child_of| parents.get(child_of.parent()).ok()) {
+            Some(parent) => parent.compute_matrix().inverse() * world,
+            None => world,
+        };
+        *transform = Transform::from_matrix(local);
+    }
+}
+
+/// Model space transform of a joint between the last two updates
+#[inline]
+fn interpolated_model_bone(
+    controller: &AnimatorController,
+    index: usize,
+    alpha: f32,
+) -> Option<Mat4> {
+    let mut joint = Some(index);
+    let mut model = Mat4::IDENTITY;
+    while let Some(current) = joint {
+        let bone = controller.interpolated_bone(current, alpha)?;
+        model = Mat4::from_scale_rotation_translation(bone.scale, bone.rotation, bone.position)
+            * model;
+        joint = joint_parent(&controller.skeleton, current);
+    }
+    Some(model)
+}
@@ -0,0 +1,140 @@
+use super::{AnimationMarker, AnimationMarkerEvent, AnimatorController, AnimatorError};
+use bevy::prelude::*;
+
+/// A marker crossed by a controller, where it happened in the world, as
+/// received by an [`AnimationAudioHook`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct AnimationAudioCue {
+    /// Entity of the controller
+    pub entity: Entity,
+    pub marker: AnimationMarker,
+    /// Joint the marker is bound to with [`AnimatorController::bind_marker_joint`]
+    pub joint: Option<usize>,
+    /// World position of the joint in the latest pose, or of the controller
+    /// entity if the marker isn't bound to a joint
+    pub position: Vec3,
+}
+
+/// Receives every marker crossed by every controller, with the position of the
+/// bone it is bound to, so an audio plugin can play spatialized footsteps and
+/// foley once instead of every game writing its own bridge. Register hooks
+/// in the [`AnimationAudioHooks`] resource.
+/// ## Example
+/// ```ignore
+/// struct Footsteps(Handle<AudioSource>);
+///
+/// impl AnimationAudioHook for Footsteps {
+///     fn on_cue(&mut self, cue: &AnimationAudioCue, commands: &mut Commands) {
+///         if cue.marker.name.starts_with("footstep") {
+///             commands.spawn((
+///                 AudioPlayer(self.0.clone()),
+///                 PlaybackSettings::DESPAWN.with_spatial(true),
+///                 Transform::from_translation(cue.position),
+///             ));
+///         }
+///     }
+/// }
+///
+/// app.world_mut()
+///     .resource_mut::<AnimationAudioHooks>()
+///     .add(Footsteps(asset_server.load("footstep.ogg")));
+/// ```
+pub trait AnimationAudioHook: Send + Sync + 'static {
+    fn on_cue(&mut self, cue: &AnimationAudioCue, commands: &mut Commands);
+}
+
+/// Every registered [`AnimationAudioHook`], called in the order they were
+/// added. Added by the plugin.
+#[derive(Resource, Default)]
+pub struct AnimationAudioHooks {
+    hooks: Vec<Box<dyn AnimationAudioHook>>,
+}
+
+impl AnimationAudioHooks {
+    #[inline]
+    pub fn add(&mut self, hook: impl AnimationAudioHook) {
+        self.hooks.push(Box::new(hook));
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.hooks.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.hooks.is_empty()
+    }
+
+    #[inline]
+    pub fn clear(&mut self) {
+        self.hooks.clear();
+    }
+}
+
+impl AnimatorController {
+    /// Place a marker at a joint, so the [`AnimationAudioCue`]s of every marker
+    /// with this name carry the joint's position, e.g. "footstep_left" at the
+    /// left foot. Markers that aren't bound play at the controller entity.
+    /// ## Example
+    /// ```ignore
+    /// controller.bind_marker_joint("footstep_left", "LeftFoot")?;
+    /// controller.bind_marker_joint("footstep_right", "RightFoot")?;
+    /// ```
+    #[inline]
+    pub fn bind_marker_joint(
+        &mut self,
+        marker: impl Into<String>,
+        joint: &str,
+    ) -> Result<(), AnimatorError> {
+        let index = self
+            .find_joint(joint)
+            .ok_or_else(|| AnimatorError::UnknownJoint(joint.to_string()))?;
+        self.marker_joints.insert(marker.into(), index);
+        Ok(())
+    }
+
+    /// Remove the joint a marker is bound to, returns false if it wasn't bound
+    #[inline]
+    pub fn unbind_marker_joint(&mut self, marker: &str) -> bool {
+        self.marker_joints.remove(marker).is_some()
+    }
+
+    /// Joint a marker is bound to
+    #[inline]
+    pub fn marker_joint(&self, marker: &str) -> Option<usize> {
+        self.marker_joints.get(marker).copied()
+    }
+}
+
+/// Pass every marker crossed this update to the audio hooks
+pub fn dispatch_animation_audio(
+    mut hooks: ResMut<AnimationAudioHooks>,
+    mut markers: EventReader<AnimationMarkerEvent>,
+    controllers: Query<(&AnimatorController, &GlobalTransform)>,
+    mut commands: Commands,
+) {
+    if hooks.is_empty() {
+        markers.clear();
+        return;
+    }
+    for event in markers.read() {
+        let Ok((controller, transform)) = controllers.get(event.entity) else {
+            continue;
+        };
+        let joint = controller.marker_joint(&event.marker.name);
+        let position = joint
+            .and_then(|joint| controller.model_space_bone(joint))
+            .map(|bone| transform.transform_point(bone.w_axis.truncate()))
+            .unwrap_or_else(|| transform.translation());
+        let cue = AnimationAudioCue {
+            entity: event.entity,
+            marker: event.marker.clone(),
+            joint,
+            position,
+        };
+        for hook in &mut hooks.hooks {
+            hook.on_cue(&cue, &mut commands);
+        }
+    }
+}
@@ -1,57 +1,395 @@
-use super::AnimatorController;
-use bevy::{prelude::*, render::mesh::skinning::SkinnedMesh};
+use super::budget::update_controller;
+use super::{
+    AnimationBudget, AnimationPriorities, AnimationPriority, AnimatorClock, AnimatorController,
+    AnimatorEventWriters, AsyncAnimation, ScaledTime,
+};
+#[cfg(feature = "render")]
+use super::{
+    BlendTreePlayer, CrowdAnimator, CrowdSource, EvaluationMode, OzzClipPlayer, PoseCopy,
+};
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+#[cfg(feature = "render")]
+use bevy::render::mesh::skinning::SkinnedMesh;
+#[cfg(feature = "render")]
+use ozz_animation_rs::Skeleton;
+use std::collections::HashMap;
+#[cfg(feature = "render")]
+use std::sync::Arc;
+use std::time::Duration;
 
 #[derive(Component)]
 pub struct BoneIndex(pub usize);
 
-#[derive(Debug, Clone, Copy)]
+/// Which schedule drives the controllers, set through `OzzAnimationPlugin`
+#[derive(Resource, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AnimatorUpdateMode {
+    /// Update once per frame in PostUpdate
+    #[default]
+    Frame,
+    /// Update in FixedUpdate alongside the simulation. Bone transforms are
+    /// interpolated between the last two fixed poses every frame, so animation
+    /// stays smooth at refresh rates above the fixed rate.
+    Fixed,
+}
+
+/// Longest time a single controller update may advance, set through
+/// `OzzAnimationPlugin::max_delta`. Controllers can override it with
+/// `AnimatorController::set_max_delta`.
+#[derive(Resource, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct AnimatorMaxDelta(pub Option<Duration>);
+
+/// Longest step a controller update is split into, set through
+/// `OzzAnimationPlugin::sub_step`. Controllers can override it with
+/// `AnimatorController::set_sub_step`.
+#[derive(Resource, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct AnimatorSubStep(pub Option<Duration>);
+
+/// The global limits of a controller update, see [`AnimatorMaxDelta`] and
+/// [`AnimatorSubStep`]
+#[derive(SystemParam)]
+pub struct AnimatorStepLimits<'w> {
+    max_delta: Option<Res<'w, AnimatorMaxDelta>>,
+    sub_step: Option<Res<'w, AnimatorSubStep>>,
+}
+
+impl AnimatorStepLimits<'_> {
+    #[inline]
+    pub fn max_delta(&self) -> Option<Duration> {
+        self.max_delta.as_deref().and_then(|max_delta| max_delta.0)
+    }
+
+    #[inline]
+    pub fn sub_step(&self) -> Option<Duration> {
+        self.sub_step.as_deref().and_then(|sub_step| sub_step.0)
+    }
+}
+
+/// Binds a skinned mesh, or an ancestor of skinned meshes such as a scene root,
+/// to the entity holding its `AnimatorController` or `CrowdAnimator`. Inserted
+/// automatically when the controller is an ancestor of the mesh, insert it
+/// yourself when it isn't.
+/// ## Example
+/// ```ignore
+/// let controller = commands.spawn(controller).id();
+/// commands.spawn((SceneRoot(mesh), AnimatorTarget(controller)));
+/// ```
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+#[relationship(relationship_target = AnimatorTargets)]
+pub struct AnimatorTarget(pub Entity);
+
+/// Every entity animated by this controller, see [`AnimatorTarget`]
+#[derive(Component, Debug, Default)]
+#[relationship_target(relationship = AnimatorTarget)]
+pub struct AnimatorTargets(Vec<Entity>);
+
+impl AnimatorTargets {
+    #[inline]
+    pub fn targets(&self) -> &[Entity] {
+        &self.0
+    }
+}
+
+/// Entity whose pose a bone follows, an `AnimatorController`, a `CrowdAnimator`,
+/// a `PoseCopy`, a `BlendTreePlayer` or an `OzzClipPlayer`
+#[derive(Component, Debug, Clone, Copy)]
+pub struct BonePoseSource(pub Entity);
+
+#[derive(Debug, Clone, Copy, PartialEq, Reflect)]
 pub struct OzzTransform {
     pub scale: Vec3,
     pub rotation: Quat,
     pub position: Vec3,
 }
 
-pub fn animate_bones(mut controller_query: Query<&mut AnimatorController>, time: Res<Time>) {
-    for mut controller in controller_query.iter_mut() {
-        let _ = controller.update(&time);
+impl From<Transform> for OzzTransform {
+    #[inline]
+    fn from(transform: Transform) -> Self {
+        Self {
+            scale: transform.scale,
+            rotation: transform.rotation,
+            position: transform.translation,
+        }
+    }
+}
+
+impl From<OzzTransform> for Transform {
+    #[inline]
+    fn from(transform: OzzTransform) -> Self {
+        Self {
+            translation: transform.position,
+            rotation: transform.rotation,
+            scale: transform.scale,
+        }
+    }
+}
+
+pub fn animate_bones(
+    mut controller_query: Query<
+        (Entity, &mut AnimatorController, Option<&AnimationPriority>),
+        Without<AsyncAnimation>,
+    >,
+    mut clocks: Query<(Entity, &mut AnimatorClock), Without<AsyncAnimation>>,
+    time: ScaledTime,
+    budget: Option<ResMut<AnimationBudget>>,
+    priorities: Option<Res<AnimationPriorities>>,
+    limits: AnimatorStepLimits,
+    mut events: AnimatorEventWriters,
+) {
+    let fallback = AnimationPriorities::default();
+    let priorities = priorities.as_deref().unwrap_or(&fallback);
+    let max_delta = limits.max_delta();
+    let sub_step = limits.sub_step();
+    // Every clock is read once, whether its controller updates this frame or not
+    let clock_deltas: HashMap<Entity, Duration> = clocks
+        .iter_mut()
+        .map(|(entity, mut clock)| (entity, clock.0.delta(time.time())))
+        .collect();
+    let scaled_delta = time.delta();
+    let delta = |entity: Entity| clock_deltas.get(&entity).copied().unwrap_or(scaled_delta);
+    let mut update = |entity: Entity,
+                      controller: &mut AnimatorController,
+                      priority: AnimationPriority| {
+        let settings = priorities.get(priority);
+        update_controller(
+            entity,
+            controller,
+            settings,
+            delta(entity),
+            max_delta,
+            sub_step,
+            &mut events,
+        );
+    };
+
+    match budget {
+        Some(mut budget) => budget.run(&mut controller_query, delta, max_delta, update),
+        None => {
+            for (entity, mut controller, priority) in controller_query.iter_mut() {
+                update(entity, &mut controller, priority.copied().unwrap_or_default());
+            }
+        }
     }
 }
 
+/// The pose a group of bones follows, kept between runs so its joints are
+/// only read again when they may have moved
+#[cfg(feature = "render")]
+#[derive(Default)]
+pub(crate) struct SourcePose {
+    /// Joints of the last pose read
+    joints: Vec<OzzTransform>,
+    /// If the source may have moved since the bones were last written
+    changed: bool,
+    /// If a bone was bound to the source since the last run
+    rebound: bool,
+    /// If any bone follows the source this run
+    followed: bool,
+    /// If the source has a pose to show this run
+    shown: bool,
+}
+
+/// Bones written by `update_bone_transforms`
+#[cfg(feature = "render")]
+type Bones<'w, 's> = Query<
+    'w,
+    's,
+    (&'static mut Transform, Ref<'static, BoneIndex>, Option<Ref<'static, BonePoseSource>>),
+>;
+
+/// Gather the pose of every source once, then write all bones in one parallel
+/// pass. Sources are only read when they may have moved since the last run,
+/// into buffers kept between runs, and unchanged transforms are left alone so
+/// they aren't propagated.
+#[cfg(feature = "render")]
 pub(crate) fn update_bone_transforms(
-    mut query: Query<(&mut Transform, &BoneIndex)>,
-    controller_query: Query<&AnimatorController, Without<BoneIndex>>,
+    mut query: Bones,
+    owners: BoneOwners,
+    update_mode: Res<AnimatorUpdateMode>,
+    fixed_time: Res<Time<Fixed>>,
+    // Pose version of every controller when its bones were last written
+    mut written: Local<HashMap<Entity, u64>>,
+    // Versions read this run, swapped into `written` so neither reallocates
+    mut versions: Local<HashMap<Entity, u64>>,
+    mut poses: Local<HashMap<Entity, SourcePose>>,
 ) {
-    for controller in controller_query.iter() {
-        let bone_trans = &controller.bone_trans;
-
-        if !bone_trans.is_empty() {
-            for (mut transform, idx) in query.iter_mut() {
-                if idx.0 < bone_trans.len() {
-                    transform.translation = bone_trans[idx.0].position;
-                    transform.rotation = bone_trans[idx.0].rotation;
-                    transform.scale = bone_trans[idx.0].scale;
-                }
+    // How far into the next fixed step this frame is, which moves the
+    // interpolated pose even when no source changed
+    let (alpha, interpolated) = match *update_mode {
+        AnimatorUpdateMode::Frame => (1.0, false),
+        AnimatorUpdateMode::Fixed => (fixed_time.overstep_fraction(), true),
+    };
+
+    for pose in poses.values_mut() {
+        pose.rebound = false;
+        pose.followed = false;
+    }
+    for (_, idx, source) in &query {
+        let Some(source) = source else {
+            continue;
+        };
+        let pose = poses.entry(source.0).or_default();
+        pose.followed = true;
+        // Bones that were just bound are written even if the pose didn't move
+        pose.rebound |= idx.is_changed() || source.is_changed();
+    }
+    // Sources nobody follows anymore are forgotten
+    poses.retain(|_, pose| pose.followed);
+
+    versions.clear();
+    for (&owner, pose) in poses.iter_mut() {
+        pose.shown = owners.pose(owner, alpha, interpolated, &written, &mut versions, pose);
+    }
+    std::mem::swap(&mut *written, &mut *versions);
+
+    query.par_iter_mut().for_each(|(mut transform, idx, source)| {
+        // Bones of meshes without an animator have nothing to follow
+        let Some(source) = source else {
+            return;
+        };
+        let Some(pose) = poses.get(&source.0).filter(|pose| pose.shown) else {
+            return;
+        };
+        let rebound = idx.is_changed() || source.is_changed();
+        if !pose.changed && !rebound {
+            return;
+        }
+        if let Some(bone) = pose.joints.get(idx.0) {
+            transform.set_if_neq((*bone).into());
+        }
+    });
+}
+
+/// Every kind of entity bones can follow the pose of
+#[cfg(feature = "render")]
+#[derive(SystemParam)]
+pub(crate) struct BoneOwners<'w, 's> {
+    controllers: Query<'w, 's, (Entity, Ref<'static, AnimatorController>), Without<BoneIndex>>,
+    crowds: Query<'w, 's, Ref<'static, CrowdAnimator>>,
+    crowd_sources: Query<'w, 's, Ref<'static, CrowdSource>>,
+    pose_copies: Query<'w, 's, Ref<'static, PoseCopy>>,
+    blend_tree_players: Query<'w, 's, Ref<'static, BlendTreePlayer>>,
+    clip_players: Query<'w, 's, Ref<'static, OzzClipPlayer>>,
+}
+
+#[cfg(feature = "render")]
+impl BoneOwners<'_, '_> {
+    /// If bones can follow `entity`
+    fn contains(&self, entity: Entity) -> bool {
+        self.controllers.contains(entity)
+            || self.crowds.contains(entity)
+            || self.pose_copies.contains(entity)
+            || self.blend_tree_players.contains(entity)
+            || self.clip_players.contains(entity)
+    }
+
+    /// Skeleton of the pose `owner` plays
+    fn skeleton(&self, owner: Entity) -> Option<Arc<Skeleton>> {
+        if let Ok(player) = self.blend_tree_players.get(owner) {
+            return Some(player.skeleton().clone());
+        }
+        if let Ok(player) = self.clip_players.get(owner) {
+            return Some(player.skeleton().clone());
+        }
+        let source = if let Ok(crowd) = self.crowds.get(owner) {
+            crowd.source
+        } else if let Ok(copy) = self.pose_copies.get(owner) {
+            copy.source
+        } else {
+            owner
+        };
+        let (_, controller) = self.controllers.get(source).ok()?;
+        Some(controller.skeleton.clone())
+    }
+
+    /// Refresh `pose` with what bones following `owner` show, false if it has
+    /// none to show. Joints are only read when the source may have moved,
+    /// always if `moving`, or a bone was just bound to it. A controller moved
+    /// if its pose version differs from the one in `written`, the versions
+    /// read are added to `versions`.
+    fn pose(
+        &self,
+        owner: Entity,
+        alpha: f32,
+        moving: bool,
+        written: &HashMap<Entity, u64>,
+        versions: &mut HashMap<Entity, u64>,
+        pose: &mut SourcePose,
+    ) -> bool {
+        fn read(
+            pose: &mut SourcePose,
+            changed: bool,
+            joint: impl FnMut(usize) -> Option<OzzTransform>,
+        ) {
+            pose.changed |= changed;
+            if pose.changed || pose.rebound {
+                pose.joints.clear();
+                pose.joints.extend((0..).map_while(joint));
             }
         }
+        pose.changed = moving;
+        // The pose isn't evaluated in logic only mode, so there is nothing new to write
+        let mut evaluated = |entity: Entity| {
+            let (_, controller) = self.controllers.get(entity).ok()?;
+            if controller.evaluation_mode() == EvaluationMode::LogicOnly {
+                return None;
+            }
+            let version = controller.pose_version();
+            versions.insert(entity, version);
+            Some((controller, written.get(&entity) != Some(&version)))
+        };
+
+        if let Some((controller, moved)) = evaluated(owner) {
+            read(pose, moved, |joint| controller.interpolated_bone(joint, alpha));
+        } else if self.controllers.contains(owner) {
+            return false;
+        } else if let Ok(crowd) = self.crowds.get(owner)
+            && let Ok(source) = self.crowd_sources.get(crowd.source)
+        {
+            read(pose, crowd.is_changed() || source.is_changed(), |joint| {
+                source.joint_at(crowd.phase_offset, joint, crowd.mirrored)
+            });
+        } else if let Ok(copy) = self.pose_copies.get(owner) {
+            let Some((controller, moved)) = evaluated(copy.source) else {
+                return false;
+            };
+            read(pose, copy.is_changed() || moved, |joint| copy.joint(&controller, joint, alpha));
+        } else if let Ok(player) = self.blend_tree_players.get(owner) {
+            read(pose, player.is_changed(), |joint| player.bone(joint));
+        } else if let Ok(player) = self.clip_players.get(owner) {
+            read(pose, player.is_changed(), |joint| player.bone(joint));
+        } else {
+            return false;
+        }
+        true
     }
 }
 
+/// Skinned meshes that were just spawned or bound to another controller
+#[cfg(feature = "render")]
+type NewSkinnedMeshes = Or<(Added<SkinnedMesh>, Changed<AnimatorTarget>)>;
+
+#[cfg(feature = "render")]
 pub(crate) fn add_bone_indexes(
-    bones: Query<(Entity, &SkinnedMesh), Added<SkinnedMesh>>,
+    bones: Query<(Entity, &SkinnedMesh), NewSkinnedMeshes>,
     parents: Query<&ChildOf>,
-    controllers: Query<&AnimatorController>,
+    targets: Query<&AnimatorTarget>,
+    owners: BoneOwners,
     names: Query<&Name>,
     mut commands: Commands,
 ) {
     for (entity, skinned_mesh) in &bones {
-        // Find AnimatorController in ancestors
+        // Use the target of the mesh or its closest ancestor with one, falling
+        // back to the closest animated ancestor
         let mut current_entity = entity;
-        let mut skeleton = None;
-
-        // Walk up to find controller
+        let mut owner = None;
         loop {
-            if let Ok(controller) = controllers.get(current_entity) {
-                skeleton = Some(&controller.skeleton);
+            if let Ok(AnimatorTarget(target)) = targets.get(current_entity) {
+                owner = Some(*target);
+                break;
+            }
+            if owners.contains(current_entity) {
+                owner = Some(current_entity);
                 break;
             }
             if let Ok(child_of) = parents.get(current_entity) {
@@ -61,7 +399,16 @@ pub(crate) fn add_bone_indexes(
             }
         }
 
-        if let Some(skeleton) = skeleton {
+        // Bind the mesh explicitly so it no longer depends on the hierarchy
+        if let Some(owner) = owner
+            && !targets.contains(entity)
+        {
+            commands.entity(entity).insert(AnimatorTarget(owner));
+        }
+
+        let skeleton = owner.and_then(|owner| Some((owner, owners.skeleton(owner)?)));
+
+        if let Some((owner, skeleton)) = skeleton {
             let joint_names = skeleton.joint_names();
             for joint_entity in &skinned_mesh.joints {
                 if let Ok(name) = names.get(*joint_entity) {
@@ -76,11 +423,15 @@ pub(crate) fn add_bone_indexes(
                     if let Some(index) = found_index {
                         commands
                             .entity(*joint_entity)
-                            .insert(BoneIndex(index as usize));
+                            .insert((BoneIndex(index as usize), BonePoseSource(owner)));
                     }
                 }
             }
         } else {
+            warn!(
+                "Skinned mesh {entity} has no AnimatorController, CrowdAnimator, PoseCopy or \
+                 player above it and no AnimatorTarget, its bones won't be animated"
+            );
             for (i, joint_entity) in skinned_mesh.joints.iter().enumerate() {
                 commands.entity(*joint_entity).insert(BoneIndex(i));
             }
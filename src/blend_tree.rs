@@ -1,16 +1,31 @@
-use super::{BlendState, Parameters, SimpleState};
+use super::{
+    AnimatorError, BlendState, MotionStats, ParameterValue, Parameters, SimpleState, read_lock,
+    write_lock, write_or_report,
+};
+use super::curve_output::CurveSample;
+use super::parameters::ParamBinding;
 use bevy::prelude::*;
 use ozz_animation_rs::{
     BlendingJob, BlendingJobArc, BlendingLayer, OzzError, Skeleton, SoaTransform,
 };
+use serde::{Deserialize, Serialize};
 use std::sync::{Arc, RwLock};
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum BlendTreeType {
     /// 1 directional blending, given a single parameter name
     Simple1D(String),
     /// 2 directional blending, given 2 parameters names
     Directional2D(String, String),
+    /// 2D blending with gradient band interpolation, for parameters that aren't
+    /// directions such as speed and lean. Motions are placed with
+    /// `MotionThreshold::Directional2D` positions, hysteresis doesn't apply.
+    FreeformCartesian2D(String, String),
+    /// Every motion is weighted by its own float parameter, given with
+    /// `MotionThreshold::Direct`, e.g. facial poses blended together. The rest
+    /// pose fills in while the weights add up to less than 1, above that they
+    /// are normalized.
+    Direct,
 }
 
 #[derive(Debug)]
@@ -19,15 +34,39 @@ pub struct BlendTree {
     motions: Vec<MotionData>,
     blend_job: BlendingJobArc,
     output: Arc<RwLock<Vec<SoaTransform>>>,
+    /// Weights latched when a transition into this tree started, and how far
+    /// the tree has moved from them towards the live weights
+    latched_weights: Option<(Vec<f32>, f32)>,
+    /// 2D inputs shorter than this are treated as zero
+    input_dead_zone: f32,
+    /// Time in seconds for the 2D input to catch up with the parameters
+    input_smoothing: f32,
+    /// Smoothed 2D input, if smoothing is enabled
+    smoothed_input: Option<Vec2>,
+    /// How far past the current segment or triangle the input may move before
+    /// switching to another one
+    hysteresis: f32,
+    /// Motions blended between by the last update
+    active_motions: Vec<usize>,
+    /// Input parameters of the blend type resolved to their ids, x then y
+    input_bindings: [ParamBinding; 2],
+    /// Weight parameter of every motion of a `Direct` tree resolved to its id
+    direct_bindings: Vec<ParamBinding>,
+    /// Number of times the dominant motion wrapped
+    loop_count: u32,
+    /// Markers of the dominant motion crossed since they were last drained
+    fired_markers: Vec<String>,
 }
 
 /// Represents a motion threshold for blending depending on the type of blend tree
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum MotionThreshold {
     /// 1D threshold with a single value
     Simple1D(f32),
-    /// 2D threshold with 2 values
+    /// 2D threshold with 2 values, also used by `FreeformCartesian2D` trees
     Directional2D(f32, f32),
+    /// Name of the float parameter weighting the motion in a `Direct` tree
+    Direct(String),
 }
 
 #[derive(Debug)]
@@ -50,6 +89,10 @@ impl BlendTree {
     ) -> Self {
         let mut blend_job = BlendingJob::default();
         blend_job.set_skeleton(skeleton.clone());
+        if matches!(blend_type, BlendTreeType::Direct) {
+            // Keep weights absolute, the rest pose fills in the remainder
+            blend_job.set_threshold(1.0);
+        }
         let output = Arc::new(RwLock::new(vec![
             SoaTransform::default();
             skeleton.num_soa_joints()
@@ -61,24 +104,62 @@ impl BlendTree {
             motions,
             blend_job,
             output,
+            latched_weights: None,
+            input_dead_zone: 0.0,
+            input_smoothing: 0.0,
+            smoothed_input: None,
+            hysteresis: 0.0,
+            active_motions: Vec::new(),
+            input_bindings: [ParamBinding::default(); 2],
+            direct_bindings: Vec::new(),
+            loop_count: 0,
+            fired_markers: Vec::new(),
         };
         tree.build_blend_layers();
         tree
     }
 
+    /// Treat 2D inputs shorter than `dead_zone` as zero, so a resting analog
+    /// stick doesn't jitter between motions
+    /// ## Example
+    /// ```ignore
+    /// let tree = BlendTree::new(&skeleton, blend_type, motions)
+    ///     .with_input_dead_zone(0.1)
+    ///     .with_input_smoothing(0.08);
+    /// ```
+    #[inline(always)]
+    pub fn with_input_dead_zone(mut self, dead_zone: f32) -> Self {
+        self.input_dead_zone = dead_zone.max(0.0);
+        self
+    }
+
+    /// Ease the 2D input towards the parameter values over roughly `seconds`
+    /// instead of following them instantly. 0 disables smoothing.
+    #[inline(always)]
+    pub fn with_input_smoothing(mut self, seconds: f32) -> Self {
+        self.input_smoothing = seconds.max(0.0);
+        self.smoothed_input = None;
+        self
+    }
+
+    /// Keep blending between the same motions until the input moves `margin`
+    /// past them, so a parameter resting on a threshold doesn't flicker motions
+    /// on and off. In 1D the margin is in parameter units, in 2D it is how
+    /// negative a barycentric weight may get before leaving the triangle.
+    #[inline(always)]
+    pub fn with_hysteresis(mut self, margin: f32) -> Self {
+        self.hysteresis = margin.max(0.0);
+        self
+    }
+
     #[inline(always)]
     pub fn build_blend_layers(&mut self) {
         self.blend_job.layers_mut().clear();
         for motion_data in &self.motions {
+            // Every motion needs a layer, the weights are indexed by motion
             let output_pointer = match motion_data.motion {
-                BlendMotionState::Animation(ref state) => match state.read() {
-                    Ok(state) => state.get_output_pointer(),
-                    Err(_) => continue,
-                },
-                BlendMotionState::SubTree(ref state) => match state.read() {
-                    Ok(state) => state.get_output_pointer(),
-                    Err(_) => continue,
-                },
+                BlendMotionState::Animation(ref state) => read_lock(state).get_output_pointer(),
+                BlendMotionState::SubTree(ref state) => read_lock(state).get_output_pointer(),
             };
 
             self.blend_job.layers_mut().push(BlendingLayer {
@@ -90,22 +171,23 @@ impl BlendTree {
     }
 
     #[inline(always)]
-    pub fn update(&mut self, time: &Time, params: &mut Parameters) -> Result<(), OzzError> {
-        // Calculate weights based on parameters
-        match &self.blend_type {
-            BlendTreeType::Simple1D(param_name) => {
-                if let Some(value) = params.get_float(param_name) {
-                    self.calculate_weights_1d(value);
-                }
-            }
-            BlendTreeType::Directional2D(x_param, y_param) => {
-                let x_value = params.get_float(x_param);
-                let y_value = params.get_float(y_param);
-                if let (Some(x), Some(y)) = (x_value, y_value) {
-                    self.calculate_weights_2d(x, y);
-                }
-            }
-        }
+    pub fn update(&mut self, time: &Time, params: &mut Parameters) -> Result<(), AnimatorError> {
+        self.tick(time, params, true)
+    }
+
+    /// Advance every active motion, only sampling and blending if `sample` is set
+    #[inline(always)]
+    pub(crate) fn tick(
+        &mut self,
+        time: &Time,
+        params: &mut Parameters,
+        sample: bool,
+    ) -> Result<(), AnimatorError> {
+        self.smooth_input(params, time.delta_secs());
+        self.apply_weights(params);
+
+        // The tree loops whenever its heaviest motion does
+        let dominant = self.dominant_motion();
 
         // TODO: STATE UPDATES CAN BE PARALLELIZED
         // Update motion states and blend layers
@@ -115,156 +197,880 @@ impl BlendTree {
                 continue;
             }
 
-            match &motion_data.motion {
+            // Only the dominant motion's markers fire, so blended clips don't
+            // double up on footsteps
+            let markers = (dominant == Some(i)).then_some(&mut self.fired_markers);
+            let loops = match &motion_data.motion {
                 BlendMotionState::Animation(state) => {
-                    if let Ok(mut state) = state.write() {
-                        state.update(time)?;
-                    }
+                    let mut state = write_or_report(state, "blend tree motion")?;
+                    let before = state.loop_count();
+                    state.tick(time, params, sample)?;
+                    state.drain_markers(markers);
+                    state.loop_count() - before
                 }
                 BlendMotionState::SubTree(state) => {
-                    if let Ok(mut state) = state.write() {
-                        state.update(time, params)?;
-                    }
+                    let mut state = write_or_report(state, "blend tree sub-tree")?;
+                    let before = state.loop_count();
+                    state.tick(time, params, sample)?;
+                    state.drain_markers(markers);
+                    state.loop_count() - before
                 }
+            };
+            if dominant == Some(i) {
+                self.loop_count += loops;
             }
         }
 
         // Run the blending job
+        if sample {
+            self.blend_job.run()?;
+        }
+        Ok(())
+    }
+
+    /// Sample every active motion at its current playback time and blend them,
+    /// without advancing any of them
+    pub(crate) fn sample(&mut self, params: &Parameters) -> Result<(), AnimatorError> {
+        self.apply_weights(params);
+
+        for (i, motion_data) in self.motions.iter().enumerate() {
+            if self.blend_job.layers()[i].weight == 0.0 {
+                continue;
+            }
+
+            match &motion_data.motion {
+                BlendMotionState::Animation(state) => {
+                    write_or_report(state, "blend tree motion")?.sample()?;
+                }
+                BlendMotionState::SubTree(state) => {
+                    write_or_report(state, "blend tree sub-tree")?.sample(params)?;
+                }
+            }
+        }
+
         self.blend_job.run()?;
         Ok(())
     }
 
+    /// Sample every motion once, active or not, and blend them with the current weights
+    pub(crate) fn prewarm(&mut self) -> Result<(), AnimatorError> {
+        for motion_data in &self.motions {
+            match &motion_data.motion {
+                BlendMotionState::Animation(state) => {
+                    write_or_report(state, "blend tree motion")?.sample()?;
+                }
+                BlendMotionState::SubTree(state) => {
+                    write_or_report(state, "blend tree sub-tree")?.prewarm()?;
+                }
+            }
+        }
+        self.blend_job.run()?;
+        Ok(())
+    }
+
+    /// Calculate the motion weights from the parameters and apply them to the
+    /// blend job, easing in from the latched weights if there are any
     #[inline(always)]
-    fn calculate_weights_1d(&mut self, param_value: f32) {
-        if self.motions.is_empty() {
+    fn apply_weights(&mut self, params: &Parameters) {
+        let Some((mut weights, active_motions)) = self.compute_blend(params, true) else {
             return;
+        };
+        self.active_motions = active_motions;
+        if let Some((latched, progress)) = &self.latched_weights {
+            for (weight, latched) in weights.iter_mut().zip(latched) {
+                *weight = latched + (*weight - latched) * progress;
+            }
+        }
+        for (layer, weight) in self.blend_job.layers_mut().iter_mut().zip(weights) {
+            layer.weight = weight;
         }
-        let blend_layers = self.blend_job.layers_mut();
+    }
 
-        if self.motions.len() == 1 {
-            blend_layers[0].weight = 1.0;
-            return;
+    /// Hold the current weights while the layer crossfades into this tree, so
+    /// the tree's weights and the crossfade don't move at the same time
+    #[inline(always)]
+    pub(crate) fn latch_weights(&mut self, params: &Parameters) {
+        let weights = self.compute_weights(params).unwrap_or_else(|| {
+            self.blend_job
+                .layers()
+                .iter()
+                .map(|layer| layer.weight)
+                .collect()
+        });
+        self.latched_weights = Some((weights, 0.0));
+    }
+
+    /// How far to move from the latched weights towards the live ones, from 0 to 1
+    #[inline(always)]
+    pub(crate) fn set_latch_progress(&mut self, progress: f32) {
+        if let Some((_, latch_progress)) = &mut self.latched_weights {
+            *latch_progress = progress.clamp(0.0, 1.0);
         }
+    }
 
-        // Find which 2 values to blend between
-        for i in 0..self.motions.len() - 1 {
-            let MotionThreshold::Simple1D(current_threshold) = self.motions[i].threshold else {
-                continue;
-            };
-            let MotionThreshold::Simple1D(next_threshold) = self.motions[i + 1].threshold else {
+    /// Go back to following the parameters directly
+    #[inline(always)]
+    pub(crate) fn release_latch(&mut self) {
+        self.latched_weights = None;
+    }
+
+    /// Sample the tree at a time in seconds into `output` without advancing any
+    /// of its clips, usable without a layer or controller e.g. to preview a
+    /// blend space. Weights come straight from `params`, ignoring input
+    /// smoothing and hysteresis. `output` should hold `num_soa_joints` transforms.
+    /// ## Example
+    /// ```ignore
+    /// let mut pose = vec![SoaTransform::default(); skeleton.num_soa_joints()];
+    /// params.set_float("speed", 0.5);
+    /// tree.evaluate(&params, 0.25, &mut pose)?;
+    /// ```
+    pub fn evaluate(
+        &self,
+        params: &Parameters,
+        time: f32,
+        output: &mut [SoaTransform],
+    ) -> Result<(), OzzError> {
+        let Some(skeleton) = self.blend_job.skeleton() else {
+            return Ok(());
+        };
+        let weights = self.weights(params).unwrap_or_else(|| {
+            self.blend_job
+                .layers()
+                .iter()
+                .map(|layer| layer.weight)
+                .collect()
+        });
+
+        let mut blend_job: BlendingJobArc = BlendingJob::default();
+        blend_job.set_skeleton(skeleton.clone());
+        blend_job.set_threshold(self.blend_job.threshold());
+        for (motion_data, weight) in self.motions.iter().zip(weights) {
+            if weight == 0.0 {
                 continue;
-            };
+            }
 
-            if param_value >= current_threshold && param_value <= next_threshold {
-                let t = (param_value - current_threshold) / (next_threshold - current_threshold);
-                let weight = 1.0 - t;
-                blend_layers[i].weight = if weight.abs() < f32::EPSILON {
-                    0.0
-                } else {
-                    weight
+            let mut pose = vec![SoaTransform::default(); output.len()];
+            match &motion_data.motion {
+                BlendMotionState::Animation(state) => {
+                    read_lock(state).evaluate_at(time, &mut pose)?;
+                }
+                BlendMotionState::SubTree(state) => {
+                    read_lock(state).evaluate_at(params, time, &mut pose)?;
+                }
+            }
+            blend_job.layers_mut().push(BlendingLayer {
+                transform: Arc::new(RwLock::new(pose)),
+                weight,
+                joint_weights: vec![],
+            });
+        }
+
+        let blend_output = Arc::new(RwLock::new(vec![SoaTransform::default(); output.len()]));
+        blend_job.set_output(blend_output.clone());
+        blend_job.run()?;
+        output.clone_from_slice(&read_lock(&blend_output)[..output.len()]);
+        Ok(())
+    }
+
+    /// Weight of every motion, in motion order, for the given parameters or
+    /// None if the parameters driving the tree are not set. Input smoothing and
+    /// hysteresis are ignored.
+    #[inline(always)]
+    pub fn weights(&self, params: &Parameters) -> Option<Vec<f32>> {
+        self.compute_blend(params, false).map(|(weights, _)| weights)
+    }
+
+    /// Weight of every motion as the tree currently sees the parameters
+    #[inline(always)]
+    fn compute_weights(&self, params: &Parameters) -> Option<Vec<f32>> {
+        self.compute_blend(params, true).map(|(weights, _)| weights)
+    }
+
+    /// Weight of every motion along with the motions blended between, the
+    /// segment in 1D or the triangle in 2D. Smoothing and hysteresis only
+    /// apply if `stateful` is set.
+    #[inline(always)]
+    fn compute_blend(&self, params: &Parameters, stateful: bool) -> Option<(Vec<f32>, Vec<usize>)> {
+        match &self.blend_type {
+            BlendTreeType::Simple1D(param_name) => {
+                let value = params.bound_float(self.input_bindings[0], param_name)?;
+                Some(self.weights_1d(value, stateful))
+            }
+            BlendTreeType::Direct => Some(self.weights_direct(params)),
+            BlendTreeType::Directional2D(..) | BlendTreeType::FreeformCartesian2D(..) => {
+                let input = match self.smoothed_input {
+                    Some(input) if stateful => input,
+                    _ => self.input_2d(params)?,
                 };
-                blend_layers[i + 1].weight = t;
-            } else {
-                blend_layers[i].weight = 0.0;
+                match self.blend_type {
+                    BlendTreeType::FreeformCartesian2D(..) => Some(self.weights_cartesian(input)),
+                    _ => Some(self.weights_2d(input.x, input.y, stateful)),
+                }
             }
         }
+    }
 
-        // Edge cases if the first or last motion exceeds the threshold
-        if let MotionThreshold::Simple1D(first_threshold) = self.motions[0].threshold {
-            if param_value <= first_threshold {
-                blend_layers[0].weight = 1.0;
+    /// 2D input from the parameters with the dead zone applied
+    #[inline(always)]
+    fn input_2d(&self, params: &Parameters) -> Option<Vec2> {
+        let (BlendTreeType::Directional2D(x_param, y_param)
+        | BlendTreeType::FreeformCartesian2D(x_param, y_param)) = &self.blend_type
+        else {
+            return None;
+        };
+        let input = Vec2::new(
+            params.bound_float(self.input_bindings[0], x_param)?,
+            params.bound_float(self.input_bindings[1], y_param)?,
+        );
+        if input.length() < self.input_dead_zone {
+            return Some(Vec2::ZERO);
+        }
+        Some(input)
+    }
+
+    /// Move the smoothed 2D input towards the parameters
+    #[inline(always)]
+    fn smooth_input(&mut self, params: &Parameters, delta_secs: f32) {
+        if self.input_smoothing <= 0.0 {
+            return;
+        }
+        let Some(target) = self.input_2d(params) else {
+            return;
+        };
+        let smoothed = match self.smoothed_input {
+            Some(current) => {
+                let t = 1.0 - (-delta_secs / self.input_smoothing).exp();
+                current.lerp(target, t)
             }
-        } else if let Some(last_motion) = self.motions.last_mut()
-            && let MotionThreshold::Simple1D(last_threshold) = last_motion.threshold
-            && param_value >= last_threshold
-            && let Some(last_layer) = blend_layers.last_mut()
+            None => target,
+        };
+        self.smoothed_input = Some(smoothed);
+    }
+
+    #[inline(always)]
+    fn weights_1d(&self, param_value: f32, sticky: bool) -> (Vec<f32>, Vec<usize>) {
+        let mut weights = vec![0.0; self.motions.len()];
+        let thresholds: Vec<(usize, f32)> = self
+            .motions
+            .iter()
+            .enumerate()
+            .filter_map(|(i, motion)| match motion.threshold {
+                MotionThreshold::Simple1D(threshold) => Some((i, threshold)),
+                _ => None,
+            })
+            .collect();
+        let (Some(&(first_idx, first)), Some(&(last_idx, last))) =
+            (thresholds.first(), thresholds.last())
+        else {
+            return (weights, Vec::new());
+        };
+
+        // Stay on the previous segment while the value is within the hysteresis band
+        if sticky
+            && self.hysteresis > 0.0
+            && let [current_idx, next_idx] = self.active_motions[..]
+            && let MotionThreshold::Simple1D(current_threshold) = self.motions[current_idx].threshold
+            && let MotionThreshold::Simple1D(next_threshold) = self.motions[next_idx].threshold
+            && param_value >= current_threshold - self.hysteresis
+            && param_value <= next_threshold + self.hysteresis
         {
-            last_layer.weight = 1.0
+            let range = next_threshold - current_threshold;
+            let t = if range.abs() < f32::EPSILON {
+                0.0
+            } else {
+                ((param_value - current_threshold) / range).clamp(0.0, 1.0)
+            };
+            weights[current_idx] = 1.0 - t;
+            weights[next_idx] = t;
+            return (weights, vec![current_idx, next_idx]);
+        }
+
+        // Edge cases if the parameter exceeds the first or last threshold
+        if param_value <= first {
+            weights[first_idx] = 1.0;
+            return (weights, vec![first_idx]);
+        }
+        if param_value >= last {
+            weights[last_idx] = 1.0;
+            return (weights, vec![last_idx]);
+        }
+
+        // Find which 2 values to blend between
+        for pair in thresholds.windows(2) {
+            let (current_idx, current_threshold) = pair[0];
+            let (next_idx, next_threshold) = pair[1];
+            if param_value >= current_threshold && param_value <= next_threshold {
+                let range = next_threshold - current_threshold;
+                let t = if range.abs() < f32::EPSILON {
+                    0.0
+                } else {
+                    (param_value - current_threshold) / range
+                };
+                weights[current_idx] = 1.0 - t;
+                weights[next_idx] = t;
+                return (weights, vec![current_idx, next_idx]);
+            }
         }
+        (weights, Vec::new())
     }
 
     #[inline(always)]
-    fn calculate_weights_2d(&mut self, x_param_value: f32, y_param_value: f32) {
-        // Reset all weights to 0 initially
-        for layer in self.blend_job.layers_mut() {
-            layer.weight = 0.0;
-        }
+    fn weights_2d(
+        &self,
+        x_param_value: f32,
+        y_param_value: f32,
+        sticky: bool,
+    ) -> (Vec<f32>, Vec<usize>) {
+        let mut weights = vec![0.0; self.motions.len()];
 
         // Need at least 3 motions for 2D blending
         if self.motions.len() < 3 {
-            if let Some(first) = self.blend_job.layers_mut().first_mut() {
-                first.weight = 1.0;
+            if let Some(first) = weights.first_mut() {
+                *first = 1.0;
+                return (weights, vec![0]);
             }
-            return;
+            return (weights, Vec::new());
         }
 
         // Get all motion thresholds as Vec2
-        let positions: Vec<Vec2> = self
+        let positions: Vec<(usize, Vec2)> = self
             .motions
             .iter()
-            .filter_map(|motion| {
+            .enumerate()
+            .filter_map(|(idx, motion)| {
                 if let MotionThreshold::Directional2D(x, y) = motion.threshold {
-                    Some(Vec2::new(x, y))
+                    Some((idx, Vec2::new(x, y)))
                 } else {
                     None
                 }
             })
             .collect();
+        let point = Vec2::new(x_param_value, y_param_value);
+
+        // Stay in the previous triangle while the point is within the hysteresis band,
+        // ignoring how far outside of it the point is
+        if sticky
+            && self.hysteresis > 0.0
+            && let [i_idx, j_idx, k_idx] = self.active_motions[..]
+            && let Some(p1) = position_2d(&self.motions[i_idx].threshold)
+            && let Some(p2) = position_2d(&self.motions[j_idx].threshold)
+            && let Some(p3) = position_2d(&self.motions[k_idx].threshold)
+            && let Some((w1, w2, w3)) = barycentric(p1, p2, p3, point)
+            && w1.min(w2).min(w3) >= -self.hysteresis
+        {
+            let (w1, w2, w3) = (w1.max(0.0), w2.max(0.0), w3.max(0.0));
+            let total = w1 + w2 + w3;
+            weights[i_idx] = w1 / total;
+            weights[j_idx] = w2 / total;
+            weights[k_idx] = w3 / total;
+            return (weights, vec![i_idx, j_idx, k_idx]);
+        }
 
         // Find the triangle that contains our point using barycentric coordinates
-        let point = Vec2::new(x_param_value, y_param_value);
         for i in 0..positions.len() {
-            let p1 = positions[i];
+            let (i_idx, p1) = positions[i];
 
             for j in i + 1..positions.len() {
-                let p2 = positions[j];
+                let (j_idx, p2) = positions[j];
 
-                for k in j + 1..positions.len() {
-                    let p3 = positions[k];
-                    // Calculate barycentric coordinates
-                    let denominator = (p2.y - p3.y) * (p1.x - p3.x) + (p3.x - p2.x) * (p1.y - p3.y);
-                    if denominator.abs() < f32::EPSILON {
+                for &(k_idx, p3) in &positions[j + 1..] {
+                    let Some((w1, w2, w3)) = barycentric(p1, p2, p3, point) else {
                         continue;
-                    }
-
-                    let w1 = ((p2.y - p3.y) * (point.x - p3.x) + (p3.x - p2.x) * (point.y - p3.y))
-                        / denominator;
-                    let w2 = ((p3.y - p1.y) * (point.x - p3.x) + (p1.x - p3.x) * (point.y - p3.y))
-                        / denominator;
-                    let w3 = 1.0 - w1 - w2;
+                    };
 
                     // If point is inside this triangle (all weights are positive)
                     if w1 >= 0.0 && w2 >= 0.0 && w3 >= 0.0 {
-                        let layers = self.blend_job.layers_mut();
-                        layers[i].weight = w1;
-                        layers[j].weight = w2;
-                        layers[k].weight = w3;
-                        return;
+                        weights[i_idx] = w1;
+                        weights[j_idx] = w2;
+                        weights[k_idx] = w3;
+                        return (weights, vec![i_idx, j_idx, k_idx]);
                     }
                 }
             }
         }
 
         // If point is outside all triangles, find nearest motion
-        let mut nearest_idx = 0;
-        let mut min_distance = f32::MAX;
-
-        for (idx, pos) in positions.iter().enumerate() {
-            let distance = point.distance(*pos);
-            if distance < min_distance {
-                min_distance = distance;
-                nearest_idx = idx;
+        let nearest = positions.iter().min_by(|(_, a), (_, b)| {
+            point.distance(*a).total_cmp(&point.distance(*b))
+        });
+
+        // Set weight to 1.0 for nearest motion
+        if let Some(&(nearest_idx, _)) = nearest {
+            weights[nearest_idx] = 1.0;
+            return (weights, vec![nearest_idx]);
+        }
+        (weights, Vec::new())
+    }
+
+    /// Weight of every motion straight from its parameter, unset parameters weigh 0
+    #[inline(always)]
+    fn weights_direct(&self, params: &Parameters) -> (Vec<f32>, Vec<usize>) {
+        let weights: Vec<f32> = self
+            .motions
+            .iter()
+            .enumerate()
+            .map(|(i, motion)| match &motion.threshold {
+                MotionThreshold::Direct(param_name) => {
+                    let binding = self.direct_bindings.get(i).copied().unwrap_or_default();
+                    params
+                        .bound_float(binding, param_name)
+                        .unwrap_or(0.0)
+                        .clamp(0.0, 1.0)
+                }
+                _ => 0.0,
+            })
+            .collect();
+        let active_motions = (0..weights.len()).filter(|&i| weights[i] > 0.0).collect();
+        (weights, active_motions)
+    }
+
+    /// Gradient band weights: every motion's influence falls off linearly
+    /// towards each other motion, and the influences are normalized
+    #[inline(always)]
+    fn weights_cartesian(&self, point: Vec2) -> (Vec<f32>, Vec<usize>) {
+        let mut weights = vec![0.0; self.motions.len()];
+        let positions: Vec<(usize, Vec2)> = self
+            .motions
+            .iter()
+            .enumerate()
+            .filter_map(|(idx, motion)| position_2d(&motion.threshold).map(|p| (idx, p)))
+            .collect();
+
+        for &(i_idx, p_i) in &positions {
+            let mut influence: f32 = 1.0;
+            for &(j_idx, p_j) in &positions {
+                let edge = p_j - p_i;
+                let length_squared = edge.length_squared();
+                if j_idx == i_idx || length_squared < f32::EPSILON {
+                    continue;
+                }
+                influence = influence.min(1.0 - (point - p_i).dot(edge) / length_squared);
             }
+            weights[i_idx] = influence.max(0.0);
         }
 
-        // Set weight to 1.0 for nearest layer
-        if let Some(layer) = self.blend_job.layers_mut().get_mut(nearest_idx) {
-            layer.weight = 1.0;
+        let total: f32 = weights.iter().sum();
+        if total <= f32::EPSILON {
+            return (weights, Vec::new());
         }
+        let mut active_motions = Vec::new();
+        for (idx, weight) in weights.iter_mut().enumerate() {
+            *weight /= total;
+            if *weight > 0.0 {
+                active_motions.push(idx);
+            }
+        }
+        (weights, active_motions)
     }
 
     #[inline(always)]
     pub fn get_output_pointer(&self) -> Arc<RwLock<Vec<SoaTransform>>> {
         self.output.clone()
     }
+
+    /// Move the markers crossed by the last update into `out`, or drop them if it is None
+    #[inline(always)]
+    pub(crate) fn drain_markers(&mut self, out: Option<&mut Vec<String>>) {
+        match out {
+            Some(out) => out.append(&mut self.fired_markers),
+            None => self.fired_markers.clear(),
+        }
+    }
+
+    /// Index of the motion with the highest weight, None if no motion is active
+    #[inline(always)]
+    fn dominant_motion(&self) -> Option<usize> {
+        self.blend_job
+            .layers()
+            .iter()
+            .enumerate()
+            .filter(|(_, layer)| layer.weight > 0.0)
+            .max_by(|(_, a), (_, b)| a.weight.total_cmp(&b.weight))
+            .map(|(i, _)| i)
+    }
+
+    /// Last sampled ratio and share of the final pose of every motion, given
+    /// the tree's own share
+    pub(crate) fn motion_stats(&self, weight: f32) -> Vec<MotionStats> {
+        let total: f32 = self.blend_job.layers().iter().map(|layer| layer.weight).sum();
+        // Direct trees only normalize above 1, the rest pose fills in below
+        let divisor = match self.blend_type {
+            BlendTreeType::Direct => total.max(1.0),
+            _ => total,
+        };
+        self.motions
+            .iter()
+            .zip(self.blend_job.layers())
+            .enumerate()
+            .map(|(index, (motion_data, layer))| {
+                let weight = if divisor > 0.0 { layer.weight / divisor * weight } else { 0.0 };
+                match &motion_data.motion {
+                    BlendMotionState::Animation(state) => MotionStats {
+                        index,
+                        ratio: read_lock(state).normalized_time(),
+                        weight,
+                        motions: Vec::new(),
+                    },
+                    BlendMotionState::SubTree(state) => {
+                        let state = read_lock(state);
+                        MotionStats {
+                            index,
+                            ratio: state.normalized_time(),
+                            weight,
+                            motions: state.motion_stats(weight),
+                        }
+                    }
+                }
+            })
+            .collect()
+    }
+
+    /// Sample the float tracks of every weighted motion into `out`, given the
+    /// tree's own weight, see [`Self::motion_stats`]
+    pub(crate) fn sample_curves(&self, weight: f32, out: &mut Vec<CurveSample>) {
+        let total: f32 = self.blend_job.layers().iter().map(|layer| layer.weight).sum();
+        let divisor = match self.blend_type {
+            BlendTreeType::Direct => total.max(1.0),
+            _ => total,
+        };
+        if divisor <= 0.0 {
+            return;
+        }
+        for (motion_data, layer) in self.motions.iter().zip(self.blend_job.layers()) {
+            if layer.weight <= 0.0 {
+                continue;
+            }
+            let weight = layer.weight / divisor * weight;
+            match &motion_data.motion {
+                BlendMotionState::Animation(state) => write_lock(state).sample_curves(weight, out),
+                BlendMotionState::SubTree(state) => read_lock(state).sample_curves(weight, out),
+            }
+        }
+    }
+
+    /// Resolve the input parameters of the tree and every parameter its
+    /// motions read to their ids, so updates skip two name lookups per tree.
+    /// Call it again whenever names are added to `params`, until then the
+    /// tree falls back to looking parameters up by name. Layers do this for
+    /// the trees of their states.
+    pub fn bind_parameters(&mut self, params: &Parameters) {
+        self.input_bindings = match &self.blend_type {
+            BlendTreeType::Simple1D(param_name) => [
+                ParamBinding::resolve(params, param_name),
+                ParamBinding::default(),
+            ],
+            BlendTreeType::Directional2D(x_param, y_param)
+            | BlendTreeType::FreeformCartesian2D(x_param, y_param) => [
+                ParamBinding::resolve(params, x_param),
+                ParamBinding::resolve(params, y_param),
+            ],
+            BlendTreeType::Direct => [ParamBinding::default(); 2],
+        };
+        self.direct_bindings = self
+            .motions
+            .iter()
+            .map(|motion_data| match &motion_data.threshold {
+                MotionThreshold::Direct(param_name) => ParamBinding::resolve(params, param_name),
+                _ => ParamBinding::default(),
+            })
+            .collect();
+        for motion_data in &self.motions {
+            match &motion_data.motion {
+                BlendMotionState::Animation(state) => write_lock(state).bind_parameters(params),
+                BlendMotionState::SubTree(state) => write_lock(state).bind_parameters(params),
+            }
+        }
+    }
+
+    /// Every parameter the tree and its motions read, with its default value
+    pub(crate) fn referenced_parameters(&self, out: &mut Vec<(String, ParameterValue)>) {
+        let mut push = |name: &String, default: f32| {
+            out.push((name.clone(), ParameterValue::Float(default)));
+        };
+        match &self.blend_type {
+            BlendTreeType::Simple1D(param_name) => push(param_name, 0.0),
+            BlendTreeType::Directional2D(x_param, y_param)
+            | BlendTreeType::FreeformCartesian2D(x_param, y_param) => {
+                push(x_param, 0.0);
+                push(y_param, 0.0);
+            }
+            BlendTreeType::Direct => {}
+        }
+        for motion_data in &self.motions {
+            if let MotionThreshold::Direct(param_name) = &motion_data.threshold {
+                push(param_name, 0.0);
+            }
+        }
+        for motion_data in &self.motions {
+            match &motion_data.motion {
+                BlendMotionState::Animation(state) => {
+                    if let Some(param_name) = read_lock(state).speed_parameter() {
+                        out.push((param_name.to_string(), ParameterValue::Float(1.0)));
+                    }
+                }
+                BlendMotionState::SubTree(state) => read_lock(state).referenced_parameters(out),
+            }
+        }
+    }
+
+    /// If both trees blend the same motions at the same thresholds, ignoring
+    /// their runtime state
+    pub(crate) fn same_graph(&self, other: &BlendTree) -> bool {
+        self.blend_type == other.blend_type
+            && self.motions.len() == other.motions.len()
+            && self.motions.iter().zip(&other.motions).all(|(old, new)| {
+                old.threshold == new.threshold
+                    && match (&old.motion, &new.motion) {
+                        (BlendMotionState::Animation(old), BlendMotionState::Animation(new)) => {
+                            Arc::ptr_eq(old, new) || read_lock(old).same_graph(&read_lock(new))
+                        }
+                        (BlendMotionState::SubTree(old), BlendMotionState::SubTree(new)) => {
+                            Arc::ptr_eq(old, new) || read_lock(old).same_graph(&read_lock(new))
+                        }
+                        _ => false,
+                    }
+            })
+    }
+
+    /// If the thresholds of this 1D tree and of every nested one rise in
+    /// motion order, as the blend expects
+    pub(crate) fn thresholds_sorted(&self) -> bool {
+        let sorted = !matches!(self.blend_type, BlendTreeType::Simple1D(_))
+            || self
+                .motions
+                .iter()
+                .filter_map(|motion| match motion.threshold {
+                    MotionThreshold::Simple1D(threshold) => Some(threshold),
+                    _ => None,
+                })
+                .is_sorted();
+        sorted
+            && self.motions.iter().all(|motion| match &motion.motion {
+                BlendMotionState::Animation(_) => true,
+                BlendMotionState::SubTree(state) => read_lock(state).thresholds_sorted(),
+            })
+    }
+
+    /// Normalized playback time of the motion with the highest weight
+    pub fn normalized_time(&self) -> f32 {
+        let Some(motion_data) = self.dominant_motion().map(|i| &self.motions[i]) else {
+            return 0.0;
+        };
+        match &motion_data.motion {
+            BlendMotionState::Animation(state) => read_lock(state).normalized_time(),
+            BlendMotionState::SubTree(state) => read_lock(state).normalized_time(),
+        }
+    }
+
+    /// If the motion with the highest weight played to the end of a clamped clip
+    pub fn is_finished(&self) -> bool {
+        let Some(motion_data) = self.dominant_motion().map(|i| &self.motions[i]) else {
+            return false;
+        };
+        match &motion_data.motion {
+            BlendMotionState::Animation(state) => read_lock(state).is_finished(),
+            BlendMotionState::SubTree(state) => read_lock(state).is_finished(),
+        }
+    }
+
+    /// Number of times the tree looped, counted each time the motion with the
+    /// highest weight wraps around
+    #[inline(always)]
+    pub fn loop_count(&self) -> u32 {
+        self.loop_count
+    }
+
+    /// Move every clip in the tree to a normalized time
+    pub fn seek(&mut self, normalized_time: f32) {
+        for motion_data in &self.motions {
+            match &motion_data.motion {
+                BlendMotionState::Animation(state) => {
+                    write_lock(state).seek(normalized_time);
+                }
+                BlendMotionState::SubTree(state) => {
+                    write_lock(state).seek(normalized_time);
+                }
+            }
+        }
+    }
+
+    /// Move every clip in the tree to a time in seconds
+    pub fn set_time(&mut self, time: f32) {
+        for motion_data in &self.motions {
+            match &motion_data.motion {
+                BlendMotionState::Animation(state) => {
+                    write_lock(state).set_time(time);
+                }
+                BlendMotionState::SubTree(state) => {
+                    write_lock(state).set_time(time);
+                }
+            }
+        }
+    }
+
+    /// Playback times of every clip in the tree, in depth-first order
+    pub(crate) fn collect_times(&self, times: &mut Vec<f32>) {
+        for motion_data in &self.motions {
+            match &motion_data.motion {
+                BlendMotionState::Animation(state) => {
+                    times.push(read_lock(state).time());
+                }
+                BlendMotionState::SubTree(state) => {
+                    read_lock(state).collect_times(times);
+                }
+            }
+        }
+    }
+
+    /// Restore playback times previously produced by `collect_times`
+    pub(crate) fn restore_times(&mut self, times: &mut impl Iterator<Item = f32>) {
+        for motion_data in &self.motions {
+            match &motion_data.motion {
+                BlendMotionState::Animation(state) => {
+                    if let Some(time) = times.next() {
+                        write_lock(state).set_time(time);
+                    }
+                }
+                BlendMotionState::SubTree(state) => {
+                    write_lock(state).restore_times(times);
+                }
+            }
+        }
+    }
+}
+
+/// Position of a 2D motion threshold
+#[inline(always)]
+fn position_2d(threshold: &MotionThreshold) -> Option<Vec2> {
+    match *threshold {
+        MotionThreshold::Directional2D(x, y) => Some(Vec2::new(x, y)),
+        _ => None,
+    }
+}
+
+/// Barycentric coordinates of `point` in the triangle `p1 p2 p3`, or None if
+/// the triangle is degenerate
+#[inline(always)]
+fn barycentric(p1: Vec2, p2: Vec2, p3: Vec2, point: Vec2) -> Option<(f32, f32, f32)> {
+    let denominator = (p2.y - p3.y) * (p1.x - p3.x) + (p3.x - p2.x) * (p1.y - p3.y);
+    if denominator.abs() < f32::EPSILON {
+        return None;
+    }
+
+    let w1 = ((p2.y - p3.y) * (point.x - p3.x) + (p3.x - p2.x) * (point.y - p3.y)) / denominator;
+    let w2 = ((p3.y - p1.y) * (point.x - p3.x) + (p1.x - p3.x) * (point.y - p3.y)) / denominator;
+    Some((w1, w2, 1.0 - w1 - w2))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{test_rig, test_state};
+
+    fn tree(blend_type: BlendTreeType, thresholds: Vec<MotionThreshold>) -> BlendTree {
+        let (skeleton, clip) = test_rig();
+        let motions = thresholds
+            .into_iter()
+            .map(|threshold| MotionData {
+                motion: BlendMotionState::Animation(Arc::new(RwLock::new(test_state(
+                    &skeleton, &clip,
+                )))),
+                threshold,
+            })
+            .collect();
+        BlendTree::new(&skeleton, blend_type, motions)
+    }
+
+    fn params(values: &[(&str, f32)]) -> Parameters {
+        let mut params = Parameters::new();
+        for &(name, value) in values {
+            params.set_float(name, value);
+        }
+        params
+    }
+
+    fn assert_weights(actual: Option<Vec<f32>>, expected: &[f32]) {
+        let actual = actual.expect("weights");
+        assert_eq!(actual.len(), expected.len());
+        for (actual, expected) in actual.iter().zip(expected) {
+            assert!((actual - expected).abs() < 1e-5, "{actual} != {expected}");
+        }
+    }
+
+    #[test]
+    fn freeform_cartesian_weights_are_normalized() {
+        let tree = tree(
+            BlendTreeType::FreeformCartesian2D("speed".to_string(), "lean".to_string()),
+            vec![
+                MotionThreshold::Directional2D(0.0, 0.0),
+                MotionThreshold::Directional2D(1.0, 0.0),
+                MotionThreshold::Directional2D(0.0, 1.0),
+                MotionThreshold::Directional2D(1.0, 1.0),
+            ],
+        );
+        // On a motion it plays alone
+        assert_weights(
+            tree.weights(&params(&[("speed", 1.0), ("lean", 0.0)])),
+            &[0.0, 1.0, 0.0, 0.0],
+        );
+        // In the middle every motion has the same influence
+        assert_weights(
+            tree.weights(&params(&[("speed", 0.5), ("lean", 0.5)])),
+            &[0.25, 0.25, 0.25, 0.25],
+        );
+        let weights = tree.weights(&params(&[("speed", 0.8), ("lean", 0.3)])).unwrap();
+        assert!((weights.iter().sum::<f32>() - 1.0).abs() < 1e-5);
+        assert!(weights.iter().all(|&weight| weight >= 0.0));
+    }
+
+    #[test]
+    fn directional_2d_uses_barycentric_weights() {
+        let tree = tree(
+            BlendTreeType::Directional2D("x".to_string(), "y".to_string()),
+            vec![
+                MotionThreshold::Directional2D(0.0, 0.0),
+                MotionThreshold::Directional2D(1.0, 0.0),
+                MotionThreshold::Directional2D(0.0, 1.0),
+            ],
+        );
+        assert_weights(tree.weights(&params(&[("x", 0.25), ("y", 0.25)])), &[0.5, 0.25, 0.25]);
+        assert_weights(tree.weights(&params(&[("x", 1.0), ("y", 0.0)])), &[0.0, 1.0, 0.0]);
+        // Outside of every triangle the nearest motion plays alone
+        assert_weights(tree.weights(&params(&[("x", -1.0), ("y", -0.5)])), &[1.0, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn simple_1d_blends_between_neighbouring_thresholds() {
+        let tree = tree(
+            BlendTreeType::Simple1D("speed".to_string()),
+            vec![
+                MotionThreshold::Simple1D(0.0),
+                MotionThreshold::Simple1D(1.0),
+                MotionThreshold::Simple1D(3.0),
+            ],
+        );
+        assert_weights(tree.weights(&params(&[("speed", 0.25)])), &[0.75, 0.25, 0.0]);
+        assert_weights(tree.weights(&params(&[("speed", 2.5)])), &[0.0, 0.25, 0.75]);
+        assert_weights(tree.weights(&params(&[("speed", 1.0)])), &[0.0, 1.0, 0.0]);
+        // Values past either end play the motion at that end
+        assert_weights(tree.weights(&params(&[("speed", -1.0)])), &[1.0, 0.0, 0.0]);
+        assert_weights(tree.weights(&params(&[("speed", 5.0)])), &[0.0, 0.0, 1.0]);
+        assert_eq!(tree.weights(&Parameters::new()), None);
+    }
+
+    #[test]
+    fn direct_weights_are_clamped_parameters() {
+        let tree = tree(
+            BlendTreeType::Direct,
+            vec![
+                MotionThreshold::Direct("a".to_string()),
+                MotionThreshold::Direct("b".to_string()),
+                MotionThreshold::Direct("c".to_string()),
+            ],
+        );
+        // Unset parameters weigh nothing
+        assert_weights(tree.weights(&params(&[("a", 0.4), ("b", 2.0)])), &[0.4, 1.0, 0.0]);
+        assert_weights(tree.weights(&params(&[("a", -1.0)])), &[0.0, 0.0, 0.0]);
+    }
 }
@@ -0,0 +1,117 @@
+use super::pose::pose_to_bones;
+use super::{
+    AnimationTime, AnimatorError, AnimatorErrorEvent, BlendTree, OzzTransform, Parameters,
+    read_lock,
+};
+use bevy::prelude::*;
+use ozz_animation_rs::Skeleton;
+use std::sync::Arc;
+
+/// Plays a single blend tree driven by its own parameters, without layers or
+/// transitions, for props and background characters that only blend a few
+/// clips. Bound to skinned meshes like an `AnimatorController`.
+/// ## Example
+/// ```ignore
+/// let tree = BlendTree::new(&skeleton, BlendTreeType::Simple1D("speed".to_string()), motions);
+/// let mut parameters = Parameters::new();
+/// parameters.set_float("speed", 0.0);
+/// commands.spawn((SceneRoot(flag_mesh), BlendTreePlayer::new(skeleton, tree, parameters)));
+///
+/// fn wind(mut flags: Query<&mut BlendTreePlayer>, wind: Res<Wind>) {
+///     for mut flag in &mut flags {
+///         flag.parameters_mut().set_float("speed", wind.strength);
+///     }
+/// }
+/// ```
+#[derive(Component, Debug)]
+pub struct BlendTreePlayer {
+    skeleton: Arc<Skeleton>,
+    blend_tree: BlendTree,
+    parameters: Parameters,
+    /// Layout of the parameters the tree was last bound to
+    bound_layout: u64,
+    /// Pose of every joint after the last update
+    pub(crate) bone_trans: Vec<OzzTransform>,
+}
+
+// SAFETY: see AnimatorController
+unsafe impl Send for BlendTreePlayer {}
+unsafe impl Sync for BlendTreePlayer {}
+
+impl BlendTreePlayer {
+    #[inline]
+    pub fn new(skeleton: Arc<Skeleton>, blend_tree: BlendTree, parameters: Parameters) -> Self {
+        Self {
+            skeleton,
+            blend_tree,
+            parameters,
+            bound_layout: 0,
+            bone_trans: Vec::new(),
+        }
+    }
+
+    #[inline]
+    pub fn skeleton(&self) -> &Arc<Skeleton> {
+        &self.skeleton
+    }
+
+    #[inline]
+    pub fn blend_tree(&self) -> &BlendTree {
+        &self.blend_tree
+    }
+
+    #[inline]
+    pub fn blend_tree_mut(&mut self) -> &mut BlendTree {
+        // The tree may be replaced, bind it again on the next update
+        self.bound_layout = 0;
+        &mut self.blend_tree
+    }
+
+    #[inline]
+    pub fn parameters(&self) -> &Parameters {
+        &self.parameters
+    }
+
+    #[inline]
+    pub fn parameters_mut(&mut self) -> &mut Parameters {
+        &mut self.parameters
+    }
+
+    /// Transform of a joint after the last update
+    #[inline]
+    pub fn bone(&self, index: usize) -> Option<OzzTransform> {
+        self.bone_trans.get(index).copied()
+    }
+
+    /// Advance the tree, blend its motions and store the resulting pose
+    pub fn update(&mut self, time: &Time) -> Result<(), AnimatorError> {
+        if self.bound_layout != self.parameters.layout() {
+            self.blend_tree.bind_parameters(&self.parameters);
+            self.bound_layout = self.parameters.layout();
+        }
+        self.blend_tree.update(time, &mut self.parameters)?;
+        self.parameters.reset_triggers();
+        // Nothing listens for markers without a controller
+        self.blend_tree.drain_markers(None);
+
+        let output = self.blend_tree.get_output_pointer();
+        let pose = read_lock(&output);
+        pose_to_bones(&pose, self.skeleton.num_joints(), &mut self.bone_trans);
+        Ok(())
+    }
+}
+
+/// Update every blend tree player, keeping the last pose of the ones that fail
+pub fn update_blend_tree_players(
+    mut players: Query<(Entity, &mut BlendTreePlayer)>,
+    time: Res<Time>,
+    animation_time: Option<Res<AnimationTime>>,
+    mut errors: EventWriter<AnimatorErrorEvent>,
+) {
+    let time = animation_time.as_deref().copied().unwrap_or_default().scale_time(&time);
+    for (entity, mut player) in &mut players {
+        if let Err(error) = player.update(&time) {
+            errors.write(AnimatorErrorEvent { entity, error });
+        }
+    }
+}
@@ -0,0 +1,297 @@
+use super::{AnimatorController, AnimatorError, AnimatorEventWriters, AsyncAnimation};
+use bevy::platform::time::Instant;
+use bevy::prelude::*;
+use std::time::Duration;
+
+/// How important a controller is to update on time. Sets the update order
+/// under an [`AnimationBudget`], and the update rate through [`AnimationPriorities`].
+/// Controllers without the component are `Normal`.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub enum AnimationPriority {
+    /// Cosmetic animation like crowds, first to be deferred
+    Low,
+    #[default]
+    Normal,
+    /// Gameplay critical animation like the player, never deferred
+    High,
+}
+
+/// How controllers of one priority are updated
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PrioritySettings {
+    /// Sample the pose every `update_interval` frames, 1 samples every frame
+    pub update_interval: u32,
+    /// Keep running the state machines on the frames in between, so
+    /// transitions and events like `StateLoopEvent` happen on time. Otherwise
+    /// those frames are skipped entirely and caught up on the next update.
+    pub events_when_throttled: bool,
+}
+
+impl Default for PrioritySettings {
+    fn default() -> Self {
+        Self {
+            update_interval: 1,
+            events_when_throttled: true,
+        }
+    }
+}
+
+/// Update settings for every [`AnimationPriority`], added by the plugin. By
+/// default `Low` priority controllers only update every other frame.
+/// ## Example
+/// ```ignore
+/// let mut priorities = AnimationPriorities::default();
+/// priorities.set(
+///     AnimationPriority::Low,
+///     PrioritySettings {
+///         update_interval: 4,
+///         events_when_throttled: false,
+///     },
+/// );
+/// app.insert_resource(priorities);
+/// ```
+#[derive(Resource, Debug, Clone)]
+pub struct AnimationPriorities {
+    low: PrioritySettings,
+    normal: PrioritySettings,
+    high: PrioritySettings,
+}
+
+impl Default for AnimationPriorities {
+    fn default() -> Self {
+        Self {
+            low: PrioritySettings {
+                update_interval: 2,
+                events_when_throttled: false,
+            },
+            normal: PrioritySettings::default(),
+            high: PrioritySettings::default(),
+        }
+    }
+}
+
+impl AnimationPriorities {
+    #[inline]
+    pub fn get(&self, priority: AnimationPriority) -> PrioritySettings {
+        match priority {
+            AnimationPriority::Low => self.low,
+            AnimationPriority::Normal => self.normal,
+            AnimationPriority::High => self.high,
+        }
+    }
+
+    #[inline]
+    pub fn set(&mut self, priority: AnimationPriority, settings: PrioritySettings) {
+        match priority {
+            AnimationPriority::Low => self.low = settings,
+            AnimationPriority::Normal => self.normal = settings,
+            AnimationPriority::High => self.high = settings,
+        }
+    }
+}
+
+/// Optional per-frame time budget for updating controllers. When inserted,
+/// controllers update in priority order and the ones left once the budget is
+/// spent are deferred to the next frame, catching up on the time they missed.
+/// ## Example
+/// ```ignore
+/// app.insert_resource(AnimationBudget::new(Duration::from_millis(2)));
+/// commands.entity(player).insert(AnimationPriority::High);
+/// ```
+#[derive(Resource, Debug, Clone)]
+pub struct AnimationBudget {
+    /// Time controllers may take to update each frame
+    pub frame_budget: Duration,
+    /// Longest a controller may be deferred for before it updates regardless of the budget
+    pub max_deferral: Duration,
+    /// Controllers deferred during the last frame
+    deferred: usize,
+}
+
+impl AnimationBudget {
+    #[inline]
+    pub fn new(frame_budget: Duration) -> Self {
+        Self {
+            frame_budget,
+            max_deferral: Duration::from_millis(100),
+            deferred: 0,
+        }
+    }
+
+    #[inline]
+    pub fn with_max_deferral(mut self, max_deferral: Duration) -> Self {
+        self.max_deferral = max_deferral;
+        self
+    }
+
+    /// Number of controllers deferred during the last frame
+    #[inline]
+    pub fn deferred(&self) -> usize {
+        self.deferred
+    }
+
+    /// Update controllers in priority order until the budget is spent, the
+    /// longest deferred first within a priority
+    pub(crate) fn run(
+        &mut self,
+        query: &mut Query<
+            (Entity, &mut AnimatorController, Option<&AnimationPriority>),
+            Without<AsyncAnimation>,
+        >,
+        delta: impl Fn(Entity) -> Duration,
+        max_delta: Option<Duration>,
+        mut update: impl FnMut(Entity, &mut AnimatorController, AnimationPriority),
+    ) {
+        let start = Instant::now();
+        let mut order: Vec<(Entity, AnimationPriority, Duration)> = query
+            .iter()
+            .map(|(entity, controller, priority)| {
+                (
+                    entity,
+                    priority.copied().unwrap_or_default(),
+                    controller.deferred_time,
+                )
+            })
+            .collect();
+        order.sort_unstable_by(|a, b| b.1.cmp(&a.1).then(b.2.cmp(&a.2)));
+
+        self.deferred = 0;
+        for (entity, priority, _) in order {
+            let Ok((_, mut controller, _)) = query.get_mut(entity) else {
+                continue;
+            };
+            if priority < AnimationPriority::High
+                && controller.deferred_time < self.max_deferral
+                && start.elapsed() >= self.frame_budget
+            {
+                let delta = frame_delta(&controller, delta(entity), max_delta);
+                controller.deferred_time += delta;
+                self.deferred += 1;
+                continue;
+            }
+            update(entity, &mut controller, priority);
+        }
+    }
+}
+
+/// Time a frame advances a controller by, scaled and clamped to `max_delta`
+#[inline]
+fn frame_delta(
+    controller: &AnimatorController,
+    delta: Duration,
+    max_delta: Option<Duration>,
+) -> Duration {
+    let delta = controller.scale_delta(delta);
+    match controller.max_delta_override().unwrap_or(max_delta) {
+        Some(max_delta) => delta.min(max_delta),
+        None => delta,
+    }
+}
+
+/// Update a single controller following its priority's settings. Every frame
+/// is clamped to `max_delta`, and time missed while throttled or deferred is
+/// caught up on in steps no longer than `max_delta` or `sub_step`.
+pub(crate) fn update_controller(
+    entity: Entity,
+    controller: &mut AnimatorController,
+    settings: PrioritySettings,
+    delta: Duration,
+    max_delta: Option<Duration>,
+    sub_step: Option<Duration>,
+    events: &mut AnimatorEventWriters,
+) {
+    let result = advance_controller(controller, settings, delta, max_delta, sub_step);
+    events.send(entity, controller, result);
+}
+
+/// [`update_controller`] without sending the events, which stay queued on
+/// the controller
+pub(crate) fn advance_controller(
+    controller: &mut AnimatorController,
+    settings: PrioritySettings,
+    delta: Duration,
+    max_delta: Option<Duration>,
+    sub_step: Option<Duration>,
+) -> Result<(), AnimatorError> {
+    let delta = frame_delta(controller, delta, max_delta);
+    let max_delta = controller.max_delta_override().unwrap_or(max_delta);
+    let sub_step = controller.sub_step_override().unwrap_or(sub_step);
+    // Both bound a single step, a zero limit means nothing to split by
+    let step_limit = [max_delta, sub_step]
+        .into_iter()
+        .flatten()
+        .filter(|limit| !limit.is_zero())
+        .min();
+    let step = |delta: Duration| {
+        let mut step = Time::<()>::default();
+        step.advance_by(delta);
+        step
+    };
+    // Run the state machines through all but the last step, which samples
+    let advance = |controller: &mut AnimatorController,
+                   delta: Duration,
+                   sample: bool|
+     -> Result<(), AnimatorError> {
+        let mut delta = delta;
+        if let Some(step_limit) = step_limit {
+            while delta > step_limit {
+                controller.tick(&step(step_limit), false)?;
+                delta -= step_limit;
+            }
+        }
+        match sample {
+            true => controller.update(&step(delta)),
+            false => controller.tick(&step(delta), false),
+        }
+    };
+
+    controller.throttled_frames += 1;
+    if controller.throttled_frames >= settings.update_interval {
+        controller.throttled_frames = 0;
+        let delta = std::mem::take(&mut controller.deferred_time) + delta;
+        advance(controller, delta, true)
+    } else if settings.events_when_throttled {
+        advance(controller, delta, false)
+    } else {
+        controller.deferred_time += delta;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{AnimationState, SyntheticAnimators};
+
+    fn clip_time(controller: &AnimatorController) -> f32 {
+        let layer = &controller.layers[0];
+        match layer.state(layer.current_state()) {
+            Some(AnimationState::Simple(state)) => state.time(),
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn throttled_time_is_caught_up_in_steps() {
+        let animators = SyntheticAnimators::new(1, 4).with_clips(1).with_clip_duration(2.0);
+        let (skeleton, clips) = animators.rig().unwrap();
+        let mut controller = animators.controller(&skeleton, &clips, 0).unwrap();
+        let settings = PrioritySettings {
+            update_interval: 4,
+            events_when_throttled: false,
+        };
+        let max_delta = Some(Duration::from_millis(100));
+        for _ in 0..4 {
+            let delta = Duration::from_millis(50);
+            advance_controller(&mut controller, settings, delta, max_delta, None).unwrap();
+        }
+        assert!((clip_time(&controller) - 0.2).abs() < 1e-4, "{}", clip_time(&controller));
+
+        // A single long frame is still clamped
+        for _ in 0..4 {
+            let delta = Duration::from_secs(2);
+            advance_controller(&mut controller, settings, delta, max_delta, None).unwrap();
+        }
+        assert!((clip_time(&controller) - 0.6).abs() < 1e-4, "{}", clip_time(&controller));
+    }
+}
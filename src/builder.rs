@@ -0,0 +1,393 @@
+use super::{
+    AnimationLayer, AnimationState, AnimatorController, AnimatorError, JointMask, LayerBlendType,
+    ParameterValue, Parameters, Transition, TransitionCondition,
+};
+use super::missing_parameters::is_set;
+use ozz_animation_rs::Skeleton;
+use std::collections::HashSet;
+use std::sync::Arc;
+
+/// Assembles an [`AnimatorController`] from layers, states, transitions and
+/// parameters, checking every name before building it
+/// ## Example
+/// ```ignore
+/// let controller = AnimatorControllerBuilder::new(skeleton.clone())
+///     .with_parameter("is_running", false)
+///     .with_layer(
+///         LayerBuilder::new("Base Layer")
+///             .with_state(StateBuilder::new("Idle", idle).with_transition(Transition {
+///                 to_state: "Run".to_string(),
+///                 duration: 0.5,
+///                 conditions: vec![TransitionCondition::Bool("is_running".to_string(), true)],
+///                 ..default()
+///             }))
+///             .with_state(StateBuilder::new("Run", run).with_transition(Transition {
+///                 to_state: "Idle".to_string(),
+///                 duration: 0.5,
+///                 conditions: vec![TransitionCondition::Bool("is_running".to_string(), false)],
+///                 ..default()
+///             })),
+///     )
+///     .build()?;
+/// ```
+#[derive(Debug)]
+pub struct AnimatorControllerBuilder {
+    skeleton: Arc<Skeleton>,
+    layers: Vec<LayerBuilder>,
+    parameters: Parameters,
+    predicates: Vec<(String, Vec<TransitionCondition>)>,
+}
+
+impl AnimatorControllerBuilder {
+    #[inline]
+    pub fn new(skeleton: Arc<Skeleton>) -> Self {
+        Self {
+            skeleton,
+            layers: Vec::new(),
+            parameters: Parameters::new(),
+            predicates: Vec::new(),
+        }
+    }
+
+    /// Add a layer, layers blend in the order they are added
+    #[inline]
+    pub fn with_layer(mut self, layer: LayerBuilder) -> Self {
+        self.layers.push(layer);
+        self
+    }
+
+    /// Declare a parameter with its initial value
+    #[inline]
+    pub fn with_parameter(mut self, name: &str, value: impl Into<ParameterValue>) -> Self {
+        self.parameters.set(name, value.into());
+        self
+    }
+
+    /// Start from existing parameters, e.g. loaded from a file
+    #[inline]
+    pub fn with_parameters(mut self, parameters: Parameters) -> Self {
+        self.parameters = parameters;
+        self
+    }
+
+    /// Define a named condition set, see [`AnimatorController::with_predicate`]
+    #[inline]
+    pub fn with_predicate(
+        mut self,
+        name: impl Into<String>,
+        conditions: Vec<TransitionCondition>,
+    ) -> Self {
+        self.predicates.push((name.into(), conditions));
+        self
+    }
+
+    /// Check every name and build the controller. Fails on duplicate layers,
+    /// states or predicates, layers without states, default states that don't
+    /// exist, transitions to states that don't exist, conditions referencing
+    /// predicates that were never defined and conditions, blend trees and speed
+    /// parameters reading parameters that weren't declared with
+    /// [`Self::with_parameter`], and transition masks naming joints the
+    /// skeleton doesn't have. Triggers don't need declaring.
+    pub fn build(self) -> Result<AnimatorController, AnimatorError> {
+        let mut predicate_names = HashSet::new();
+        for (name, _) in &self.predicates {
+            if !predicate_names.insert(name.as_str()) {
+                return Err(AnimatorError::DuplicatePredicate(name.clone()));
+            }
+        }
+        let check_condition = |condition: &TransitionCondition| match condition {
+            TransitionCondition::Predicate(name) if !predicate_names.contains(name.as_str()) => {
+                Err(AnimatorError::UnknownPredicate(name.clone()))
+            }
+            _ => Ok(()),
+        };
+        for condition in self.predicates.iter().flat_map(|(_, conditions)| conditions) {
+            check_condition(condition)?;
+        }
+
+        let mut layer_names = HashSet::new();
+        for layer in &self.layers {
+            if !layer_names.insert(layer.name.as_str()) {
+                return Err(AnimatorError::DuplicateLayer(layer.name.clone()));
+            }
+            if layer.states.is_empty() {
+                return Err(AnimatorError::EmptyLayer(layer.name.clone()));
+            }
+            let mut state_names = HashSet::new();
+            for state in &layer.states {
+                if !state_names.insert(state.name.as_str()) {
+                    return Err(AnimatorError::DuplicateState {
+                        layer: layer.name.clone(),
+                        state: state.name.clone(),
+                    });
+                }
+            }
+            for condition in &layer.activation {
+                check_condition(condition)?;
+            }
+            for transition in layer.states.iter().flat_map(|state| &state.transitions) {
+                if !state_names.contains(transition.to_state.as_str()) {
+                    return Err(AnimatorError::UnknownState {
+                        layer: layer.name.clone(),
+                        state: transition.to_state.clone(),
+                    });
+                }
+                for condition in &transition.conditions {
+                    check_condition(condition)?;
+                }
+                if let Some(mask) = &transition.mask {
+                    mask.clone().bind(&self.skeleton)?;
+                }
+            }
+        }
+
+        let layers: Vec<AnimationLayer> = self
+            .layers
+            .into_iter()
+            .map(|layer| layer.build(&self.skeleton))
+            .collect();
+        let mut referenced = Vec::new();
+        for layer in &layers {
+            layer.referenced_parameters(&mut referenced);
+        }
+        referenced.extend(
+            self.predicates
+                .iter()
+                .flat_map(|(_, conditions)| conditions)
+                .filter_map(TransitionCondition::referenced_parameter),
+        );
+        for (name, default) in referenced {
+            // Loop counts are written by the controller itself
+            if !is_set(&self.parameters, &name, default)
+                && !layers.iter().any(|layer| layer.loop_count_parameter() == name)
+            {
+                return Err(AnimatorError::UnknownParameter(name));
+            }
+        }
+        let mut controller = AnimatorController::new(self.skeleton, layers, self.parameters)?;
+        for (name, conditions) in self.predicates {
+            controller.set_predicate(name, conditions);
+        }
+        Ok(controller)
+    }
+}
+
+/// A layer of an [`AnimatorControllerBuilder`]
+#[derive(Debug)]
+pub struct LayerBuilder {
+    name: String,
+    blend_type: LayerBlendType,
+    weight: f32,
+    joint_mask: Option<JointMask>,
+    activation: Vec<TransitionCondition>,
+    random_start: Vec<String>,
+    /// None plays the first state added
+    default_state: Option<String>,
+    states: Vec<StateBuilder>,
+}
+
+impl LayerBuilder {
+    /// An override layer with a weight of 1
+    #[inline]
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            blend_type: LayerBlendType::Override,
+            weight: 1.0,
+            joint_mask: None,
+            activation: Vec::new(),
+            random_start: Vec::new(),
+            default_state: None,
+            states: Vec::new(),
+        }
+    }
+
+    #[inline]
+    pub fn with_blend_type(mut self, blend_type: LayerBlendType) -> Self {
+        self.blend_type = blend_type;
+        self
+    }
+
+    #[inline]
+    pub fn with_weight(mut self, weight: f32) -> Self {
+        self.weight = weight;
+        self
+    }
+
+    #[inline]
+    pub fn with_joint_mask(mut self, mask: JointMask) -> Self {
+        self.joint_mask = Some(mask);
+        self
+    }
+
+    /// Only play the layer while all of `conditions` hold, see
+    /// [`AnimationLayer::with_activation`]
+    #[inline]
+    pub fn with_activation(mut self, conditions: Vec<TransitionCondition>) -> Self {
+        self.activation = conditions;
+        self
+    }
+
+    /// Start a clip state at a random time whenever it is entered, see
+    /// [`AnimationLayer::with_random_start`]
+    #[inline]
+    pub fn with_random_start(mut self, state: impl Into<String>) -> Self {
+        self.random_start.push(state.into());
+        self
+    }
+
+    /// State the layer starts in, the first state added by default
+    #[inline]
+    pub fn with_default_state(mut self, name: impl Into<String>) -> Self {
+        self.default_state = Some(name.into());
+        self
+    }
+
+    #[inline]
+    pub fn with_state(mut self, state: StateBuilder) -> Self {
+        self.states.push(state);
+        self
+    }
+
+    fn build(self, skeleton: &Arc<Skeleton>) -> AnimationLayer {
+        let default_state = self
+            .default_state
+            .or_else(|| self.states.first().map(|state| state.name.clone()))
+            .unwrap_or_default();
+        let mut layer =
+            AnimationLayer::new(self.name, self.blend_type, self.weight, skeleton, default_state);
+        if let Some(mask) = self.joint_mask {
+            layer = layer.with_joint_mask(mask);
+        }
+        layer.set_activation(self.activation);
+        for state in &self.random_start {
+            layer.set_random_start(state, true);
+        }
+        for state in self.states {
+            layer.add_state(state.name.clone(), state.state);
+            for tag in state.tags {
+                layer.add_state_tag(state.name.clone(), tag);
+            }
+            for transition in state.transitions {
+                layer.add_transition(state.name.clone(), transition);
+            }
+        }
+        layer
+    }
+}
+
+/// A state of a [`LayerBuilder`] with the transitions leaving it
+#[derive(Debug)]
+pub struct StateBuilder {
+    name: String,
+    state: AnimationState,
+    transitions: Vec<Transition>,
+    tags: Vec<String>,
+}
+
+impl StateBuilder {
+    #[inline]
+    pub fn new(name: impl Into<String>, state: impl Into<AnimationState>) -> Self {
+        Self {
+            name: name.into(),
+            state: state.into(),
+            transitions: Vec::new(),
+            tags: Vec::new(),
+        }
+    }
+
+    /// Add a transition out of the state, transitions are checked in the order
+    /// they are added
+    #[inline]
+    pub fn with_transition(mut self, transition: Transition) -> Self {
+        self.transitions.push(transition);
+        self
+    }
+
+    /// Tag the state, see [`AnimationLayer::with_state_tag`]
+    #[inline]
+    pub fn with_tag(mut self, tag: impl Into<String>) -> Self {
+        self.tags.push(tag.into());
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        BlendMotionState, BlendState, BlendTree, BlendTreeType, MotionData, MotionThreshold,
+        SimpleState, synthetic_clip, synthetic_skeleton,
+    };
+    use std::sync::RwLock;
+
+    fn idle_and_run(skeleton: &Arc<Skeleton>, condition: TransitionCondition) -> LayerBuilder {
+        let clip = Arc::new(synthetic_clip(skeleton, 1.0, 30.0, 0));
+        let state = || SimpleState::from_recording(clip.clone(), skeleton.num_soa_joints());
+        LayerBuilder::new("Base")
+            .with_state(StateBuilder::new("Idle", state()).with_transition(Transition {
+                to_state: "Run".to_string(),
+                conditions: vec![condition],
+                ..Default::default()
+            }))
+            .with_state(StateBuilder::new("Run", state()))
+    }
+
+    #[test]
+    fn conditions_need_declared_parameters() {
+        let skeleton = Arc::new(synthetic_skeleton(4, 1).unwrap());
+        let running = TransitionCondition::Bool("is_running".to_string(), true);
+
+        let error = AnimatorControllerBuilder::new(skeleton.clone())
+            .with_layer(idle_and_run(&skeleton, running.clone()))
+            .build()
+            .unwrap_err();
+        assert!(matches!(error, AnimatorError::UnknownParameter(name) if name == "is_running"));
+        // Declared with the wrong type
+        let error = AnimatorControllerBuilder::new(skeleton.clone())
+            .with_parameter("is_running", 1.0)
+            .with_layer(idle_and_run(&skeleton, running.clone()))
+            .build()
+            .unwrap_err();
+        assert!(matches!(error, AnimatorError::UnknownParameter(_)));
+
+        assert!(
+            AnimatorControllerBuilder::new(skeleton.clone())
+                .with_parameter("is_running", false)
+                .with_layer(idle_and_run(&skeleton, running))
+                .build()
+                .is_ok()
+        );
+        let jump = TransitionCondition::Trigger("jump".to_string());
+        assert!(
+            AnimatorControllerBuilder::new(skeleton.clone())
+                .with_layer(idle_and_run(&skeleton, jump))
+                .build()
+                .is_ok()
+        );
+    }
+
+    #[test]
+    fn blend_trees_need_declared_parameters() {
+        let skeleton = Arc::new(synthetic_skeleton(4, 1).unwrap());
+        let clip = Arc::new(synthetic_clip(&skeleton, 1.0, 30.0, 0));
+        let builder = || {
+            let motion = MotionData {
+                motion: BlendMotionState::Animation(Arc::new(RwLock::new(
+                    SimpleState::from_recording(clip.clone(), skeleton.num_soa_joints()),
+                ))),
+                threshold: MotionThreshold::Simple1D(0.0),
+            };
+            let blend_type = BlendTreeType::Simple1D("speed".to_string());
+            let tree = BlendTree::new(&skeleton, blend_type, vec![motion]);
+            AnimatorControllerBuilder::new(skeleton.clone()).with_layer(
+                LayerBuilder::new("Base")
+                    .with_state(StateBuilder::new("Move", BlendState::new(tree))),
+            )
+        };
+
+        let error = builder().build().unwrap_err();
+        assert!(matches!(error, AnimatorError::UnknownParameter(name) if name == "speed"));
+        assert!(builder().with_parameter("speed", 0.0).build().is_ok());
+    }
+}
@@ -0,0 +1,172 @@
+use super::pose::get_joint;
+use super::skeleton::find_joint;
+use super::{PoseRecorder, RecordedClip};
+use bevy::animation::graph::{AnimationGraph, AnimationGraphHandle, AnimationNodeIndex};
+use bevy::animation::{AnimationClip, AnimationPlayer};
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+use ozz_animation_rs::Skeleton;
+use std::sync::Arc;
+
+/// Bakes a Bevy `AnimationClip`, e.g. one that only exists in a glTF file, into
+/// a [`RecordedClip`] playable with
+/// [`SimpleState::from_recording`](super::SimpleState::from_recording). Put it
+/// on a spawned scene of the glTF: Bevy's own animation player poses the scene
+/// one frame at a time and the joints are recorded by name against the ozz
+/// skeleton, ignoring case and namespace prefixes. Joints the scene doesn't
+/// have keep their rest pose.
+/// ## Example
+/// ```ignore
+/// commands.spawn((
+///     SceneRoot(asset_server.load("wave.glb#Scene0")),
+///     Visibility::Hidden,
+///     AnimationClipBaker::new(asset_server.load("wave.glb#Animation0"), skeleton.clone(), 30.0),
+/// ));
+///
+/// fn use_baked(bakers: Query<(Entity, &AnimationClipBaker)>, mut commands: Commands) {
+///     for (entity, baker) in &bakers {
+///         if let Some(clip) = baker.baked_clip() {
+///             let wave = SimpleState::from_recording(clip.clone(), skeleton.num_soa_joints());
+///             // .. add the state to a layer
+///             commands.entity(entity).despawn();
+///         }
+///     }
+/// }
+/// ```
+#[derive(Component, Debug)]
+pub struct AnimationClipBaker {
+    clip: Handle<AnimationClip>,
+    skeleton: Arc<Skeleton>,
+    /// Frames recorded per second of the clip
+    frame_rate: f32,
+    /// Player posing the scene, once found
+    player: Option<(Entity, AnimationNodeIndex)>,
+    /// Scene entity of every skeleton joint, in skeleton order
+    joints: Vec<Option<Entity>>,
+    /// Clip time the scene was last posed at, None until the first pose was requested
+    posed_time: Option<f32>,
+    recorder: PoseRecorder,
+    baked: Option<Arc<RecordedClip>>,
+}
+
+impl AnimationClipBaker {
+    #[inline]
+    pub fn new(clip: Handle<AnimationClip>, skeleton: Arc<Skeleton>, frame_rate: f32) -> Self {
+        Self {
+            clip,
+            skeleton,
+            frame_rate: frame_rate.max(1.0),
+            player: None,
+            joints: Vec::new(),
+            posed_time: None,
+            recorder: PoseRecorder::new(0.0),
+            baked: None,
+        }
+    }
+
+    /// The finished clip, None while baking
+    #[inline]
+    pub fn baked_clip(&self) -> Option<&Arc<RecordedClip>> {
+        self.baked.as_ref()
+    }
+
+    #[inline]
+    pub fn is_finished(&self) -> bool {
+        self.baked.is_some()
+    }
+}
+
+/// The Bevy animation assets bakers read and add
+#[derive(SystemParam)]
+pub struct BakerAssets<'w> {
+    clips: Res<'w, Assets<AnimationClip>>,
+    graphs: ResMut<'w, Assets<AnimationGraph>>,
+}
+
+/// Step every baker through its clip, one frame of the clip per app frame.
+/// Added by the plugin when `OzzAnimationPlugin::bake_clips` is set.
+pub fn bake_animation_clips(
+    mut bakers: Query<(Entity, &mut AnimationClipBaker)>,
+    mut players: Query<&mut AnimationPlayer>,
+    children: Query<&Children>,
+    names: Query<&Name>,
+    transforms: Query<&Transform>,
+    mut assets: BakerAssets,
+    mut commands: Commands,
+) {
+    for (entity, mut baker) in &mut bakers {
+        let baker = &mut *baker;
+        if baker.baked.is_some() {
+            continue;
+        }
+        let Some(clip) = assets.clips.get(&baker.clip) else {
+            continue;
+        };
+
+        // Wait for the scene to spawn, then take over its animation player
+        let Some((player_entity, node)) = baker.player else {
+            let Some(player_entity) = children
+                .iter_descendants(entity)
+                .find(|descendant| players.contains(*descendant))
+            else {
+                continue;
+            };
+            let (graph, node) = AnimationGraph::from_clip(baker.clip.clone());
+            commands
+                .entity(player_entity)
+                .insert(AnimationGraphHandle(assets.graphs.add(graph)));
+            baker.player = Some((player_entity, node));
+            baker.joints = joint_entities(&baker.skeleton, entity, &children, &names);
+            continue;
+        };
+        let Ok(mut player) = players.get_mut(player_entity) else {
+            continue;
+        };
+
+        // The scene now shows the pose requested last frame
+        let step = 1.0 / baker.frame_rate;
+        let duration = clip.duration();
+        if let Some(posed_time) = baker.posed_time {
+            let pose: Vec<Transform> = baker
+                .joints
+                .iter()
+                .enumerate()
+                .map(|(joint, joint_entity)| {
+                    joint_entity
+                        .and_then(|joint_entity| transforms.get(joint_entity).ok().copied())
+                        .unwrap_or_else(|| get_joint(baker.skeleton.joint_rest_poses(), joint))
+                })
+                .collect();
+            let delta = posed_time - baker.recorder.duration();
+            baker.recorder.record_transforms(&pose, delta);
+            if posed_time >= duration {
+                let recorder = std::mem::replace(&mut baker.recorder, PoseRecorder::new(0.0));
+                baker.baked = Some(Arc::new(recorder.finish()));
+                player.stop_all();
+                continue;
+            }
+        }
+
+        let next_time = baker.posed_time.map_or(0.0, |time| (time + step).min(duration));
+        player.play(node).pause().seek_to(next_time);
+        baker.posed_time = Some(next_time);
+    }
+}
+
+/// Scene entity named like every joint of the skeleton
+fn joint_entities(
+    skeleton: &Skeleton,
+    root: Entity,
+    children: &Query<&Children>,
+    names: &Query<&Name>,
+) -> Vec<Option<Entity>> {
+    let mut joints = vec![None; skeleton.num_joints()];
+    for descendant in children.iter_descendants(root) {
+        if let Ok(name) = names.get(descendant)
+            && let Some(joint) = find_joint(skeleton, name.as_str(), true)
+        {
+            joints[joint] = Some(descendant);
+        }
+    }
+    joints
+}
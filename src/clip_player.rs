@@ -0,0 +1,180 @@
+use super::pose::pose_to_bones;
+use super::{
+    AnimationTime, AnimatorErrorEvent, OzzAsset, OzzTransform, PlaybackMode, SimpleState,
+    read_lock,
+};
+use bevy::prelude::*;
+use ozz_animation_rs::Skeleton;
+use std::sync::Arc;
+
+/// Plays a single clip without a controller, for doors, pickups and other
+/// props with one looping or one-shot animation. Bound to skinned meshes like
+/// an `AnimatorController`, playback starts once the clip has loaded.
+/// ## Example
+/// ```ignore
+/// let skeleton = ozz_assets.get(&door_skeleton).and_then(OzzAsset::skeleton).unwrap();
+/// commands.spawn((
+///     SceneRoot(door_mesh),
+///     OzzClipPlayer::new(skeleton.clone(), door_open_clip).looping(false).paused(),
+/// ));
+///
+/// // When the player walks up
+/// door.play();
+/// ```
+#[derive(Component, Debug)]
+pub struct OzzClipPlayer {
+    pub clip: Handle<OzzAsset>,
+    /// Playback speed multiplier, 1 plays the clip at its authored speed
+    pub speed: f32,
+    /// Wrap around at the end of the clip, otherwise hold the last frame
+    pub looping: bool,
+    paused: bool,
+    skeleton: Arc<Skeleton>,
+    /// Clip the current state was built from
+    loaded_clip: Option<AssetId<OzzAsset>>,
+    state: Option<SimpleState>,
+    /// Pose of every joint after the last update
+    pub(crate) bone_trans: Vec<OzzTransform>,
+}
+
+impl OzzClipPlayer {
+    #[inline]
+    pub fn new(skeleton: Arc<Skeleton>, clip: Handle<OzzAsset>) -> Self {
+        Self {
+            clip,
+            speed: 1.0,
+            looping: true,
+            paused: false,
+            skeleton,
+            loaded_clip: None,
+            state: None,
+            bone_trans: Vec::new(),
+        }
+    }
+
+    #[inline]
+    pub fn with_speed(mut self, speed: f32) -> Self {
+        self.speed = speed;
+        self
+    }
+
+    #[inline]
+    pub fn looping(mut self, looping: bool) -> Self {
+        self.looping = looping;
+        self
+    }
+
+    /// Start paused on the first frame
+    #[inline]
+    pub fn paused(mut self) -> Self {
+        self.paused = true;
+        self
+    }
+
+    /// Resume playback, restarting a clip that played to its end
+    #[inline]
+    pub fn play(&mut self) {
+        self.paused = false;
+        if self.is_finished() {
+            self.set_time(0.0);
+        }
+    }
+
+    #[inline]
+    pub fn pause(&mut self) {
+        self.paused = true;
+    }
+
+    #[inline]
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    /// If a clip that doesn't loop played to its end
+    #[inline]
+    pub fn is_finished(&self) -> bool {
+        self.state.as_ref().is_some_and(SimpleState::is_finished)
+    }
+
+    /// Playback time in seconds, 0 until the clip has loaded
+    #[inline]
+    pub fn time(&self) -> f32 {
+        self.state.as_ref().map_or(0.0, SimpleState::time)
+    }
+
+    #[inline]
+    pub fn set_time(&mut self, time: f32) {
+        if let Some(state) = &mut self.state {
+            state.set_time(time);
+        }
+    }
+
+    #[inline]
+    pub fn skeleton(&self) -> &Arc<Skeleton> {
+        &self.skeleton
+    }
+
+    /// Transform of a joint after the last update
+    #[inline]
+    pub fn bone(&self, index: usize) -> Option<OzzTransform> {
+        self.bone_trans.get(index).copied()
+    }
+}
+
+/// Advance every clip player and store its pose, building its state once the
+/// clip has loaded
+pub fn update_clip_players(
+    mut players: Query<(Entity, &mut OzzClipPlayer)>,
+    ozz_assets: Res<Assets<OzzAsset>>,
+    time: Res<Time>,
+    animation_time: Option<Res<AnimationTime>>,
+    mut errors: EventWriter<AnimatorErrorEvent>,
+) {
+    let time = animation_time.as_deref().copied().unwrap_or_default().scale_time(&time);
+    for (entity, mut player) in &mut players {
+        let player = &mut *player;
+        if player.loaded_clip != Some(player.clip.id()) {
+            let Some(animation) = ozz_assets.get(&player.clip).and_then(OzzAsset::animation)
+            else {
+                continue;
+            };
+            player.state = Some(SimpleState::new(
+                animation.clone(),
+                player.skeleton.num_soa_joints(),
+            ));
+            player.loaded_clip = Some(player.clip.id());
+        }
+        let Some(state) = &mut player.state else {
+            continue;
+        };
+
+        let playback_mode = match player.looping {
+            true => PlaybackMode::Loop,
+            false => PlaybackMode::Clamp,
+        };
+        if state.playback_mode() != playback_mode {
+            state.set_playback_mode(playback_mode);
+        }
+        state.set_speed(player.speed);
+
+        let result = if player.paused {
+            state.update(&Time::default())
+        } else {
+            state.update(&time)
+        };
+        // Nothing listens for markers without a controller
+        state.drain_markers(None);
+        if let Err(error) = result {
+            errors.write(AnimatorErrorEvent {
+                entity,
+                error: error.into(),
+            });
+            continue;
+        }
+
+        let output = state.get_output_pointer();
+        let pose = read_lock(&output);
+        let num_joints = player.skeleton.num_joints();
+        pose_to_bones(&pose, num_joints, &mut player.bone_trans);
+    }
+}
@@ -1,22 +1,177 @@
-use super::{AnimationLayer, LayerBlendType, OzzTransform, Parameters};
+use super::layer::LayerStates;
+use super::missing_parameters::ParameterCheck;
+use super::pose::{pose_to_bones, soa_weights};
+use super::additive_shot::ActiveAdditiveShot;
+use super::curve_output::CurveSample;
+use super::ik::ActiveIkChain;
+use super::overrides::ActiveBoneOverride;
+use super::pose_modifier::ActivePoseModifier;
+use super::predicates::Predicates;
+use super::ragdoll::PhysicsBlend;
+use super::random::layer_seed;
+use super::time_source::scale_duration;
+use super::triggers::hide_routed_triggers;
+use super::{
+    AdditiveAccumulation, AnimationLayer, AnimationMarker, AnimatorError, AnimatorSnapshot,
+    BakedPose, LayerBlendType, OverrideNormalization, OzzTransform, ParameterValue, Parameters,
+    PlaybackMode, SimpleState, StateChange, StateLoop, TriggerPolicy, read_lock,
+};
 use bevy::prelude::*;
 use ozz_animation_rs::*;
+use std::collections::HashMap;
 use std::sync::{Arc, RwLock};
+use std::time::Duration;
 
-#[derive(Component, Debug)]
+/// How much of the animator is evaluated each update
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Reflect)]
+pub enum EvaluationMode {
+    /// Run the state machines, then sample and blend every active state
+    #[default]
+    Full,
+    /// Only run the state machines, advancing playback without sampling or
+    /// blending. Useful for off-screen characters that still need transitions.
+    LogicOnly,
+}
+
+/// Where a layer's output lives in the final blending job
+#[derive(Debug, Clone, Copy)]
+enum BlendSlot {
+    Override(usize),
+    Additive(usize),
+}
+
+/// Drives the pose of a skeleton through layers of state machines. The
+/// parameters, layers and final pose are reflected so inspectors and scenes
+/// see the current state, the skeleton and states are not. Reflection can
+/// patch an existing controller but not build one.
+#[derive(Component, Debug, Reflect)]
+#[reflect(Component, Debug, from_reflect = false)]
 pub struct AnimatorController {
     /// Animation layers
-    layers: Vec<AnimationLayer>,
+    pub(crate) layers: Vec<AnimationLayer>,
     /// Parameters for the animator
-    parameters: Parameters,
+    pub(crate) parameters: Parameters,
     /// Final blending job
-    final_blending_job: BlendingJobArc,
+    #[reflect(ignore)]
+    pub(crate) final_blending_job: BlendingJobArc,
+    /// Blending job slot for every layer, in layer order
+    #[reflect(ignore)]
+    blend_slots: Vec<BlendSlot>,
+    /// How the weights of additive layers accumulate
+    additive_accumulation: AdditiveAccumulation,
+    /// How override layer weights are normalized in the final blend
+    override_normalization: OverrideNormalization,
+    /// Total override weight below which the rest pose fills in
+    rest_pose_threshold: f32,
+    /// Pose filling in instead of the skeleton's rest pose, blended as the
+    /// last override layer of the final blending job
+    #[reflect(ignore)]
+    pub(crate) rest_pose: Option<Arc<RwLock<Vec<SoaTransform>>>>,
     /// Transforms for bones
     pub bone_trans: Vec<OzzTransform>,
+    /// Transforms for bones before the last update, for interpolation
+    #[reflect(ignore)]
+    previous_bone_trans: Vec<OzzTransform>,
+    /// Bumped by every update that moved a bone, so bones of still controllers
+    /// aren't written again
+    #[reflect(ignore)]
+    pose_version: u64,
     /// Skeleton
+    #[reflect(ignore)]
     pub skeleton: Arc<Skeleton>,
+    /// Version of the animation graph, used to validate snapshots
+    graph_version: u32,
+    /// Seed of the layers' random numbers
+    pub(crate) seed: u64,
+    /// How much of the animator is evaluated each update
+    evaluation_mode: EvaluationMode,
+    /// Runtime weight of every joint in the final blend, empty if none are overridden
+    #[reflect(ignore)]
+    joint_weight_overrides: Vec<f32>,
+    /// Bone overrides applied after blending
+    #[reflect(ignore)]
+    pub(crate) bone_overrides: Vec<ActiveBoneOverride>,
+    /// Externally computed pose blended over the final pose
+    #[reflect(ignore)]
+    pub(crate) physics: PhysicsBlend,
+    /// Procedural modifiers applied in order after blending, before IK
+    #[reflect(ignore)]
+    pub(crate) pose_modifiers: Vec<ActivePoseModifier>,
+    /// IK chains and look-at constraints applied after blending, before the bone overrides
+    #[reflect(ignore)]
+    pub(crate) ik_chains: Vec<ActiveIkChain>,
+    /// Additive layers currently played once by `fire_additive`
+    #[reflect(ignore)]
+    pub(crate) additive_shots: Vec<ActiveAdditiveShot>,
+    /// Loops since they were last drained
+    #[reflect(ignore)]
+    pub(crate) state_loops: Vec<StateLoop>,
+    /// Markers crossed since they were last drained
+    #[reflect(ignore)]
+    pub(crate) markers: Vec<AnimationMarker>,
+    /// Curves blended from the float tracks of the playing states
+    #[reflect(ignore)]
+    pub(crate) curve_values: HashMap<Arc<str>, f32>,
+    /// Float track samples of the last update, kept to reuse the buffers
+    #[reflect(ignore)]
+    pub(crate) curve_samples: Vec<CurveSample>,
+    /// Weighted sum and total weight of every curve of the last update
+    #[reflect(ignore)]
+    pub(crate) curve_totals: HashMap<Arc<str>, (f32, f32)>,
+    /// Registered tags whose components are on the entity, in layer order
+    /// and then the order they were added to the state, without duplicates
+    #[reflect(ignore)]
+    pub(crate) applied_tags: Vec<String>,
+    /// Joint every bound marker plays at, keyed by marker name
+    #[reflect(ignore)]
+    pub(crate) marker_joints: HashMap<String, usize>,
+    /// State changes since they were last drained
+    #[reflect(ignore)]
+    pub(crate) state_changes: Vec<StateChange>,
+    /// Current state of every layer, for cross-layer transition conditions
+    #[reflect(ignore)]
+    layer_states: LayerStates,
+    /// Named condition sets referenced by transitions
+    #[reflect(ignore)]
+    pub(crate) predicates: Predicates,
+    /// Which layers see a trigger
+    #[reflect(ignore)]
+    pub(crate) trigger_policy: TriggerPolicy,
+    /// Layer every routed trigger is limited to, keyed by trigger
+    #[reflect(ignore)]
+    pub(crate) trigger_routes: HashMap<String, String>,
+    /// Triggers consumed by the layer that just updated
+    #[reflect(ignore)]
+    consumed_triggers: Vec<String>,
+    /// Triggers routed to other layers, unset while a layer updates
+    #[reflect(ignore)]
+    hidden_triggers: Vec<String>,
+    /// Time the controller missed while deferred or throttled
+    #[reflect(ignore)]
+    pub(crate) deferred_time: Duration,
+    /// Frames since the last full update, see `PrioritySettings::update_interval`
+    #[reflect(ignore)]
+    pub(crate) throttled_frames: u32,
+    /// Multiplies the time every update advances by, on top of `AnimationTime`
+    #[reflect(ignore)]
+    time_scale: f32,
+    /// Longest time a single update may advance, None follows `AnimatorMaxDelta`
+    #[reflect(ignore)]
+    max_delta: Option<Option<Duration>>,
+    /// Longest step an update is split into, None follows `AnimatorSubStep`
+    #[reflect(ignore)]
+    sub_step: Option<Option<Duration>>,
+    /// Parameters read by the graph that were never set
+    #[reflect(ignore)]
+    pub(crate) parameter_check: ParameterCheck,
+    /// Handles of the assets the controller was built from
+    #[reflect(ignore)]
+    pub(crate) dependencies: Vec<UntypedHandle>,
 }
 
+// SAFETY: every pose shared between jobs lives behind an Arc<RwLock>, and the
+// ozz jobs only hold Arcs to immutable skeleton and animation data. Nothing here
+// relies on threads, so single-threaded targets like wasm32 work unchanged.
 unsafe impl Send for AnimatorController {}
 unsafe impl Sync for AnimatorController {}
 
@@ -26,7 +181,15 @@ impl AnimatorController {
         skeleton: Arc<Skeleton>,
         layers: Vec<AnimationLayer>,
         parameters: Parameters,
-    ) -> Self {
+    ) -> Result<Self, AnimatorError> {
+        // Make sure every layer can output a pose
+        if let Some(layer) = layers.iter().find(|l| !l.has_state(l.current_state())) {
+            return Err(AnimatorError::MissingDefaultState {
+                layer: layer.name.clone(),
+                state: layer.current_state().to_string(),
+            });
+        }
+
         // Setup blending job
         let mut final_blending_job: BlendingJobArc = BlendingJob::default();
         let blending_output = Arc::new(RwLock::new(vec![
@@ -35,6 +198,7 @@ impl AnimatorController {
         ]));
         final_blending_job.set_skeleton(skeleton.clone());
         final_blending_job.set_output(blending_output.clone());
+        let rest_pose_threshold = final_blending_job.threshold();
 
         // Count the number of bones
         let mut bone_count = 0;
@@ -46,123 +210,839 @@ impl AnimatorController {
             layers,
             parameters,
             final_blending_job,
+            blend_slots: Vec::new(),
+            additive_accumulation: AdditiveAccumulation::default(),
+            override_normalization: OverrideNormalization::default(),
+            rest_pose_threshold,
+            rest_pose: None,
             bone_trans: Vec::with_capacity(bone_count),
+            previous_bone_trans: Vec::with_capacity(bone_count),
+            pose_version: 0,
             skeleton,
+            graph_version: 0,
+            seed: 0,
+            evaluation_mode: EvaluationMode::Full,
+            joint_weight_overrides: Vec::new(),
+            bone_overrides: Vec::new(),
+            physics: PhysicsBlend::default(),
+            pose_modifiers: Vec::new(),
+            ik_chains: Vec::new(),
+            additive_shots: Vec::new(),
+            state_loops: Vec::new(),
+            markers: Vec::new(),
+            curve_values: HashMap::new(),
+            curve_samples: Vec::new(),
+            curve_totals: HashMap::new(),
+            applied_tags: Vec::new(),
+            marker_joints: HashMap::new(),
+            state_changes: Vec::new(),
+            layer_states: LayerStates::default(),
+            predicates: Predicates::default(),
+            trigger_policy: TriggerPolicy::default(),
+            trigger_routes: HashMap::new(),
+            consumed_triggers: Vec::new(),
+            hidden_triggers: Vec::new(),
+            deferred_time: Duration::ZERO,
+            throttled_frames: 0,
+            time_scale: 1.0,
+            max_delta: None,
+            sub_step: None,
+            parameter_check: ParameterCheck::default(),
+            dependencies: Vec::new(),
         };
-        controller
-            .build_blending_layers()
-            .expect("Failed to build blending layers");
-        controller
+        controller.set_seed(0);
+        controller.build_blending_layers();
+        Ok(controller)
     }
 
     #[inline]
-    pub fn add_layer(&mut self, layer: AnimationLayer) {
+    pub fn add_layer(&mut self, mut layer: AnimationLayer) -> Result<(), AnimatorError> {
+        if !layer.has_state(layer.current_state()) {
+            return Err(AnimatorError::MissingDefaultState {
+                layer: layer.name.clone(),
+                state: layer.current_state().to_string(),
+            });
+        }
+        layer.seed_rng(layer_seed(self.seed, self.layers.len()));
         self.layers.push(layer);
+        self.build_blending_layers();
+        self.parameter_check.checked = false;
+        Ok(())
+    }
+
+    #[inline]
+    pub fn update(&mut self, time: &Time) -> Result<(), AnimatorError> {
+        self.tick(time, self.evaluation_mode == EvaluationMode::Full)
     }
 
+    /// Resolve every parameter the graph reads to its id right away. Updates
+    /// already rebind whenever a parameter name, state or transition is added,
+    /// call this after changing the graph in place, e.g. the blend type of a
+    /// tree, to skip the name lookups until then.
     #[inline]
-    pub fn update(&mut self, time: &Time) -> Result<(), OzzError> {
+    pub fn rebind_parameters(&mut self) {
+        for layer in &mut self.layers {
+            layer.bind_parameters(&self.parameters);
+        }
+        self.predicates.bind_parameters(&self.parameters);
+    }
+
+    /// Run the state machines, only sampling and blending the pose if `sample` is set
+    pub(crate) fn tick(&mut self, time: &Time, sample: bool) -> Result<(), AnimatorError> {
+        if !self.parameter_check.checked {
+            self.check_parameters();
+        }
+        // Read parameters by id, rebinding layers whose graph or parameter
+        // names changed since they were last bound
+        for layer in &mut self.layers {
+            if layer.needs_binding(&self.parameters) {
+                layer.bind_parameters(&self.parameters);
+            }
+        }
+        if self.predicates.needs_binding(&self.parameters) {
+            self.predicates.bind_parameters(&self.parameters);
+        }
+        self.update_additive_shots(time.delta_secs());
+        self.update_physics_weight(time.delta_secs());
+
+        // States may have been changed directly since the last update
+        for (i, layer) in self.layers.iter().enumerate() {
+            self.layer_states.set(i, layer);
+        }
+
         // TODO: STATE UPDATES CAN BE PARALLELIZED
         // Update all layers
-        for (index, layer) in self.layers.iter_mut().enumerate() {
-            layer.update(time, &mut self.parameters)?;
+        for (i, (layer, slot)) in self.layers.iter_mut().zip(&self.blend_slots).enumerate() {
+            // Inactive layers are skipped entirely and weigh 0 in the final blend
+            if !layer.update_activation(&self.parameters, &self.layer_states, &self.predicates) {
+                continue;
+            }
+            // Layers weighing nothing keep running their state machine but
+            // aren't sampled, their states are sampled again at their current
+            // time on the update their weight rises, before it is blended in
+            let sample_layer = sample && layer.effective_weight() > 0.0;
+            hide_routed_triggers(
+                &mut self.parameters,
+                &self.trigger_routes,
+                &layer.name,
+                &mut self.hidden_triggers,
+            );
+            let result = layer.tick(
+                time,
+                &mut self.parameters,
+                &self.layer_states,
+                &self.predicates,
+                sample_layer,
+            );
+            for trigger in &self.hidden_triggers {
+                self.parameters.set_trigger(trigger);
+            }
+            result?;
+            layer.update_transient_clips();
+            self.layer_states.set(i, layer);
+
+            // Triggers that fired a transition are gone for later layers
+            layer.drain_consumed_triggers(&mut self.consumed_triggers);
+            if self.trigger_policy == TriggerPolicy::FirstLayer {
+                for trigger in &self.consumed_triggers {
+                    self.parameters.reset_trigger(trigger);
+                }
+            }
+            self.consumed_triggers.clear();
+
+            // Keep the loop count parameter in sync with the current state
+            if layer.take_loop_count_changed() {
+                self.parameters
+                    .set_int(&layer.loop_count_parameter(), layer.loop_count() as i32);
+            }
+            layer.drain_loops(&mut self.state_loops);
+            layer.drain_markers(&mut self.markers);
+            layer.drain_state_changes(&mut self.state_changes);
 
             // Only update the input pointer if the output source has changed
             if layer.has_output_changed() {
-                // There will always be a blending layer at the same index as the animation layer being updated
-                self.final_blending_job.layers_mut()[index].transform = layer.get_output_pointer();
+                let transform = layer.get_output_pointer();
+                match *slot {
+                    BlendSlot::Override(i) => {
+                        self.final_blending_job.layers_mut()[i].transform = transform;
+                    }
+                    BlendSlot::Additive(i) => {
+                        self.final_blending_job.additive_layers_mut()[i].transform = transform;
+                    }
+                }
                 layer.clear_output_changed();
             }
         }
+        self.apply_layer_weights();
+        self.update_curve_values();
 
         // Reset triggers after update
         self.parameters.reset_triggers();
 
-        self.final_blending_job.run()?;
+        if !sample {
+            return Ok(());
+        }
+        self.final_blending_job
+            .run()
+            .map_err(AnimatorError::FinalBlending)?;
+        self.apply_physics_pose()?;
+        self.apply_pose_modifiers(time.delta_secs())?;
+        self.previous_bone_trans.clone_from(&self.bone_trans);
         let skeleton = self.skeleton.clone();
-        self.update_bones(&skeleton);
+        self.update_bones(&skeleton)?;
+        self.apply_ik()?;
+        self.apply_bone_overrides(time.delta_secs());
+        if self.bone_trans != self.previous_bone_trans {
+            self.pose_version += 1;
+        }
+        Ok(())
+    }
+
+    /// Sample every state of every layer once without advancing playback, so
+    /// sampling caches and buffers are allocated up front. Call it during a
+    /// loading screen to avoid a hitch the first time a rarely used state plays.
+    pub fn prewarm(&mut self) -> Result<(), AnimatorError> {
+        for layer in &mut self.layers {
+            layer.prewarm()?;
+        }
         Ok(())
     }
 
+    /// Changes whenever an update moves a bone
     #[inline]
-    pub fn update_bones(&mut self, skeleton: &Skeleton) {
-        self.bone_trans.clear();
+    pub fn pose_version(&self) -> u64 {
+        self.pose_version
+    }
 
-        if let Ok(local_transforms) = self.final_blending_job.output().unwrap().read() {
-            for i in 0..skeleton.num_joints() {
-                let current_soa_index = i / 4;
-                let current_lane = i % 4;
+    /// Write the bones out again on the next frame, after editing `bone_trans`
+    /// by hand
+    #[inline]
+    pub fn mark_pose_changed(&mut self) {
+        self.pose_version += 1;
+    }
 
-                let current_pos = Vec3::new(
-                    local_transforms[current_soa_index].translation.x[current_lane],
-                    local_transforms[current_soa_index].translation.y[current_lane],
-                    local_transforms[current_soa_index].translation.z[current_lane],
-                );
+    /// Transform of a bone between the previous update (`alpha` 0) and the
+    /// latest one (`alpha` 1), used to smooth fixed timestep animation
+    #[inline]
+    pub fn interpolated_bone(&self, index: usize, alpha: f32) -> Option<OzzTransform> {
+        let current = self.bone_trans.get(index)?;
+        let Some(previous) = self.previous_bone_trans.get(index) else {
+            return Some(*current);
+        };
+        Some(OzzTransform {
+            scale: previous.scale.lerp(current.scale, alpha),
+            rotation: previous.rotation.slerp(current.rotation, alpha),
+            position: previous.position.lerp(current.position, alpha),
+        })
+    }
 
-                let current_rot = Quat::from_xyzw(
-                    local_transforms[current_soa_index].rotation.x[current_lane],
-                    local_transforms[current_soa_index].rotation.y[current_lane],
-                    local_transforms[current_soa_index].rotation.z[current_lane],
-                    local_transforms[current_soa_index].rotation.w[current_lane],
-                );
+    #[inline]
+    pub fn update_bones(&mut self, skeleton: &Skeleton) -> Result<(), AnimatorError> {
+        // Keep the last pose if the final pose can't be read
+        let Some(output) = self.final_blending_job.output() else {
+            return Ok(());
+        };
+        let local_transforms = output.read().map_err(|_| {
+            output.clear_poison();
+            AnimatorError::PoisonedLock("final pose")
+        })?;
 
-                let current_scale = Vec3::new(
-                    local_transforms[current_soa_index].scale.x[current_lane],
-                    local_transforms[current_soa_index].scale.y[current_lane],
-                    local_transforms[current_soa_index].scale.z[current_lane],
-                );
+        pose_to_bones(&local_transforms, skeleton.num_joints(), &mut self.bone_trans);
+        Ok(())
+    }
 
-                self.bone_trans.push(OzzTransform {
-                    scale: current_scale,
-                    rotation: current_rot,
-                    position: current_pos,
-                });
+    #[inline]
+    pub fn build_blending_layers(&mut self) {
+        // Blend all layers together
+        self.final_blending_job.layers_mut().clear();
+        self.final_blending_job.additive_layers_mut().clear();
+        self.blend_slots.clear();
+        for layer in &self.layers {
+            let blending_layer = BlendingLayer {
+                transform: layer.get_output_pointer(),
+                weight: layer.layer_weight,
+                joint_weights: vec![],
+            };
+            match layer.layer_blend_type {
+                LayerBlendType::Override => {
+                    let layers = self.final_blending_job.layers_mut();
+                    self.blend_slots.push(BlendSlot::Override(layers.len()));
+                    layers.push(blending_layer);
+                }
+                LayerBlendType::Additive => {
+                    let layers = self.final_blending_job.additive_layers_mut();
+                    self.blend_slots.push(BlendSlot::Additive(layers.len()));
+                    layers.push(blending_layer);
+                }
             }
         }
+        if let Some(rest_pose) = &self.rest_pose {
+            self.final_blending_job.layers_mut().push(BlendingLayer {
+                transform: rest_pose.clone(),
+                weight: 0.0,
+                joint_weights: vec![],
+            });
+        }
+        self.apply_layer_weights();
+        self.apply_joint_weights();
+        self.layer_states.rebuild(&self.layers);
+    }
+
+    /// Copy the joint masks of every layer, scaled by the joint weight
+    /// overrides, into the final blending job
+    #[inline]
+    pub(crate) fn apply_joint_weights(&mut self) {
+        let num_soa_joints = self.skeleton.num_soa_joints();
+        let overrides = &self.joint_weight_overrides;
+        for (layer, slot) in self.layers.iter().zip(&self.blend_slots) {
+            let joint_weights = match layer.joint_mask() {
+                None if overrides.is_empty() => Vec::new(),
+                None => soa_weights(overrides, num_soa_joints),
+                Some(mask) if overrides.is_empty() => soa_weights(mask.weights(), num_soa_joints),
+                Some(mask) => {
+                    let weights: Vec<f32> = mask
+                        .weights()
+                        .iter()
+                        .zip(overrides)
+                        .map(|(mask, weight)| mask * weight)
+                        .collect();
+                    soa_weights(&weights, num_soa_joints)
+                }
+            };
+            let blending_layer = match *slot {
+                BlendSlot::Override(i) => &mut self.final_blending_job.layers_mut()[i],
+                BlendSlot::Additive(i) => &mut self.final_blending_job.additive_layers_mut()[i],
+            };
+            blending_layer.joint_weights = joint_weights;
+        }
+        self.apply_rest_pose_weights();
+    }
+
+    /// Scale how much a joint is animated in the final blend, from 0 (not
+    /// animated, e.g. while physics holds it) to 1. Joints with a weight of 0
+    /// on every layer fall back to the rest pose. Does nothing for unknown joints.
+    /// ## Example
+    /// ```ignore
+    /// for joint in controller.joint_subtree(right_arm).map(|j| j.index).collect::<Vec<_>>() {
+    ///     controller.set_joint_weight(joint, 0.0);
+    /// }
+    /// ```
+    #[inline]
+    pub fn set_joint_weight(&mut self, joint: usize, weight: f32) {
+        let num_joints = self.skeleton.num_joints();
+        if joint >= num_joints {
+            return;
+        }
+        if self.joint_weight_overrides.is_empty() {
+            self.joint_weight_overrides = vec![1.0; num_joints];
+        }
+        self.joint_weight_overrides[joint] = weight.clamp(0.0, 1.0);
+        self.apply_joint_weights();
+    }
+
+    /// Current weight of a joint in the final blend
+    #[inline]
+    pub fn joint_weight(&self, joint: usize) -> f32 {
+        self.joint_weight_overrides.get(joint).copied().unwrap_or(1.0)
     }
 
+    /// Animate every joint fully again
     #[inline]
-    pub fn build_blending_layers(&mut self) -> Result<(), OzzError> {
-        // Collect layer data to avoid borrow checker issues
-        let layer_data = self
+    pub fn clear_joint_weights(&mut self) {
+        self.joint_weight_overrides.clear();
+        self.apply_joint_weights();
+    }
+
+    /// Copy the layer weights into the final blending job
+    #[inline]
+    fn apply_layer_weights(&mut self) {
+        let additive_weights: Vec<f32> = self
             .layers
             .iter()
-            .map(|l| (l.layer_blend_type, l.layer_weight, l.get_output_pointer()));
+            .filter(|l| l.layer_blend_type == LayerBlendType::Additive)
+            .map(|l| l.effective_weight())
+            .collect();
+        let mut additive_weights = self
+            .additive_accumulation
+            .accumulate(&additive_weights)
+            .into_iter();
 
-        // Blend all layers together
-        self.final_blending_job.layers_mut().clear();
-        let mut base_added = false;
-        for (blend_type, weight, transform) in layer_data {
-            match blend_type {
-                LayerBlendType::Override => {
-                    if !base_added {
-                        self.final_blending_job.layers_mut().push(BlendingLayer {
-                            transform,
-                            weight,
-                            joint_weights: vec![],
-                        });
-                        base_added = true;
-                    } else {
-                        self.final_blending_job.layers_mut().push(BlendingLayer {
-                            transform,
-                            weight,
-                            joint_weights: vec![],
-                        });
-                    }
+        for (layer, slot) in self.layers.iter().zip(&self.blend_slots) {
+            match *slot {
+                BlendSlot::Override(i) => {
+                    self.final_blending_job.layers_mut()[i].weight = layer.effective_weight();
                 }
-                LayerBlendType::Additive => {
-                    // Handle additive blending when implemented
-                    // return Err("Additive blending not yet implemented".into());
-                    panic!("Additive blending not yet implemented");
+                BlendSlot::Additive(i) => {
+                    if let Some(weight) = additive_weights.next() {
+                        self.final_blending_job.additive_layers_mut()[i].weight = weight;
+                    }
                 }
             }
         }
+        self.apply_rest_pose_weights();
+    }
+
+    /// Weigh the custom rest pose with whatever the override layers miss from
+    /// the threshold on every joint, so ozz never falls back to the skeleton's
+    fn apply_rest_pose_weights(&mut self) {
+        if self.rest_pose.is_none() {
+            return;
+        }
+        let threshold = self.final_blending_job.threshold();
+        let num_soa_joints = self.skeleton.num_soa_joints();
+        let Some((rest_pose, layers)) = self.final_blending_job.layers_mut().split_last_mut()
+        else {
+            return;
+        };
+        if layers.iter().all(|layer| layer.joint_weights.is_empty()) {
+            let total: f32 = layers.iter().map(|layer| layer.weight.max(0.0)).sum();
+            rest_pose.weight = (threshold - total).max(0.0);
+            rest_pose.joint_weights.clear();
+            return;
+        }
+
+        // Masked layers weigh differently on every joint, the weights are
+        // accumulated in place so updates don't allocate
+        let missing = &mut rest_pose.joint_weights;
+        missing.clear();
+        missing.resize(num_soa_joints, glam::Vec4::splat(threshold));
+        for layer in layers.iter().filter(|layer| layer.weight > 0.0) {
+            for (joint, missing) in missing.iter_mut().enumerate() {
+                let weight = layer.joint_weights.get(joint).copied().unwrap_or(glam::Vec4::ONE);
+                *missing -= weight * layer.weight;
+            }
+        }
+        for missing in missing.iter_mut() {
+            *missing = missing.max(glam::Vec4::ZERO);
+        }
+        rest_pose.weight = 1.0;
+    }
+
+    /// How the weights of several active additive layers combine
+    #[inline]
+    pub fn set_additive_accumulation(&mut self, additive_accumulation: AdditiveAccumulation) {
+        self.additive_accumulation = additive_accumulation;
+    }
+
+    #[inline]
+    pub fn additive_accumulation(&self) -> AdditiveAccumulation {
+        self.additive_accumulation
+    }
+
+    /// Whether the final blend normalizes override layer weights or uses them
+    /// as they are, filling the rest with the rest pose
+    #[inline]
+    pub fn set_override_normalization(&mut self, normalization: OverrideNormalization) {
+        self.override_normalization = normalization;
+        self.apply_normalization();
+    }
+
+    #[inline]
+    pub fn override_normalization(&self) -> OverrideNormalization {
+        self.override_normalization
+    }
+
+    /// Total override layer weight below which the rest pose is blended in to
+    /// make up the difference, when normalizing. Defaults to ozz's 0.1.
+    #[inline]
+    pub fn set_rest_pose_threshold(&mut self, threshold: f32) {
+        self.rest_pose_threshold = threshold.max(0.0);
+        self.apply_normalization();
+    }
+
+    #[inline]
+    pub fn rest_pose_threshold(&self) -> f32 {
+        self.rest_pose_threshold
+    }
+
+    #[inline]
+    fn apply_normalization(&mut self) {
+        // Ozz blends the rest pose in with whatever weight the total is
+        // missing from the threshold, a threshold of 1 keeps weights absolute
+        let threshold = match self.override_normalization {
+            OverrideNormalization::Normalized => self.rest_pose_threshold,
+            OverrideNormalization::PassThrough => 1.0,
+        };
+        self.final_blending_job.set_threshold(threshold);
+        self.apply_rest_pose_weights();
+    }
 
+    /// Fill in with this pose instead of the skeleton's rest pose wherever the
+    /// override layers weigh less than the rest pose threshold, see
+    /// [`Self::set_rest_pose_from_clip`]. None goes back to the skeleton's.
+    pub fn set_rest_pose(&mut self, pose: Option<Vec<SoaTransform>>) -> Result<(), AnimatorError> {
+        self.rest_pose = pose.map(|pose| {
+            // Joints the pose doesn't cover keep the skeleton's rest pose
+            let mut rest_pose = self.skeleton.joint_rest_poses().to_vec();
+            let len = rest_pose.len().min(pose.len());
+            rest_pose[..len].copy_from_slice(&pose[..len]);
+            Arc::new(RwLock::new(rest_pose))
+        });
+        self.build_blending_layers();
         Ok(())
     }
 
+    /// Fill in with a frame of a clip instead of the skeleton's rest pose, e.g.
+    /// the first frame of a relaxed idle so a character with no active state
+    /// doesn't flash a T-pose
+    /// ## Example
+    /// ```ignore
+    /// let idle = SimpleState::new(idle_clip.clone(), skeleton.num_soa_joints());
+    /// controller.set_rest_pose_from_clip(&idle, 0.0)?;
+    /// ```
+    pub fn set_rest_pose_from_clip(
+        &mut self,
+        clip: &SimpleState,
+        time: f32,
+    ) -> Result<(), AnimatorError> {
+        let mut pose = self.skeleton.joint_rest_poses().to_vec();
+        clip.evaluate_at(time, &mut pose)?;
+        self.set_rest_pose(Some(pose))
+    }
+
+    #[inline]
+    pub fn with_rest_pose(mut self, pose: Vec<SoaTransform>) -> Result<Self, AnimatorError> {
+        self.set_rest_pose(Some(pose))?;
+        Ok(self)
+    }
+
+    /// Pose filling in below the rest pose threshold, the skeleton's rest pose
+    /// unless one was set
+    #[inline]
+    pub fn rest_pose(&self) -> Vec<SoaTransform> {
+        match &self.rest_pose {
+            Some(pose) => read_lock(pose).clone(),
+            None => self.skeleton.joint_rest_poses().to_vec(),
+        }
+    }
+
+    /// Play this controller `time_scale` times faster, e.g. a slowed enemy or
+    /// a hit-stop on the character that was hit. 0 freezes it, while its
+    /// state machines keep running.
+    /// ## Example
+    /// ```ignore
+    /// controller.set_time_scale(0.0);
+    /// // Once the hit-stop is over
+    /// controller.set_time_scale(1.0);
+    /// ```
+    #[inline]
+    pub fn set_time_scale(&mut self, time_scale: f32) {
+        self.time_scale = time_scale.max(0.0);
+    }
+
+    #[inline]
+    pub fn time_scale(&self) -> f32 {
+        self.time_scale
+    }
+
+    /// Time this controller advances by for `delta` of its clock
+    #[inline]
+    pub(crate) fn scale_delta(&self, delta: Duration) -> Duration {
+        scale_duration(delta, self.time_scale)
+    }
+
+    /// Limit how far a single update of this controller may advance, ignoring
+    /// the plugin's `max_delta`. None never clamps.
+    /// ## Example
+    /// ```ignore
+    /// // A cutscene character that must stay in sync with audio
+    /// controller.set_max_delta(None);
+    /// ```
+    #[inline]
+    pub fn set_max_delta(&mut self, max_delta: Option<Duration>) {
+        self.max_delta = Some(max_delta);
+    }
+
+    /// Go back to following the plugin's `max_delta`
+    #[inline]
+    pub fn clear_max_delta(&mut self) {
+        self.max_delta = None;
+    }
+
+    /// Limit set with `set_max_delta`, None when following the plugin
+    #[inline]
+    pub fn max_delta_override(&self) -> Option<Option<Duration>> {
+        self.max_delta
+    }
+
+    /// Split updates of this controller into steps no longer than `sub_step`,
+    /// ignoring the plugin's `sub_step`. None runs every update as one step.
+    /// ## Example
+    /// ```ignore
+    /// // A boss whose attack windows must never be skipped over
+    /// controller.set_sub_step(Some(Duration::from_millis(20)));
+    /// ```
+    #[inline]
+    pub fn set_sub_step(&mut self, sub_step: Option<Duration>) {
+        self.sub_step = Some(sub_step);
+    }
+
+    /// Go back to following the plugin's `sub_step`
+    #[inline]
+    pub fn clear_sub_step(&mut self) {
+        self.sub_step = None;
+    }
+
+    /// Step set with `set_sub_step`, None when following the plugin
+    #[inline]
+    pub fn sub_step_override(&self) -> Option<Option<Duration>> {
+        self.sub_step
+    }
+
+    #[inline]
+    pub fn parameters(&self) -> &Parameters {
+        &self.parameters
+    }
+
     #[inline]
     pub fn parameters_mut(&mut self) -> &mut Parameters {
         &mut self.parameters
     }
+
+    /// Set many parameters at once, see [`Parameters::apply`]
+    #[inline]
+    pub fn set_params<'a>(&mut self, batch: impl IntoIterator<Item = (&'a str, ParameterValue)>) {
+        self.parameters.apply(batch);
+    }
+
+    #[inline]
+    pub fn evaluation_mode(&self) -> EvaluationMode {
+        self.evaluation_mode
+    }
+
+    /// Switch between full evaluation and running only the state machines.
+    /// Switching back to `Full` resamples every active state on the next update.
+    #[inline]
+    pub fn set_evaluation_mode(&mut self, evaluation_mode: EvaluationMode) {
+        self.evaluation_mode = evaluation_mode;
+    }
+
+    /// Move playback of a layer's current state to a normalized time, cancelling
+    /// any transition in progress. Returns false if the layer doesn't exist.
+    #[inline]
+    pub fn seek(&mut self, layer: usize, normalized_time: f32) -> bool {
+        let Some(layer) = self.layers.get_mut(layer) else {
+            return false;
+        };
+        layer.seek(normalized_time);
+        true
+    }
+
+    /// Jump to a state and move its playback to a time in seconds, on the first
+    /// layer that contains it. Returns false if no layer has the state.
+    #[inline]
+    pub fn seek_state(&mut self, state: &str, time: f32) -> bool {
+        let Some(layer) = self.layers.iter_mut().find(|l| l.has_state(state)) else {
+            return false;
+        };
+        layer.seek_state(state, time)
+    }
+
+    /// Switch a layer to a state directly, see [`AnimationLayer::force_state`]
+    #[inline]
+    pub fn force_state(
+        &mut self,
+        layer: &str,
+        state: &str,
+        blend_duration: f32,
+    ) -> Result<(), AnimatorError> {
+        let Some(layer) = self.layers.iter_mut().find(|l| l.name == layer) else {
+            return Err(AnimatorError::UnknownLayer(layer.to_string()));
+        };
+        layer.force_state(state, blend_duration)
+    }
+
+    /// Restart a state from the beginning on the first layer that contains it,
+    /// without a transition. Returns false if no layer has the state.
+    /// ## Example
+    /// ```ignore
+    /// // Respawn
+    /// controller.play("Idle");
+    /// ```
+    #[inline]
+    pub fn play(&mut self, state: &str) -> bool {
+        let Some(layer) = self.layers.iter_mut().find(|l| l.has_state(state)) else {
+            return false;
+        };
+        layer.play(state).is_ok()
+    }
+
+    /// Crossfade a layer to a state over `duration` seconds, starting the state
+    /// from the beginning. Transition conditions are not checked.
+    /// ## Example
+    /// ```ignore
+    /// controller.cross_fade("Cutscene Sit", 0.25, "Base Layer")?;
+    /// ```
+    #[inline]
+    pub fn cross_fade(
+        &mut self,
+        state: &str,
+        duration: f32,
+        layer: &str,
+    ) -> Result<(), AnimatorError> {
+        let Some(layer) = self.layers.iter_mut().find(|l| l.name == layer) else {
+            return Err(AnimatorError::UnknownLayer(layer.to_string()));
+        };
+        layer.cross_fade(state, duration)
+    }
+
+    /// Crossfade a layer to a clip that isn't part of its graph, for one-off
+    /// scripted moments, see [`AnimationLayer::cross_fade_to_clip`]. Returns
+    /// the name of the state playing the clip.
+    /// ## Example
+    /// ```ignore
+    /// controller.cross_fade_to_clip("Base Layer", wave.clone(), 0.25, PlaybackMode::Clamp)?;
+    /// ```
+    #[inline]
+    pub fn cross_fade_to_clip(
+        &mut self,
+        layer: &str,
+        animation: Arc<Animation>,
+        duration: f32,
+        playback_mode: PlaybackMode,
+    ) -> Result<String, AnimatorError> {
+        let joint_count = self.skeleton.num_soa_joints();
+        let Some(layer) = self.layers.iter_mut().find(|l| l.name == layer) else {
+            return Err(AnimatorError::UnknownLayer(layer.to_string()));
+        };
+        let name = animation.name().to_string();
+        let clip = SimpleState::new(animation, joint_count).with_playback_mode(playback_mode);
+        layer.cross_fade_to_clip(&name, clip, duration)
+    }
+
+    /// Sample a state's pose at a time in seconds into `output`, without
+    /// advancing the controller. `output` should hold `skeleton.num_soa_joints()`
+    /// transforms. Returns false if no layer has the state.
+    #[inline]
+    pub fn evaluate_at(
+        &self,
+        state: &str,
+        time: f32,
+        output: &mut [SoaTransform],
+    ) -> Result<bool, OzzError> {
+        let Some(layer) = self.layers.iter().find(|l| l.has_state(state)) else {
+            return Ok(false);
+        };
+        layer.evaluate_at(state, &self.parameters, time, output)
+    }
+
+    /// Evaluate every layer's current state at a time in seconds with the given
+    /// parameters and blend them into a static pose, without advancing the
+    /// controller. Transitions in progress are ignored, use `seek_state` first
+    /// to pick the states to bake.
+    /// ## Example
+    /// ```ignore
+    /// controller.seek_state("death", 0.0);
+    /// let death_pose = controller.bake_pose(&parameters, 1.2)?;
+    /// ```
+    pub fn bake_pose(
+        &self,
+        parameters: &Parameters,
+        time: f32,
+    ) -> Result<BakedPose, AnimatorError> {
+        let num_soa_joints = self.skeleton.num_soa_joints();
+        let mut blending_job: BlendingJobArc = BlendingJob::default();
+        blending_job.set_skeleton(self.skeleton.clone());
+
+        let additive_weights: Vec<f32> = self
+            .layers
+            .iter()
+            .filter(|l| l.layer_blend_type == LayerBlendType::Additive)
+            .map(|l| l.layer_weight)
+            .collect();
+        let mut additive_weights = self
+            .additive_accumulation
+            .accumulate(&additive_weights)
+            .into_iter();
+
+        for layer in &self.layers {
+            let mut pose = vec![SoaTransform::default(); num_soa_joints];
+            layer
+                .evaluate_at(layer.current_state(), parameters, time, &mut pose)
+                .map_err(|error| {
+                    AnimatorError::from(error).in_state(&layer.name, layer.current_state())
+                })?;
+            let transform = Arc::new(RwLock::new(pose));
+            match layer.layer_blend_type {
+                LayerBlendType::Override => blending_job.layers_mut().push(BlendingLayer {
+                    transform,
+                    weight: layer.layer_weight,
+                    joint_weights: vec![],
+                }),
+                LayerBlendType::Additive => {
+                    blending_job.additive_layers_mut().push(BlendingLayer {
+                        transform,
+                        weight: additive_weights.next().unwrap_or(0.0),
+                        joint_weights: vec![],
+                    })
+                }
+            }
+        }
+
+        let output = Arc::new(RwLock::new(vec![SoaTransform::default(); num_soa_joints]));
+        blending_job.set_output(output.clone());
+        blending_job.run().map_err(AnimatorError::FinalBlending)?;
+        let pose = read_lock(&output).clone();
+        Ok(BakedPose::new(pose, self.skeleton.num_joints()))
+    }
+
+    /// Set the version of the animation graph. Bump this whenever the layers,
+    /// states or transitions change so old snapshots are rejected.
+    /// ## Example
+    /// ```ignore
+    /// let controller = AnimatorController::new(skeleton, layers, parameters)?.with_graph_version(3);
+    /// ```
+    #[inline]
+    pub fn with_graph_version(mut self, graph_version: u32) -> Self {
+        self.graph_version = graph_version;
+        self
+    }
+
+    #[inline]
+    pub fn graph_version(&self) -> u32 {
+        self.graph_version
+    }
+
+    /// Capture the runtime state of the controller for saving
+    pub fn snapshot(&self) -> AnimatorSnapshot {
+        AnimatorSnapshot {
+            graph_version: self.graph_version,
+            parameters: self.parameters.clone(),
+            layers: self.layers.iter().map(|layer| layer.snapshot()).collect(),
+        }
+    }
+
+    /// Restore the runtime state of the controller from a snapshot, resuming
+    /// every layer mid-animation. Parameters are restored by name, so their
+    /// ids stay valid, and the snapshot may only hold parameters the
+    /// controller has with the same type.
+    pub fn restore_snapshot(&mut self, snapshot: &AnimatorSnapshot) -> Result<(), AnimatorError> {
+        if snapshot.graph_version != self.graph_version {
+            return Err(AnimatorError::GraphVersionMismatch {
+                expected: self.graph_version,
+                found: snapshot.graph_version,
+            });
+        }
+        // Check every layer and parameter before changing any, so a bad
+        // snapshot leaves the controller as it was
+        let is_loop_count = |name: &str| {
+            self.layers.iter().any(|layer| layer.loop_count_parameter() == name)
+        };
+        self.parameters.check_snapshot(&snapshot.parameters, is_loop_count)?;
+        for layer_snapshot in &snapshot.layers {
+            let Some(layer) = self.layers.iter().find(|l| l.name == layer_snapshot.name) else {
+                return Err(AnimatorError::UnknownLayer(layer_snapshot.name.clone()));
+            };
+            layer.check_snapshot(layer_snapshot)?;
+        }
+
+        for layer_snapshot in &snapshot.layers {
+            if let Some(layer) = self.layers.iter_mut().find(|l| l.name == layer_snapshot.name) {
+                layer.restore(layer_snapshot)?;
+            }
+        }
+        self.parameters.restore_snapshot(&snapshot.parameters);
+        self.build_blending_layers();
+        Ok(())
+    }
 }
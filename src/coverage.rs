@@ -0,0 +1,112 @@
+use super::AnimatorController;
+use std::fmt;
+
+/// Which states and transitions of a controller were exercised while usage
+/// tracking was enabled, e.g. by a scripted headless test run
+/// ## Example
+/// ```ignore
+/// controller.set_usage_tracking(true);
+/// // ... drive the controller through a scripted run
+/// let coverage = controller.coverage();
+/// assert!(coverage.is_complete(), "{coverage}");
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CoverageReport {
+    pub covered_states: usize,
+    pub total_states: usize,
+    pub covered_transitions: usize,
+    pub total_transitions: usize,
+    /// States never entered, as `(layer, state)`
+    pub missed_states: Vec<(String, String)>,
+    /// Transitions never fired, as `(layer, from, to)`
+    pub missed_transitions: Vec<(String, String, String)>,
+}
+
+impl CoverageReport {
+    /// If every state was entered and every transition fired
+    #[inline]
+    pub fn is_complete(&self) -> bool {
+        self.missed_states.is_empty() && self.missed_transitions.is_empty()
+    }
+
+    /// Fraction of states entered, 1 for graphs without states
+    #[inline]
+    pub fn state_coverage(&self) -> f32 {
+        ratio(self.covered_states, self.total_states)
+    }
+
+    /// Fraction of transitions fired, 1 for graphs without transitions
+    #[inline]
+    pub fn transition_coverage(&self) -> f32 {
+        ratio(self.covered_transitions, self.total_transitions)
+    }
+}
+
+#[inline]
+fn ratio(covered: usize, total: usize) -> f32 {
+    if total == 0 {
+        1.0
+    } else {
+        covered as f32 / total as f32
+    }
+}
+
+impl fmt::Display for CoverageReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "states {}/{}, transitions {}/{}",
+            self.covered_states,
+            self.total_states,
+            self.covered_transitions,
+            self.total_transitions
+        )?;
+        for (layer, state) in &self.missed_states {
+            writeln!(f, "  missed state {layer}/{state}")?;
+        }
+        for (layer, from, to) in &self.missed_transitions {
+            writeln!(f, "  missed transition {layer}/{from} -> {to}")?;
+        }
+        Ok(())
+    }
+}
+
+impl AnimatorController {
+    /// Coverage of the layers with usage tracking enabled, see
+    /// [`AnimatorController::set_usage_tracking`]. Missed states and
+    /// transitions are sorted by layer then state name.
+    pub fn coverage(&self) -> CoverageReport {
+        let mut report = CoverageReport::default();
+        for layer in &self.layers {
+            let Some(usage) = layer.usage() else {
+                continue;
+            };
+            let mut states: Vec<&str> = layer.state_names().collect();
+            states.sort_unstable();
+            for state in states {
+                report.total_states += 1;
+                if usage.get(state).is_some_and(|usage| usage.plays > 0) {
+                    report.covered_states += 1;
+                } else {
+                    report
+                        .missed_states
+                        .push((layer.name.clone(), state.to_string()));
+                }
+
+                for (i, transition) in layer.transitions(state).iter().enumerate() {
+                    report.total_transitions += 1;
+                    if layer.transition_fires(state, i).unwrap_or(0) > 0 {
+                        report.covered_transitions += 1;
+                    } else {
+                        report.missed_transitions.push((
+                            layer.name.clone(),
+                            state.to_string(),
+                            transition.to_state.clone(),
+                        ));
+                    }
+                }
+            }
+        }
+        report
+    }
+}
@@ -0,0 +1,177 @@
+use super::{AnimatorController, OzzTransform};
+use bevy::prelude::*;
+use std::collections::VecDeque;
+
+/// Keeps a short history of a controller's poses so many [`CrowdAnimator`]
+/// entities can share it, each looking a little further back in time
+/// ## Example
+/// ```ignore
+/// let leader = commands.spawn((controller, CrowdSource::new(2.0))).id();
+/// for i in 0..50 {
+///     commands.spawn((
+///         SceneRoot(mesh.clone()),
+///         CrowdAnimator::new(leader).with_phase_offset(i as f32 * 0.04).mirrored(i % 2 == 0),
+///     ));
+/// }
+/// ```
+#[derive(Component, Debug)]
+pub struct CrowdSource {
+    /// How many seconds of poses to keep
+    history_length: f32,
+    /// Time since the source started recording
+    elapsed: f32,
+    /// Recorded poses with the time they were recorded at, newest first
+    history: VecDeque<(f32, Vec<OzzTransform>)>,
+    /// Index of the mirrored counterpart of every joint
+    mirror_map: Vec<usize>,
+}
+
+impl CrowdSource {
+    /// Keep `history_length` seconds of poses, the largest usable phase offset
+    #[inline]
+    pub fn new(history_length: f32) -> Self {
+        Self {
+            history_length: history_length.max(0.0),
+            elapsed: 0.0,
+            history: VecDeque::new(),
+            mirror_map: Vec::new(),
+        }
+    }
+
+    /// The pose from `offset` seconds ago, or the oldest one kept
+    #[inline]
+    pub fn pose_at(&self, offset: f32) -> Option<&[OzzTransform]> {
+        let time = self.elapsed - offset.max(0.0);
+        self.history
+            .iter()
+            .find(|(recorded, _)| *recorded <= time)
+            .or(self.history.back())
+            .map(|(_, pose)| pose.as_slice())
+    }
+
+    /// Joint that mirrors `joint`, itself for joints on the center line
+    #[inline]
+    pub fn mirrored_joint(&self, joint: usize) -> usize {
+        self.mirror_map.get(joint).copied().unwrap_or(joint)
+    }
+
+    /// Transform of a joint in the pose from `offset` seconds ago, mirrored
+    /// left to right if asked
+    #[inline]
+    pub fn joint_at(&self, offset: f32, joint: usize, mirrored: bool) -> Option<OzzTransform> {
+        let pose = self.pose_at(offset)?;
+        if !mirrored {
+            return pose.get(joint).copied();
+        }
+        pose.get(self.mirrored_joint(joint)).map(mirror_transform)
+    }
+}
+
+/// Plays the pose of a [`CrowdSource`] without running a state machine, for
+/// cheap background characters
+#[derive(Component, Debug, Clone)]
+pub struct CrowdAnimator {
+    /// Entity with the `AnimatorController` and `CrowdSource`
+    pub source: Entity,
+    /// How many seconds behind the source this entity plays
+    pub phase_offset: f32,
+    /// Swap left and right
+    pub mirrored: bool,
+}
+
+impl CrowdAnimator {
+    #[inline]
+    pub fn new(source: Entity) -> Self {
+        Self {
+            source,
+            phase_offset: 0.0,
+            mirrored: false,
+        }
+    }
+
+    #[inline]
+    pub fn with_phase_offset(mut self, phase_offset: f32) -> Self {
+        self.phase_offset = phase_offset;
+        self
+    }
+
+    #[inline]
+    pub fn mirrored(mut self, mirrored: bool) -> Self {
+        self.mirrored = mirrored;
+        self
+    }
+}
+
+/// Store the latest pose of every crowd source
+pub fn record_crowd_sources(
+    mut sources: Query<(&AnimatorController, &mut CrowdSource)>,
+    time: Res<Time>,
+) {
+    for (controller, mut source) in &mut sources {
+        if source.mirror_map.len() != controller.joint_count() {
+            source.mirror_map = mirror_map(controller);
+        }
+        if controller.bone_trans.is_empty() {
+            continue;
+        }
+
+        source.elapsed += time.delta_secs();
+        let elapsed = source.elapsed;
+        source.history.push_front((elapsed, controller.bone_trans.clone()));
+
+        // Keep one pose older than the history length so the largest offset stays valid
+        let oldest = elapsed - source.history_length;
+        while source.history.len() > 1 && source.history[source.history.len() - 2].0 <= oldest {
+            source.history.pop_back();
+        }
+    }
+}
+
+/// Find the left/right counterpart of every joint by name
+pub(crate) fn mirror_map(controller: &AnimatorController) -> Vec<usize> {
+    const SIDES: [(&str, &str); 7] = [
+        ("Left", "Right"),
+        ("left", "right"),
+        ("LEFT", "RIGHT"),
+        ("_L", "_R"),
+        ("L_", "R_"),
+        ("_l", "_r"),
+        (".L", ".R"),
+    ];
+
+    controller
+        .joints()
+        .map(|joint| {
+            SIDES
+                .iter()
+                .flat_map(|&(left, right)| {
+                    [
+                        joint.name.replace(left, right),
+                        joint.name.replace(right, left),
+                    ]
+                })
+                .filter(|name| name.as_str() != joint.name)
+                .find_map(|name| controller.find_joint(&name))
+                .unwrap_or(joint.index)
+        })
+        .collect()
+}
+
+/// Reflect a local transform across the YZ plane
+#[inline]
+pub(crate) fn mirror_transform(transform: &OzzTransform) -> OzzTransform {
+    OzzTransform {
+        scale: transform.scale,
+        rotation: Quat::from_xyzw(
+            transform.rotation.x,
+            -transform.rotation.y,
+            -transform.rotation.z,
+            transform.rotation.w,
+        ),
+        position: Vec3::new(
+            -transform.position.x,
+            transform.position.y,
+            transform.position.z,
+        ),
+    }
+}
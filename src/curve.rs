@@ -0,0 +1,215 @@
+use super::AnimatorController;
+use bevy::{
+    asset::{AssetLoader, LoadContext, io::Reader},
+    prelude::*,
+};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+/// A keyframed float curve, loaded from `.curve.ron` files
+/// ## Example
+/// ```ron
+/// (
+///     interpolation: Smooth,
+///     keys: [
+///         (time: 0.0, value: 0.0),
+///         (time: 0.25, value: 1.0),
+///     ],
+/// )
+/// ```
+#[derive(Asset, TypePath, Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct CurveAsset {
+    #[serde(default)]
+    pub interpolation: CurveInterpolation,
+    /// Keys in time order
+    pub keys: Vec<CurveKey>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct CurveKey {
+    /// Time in seconds
+    pub time: f32,
+    pub value: f32,
+}
+
+/// How a curve moves between its keys
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum CurveInterpolation {
+    /// Hold each key's value until the next key
+    Step,
+    #[default]
+    Linear,
+    /// Ease in and out of every key
+    Smooth,
+}
+
+impl CurveAsset {
+    /// Create a curve, sorting the keys by time
+    #[inline]
+    pub fn new(interpolation: CurveInterpolation, mut keys: Vec<CurveKey>) -> Self {
+        keys.sort_by(|a, b| a.time.total_cmp(&b.time));
+        Self {
+            interpolation,
+            keys,
+        }
+    }
+
+    /// Time of the last key in seconds
+    #[inline]
+    pub fn duration(&self) -> f32 {
+        self.keys.last().map_or(0.0, |key| key.time)
+    }
+
+    /// Value of the curve at a time in seconds, holding the first and last
+    /// values outside of the keys. 0 for curves without keys.
+    pub fn sample(&self, time: f32) -> f32 {
+        let Some(first) = self.keys.first() else {
+            return 0.0;
+        };
+        let next = self.keys.partition_point(|key| key.time <= time);
+        if next == 0 {
+            return first.value;
+        }
+        let from = self.keys[next - 1];
+        let Some(to) = self.keys.get(next) else {
+            return from.value;
+        };
+
+        let t = (time - from.time) / (to.time - from.time);
+        let t = match self.interpolation {
+            CurveInterpolation::Step => 0.0,
+            CurveInterpolation::Linear => t,
+            CurveInterpolation::Smooth => t * t * (3.0 - 2.0 * t),
+        };
+        from.value + (to.value - from.value) * t
+    }
+}
+
+/// Loads `.curve.ron` files into [`CurveAsset`]s
+#[derive(Default)]
+pub struct CurveAssetLoader;
+
+/// Possible errors that can be produced by CurveAssetLoader
+#[non_exhaustive]
+#[derive(Debug, Error)]
+pub enum CurveLoaderError {
+    /// An IO Error
+    #[error("Could not read the file: {0}")]
+    Io(#[from] std::io::Error),
+    /// A RON Error
+    #[error("Could not parse the curve: {0}")]
+    Ron(#[from] ron::error::SpannedError),
+}
+
+impl AssetLoader for CurveAssetLoader {
+    type Asset = CurveAsset;
+    type Settings = ();
+    type Error = CurveLoaderError;
+
+    async fn load(
+        &self,
+        reader: &mut dyn Reader,
+        _settings: &(),
+        _load_context: &mut LoadContext<'_>,
+    ) -> Result<Self::Asset, Self::Error> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+        let curve: CurveAsset = ron::de::from_bytes(&bytes)?;
+        Ok(CurveAsset::new(curve.interpolation, curve.keys))
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["curve.ron"]
+    }
+}
+
+/// Plays a curve into a float parameter of the controller on the same entity
+#[derive(Debug, Clone)]
+pub struct CurveDriver {
+    pub curve: Handle<CurveAsset>,
+    /// Float parameter the curve is written to
+    pub parameter: String,
+    /// Trigger parameter that starts the curve, if any
+    pub trigger: Option<String>,
+    /// Playback time, None while stopped
+    time: Option<f32>,
+}
+
+impl CurveDriver {
+    #[inline]
+    pub fn new(curve: Handle<CurveAsset>, parameter: impl Into<String>) -> Self {
+        Self {
+            curve,
+            parameter: parameter.into(),
+            trigger: None,
+            time: None,
+        }
+    }
+
+    /// Start playing whenever the trigger parameter is set
+    #[inline]
+    pub fn with_trigger(mut self, trigger: impl Into<String>) -> Self {
+        self.trigger = Some(trigger.into());
+        self
+    }
+
+    /// Play the curve from the start
+    #[inline]
+    pub fn play(&mut self) {
+        self.time = Some(0.0);
+    }
+
+    /// Stop playing, leaving the parameter at its current value
+    #[inline]
+    pub fn stop(&mut self) {
+        self.time = None;
+    }
+
+    #[inline]
+    pub fn is_playing(&self) -> bool {
+        self.time.is_some()
+    }
+}
+
+/// Curves driving parameters of the `AnimatorController` on the same entity
+/// ## Example
+/// ```ignore
+/// commands.entity(player).insert(CurveDrivers(vec![
+///     CurveDriver::new(asset_server.load("aim_ramp.curve.ron"), "aim_weight")
+///         .with_trigger("aim"),
+/// ]));
+/// ```
+#[derive(Component, Debug, Clone, Default)]
+pub struct CurveDrivers(pub Vec<CurveDriver>);
+
+/// Start triggered curves and write every playing curve into its parameter,
+/// before the controllers update and reset their triggers
+pub fn drive_curves(
+    mut query: Query<(&mut CurveDrivers, &mut AnimatorController)>,
+    curves: Res<Assets<CurveAsset>>,
+    time: Res<Time>,
+) {
+    for (mut drivers, mut controller) in query.iter_mut() {
+        for driver in drivers.0.iter_mut() {
+            let triggered = driver
+                .trigger
+                .as_ref()
+                .is_some_and(|trigger| controller.parameters().get_trigger(trigger));
+            let time = match (triggered, driver.time) {
+                (true, _) => 0.0,
+                (false, Some(current)) => current + time.delta_secs(),
+                (false, None) => continue,
+            };
+            // Wait for the curve to load before starting
+            let Some(curve) = curves.get(&driver.curve) else {
+                driver.time = Some(0.0);
+                continue;
+            };
+
+            controller
+                .parameters_mut()
+                .set_float(&driver.parameter, curve.sample(time));
+            driver.time = (time < curve.duration()).then_some(time);
+        }
+    }
+}
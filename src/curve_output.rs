@@ -0,0 +1,150 @@
+use super::AnimatorController;
+use bevy::prelude::*;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// A float track sampled this update, as curve name, value and weight
+pub(crate) type CurveSample = (Arc<str>, f32, f32);
+
+impl AnimatorController {
+    /// Value of a named curve this update, blended from the float tracks of
+    /// every playing state that has it by the state and layer weights. None
+    /// while no playing state has the curve.
+    /// ## Example
+    /// ```ignore
+    /// if let Some(intensity) = controller.curve_value("weapon_trail_intensity") {
+    ///     trail.intensity = intensity;
+    /// }
+    /// ```
+    #[inline]
+    pub fn curve_value(&self, name: &str) -> Option<f32> {
+        self.curve_values.get(name).copied()
+    }
+
+    /// Every curve with a value this update
+    #[inline]
+    pub fn curve_values(&self) -> impl Iterator<Item = (&str, f32)> {
+        self.curve_values.iter().map(|(name, value)| (&**name, *value))
+    }
+
+    /// Blend the float tracks of the playing states into the curve values
+    pub(crate) fn update_curve_values(&mut self) {
+        let samples = &mut self.curve_samples;
+        samples.clear();
+        for layer in self.layers.iter_mut().filter(|layer| layer.is_active()) {
+            let weight = layer.effective_weight();
+            layer.sample_curves(weight, samples);
+        }
+
+        // Names are shared with the tracks, nothing is allocated once every
+        // curve was seen
+        let totals = &mut self.curve_totals;
+        totals.clear();
+        for (name, value, weight) in samples.drain(..) {
+            let (sum, total) = totals.entry(name).or_default();
+            *sum += value * weight;
+            *total += weight;
+        }
+        self.curve_values.clear();
+        self.curve_values.extend(
+            totals
+                .drain()
+                .filter(|(_, (_, total))| *total > 0.0)
+                .map(|(name, (sum, total))| (name, sum / total)),
+        );
+    }
+}
+
+/// The curve values of the `AnimatorController` on the same entity, copied
+/// after every update, for systems that shouldn't borrow the controller
+/// ## Example
+/// ```ignore
+/// commands.entity(player).insert(CurveOutputs::default());
+///
+/// fn weapon_trails(mut trails: Query<(&CurveOutputs, &mut WeaponTrail)>) {
+///     for (curves, mut trail) in &mut trails {
+///         trail.intensity = curves.get("weapon_trail_intensity").unwrap_or(0.0);
+///     }
+/// }
+/// ```
+#[derive(Component, Debug, Clone, Default)]
+pub struct CurveOutputs(pub HashMap<String, f32>);
+
+impl CurveOutputs {
+    #[inline]
+    pub fn get(&self, name: &str) -> Option<f32> {
+        self.0.get(name).copied()
+    }
+}
+
+/// Copy the curve values of every controller into its `CurveOutputs`
+pub fn sync_curve_outputs(mut query: Query<(&AnimatorController, &mut CurveOutputs)>) {
+    for (controller, mut outputs) in &mut query {
+        let curves = &controller.curve_values;
+        let unchanged = curves.len() == outputs.0.len()
+            && curves.iter().all(|(name, value)| outputs.get(name) == Some(*value));
+        if unchanged {
+            continue;
+        }
+        // Values are updated in place, names only allocated for new curves
+        let outputs = &mut outputs.0;
+        outputs.retain(|name, _| curves.contains_key(name.as_str()));
+        for (name, value) in curves {
+            match outputs.get_mut(&**name) {
+                Some(output) => *output = *value,
+                None => {
+                    outputs.insert(name.to_string(), *value);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        AnimationLayer, BlendMotionState, BlendState, BlendTree, BlendTreeType, LayerBlendType,
+        MotionData, MotionThreshold, Parameters, SimpleState, synthetic_clip, synthetic_skeleton,
+        test_track,
+    };
+    use std::sync::RwLock;
+
+    #[test]
+    fn blend_trees_blend_the_curves_of_their_motions() {
+        let skeleton = Arc::new(synthetic_skeleton(4, 1).unwrap());
+        let clip = Arc::new(synthetic_clip(&skeleton, 1.0, 30.0, 0));
+        let motions = [(0.0, 1.0), (1.0, 0.0)]
+            .into_iter()
+            .map(|(threshold, trail)| MotionData {
+                motion: BlendMotionState::Animation(Arc::new(RwLock::new(
+                    SimpleState::from_recording(clip.clone(), skeleton.num_soa_joints())
+                        .with_float_track("trail", test_track(trail)),
+                ))),
+                threshold: MotionThreshold::Simple1D(threshold),
+            })
+            .collect();
+        let tree = BlendTree::new(&skeleton, BlendTreeType::Simple1D("speed".to_string()), motions);
+        let mut layer = AnimationLayer::new(
+            "Base".to_string(),
+            LayerBlendType::Override,
+            1.0,
+            &skeleton,
+            "Move".to_string(),
+        );
+        layer.add_state("Move".to_string(), BlendState::new(tree).into());
+        let mut parameters = Parameters::new();
+        parameters.set_float("speed", 0.25);
+        let mut controller =
+            AnimatorController::new(skeleton.clone(), vec![layer], parameters).unwrap();
+
+        for _ in 0..2 {
+            controller.update(&Time::default()).unwrap();
+            let trail = controller.curve_value("trail").unwrap();
+            assert!((trail - 0.75).abs() < 1e-5, "{trail}");
+        }
+        controller.parameters_mut().set_float("speed", 1.0);
+        controller.update(&Time::default()).unwrap();
+        assert_eq!(controller.curve_value("trail"), Some(0.0));
+    }
+}
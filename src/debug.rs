@@ -0,0 +1,58 @@
+use super::AnimatorController;
+use bevy::prelude::*;
+
+/// Draws the animated skeleton of a controller with gizmos, like the skeleton
+/// view of the ozz samples. Add it next to an `AnimatorController`.
+/// ## Example
+/// ```ignore
+/// commands.entity(player).insert(DebugSkeleton::default());
+/// ```
+#[derive(Component, Debug, Clone)]
+pub struct DebugSkeleton {
+    /// Color of the bones
+    pub color: Color,
+    /// Radius of the sphere drawn at every joint, 0 draws only the bones
+    pub joint_radius: f32,
+}
+
+impl Default for DebugSkeleton {
+    fn default() -> Self {
+        Self {
+            color: Color::srgb(1.0, 0.8, 0.1),
+            joint_radius: 0.02,
+        }
+    }
+}
+
+/// Draw every controller that has a `DebugSkeleton`
+pub fn draw_debug_skeletons(
+    controllers: Query<(&AnimatorController, &GlobalTransform, &DebugSkeleton)>,
+    mut gizmos: Gizmos,
+) {
+    for (controller, global_transform, debug) in &controllers {
+        let world = global_transform.compute_matrix();
+        let joints: Vec<Vec3> = controller
+            .model_space_bones()
+            .iter()
+            .map(|model| (world * *model).w_axis.truncate())
+            .collect();
+        // Nothing to draw before the first update
+        if joints.len() != controller.joint_count() {
+            continue;
+        }
+
+        for joint in controller.joints() {
+            let position = joints[joint.index];
+            if let Some(parent) = joint.parent {
+                gizmos.line(joints[parent], position, debug.color);
+            }
+            if debug.joint_radius > 0.0 {
+                gizmos.sphere(
+                    Isometry3d::from_translation(position),
+                    debug.joint_radius,
+                    debug.color,
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,385 @@
+use super::{
+    AnimationLayer, AnimationState, AnimatorController, AnimatorError, BlendMotionState,
+    BlendState, BlendTree, BlendTreeType, LayerBlendType, MotionData, MotionThreshold, OzzAsset,
+    Parameters, PlaybackMode, SimpleState, Transition, TransitionCondition,
+};
+use bevy::{
+    asset::{AssetLoader, LoadContext, LoadDirectError, io::Reader},
+    prelude::*,
+};
+use ozz_animation_rs::{Animation, OzzError, Skeleton};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use thiserror::Error;
+
+/// Layers, states, blend trees, transitions and parameters of a controller,
+/// referencing `.ozz` files by asset path. Usually loaded from a
+/// `.controller.ron` file as an [`AnimatorControllerAsset`].
+/// ## Example
+/// ```ron
+/// (
+///     skeleton: "skeleton.ozz",
+///     parameters: (floats: {"speed": 0.0}),
+///     predicates: {"IsMoving": [Float("speed", 0.1, Greater)]},
+///     layers: [(
+///         name: "Base Layer",
+///         default_state: "Idle",
+///         states: [
+///             (
+///                 name: "Idle",
+///                 motion: Clip((path: "idle.ozz")),
+///                 transitions: [(
+///                     to_state: "Locomotion",
+///                     duration: 0.2,
+///                     conditions: [Predicate("IsMoving")],
+///                 )],
+///             ),
+///             (
+///                 name: "Locomotion",
+///                 motion: BlendTree((
+///                     blend_type: Simple1D("speed"),
+///                     motions: [
+///                         (Simple1D(0.0), Clip((path: "walk.ozz"))),
+///                         (Simple1D(1.0), Clip((path: "run.ozz"))),
+///                     ],
+///                 )),
+///             ),
+///         ],
+///     )],
+/// )
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ControllerDefinition {
+    /// Asset path of the skeleton
+    pub skeleton: String,
+    #[serde(default)]
+    pub parameters: Parameters,
+    /// Named condition sets transitions can reference with `Predicate`
+    #[serde(default)]
+    pub predicates: HashMap<String, Vec<TransitionCondition>>,
+    /// Layers in blending order
+    pub layers: Vec<LayerDefinition>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LayerDefinition {
+    pub name: String,
+    #[serde(default = "default_blend_type")]
+    pub blend_type: LayerBlendType,
+    #[serde(default = "default_one")]
+    pub weight: f32,
+    pub default_state: String,
+    /// Conditions that must all hold for the layer to play, empty for always
+    #[serde(default)]
+    pub activation: Vec<TransitionCondition>,
+    /// Clip states that start at a random time whenever they are entered
+    #[serde(default)]
+    pub random_start: Vec<String>,
+    pub states: Vec<StateDefinition>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StateDefinition {
+    pub name: String,
+    pub motion: MotionDefinition,
+    /// Transitions out of this state, in priority order
+    #[serde(default)]
+    pub transitions: Vec<Transition>,
+    /// Gameplay tags active while the state is current
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+/// What a state or blend tree motion plays
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum MotionDefinition {
+    Clip(ClipDefinition),
+    BlendTree(BlendTreeDefinition),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ClipDefinition {
+    /// Asset path of the animation
+    pub path: String,
+    #[serde(default = "default_one")]
+    pub speed: f32,
+    /// Float parameter the speed is multiplied by
+    #[serde(default)]
+    pub speed_parameter: Option<String>,
+    #[serde(default)]
+    pub playback_mode: PlaybackMode,
+    /// Named markers at normalized times
+    #[serde(default)]
+    pub markers: Vec<(String, f32)>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BlendTreeDefinition {
+    pub blend_type: BlendTreeType,
+    /// Every motion of the tree with its threshold
+    pub motions: Vec<(MotionThreshold, MotionDefinition)>,
+}
+
+#[inline]
+fn default_blend_type() -> LayerBlendType {
+    LayerBlendType::Override
+}
+
+#[inline]
+fn default_one() -> f32 {
+    1.0
+}
+
+impl ControllerDefinition {
+    /// Asset path of every clip the definition plays, without duplicates
+    pub fn clip_paths(&self) -> Vec<&str> {
+        fn collect<'a>(motion: &'a MotionDefinition, paths: &mut Vec<&'a str>) {
+            match motion {
+                MotionDefinition::Clip(clip) => {
+                    if !paths.contains(&clip.path.as_str()) {
+                        paths.push(&clip.path);
+                    }
+                }
+                MotionDefinition::BlendTree(tree) => {
+                    for (_, motion) in &tree.motions {
+                        collect(motion, paths);
+                    }
+                }
+            }
+        }
+
+        let mut paths = Vec::new();
+        for state in self.layers.iter().flat_map(|layer| &layer.states) {
+            collect(&state.motion, &mut paths);
+        }
+        paths
+    }
+
+    /// Build a controller from the definition, with `clips` holding the
+    /// animation of every path in `clip_paths()`
+    pub fn build(
+        &self,
+        skeleton: Arc<Skeleton>,
+        clips: &HashMap<String, Arc<Animation>>,
+    ) -> Result<AnimatorController, AnimatorError> {
+        let mut layers = Vec::with_capacity(self.layers.len());
+        for definition in &self.layers {
+            let mut layer = AnimationLayer::new(
+                definition.name.clone(),
+                definition.blend_type,
+                definition.weight,
+                &skeleton,
+                definition.default_state.clone(),
+            )
+            .with_activation(definition.activation.clone());
+            for state in &definition.random_start {
+                layer.set_random_start(state, true);
+            }
+            for state in &definition.states {
+                let animation_state = match &state.motion {
+                    MotionDefinition::Clip(clip) => {
+                        AnimationState::Simple(build_clip(clip, &skeleton, clips)?)
+                    }
+                    MotionDefinition::BlendTree(tree) => {
+                        AnimationState::Blend(build_blend_tree(tree, &skeleton, clips)?)
+                    }
+                };
+                layer.add_state(state.name.clone(), animation_state);
+                for tag in &state.tags {
+                    layer.add_state_tag(state.name.clone(), tag.clone());
+                }
+                for transition in &state.transitions {
+                    let mut transition = transition.clone();
+                    if let Some(mask) = &mut transition.mask {
+                        mask.bind(&skeleton)?;
+                    }
+                    layer.add_transition(state.name.clone(), transition);
+                }
+            }
+            layers.push(layer);
+        }
+        let mut controller = AnimatorController::new(skeleton, layers, self.parameters.clone())?;
+        for (name, conditions) in &self.predicates {
+            controller.set_predicate(name.clone(), conditions.clone());
+        }
+        Ok(controller)
+    }
+}
+
+fn build_clip(
+    definition: &ClipDefinition,
+    skeleton: &Arc<Skeleton>,
+    clips: &HashMap<String, Arc<Animation>>,
+) -> Result<SimpleState, AnimatorError> {
+    let animation = clips
+        .get(&definition.path)
+        .ok_or_else(|| AnimatorError::UnknownClip(definition.path.clone()))?;
+    let mut state = SimpleState::new(animation.clone(), skeleton.num_soa_joints())
+        .with_speed(definition.speed)
+        .with_playback_mode(definition.playback_mode);
+    if let Some(parameter) = &definition.speed_parameter {
+        state = state.with_speed_parameter(parameter.clone());
+    }
+    for (name, normalized_time) in &definition.markers {
+        state.add_marker(name.clone(), *normalized_time);
+    }
+    Ok(state)
+}
+
+fn build_blend_tree(
+    definition: &BlendTreeDefinition,
+    skeleton: &Arc<Skeleton>,
+    clips: &HashMap<String, Arc<Animation>>,
+) -> Result<BlendState, AnimatorError> {
+    let mut motions = Vec::with_capacity(definition.motions.len());
+    for (threshold, motion) in &definition.motions {
+        let motion = match motion {
+            MotionDefinition::Clip(clip) => BlendMotionState::Animation(Arc::new(RwLock::new(
+                build_clip(clip, skeleton, clips)?,
+            ))),
+            MotionDefinition::BlendTree(tree) => BlendMotionState::SubTree(Arc::new(
+                RwLock::new(build_blend_tree(tree, skeleton, clips)?),
+            )),
+        };
+        motions.push(MotionData {
+            motion,
+            threshold: threshold.clone(),
+        });
+    }
+    let tree = BlendTree::new(skeleton, definition.blend_type.clone(), motions);
+    Ok(BlendState::new(tree))
+}
+
+/// A [`ControllerDefinition`] loaded together with its skeleton and clips
+/// ## Example
+/// ```ignore
+/// let handle: Handle<AnimatorControllerAsset> = asset_server.load("player.controller.ron");
+///
+/// // Once loaded
+/// let controller = controller_assets.get(&handle).unwrap().build()?;
+/// commands.spawn((SceneRoot(player_mesh), controller));
+/// ```
+#[derive(Asset, TypePath, Debug)]
+pub struct AnimatorControllerAsset {
+    pub definition: ControllerDefinition,
+    skeleton: Arc<Skeleton>,
+    clips: HashMap<String, Arc<Animation>>,
+    /// Handles of the skeleton and every clip, held by every controller built
+    /// from the asset
+    #[dependency]
+    dependencies: Vec<UntypedHandle>,
+}
+
+impl AnimatorControllerAsset {
+    /// Build a new controller from the definition, every call gets its own
+    /// playback state
+    #[inline]
+    pub fn build(&self) -> Result<AnimatorController, AnimatorError> {
+        let mut controller = self.definition.build(self.skeleton.clone(), &self.clips)?;
+        for handle in &self.dependencies {
+            controller.add_dependency(handle.clone());
+        }
+        Ok(controller)
+    }
+
+    /// Handles of the skeleton and every clip of the definition
+    #[inline]
+    pub fn dependencies(&self) -> &[UntypedHandle] {
+        &self.dependencies
+    }
+
+    #[inline]
+    pub fn skeleton(&self) -> &Arc<Skeleton> {
+        &self.skeleton
+    }
+
+    /// A clip of the definition by asset path
+    #[inline]
+    pub fn clip(&self, path: &str) -> Option<&Arc<Animation>> {
+        self.clips.get(path)
+    }
+}
+
+/// Loads `.controller.ron` files into [`AnimatorControllerAsset`]s
+#[derive(Default)]
+pub struct AnimatorControllerAssetLoader;
+
+/// Possible errors that can be produced by AnimatorControllerAssetLoader
+#[non_exhaustive]
+#[derive(Debug, Error)]
+pub enum ControllerLoaderError {
+    /// An IO Error
+    #[error("Could not read the file: {0}")]
+    Io(#[from] std::io::Error),
+    /// A RON Error
+    #[error("Could not parse the controller: {0}")]
+    Ron(#[from] ron::error::SpannedError),
+    /// The skeleton or a clip couldn't be loaded
+    #[error("Could not load a referenced file: {0}")]
+    Dependency(#[from] LoadDirectError),
+    /// The skeleton or a clip isn't a valid Ozz archive
+    #[error("Could not parse Ozz animation: {0}")]
+    Ozz(#[from] OzzError),
+}
+
+impl AssetLoader for AnimatorControllerAssetLoader {
+    type Asset = AnimatorControllerAsset;
+    type Settings = ();
+    type Error = ControllerLoaderError;
+
+    async fn load(
+        &self,
+        reader: &mut dyn Reader,
+        _settings: &(),
+        load_context: &mut LoadContext<'_>,
+    ) -> Result<Self::Asset, Self::Error> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+        let definition: ControllerDefinition = ron::de::from_bytes(&bytes)?;
+
+        let mut skeleton = load_context
+            .loader()
+            .immediate()
+            .load::<OzzAsset>(definition.skeleton.as_str())
+            .await?
+            .take();
+        let skeleton = Arc::new(Skeleton::from_archive(&mut skeleton.archive)?);
+        let mut dependencies = vec![
+            load_context
+                .load::<OzzAsset>(definition.skeleton.as_str())
+                .untyped(),
+        ];
+
+        let mut clips = HashMap::new();
+        for path in definition.clip_paths() {
+            dependencies.push(load_context.load::<OzzAsset>(path).untyped());
+            let mut clip = load_context
+                .loader()
+                .immediate()
+                .load::<OzzAsset>(path)
+                .await?
+                .take();
+            let animation = Animation::from_archive(&mut clip.archive)?;
+            clips.insert(path.to_string(), Arc::new(animation));
+        }
+
+        // Build errors are reported when the asset is built, only warn here
+        if let Ok(controller) = definition.build(skeleton.clone(), &clips) {
+            for issue in controller.validate() {
+                warn!("{}: {issue}", load_context.path().display());
+            }
+        }
+
+        Ok(AnimatorControllerAsset {
+            definition,
+            skeleton,
+            clips,
+            dependencies,
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["controller.ron"]
+    }
+}
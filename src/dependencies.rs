@@ -0,0 +1,44 @@
+use super::AnimatorController;
+use bevy::prelude::*;
+
+impl AnimatorController {
+    /// Handles of every asset the controller was built from: its controller
+    /// asset, skeleton and clips when built by an `AnimatorControllerHandle`,
+    /// plus any added with [`Self::add_dependency`]. The controller holds them,
+    /// so they stay loaded for as long as it exists, and streaming, reloading
+    /// or memory accounting systems can tell what a character needs resident.
+    /// ## Example
+    /// ```ignore
+    /// let resident = controller
+    ///     .dependencies()
+    ///     .iter()
+    ///     .all(|handle| asset_server.is_loaded_with_dependencies(handle.id()));
+    /// ```
+    #[inline]
+    pub fn dependencies(&self) -> &[UntypedHandle] {
+        &self.dependencies
+    }
+
+    /// Keep an asset loaded for as long as the controller exists, e.g. the
+    /// `OzzAsset`s of a controller built by hand. Adding a handle twice keeps
+    /// a single one.
+    #[inline]
+    pub fn add_dependency(&mut self, handle: impl Into<UntypedHandle>) {
+        let handle = handle.into();
+        if !self.dependencies.contains(&handle) {
+            self.dependencies.push(handle);
+        }
+    }
+
+    #[inline]
+    pub fn with_dependency(mut self, handle: impl Into<UntypedHandle>) -> Self {
+        self.add_dependency(handle);
+        self
+    }
+
+    /// Release every dependency, the assets unload once nothing else holds them
+    #[inline]
+    pub fn clear_dependencies(&mut self) {
+        self.dependencies.clear();
+    }
+}
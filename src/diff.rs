@@ -0,0 +1,200 @@
+use super::{
+    AnimationLayer, AnimationState, AnimatorController, ParameterValue, Parameters,
+    TransitionCondition,
+};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::sync::Arc;
+
+/// How something differs between two graphs
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiffKind {
+    Added,
+    Removed,
+    Changed,
+}
+
+/// Differences between two animator graphs, from `old` to `new`
+/// ## Example
+/// ```ignore
+/// let diff = GraphDiff::between(&old_controller, &new_controller);
+/// if !diff.is_empty() {
+///     info!("Animator changed:\n{diff}");
+/// }
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct GraphDiff {
+    /// Layers added, removed, or with a different blend type or weight
+    pub layers: Vec<(String, DiffKind)>,
+    /// States added, removed, or playing a different clip, blend tree or pose,
+    /// as `(layer, state, kind)`
+    pub states: Vec<(String, String, DiffKind)>,
+    /// Transitions between a pair of states added, removed or changed, as
+    /// `(layer, from, to, kind)`
+    pub transitions: Vec<(String, String, String, DiffKind)>,
+    /// Parameters added, removed, or with a different default value
+    pub parameters: Vec<(String, DiffKind)>,
+    /// Predicates added, removed, or with different conditions
+    pub predicates: Vec<(String, DiffKind)>,
+}
+
+impl GraphDiff {
+    /// Diff the graphs of two controllers, ignoring their runtime state.
+    /// Everything is reported in name order.
+    pub fn between(old: &AnimatorController, new: &AnimatorController) -> Self {
+        let mut diff = Self {
+            parameters: diff_parameters(old.parameters(), new.parameters()),
+            predicates: diff_predicates(old, new),
+            ..Default::default()
+        };
+
+        let old_layers: BTreeMap<&str, &AnimationLayer> =
+            old.layers.iter().map(|layer| (layer.name.as_str(), layer)).collect();
+        let new_layers: BTreeMap<&str, &AnimationLayer> =
+            new.layers.iter().map(|layer| (layer.name.as_str(), layer)).collect();
+        for name in old_layers.keys().chain(new_layers.keys()).collect::<BTreeSet<_>>() {
+            match (old_layers.get(name), new_layers.get(name)) {
+                (Some(old), Some(new)) => {
+                    if old.layer_blend_type != new.layer_blend_type
+                        || old.layer_weight != new.layer_weight
+                    {
+                        diff.layers.push((name.to_string(), DiffKind::Changed));
+                    }
+                    diff.diff_layer(old, new);
+                }
+                // The contents of added and removed layers aren't listed
+                (Some(_), None) => diff.layers.push((name.to_string(), DiffKind::Removed)),
+                (None, Some(_)) => diff.layers.push((name.to_string(), DiffKind::Added)),
+                (None, None) => unreachable!(),
+            }
+        }
+        diff
+    }
+
+    /// If both graphs are the same
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.layers.is_empty()
+            && self.states.is_empty()
+            && self.transitions.is_empty()
+            && self.parameters.is_empty()
+            && self.predicates.is_empty()
+    }
+
+    fn diff_layer(&mut self, old: &AnimationLayer, new: &AnimationLayer) {
+        let names: BTreeSet<&str> = old.state_names().chain(new.state_names()).collect();
+        for &state in &names {
+            let kind = match (old.state(state), new.state(state)) {
+                (Some(old), Some(new)) if same_state(old, new) => None,
+                (Some(_), Some(_)) => Some(DiffKind::Changed),
+                (Some(_), None) => Some(DiffKind::Removed),
+                (None, _) => Some(DiffKind::Added),
+            };
+            if let Some(kind) = kind {
+                self.states.push((new.name.clone(), state.to_string(), kind));
+            }
+
+            // Transitions are compared per destination, in evaluation order
+            let (old_transitions, new_transitions) = (old.transitions(state), new.transitions(state));
+            let destinations: BTreeSet<&str> = old_transitions
+                .iter()
+                .chain(new_transitions)
+                .map(|transition| transition.to_state.as_str())
+                .collect();
+            for to in destinations {
+                let old_to = old_transitions.iter().filter(|t| t.to_state == to);
+                let new_to = new_transitions.iter().filter(|t| t.to_state == to);
+                let kind = match (old_to.clone().count(), new_to.clone().count()) {
+                    (0, _) => DiffKind::Added,
+                    (_, 0) => DiffKind::Removed,
+                    _ if old_to.eq(new_to) => continue,
+                    _ => DiffKind::Changed,
+                };
+                self.transitions
+                    .push((new.name.clone(), state.to_string(), to.to_string(), kind));
+            }
+        }
+    }
+}
+
+/// If two states play the same clips, blend trees or poses the same way
+fn same_state(old: &AnimationState, new: &AnimationState) -> bool {
+    match (old, new) {
+        (AnimationState::Simple(old), AnimationState::Simple(new)) => old.same_graph(new),
+        (AnimationState::Blend(old), AnimationState::Blend(new)) => old.same_graph(new),
+        (AnimationState::External(old), AnimationState::External(new)) => old.same_pose(new),
+        (AnimationState::Controller(old), AnimationState::Controller(new)) => {
+            Arc::ptr_eq(&old.skeleton, &new.skeleton) && GraphDiff::between(old, new).is_empty()
+        }
+        _ => false,
+    }
+}
+
+fn diff_parameters(old: &Parameters, new: &Parameters) -> Vec<(String, DiffKind)> {
+    let old: BTreeMap<&str, ParameterValue> = old.iter().collect();
+    let new: BTreeMap<&str, ParameterValue> = new.iter().collect();
+    old.keys()
+        .chain(new.keys())
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .filter_map(|name| {
+            let kind = match (old.get(name), new.get(name)) {
+                (Some(old), Some(new)) if old == new => return None,
+                (Some(_), Some(_)) => DiffKind::Changed,
+                (Some(_), None) => DiffKind::Removed,
+                (None, _) => DiffKind::Added,
+            };
+            Some((name.to_string(), kind))
+        })
+        .collect()
+}
+
+fn diff_predicates(
+    old: &AnimatorController,
+    new: &AnimatorController,
+) -> Vec<(String, DiffKind)> {
+    let old: BTreeMap<&str, &[TransitionCondition]> = old.predicates().collect();
+    let new: BTreeMap<&str, &[TransitionCondition]> = new.predicates().collect();
+    old.keys()
+        .chain(new.keys())
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .filter_map(|name| {
+            let kind = match (old.get(name), new.get(name)) {
+                (Some(old), Some(new)) if old == new => return None,
+                (Some(_), Some(_)) => DiffKind::Changed,
+                (Some(_), None) => DiffKind::Removed,
+                (None, _) => DiffKind::Added,
+            };
+            Some((name.to_string(), kind))
+        })
+        .collect()
+}
+
+impl fmt::Display for GraphDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fn sign(kind: DiffKind) -> char {
+            match kind {
+                DiffKind::Added => '+',
+                DiffKind::Removed => '-',
+                DiffKind::Changed => '~',
+            }
+        }
+        for (layer, kind) in &self.layers {
+            writeln!(f, "{} layer {layer}", sign(*kind))?;
+        }
+        for (layer, state, kind) in &self.states {
+            writeln!(f, "{} state {layer}/{state}", sign(*kind))?;
+        }
+        for (layer, from, to, kind) in &self.transitions {
+            writeln!(f, "{} transition {layer}/{from} -> {to}", sign(*kind))?;
+        }
+        for (name, kind) in &self.parameters {
+            writeln!(f, "{} parameter {name}", sign(*kind))?;
+        }
+        for (name, kind) in &self.predicates {
+            writeln!(f, "{} predicate {name}", sign(*kind))?;
+        }
+        Ok(())
+    }
+}
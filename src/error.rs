@@ -0,0 +1,128 @@
+use bevy::prelude::*;
+use ozz_animation_rs::OzzError;
+use std::sync::{PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard};
+use thiserror::Error;
+
+/// Possible errors produced while building or driving an `AnimatorController`
+#[non_exhaustive]
+#[derive(Debug, Error)]
+pub enum AnimatorError {
+    /// An Ozz Error
+    #[error("Ozz animation error: {0}")]
+    Ozz(#[from] OzzError),
+    /// Sampling a state failed
+    #[error("Failed to sample state '{state}' in layer '{layer}': {source}")]
+    Sampling {
+        layer: String,
+        state: String,
+        source: OzzError,
+    },
+    /// Blending two states during a transition failed
+    #[error("Failed to blend transition in layer '{layer}': {source}")]
+    TransitionBlending { layer: String, source: OzzError },
+    /// Blending the layers together failed
+    #[error("Failed to blend layers: {0}")]
+    FinalBlending(OzzError),
+    /// A lock guarding animation data was poisoned by a panic elsewhere
+    #[error("Lock guarding the {0} was poisoned by a panic in another system")]
+    PoisonedLock(&'static str),
+    /// A layer's default state was never added
+    #[error("Layer '{layer}' has default state '{state}' but no such state was added, call add_state(\"{state}\", ..) before building the controller")]
+    MissingDefaultState { layer: String, state: String },
+    /// A layer name that doesn't exist in the controller
+    #[error("Unknown layer '{0}'")]
+    UnknownLayer(String),
+    /// A state name that doesn't exist in the layer
+    #[error("Unknown state '{state}' in layer '{layer}'")]
+    UnknownState { layer: String, state: String },
+    /// Two layers of a controller share a name
+    #[error("Layer '{0}' was added twice")]
+    DuplicateLayer(String),
+    /// Two states of a layer share a name
+    #[error("State '{state}' was added twice to layer '{layer}'")]
+    DuplicateState { layer: String, state: String },
+    /// A layer was built without any state
+    #[error("Layer '{0}' has no states")]
+    EmptyLayer(String),
+    /// Two predicates share a name
+    #[error("Predicate '{0}' was defined twice")]
+    DuplicatePredicate(String),
+    /// A transition condition references a predicate that was never defined
+    #[error("Unknown predicate '{0}'")]
+    UnknownPredicate(String),
+    /// A snapshot parameter the controller doesn't have, or has with another type
+    #[error("Snapshot parameter '{0}' doesn't exist in the controller with that type")]
+    SnapshotParameter(String),
+    /// A snapshot was taken from a different version of the graph
+    #[error("Snapshot graph version {found} does not match controller graph version {expected}")]
+    GraphVersionMismatch { expected: u32, found: u32 },
+    /// A joint name that doesn't exist in the skeleton
+    #[error("Unknown joint '{0}'")]
+    UnknownJoint(String),
+    /// The joints of an IK chain aren't each an ancestor of the next
+    #[error("IK chain '{0}' isn't a chain of parent and child joints")]
+    InvalidIkChain(String),
+    /// Solving an IK chain failed
+    #[error("Failed to solve IK chain '{chain}': {source}")]
+    Ik { chain: String, source: OzzError },
+    /// A runtime skeleton couldn't be built from the given joints
+    #[error("Invalid skeleton: {0}")]
+    InvalidSkeleton(String),
+    /// A clip path that wasn't loaded with the controller definition
+    #[error("Unknown clip '{0}'")]
+    UnknownClip(String),
+    /// A condition or blend tree of a built graph reads a parameter that wasn't
+    /// declared with its type
+    #[error("Parameter '{0}' is read by the animator graph but was never declared, call with_parameter(\"{0}\", ..) before building the controller")]
+    UnknownParameter(String),
+    /// A parameter read by the graph was never set, see `MissingParameterPolicy`
+    #[error("Parameter '{0}' is read by the animator graph but was never set")]
+    MissingParameter(String),
+}
+
+impl AnimatorError {
+    /// Attach the layer and state to an error raised while sampling a state
+    pub(crate) fn in_state(self, layer: &str, state: &str) -> Self {
+        match self {
+            AnimatorError::Ozz(source) => AnimatorError::Sampling {
+                layer: layer.to_string(),
+                state: state.to_string(),
+                source,
+            },
+            error => error,
+        }
+    }
+}
+
+/// Sent when an `AnimatorController` or `BlendTreePlayer` fails to update, the entity keeps its last pose
+#[derive(Event, Debug)]
+pub struct AnimatorErrorEvent {
+    pub entity: Entity,
+    pub error: AnimatorError,
+}
+
+// Lock policy: poisoned locks only mean another thread panicked mid-update, the
+// poses behind them are still usable. Update paths report the poison as an error
+// for a single frame (the entity keeps its last pose) and clear it so the next
+// update recovers, every other path reads through it.
+
+/// Write-lock shared animation state during an update
+pub(crate) fn write_or_report<'a, T>(
+    lock: &'a RwLock<T>,
+    what: &'static str,
+) -> Result<RwLockWriteGuard<'a, T>, AnimatorError> {
+    lock.write().map_err(|_| {
+        lock.clear_poison();
+        AnimatorError::PoisonedLock(what)
+    })
+}
+
+/// Read-lock shared animation state, ignoring poison
+pub(crate) fn read_lock<T>(lock: &RwLock<T>) -> RwLockReadGuard<'_, T> {
+    lock.read().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Write-lock shared animation state, ignoring poison
+pub(crate) fn write_lock<T>(lock: &RwLock<T>) -> RwLockWriteGuard<'_, T> {
+    lock.write().unwrap_or_else(PoisonError::into_inner)
+}
@@ -0,0 +1,199 @@
+use super::{AnimatorController, AnimatorError, AnimatorErrorEvent};
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+
+/// A looping state wrapped around the end of its clip
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StateLoop {
+    pub layer: String,
+    pub state: String,
+    /// Times the state looped since the layer entered it, including this one
+    pub loop_count: u32,
+}
+
+/// Sent every time the current state of a layer loops, see [`StateLoop`]
+#[derive(Event, Debug, Clone)]
+pub struct StateLoopEvent {
+    pub entity: Entity,
+    pub state_loop: StateLoop,
+}
+
+/// A state's playback crossed one of its markers, see [`SimpleState::with_marker`](super::SimpleState::with_marker)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AnimationMarker {
+    pub layer: String,
+    pub state: String,
+    /// Name of the marker
+    pub name: String,
+}
+
+/// Sent every time a playing state crosses one of its markers. Also
+/// triggered on the controller entity for observers.
+/// ## Example
+/// ```ignore
+/// commands.entity(player).observe(|trigger: Trigger<AnimationMarkerEvent>| {
+///     if trigger.event().marker.name == "footstep" {
+///         // Play a footstep sound
+///     }
+/// });
+/// ```
+#[derive(Event, Debug, Clone)]
+pub struct AnimationMarkerEvent {
+    pub entity: Entity,
+    pub marker: AnimationMarker,
+}
+
+/// A change of a layer's current state. A transition sends `TransitionStarted`
+/// when it fires, then `Exited`, `Entered` and `TransitionCompleted` once the
+/// crossfade ends. Switching without a crossfade only sends `Exited` and `Entered`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StateChange {
+    /// The layer started crossfading between two states
+    TransitionStarted { layer: String, from: String, to: String },
+    /// A crossfade ended and its destination became the current state
+    TransitionCompleted { layer: String, from: String, to: String },
+    /// A state became the current state of the layer
+    Entered { layer: String, state: String },
+    /// A state stopped being the current state of the layer
+    Exited { layer: String, state: String },
+    /// The current state played to the end of a clamped clip
+    Finished { layer: String, state: String },
+}
+
+/// Sent when a state becomes the current state of a layer, see [`StateChange`]
+#[derive(Event, Debug, Clone)]
+pub struct AnimatorStateEntered {
+    pub entity: Entity,
+    pub layer: String,
+    pub state: String,
+}
+
+/// Sent when a state stops being the current state of a layer, see [`StateChange`]
+#[derive(Event, Debug, Clone)]
+pub struct AnimatorStateExited {
+    pub entity: Entity,
+    pub layer: String,
+    pub state: String,
+}
+
+/// Sent when the current state of a layer plays to the end of a clamped clip,
+/// see [`PlaybackMode::Clamp`](super::PlaybackMode::Clamp)
+#[derive(Event, Debug, Clone)]
+pub struct AnimatorStateFinished {
+    pub entity: Entity,
+    pub layer: String,
+    pub state: String,
+}
+
+/// Sent when a layer starts crossfading between two states
+#[derive(Event, Debug, Clone)]
+pub struct AnimatorTransitionStarted {
+    pub entity: Entity,
+    pub layer: String,
+    pub from: String,
+    pub to: String,
+}
+
+/// Sent when a crossfade ends, after the matching [`AnimatorStateEntered`]
+#[derive(Event, Debug, Clone)]
+pub struct AnimatorTransitionCompleted {
+    pub entity: Entity,
+    pub layer: String,
+    pub from: String,
+    pub to: String,
+}
+
+/// Everything needed to send the events of a controller update
+#[derive(SystemParam)]
+pub struct AnimatorEventWriters<'w, 's> {
+    errors: EventWriter<'w, AnimatorErrorEvent>,
+    loops: EventWriter<'w, StateLoopEvent>,
+    markers: EventWriter<'w, AnimationMarkerEvent>,
+    entered: EventWriter<'w, AnimatorStateEntered>,
+    exited: EventWriter<'w, AnimatorStateExited>,
+    finished: EventWriter<'w, AnimatorStateFinished>,
+    transitions_started: EventWriter<'w, AnimatorTransitionStarted>,
+    transitions_completed: EventWriter<'w, AnimatorTransitionCompleted>,
+    commands: Commands<'w, 's>,
+}
+
+impl AnimatorEventWriters<'_, '_> {
+    /// Send the result of a controller update and everything it queued
+    pub(crate) fn send(
+        &mut self,
+        entity: Entity,
+        controller: &mut AnimatorController,
+        result: Result<(), AnimatorError>,
+    ) {
+        if let Err(error) = result {
+            self.errors.write(AnimatorErrorEvent { entity, error });
+        }
+        self.errors.write_batch(
+            controller
+                .drain_parameter_errors()
+                .map(|error| AnimatorErrorEvent { entity, error }),
+        );
+        self.loops.write_batch(
+            controller
+                .drain_state_loops()
+                .map(|state_loop| StateLoopEvent { entity, state_loop }),
+        );
+        for state_change in controller.drain_state_changes() {
+            match state_change {
+                StateChange::TransitionStarted { layer, from, to } => {
+                    self.transitions_started.write(AnimatorTransitionStarted {
+                        entity,
+                        layer,
+                        from,
+                        to,
+                    });
+                }
+                StateChange::TransitionCompleted { layer, from, to } => {
+                    self.transitions_completed.write(AnimatorTransitionCompleted {
+                        entity,
+                        layer,
+                        from,
+                        to,
+                    });
+                }
+                StateChange::Entered { layer, state } => {
+                    self.entered.write(AnimatorStateEntered { entity, layer, state });
+                }
+                StateChange::Exited { layer, state } => {
+                    self.exited.write(AnimatorStateExited { entity, layer, state });
+                }
+                StateChange::Finished { layer, state } => {
+                    self.finished.write(AnimatorStateFinished { entity, layer, state });
+                }
+            }
+        }
+        for marker in controller.drain_markers() {
+            let event = AnimationMarkerEvent { entity, marker };
+            self.commands.trigger_targets(event.clone(), entity);
+            self.markers.write(event);
+        }
+    }
+}
+
+impl AnimatorController {
+    /// Take the loops that happened since the last call. The plugin drains
+    /// these into [`StateLoopEvent`]s, call this when driving the controller
+    /// yourself.
+    #[inline]
+    pub fn drain_state_loops(&mut self) -> std::vec::Drain<'_, StateLoop> {
+        self.state_loops.drain(..)
+    }
+
+    /// Take the markers crossed since the last call, see [`drain_state_loops`](Self::drain_state_loops)
+    #[inline]
+    pub fn drain_markers(&mut self) -> std::vec::Drain<'_, AnimationMarker> {
+        self.markers.drain(..)
+    }
+
+    /// Take the state changes of every layer since the last call, in the order
+    /// they happened
+    #[inline]
+    pub fn drain_state_changes(&mut self) -> std::vec::Drain<'_, StateChange> {
+        self.state_changes.drain(..)
+    }
+}
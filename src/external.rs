@@ -0,0 +1,127 @@
+use super::pose::{get_joint, set_joint};
+use super::skeleton::joint_parent;
+use super::{AnimationLayer, AnimationState, LayerBlendType, read_lock, write_lock};
+use bevy::prelude::*;
+use ozz_animation_rs::{Skeleton, SoaTransform};
+use std::sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+/// A pose filled in by gameplay code every frame, e.g. procedural animation,
+/// and blended with the other layers like a clip. Clones share the same pose,
+/// keep one to write to and hand another to an [`AnimationState::External`]
+/// or [`AnimationLayer::external`].
+/// ## Example
+/// ```ignore
+/// let pose = ExternalPose::new(&skeleton);
+/// let layer = AnimationLayer::external(
+///     "Procedural".to_string(),
+///     LayerBlendType::Override,
+///     0.5,
+///     &skeleton,
+///     pose.clone(),
+/// );
+/// // Every frame
+/// pose.set_joint(head, &look_transform);
+/// // Or by name
+/// set_joint_rotation(&skeleton, &mut pose.write(), "head", look_rotation)?;
+/// ```
+#[derive(Debug, Clone)]
+pub struct ExternalPose {
+    pose: Arc<RwLock<Vec<SoaTransform>>>,
+}
+
+impl ExternalPose {
+    /// If both handles write to the same pose
+    #[inline]
+    pub(crate) fn same_pose(&self, other: &ExternalPose) -> bool {
+        Arc::ptr_eq(&self.pose, &other.pose)
+    }
+
+    /// Create a pose starting at the skeleton's rest pose
+    #[inline]
+    pub fn new(skeleton: &Skeleton) -> Self {
+        Self {
+            pose: Arc::new(RwLock::new(skeleton.joint_rest_poses().to_vec())),
+        }
+    }
+
+    /// Set the local transform of a joint, does nothing for unknown joints
+    #[inline]
+    pub fn set_joint(&self, joint: usize, transform: &Transform) {
+        let mut pose = write_lock(&self.pose);
+        if joint < pose.len() * 4 {
+            set_joint(&mut pose, joint, transform);
+        }
+    }
+
+    /// Local transform of a joint, None for unknown joints
+    #[inline]
+    pub fn joint(&self, joint: usize) -> Option<Transform> {
+        let pose = read_lock(&self.pose);
+        (joint < pose.len() * 4).then(|| get_joint(&pose, joint))
+    }
+
+    /// Set the local transform of every joint, in skeleton order
+    #[inline]
+    pub fn set_transforms(&self, transforms: &[Transform]) {
+        let mut pose = write_lock(&self.pose);
+        for (joint, transform) in transforms.iter().enumerate().take(pose.len() * 4) {
+            set_joint(&mut pose, joint, transform);
+        }
+    }
+
+    /// Set every joint from its model space transform, relative to the skeleton
+    /// root, in skeleton order. Physics bodies usually come in this space.
+    pub fn set_model_transforms(&self, skeleton: &Skeleton, models: &[Transform]) {
+        let mut pose = write_lock(&self.pose);
+        for (joint, model) in models.iter().enumerate().take(pose.len() * 4) {
+            let local = match joint_parent(skeleton, joint).and_then(|parent| models.get(parent)) {
+                Some(parent) => {
+                    let local = parent.compute_matrix().inverse() * model.compute_matrix();
+                    Transform::from_matrix(local)
+                }
+                None => *model,
+            };
+            set_joint(&mut pose, joint, &local);
+        }
+    }
+
+    /// Direct access to the pose in ozz's SoA layout
+    #[inline]
+    pub fn write(&self) -> RwLockWriteGuard<'_, Vec<SoaTransform>> {
+        write_lock(&self.pose)
+    }
+
+    #[inline]
+    pub(crate) fn read(&self) -> RwLockReadGuard<'_, Vec<SoaTransform>> {
+        read_lock(&self.pose)
+    }
+
+    #[inline]
+    pub(crate) fn output(&self) -> Arc<RwLock<Vec<SoaTransform>>> {
+        self.pose.clone()
+    }
+}
+
+impl AnimationLayer {
+    /// A layer playing nothing but an external pose. On additive layers the
+    /// pose should hold the offsets to add, not a full pose.
+    #[inline]
+    pub fn external(
+        name: String,
+        layer_blend_type: LayerBlendType,
+        layer_weight: f32,
+        skeleton: &Arc<Skeleton>,
+        pose: ExternalPose,
+    ) -> Self {
+        let state_name = "External".to_string();
+        let mut layer = Self::new(
+            name,
+            layer_blend_type,
+            layer_weight,
+            skeleton,
+            state_name.clone(),
+        );
+        layer.add_state(state_name, AnimationState::External(pose));
+        layer
+    }
+}
@@ -0,0 +1,210 @@
+use super::{AnimationLayer, AnimatorController, CompareType, Transition, TransitionCondition};
+use std::collections::BTreeSet;
+use std::fmt::Write;
+
+impl AnimationLayer {
+    /// Graphviz DOT graph of the layer's states and transitions, for
+    /// visualizing large state machines with `dot -Tsvg`. Edges are labelled
+    /// with their conditions, the current state is filled and transitions to
+    /// missing states point at dashed red nodes.
+    /// ## Example
+    /// ```ignore
+    /// std::fs::write("locomotion.dot", locomotion.to_dot())?;
+    /// ```
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph {\n    rankdir=LR;\n");
+        self.write_dot(&mut dot, "s", "    ");
+        dot.push_str("}\n");
+        dot
+    }
+
+    /// Mermaid state diagram of the layer's states and transitions, renders in
+    /// markdown viewers that support mermaid code blocks
+    pub fn to_mermaid(&self) -> String {
+        let mut mermaid = String::from("stateDiagram-v2\n");
+        self.write_mermaid(&mut mermaid, "s", "    ");
+        self.write_mermaid_classes(&mut mermaid, "s");
+        mermaid.push_str(MERMAID_CLASSES);
+        mermaid
+    }
+
+    fn write_dot(&self, out: &mut String, prefix: &str, indent: &str) {
+        let (states, missing) = self.graph_states();
+        let id = |name: &str| node_id(prefix, &states, &missing, name);
+
+        let _ = writeln!(out, "{indent}{prefix}_entry [shape=point];");
+        for &state in &states {
+            let style = if state == self.current_state() {
+                ", style=filled, fillcolor=lightblue"
+            } else {
+                ""
+            };
+            let _ = writeln!(out, "{indent}{} [label=\"{}\"{style}];", id(state), escape(state));
+        }
+        for &state in &missing {
+            let _ = writeln!(
+                out,
+                "{indent}{} [label=\"{}\", style=dashed, color=red];",
+                id(state),
+                escape(state)
+            );
+        }
+
+        let _ = writeln!(out, "{indent}{prefix}_entry -> {};", id(self.default_state()));
+        for &state in &states {
+            for transition in self.transitions(state) {
+                let _ = writeln!(
+                    out,
+                    "{indent}{} -> {} [label=\"{}\"];",
+                    id(state),
+                    id(&transition.to_state),
+                    escape(&transition_label(transition))
+                );
+            }
+        }
+    }
+
+    fn write_mermaid(&self, out: &mut String, prefix: &str, indent: &str) {
+        let (states, missing) = self.graph_states();
+        let id = |name: &str| node_id(prefix, &states, &missing, name);
+
+        for &state in states.iter().chain(&missing) {
+            let _ = writeln!(out, "{indent}state \"{}\" as {}", mermaid_text(state), id(state));
+        }
+        let _ = writeln!(out, "{indent}[*] --> {}", id(self.default_state()));
+        for &state in &states {
+            for transition in self.transitions(state) {
+                let label = transition_label(transition);
+                let _ = write!(out, "{indent}{} --> {}", id(state), id(&transition.to_state));
+                if !label.is_empty() {
+                    let _ = write!(out, " : {}", mermaid_text(&label));
+                }
+                out.push('\n');
+            }
+        }
+    }
+
+    /// Mermaid only styles states from the top level of the diagram
+    fn write_mermaid_classes(&self, out: &mut String, prefix: &str) {
+        let (states, missing) = self.graph_states();
+        let id = |name: &str| node_id(prefix, &states, &missing, name);
+        let _ = writeln!(out, "    class {} current", id(self.current_state()));
+        for &state in &missing {
+            let _ = writeln!(out, "    class {} missing", id(state));
+        }
+    }
+
+    /// States of the layer in name order, and the missing states transitions
+    /// lead to
+    fn graph_states(&self) -> (Vec<&str>, Vec<&str>) {
+        let states: BTreeSet<&str> = self.state_names().collect();
+        let missing: BTreeSet<&str> = states
+            .iter()
+            .flat_map(|&state| self.transitions(state))
+            .map(|transition| transition.to_state.as_str())
+            .chain([self.default_state()])
+            .filter(|state| !states.contains(state))
+            .collect();
+        (states.into_iter().collect(), missing.into_iter().collect())
+    }
+}
+
+impl AnimatorController {
+    /// Graphviz DOT graph of every layer, each in its own cluster, see
+    /// [`AnimationLayer::to_dot`]
+    /// ## Example
+    /// ```ignore
+    /// std::fs::write("player.dot", controller.to_dot())?;
+    /// // dot -Tsvg player.dot -o player.svg
+    /// ```
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph {\n    rankdir=LR;\n");
+        for (index, layer) in self.layers.iter().enumerate() {
+            let _ = writeln!(dot, "    subgraph cluster_{index} {{");
+            let _ = writeln!(dot, "        label=\"{}\";", escape(&layer.name));
+            layer.write_dot(&mut dot, &format!("l{index}_s"), "        ");
+            dot.push_str("    }\n");
+        }
+        dot.push_str("}\n");
+        dot
+    }
+
+    /// Mermaid state diagram of every layer, each in its own composite state,
+    /// see [`AnimationLayer::to_mermaid`]
+    pub fn to_mermaid(&self) -> String {
+        let mut mermaid = String::from("stateDiagram-v2\n");
+        for (index, layer) in self.layers.iter().enumerate() {
+            let _ = writeln!(mermaid, "    state \"{}\" as l{index} {{", mermaid_text(&layer.name));
+            layer.write_mermaid(&mut mermaid, &format!("l{index}_s"), "        ");
+            mermaid.push_str("    }\n");
+        }
+        for (index, layer) in self.layers.iter().enumerate() {
+            layer.write_mermaid_classes(&mut mermaid, &format!("l{index}_s"));
+        }
+        mermaid.push_str(MERMAID_CLASSES);
+        mermaid
+    }
+}
+
+/// Id of a state's node, its index among the states followed by the missing
+/// states
+#[inline]
+fn node_id(prefix: &str, states: &[&str], missing: &[&str], name: &str) -> String {
+    let index = states.iter().chain(missing).position(|&state| state == name);
+    format!("{prefix}{}", index.unwrap_or_default())
+}
+
+const MERMAID_CLASSES: &str =
+    "    classDef current fill:lightblue\n    classDef missing stroke:red,stroke-dasharray:4\n";
+
+/// Conditions of a transition joined by "and", with its exit time
+fn transition_label(transition: &Transition) -> String {
+    let mut parts: Vec<String> = transition.conditions.iter().map(condition_label).collect();
+    if transition.has_exit_time {
+        parts.push(format!("exit {}", transition.exit_time));
+    }
+    parts.join(" and ")
+}
+
+fn condition_label(condition: &TransitionCondition) -> String {
+    match condition {
+        TransitionCondition::Bool(name, true) => name.clone(),
+        TransitionCondition::Bool(name, false) => format!("!{name}"),
+        TransitionCondition::Float(name, value, compare) => {
+            format!("{name} {} {value}", compare_symbol(compare))
+        }
+        TransitionCondition::Int(name, value, compare) => {
+            format!("{name} {} {value}", compare_symbol(compare))
+        }
+        TransitionCondition::Trigger(name) => format!("{name} (trigger)"),
+        TransitionCondition::StateFinished => "finished".to_string(),
+        TransitionCondition::LayerState(layer, state, true) => format!("{layer} in {state}"),
+        TransitionCondition::LayerState(layer, state, false) => format!("{layer} not in {state}"),
+        TransitionCondition::LayerTag(layer, tag, true) => format!("{layer} has #{tag}"),
+        TransitionCondition::LayerTag(layer, tag, false) => format!("{layer} lacks #{tag}"),
+        TransitionCondition::Predicate(name) => format!("[{name}]"),
+        TransitionCondition::Random(min, max) => format!("random in [{min}, {max})"),
+    }
+}
+
+#[inline]
+fn compare_symbol(compare: &CompareType) -> &'static str {
+    match compare {
+        CompareType::Greater => ">",
+        CompareType::Less => "<",
+        CompareType::Equals => "==",
+        CompareType::NotEqual => "!=",
+    }
+}
+
+/// Escape text for a quoted DOT string
+#[inline]
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+/// Replace the characters mermaid reads as syntax with entity codes
+#[inline]
+fn mermaid_text(text: &str) -> String {
+    text.replace('"', "#quot;").replace(':', "#58;")
+}
@@ -0,0 +1,155 @@
+use super::{
+    AnimatorController, AnimatorControllerAsset, AnimatorError, AnimatorErrorEvent, LayerSnapshot,
+    ParameterValue,
+};
+use bevy::prelude::*;
+use std::collections::HashSet;
+
+/// Builds the `AnimatorController` of its entity from a controller asset once
+/// it has loaded, and rebuilds it whenever the asset or one of its clips
+/// changes on disk. Current states, playback times and parameters are kept
+/// wherever they still exist in the new graph.
+/// ## Example
+/// ```ignore
+/// commands.spawn((
+///     SceneRoot(player_mesh),
+///     AnimatorControllerHandle(asset_server.load("player.controller.ron")),
+/// ));
+/// ```
+#[derive(Component, Debug, Clone)]
+pub struct AnimatorControllerHandle(pub Handle<AnimatorControllerAsset>);
+
+/// Build controllers for newly added handles and rebuild the ones whose asset
+/// was reloaded
+pub fn sync_controller_assets(
+    mut asset_events: EventReader<AssetEvent<AnimatorControllerAsset>>,
+    assets: Res<Assets<AnimatorControllerAsset>>,
+    pending: Query<(Entity, &AnimatorControllerHandle), Without<AnimatorController>>,
+    mut controllers: Query<(Entity, &AnimatorControllerHandle, &mut AnimatorController)>,
+    mut errors: EventWriter<AnimatorErrorEvent>,
+    mut commands: Commands,
+) {
+    for (entity, handle) in &pending {
+        let Some(asset) = assets.get(&handle.0) else {
+            continue;
+        };
+        match asset.build() {
+            Ok(controller) => {
+                commands
+                    .entity(entity)
+                    .insert(controller.with_dependency(handle.0.clone()));
+            }
+            Err(error) => {
+                errors.write(AnimatorErrorEvent { entity, error });
+            }
+        }
+    }
+
+    let modified: HashSet<AssetId<AnimatorControllerAsset>> = asset_events
+        .read()
+        .filter_map(|event| match event {
+            AssetEvent::Modified { id } => Some(*id),
+            _ => None,
+        })
+        .collect();
+    if modified.is_empty() {
+        return;
+    }
+
+    for (entity, handle, mut controller) in &mut controllers {
+        if !modified.contains(&handle.0.id()) {
+            continue;
+        }
+        let Some(asset) = assets.get(&handle.0) else {
+            continue;
+        };
+        let rebuilt = asset.build().and_then(|mut rebuilt| {
+            rebuilt.add_dependency(handle.0.clone());
+            rebuilt.carry_over(&controller)?;
+            Ok(rebuilt)
+        });
+        match rebuilt {
+            Ok(rebuilt) => {
+                debug!("Reloaded the animator controller of {entity}");
+                *controller = rebuilt;
+            }
+            // Keep the old graph running until the file is fixed
+            Err(error) => {
+                errors.write(AnimatorErrorEvent { entity, error });
+            }
+        }
+    }
+}
+
+impl AnimatorController {
+    /// Take over the runtime state of the controller this one replaces, for
+    /// every layer, state and parameter that still exists
+    pub(crate) fn carry_over(&mut self, old: &AnimatorController) -> Result<(), AnimatorError> {
+        // Layers that still exist continue their own random numbers below
+        self.set_seed(old.seed());
+        for (name, value) in old.parameters().iter() {
+            let parameters = self.parameters_mut();
+            let exists = match value {
+                ParameterValue::Bool(_) => parameters.get_bool(name).is_some(),
+                ParameterValue::Float(_) => parameters.get_float(name).is_some(),
+                ParameterValue::Int(_) => parameters.get_int(name).is_some(),
+                // Triggers only last a frame
+                ParameterValue::Trigger => false,
+            };
+            if exists {
+                parameters.set(name, value);
+            }
+        }
+
+        for layer in &mut self.layers {
+            let Some(old_layer) = old.layers.iter().find(|l| l.name == layer.name) else {
+                continue;
+            };
+            if !layer.has_state(old_layer.current_state()) {
+                continue;
+            }
+            let snapshot = old_layer.snapshot();
+            let next_state = snapshot.next_state.filter(|state| layer.has_state(state));
+            let in_transition = next_state.is_some();
+            let snapshot = LayerSnapshot {
+                // The weight comes from the new definition, so it can be tuned too
+                weight: layer.layer_weight,
+                next_state,
+                transition_time: if in_transition { snapshot.transition_time } else { 0.0 },
+                transition_duration: if in_transition {
+                    snapshot.transition_duration
+                } else {
+                    0.0
+                },
+                state_times: snapshot
+                    .state_times
+                    .into_iter()
+                    .filter(|(state, _)| layer.has_state(state))
+                    .collect(),
+                ..snapshot
+            };
+            layer.restore(&snapshot)?;
+        }
+        self.rest_pose.clone_from(&old.rest_pose);
+        self.build_blending_layers();
+
+        self.set_evaluation_mode(old.evaluation_mode());
+        self.set_trigger_policy(old.trigger_policy());
+        self.trigger_routes.clone_from(&old.trigger_routes);
+        // Components of tags the new graph dropped are removed on the next sync
+        self.applied_tags.clone_from(&old.applied_tags);
+        self.set_time_scale(old.time_scale());
+        if let Some(max_delta) = old.max_delta_override() {
+            self.set_max_delta(max_delta);
+        }
+        if let Some(sub_step) = old.sub_step_override() {
+            self.set_sub_step(sub_step);
+        }
+        // Keep showing the old pose until the new graph has updated once
+        if old.bone_trans.len() == self.joint_count() {
+            self.bone_trans.clone_from(&old.bone_trans);
+            self.mark_pose_changed();
+        }
+        Ok(())
+    }
+}
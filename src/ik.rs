@@ -0,0 +1,705 @@
+use super::skeleton::joint_parent;
+use super::{AnimatorController, AnimatorError};
+use bevy::prelude::*;
+use ozz_animation_rs::{IKAimJob, IKTwoBoneJob, OzzError};
+
+/// Where an IK chain reaches for
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum IkTarget {
+    /// A position in model space, relative to the skeleton root
+    Model(Vec3),
+    /// The world position of an entity, e.g. a ground probe under a foot. It
+    /// is read from the entity's `GlobalTransform` before the controller
+    /// updates, relative to the controller entity's `GlobalTransform`.
+    Entity(Entity),
+}
+
+/// Bends a chain of three joints, like hip-knee-ankle or shoulder-elbow-wrist,
+/// so its end reaches a target, after the layers are blended. Feet plant on
+/// uneven terrain and hands land on ledges without authoring a clip per case.
+/// ## Example
+/// ```ignore
+/// controller.add_two_bone_ik(
+///     "LeftFoot",
+///     TwoBoneIk::new("LeftUpLeg", "LeftLeg", "LeftFoot", IkTarget::Entity(left_probe))
+///         .with_pole_vector(Vec3::Z),
+/// )?;
+///
+/// // Fade the chain out while jumping
+/// controller.set_ik_weight("LeftFoot", if grounded { 1.0 } else { 0.0 });
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct TwoBoneIk {
+    /// Name of the first joint of the chain, e.g. the hip
+    pub start_joint: String,
+    /// Name of the joint the chain bends at, e.g. the knee
+    pub mid_joint: String,
+    /// Name of the joint that reaches the target, e.g. the ankle
+    pub end_joint: String,
+    pub target: IkTarget,
+    /// Direction the middle joint bends toward, in model space
+    pub pole_vector: Vec3,
+    /// Axis the middle joint bends around, in its local space
+    pub mid_axis: Vec3,
+    /// How much of the correction to apply, from 0 (animated pose) to 1
+    pub weight: f32,
+    /// Ratio of the chain's length at which it starts to straighten more
+    /// slowly, avoiding the pop of a fully stretched chain. 1 disables it.
+    pub soften: f32,
+    /// Rotation of the middle joint's plane around the start to target axis,
+    /// in radians
+    pub twist_angle: f32,
+}
+
+impl TwoBoneIk {
+    /// A chain bending toward +Y around its middle joint's local Z axis
+    #[inline]
+    pub fn new(
+        start_joint: impl Into<String>,
+        mid_joint: impl Into<String>,
+        end_joint: impl Into<String>,
+        target: IkTarget,
+    ) -> Self {
+        Self {
+            start_joint: start_joint.into(),
+            mid_joint: mid_joint.into(),
+            end_joint: end_joint.into(),
+            target,
+            pole_vector: Vec3::Y,
+            mid_axis: Vec3::Z,
+            weight: 1.0,
+            soften: 1.0,
+            twist_angle: 0.0,
+        }
+    }
+
+    #[inline]
+    pub fn with_pole_vector(mut self, pole_vector: Vec3) -> Self {
+        self.pole_vector = pole_vector;
+        self
+    }
+
+    #[inline]
+    pub fn with_mid_axis(mut self, mid_axis: Vec3) -> Self {
+        self.mid_axis = mid_axis;
+        self
+    }
+
+    #[inline]
+    pub fn with_weight(mut self, weight: f32) -> Self {
+        self.weight = weight;
+        self
+    }
+
+    #[inline]
+    pub fn with_soften(mut self, soften: f32) -> Self {
+        self.soften = soften;
+        self
+    }
+
+    #[inline]
+    pub fn with_twist_angle(mut self, twist_angle: f32) -> Self {
+        self.twist_angle = twist_angle;
+        self
+    }
+}
+
+/// A joint of a [`LookAt`] constraint
+#[derive(Debug, Clone, PartialEq)]
+pub struct LookAtJoint {
+    pub joint: String,
+    /// Share of the rotation still missing once the joints after it turned,
+    /// from 0 to 1. Joints turn from the last one up the chain, so a weight of
+    /// 1 on the first joint makes sure the target is faced.
+    pub weight: f32,
+    /// Largest rotation the constraint may apply to this joint, in radians
+    pub max_angle: f32,
+}
+
+/// Turns a chain of joints, like spine-neck-head, so the last one faces a
+/// target, after the layers are blended. Every joint takes part of the
+/// rotation, up to its own angle limit, so a character can follow a target
+/// with the upper body without twisting its neck beyond what is natural.
+/// ## Example
+/// ```ignore
+/// controller.add_look_at(
+///     "Head",
+///     LookAt::new(IkTarget::Entity(camera))
+///         .with_joint("Spine2", 1.0, 20f32.to_radians())
+///         .with_joint("Neck", 0.5, 30f32.to_radians())
+///         .with_joint("Head", 0.5, 45f32.to_radians())
+///         .with_offset(Vec3::new(0.0, 0.07, 0.1)), // Between the eyes
+/// )?;
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct LookAt {
+    /// Joints of the chain from the root, each the ancestor of the next. The
+    /// last one faces the target.
+    pub joints: Vec<LookAtJoint>,
+    pub target: IkTarget,
+    /// Axis of the last joint that should point at the target, in its local
+    /// space
+    pub forward: Vec3,
+    /// Axis of the last joint kept as close to `pole_vector` as possible, in
+    /// its local space
+    pub up: Vec3,
+    /// Point that looks at the target, like the eyes, in the last joint's
+    /// local space
+    pub offset: Vec3,
+    /// Direction `up` is aligned with, in model space
+    pub pole_vector: Vec3,
+    /// Rotation around the axis to the target, in radians
+    pub twist_angle: f32,
+    /// How much of the constraint to apply, from 0 (animated pose) to 1
+    pub weight: f32,
+}
+
+impl LookAt {
+    /// A constraint without any joint, looking along +Z with +Y up
+    #[inline]
+    pub fn new(target: IkTarget) -> Self {
+        Self {
+            joints: Vec::new(),
+            target,
+            forward: Vec3::Z,
+            up: Vec3::Y,
+            offset: Vec3::ZERO,
+            pole_vector: Vec3::Y,
+            twist_angle: 0.0,
+            weight: 1.0,
+        }
+    }
+
+    /// Add the next joint of the chain, see [`LookAtJoint`]
+    #[inline]
+    pub fn with_joint(mut self, joint: impl Into<String>, weight: f32, max_angle: f32) -> Self {
+        self.joints.push(LookAtJoint {
+            joint: joint.into(),
+            weight,
+            max_angle,
+        });
+        self
+    }
+
+    #[inline]
+    pub fn with_forward(mut self, forward: Vec3) -> Self {
+        self.forward = forward;
+        self
+    }
+
+    #[inline]
+    pub fn with_up(mut self, up: Vec3) -> Self {
+        self.up = up;
+        self
+    }
+
+    #[inline]
+    pub fn with_offset(mut self, offset: Vec3) -> Self {
+        self.offset = offset;
+        self
+    }
+
+    #[inline]
+    pub fn with_pole_vector(mut self, pole_vector: Vec3) -> Self {
+        self.pole_vector = pole_vector;
+        self
+    }
+
+    #[inline]
+    pub fn with_weight(mut self, weight: f32) -> Self {
+        self.weight = weight;
+        self
+    }
+}
+
+/// What an IK chain solves for
+#[derive(Debug, Clone)]
+enum IkSolver {
+    TwoBone {
+        /// Start, middle and end joint
+        joints: [usize; 3],
+        ik: TwoBoneIk,
+    },
+    LookAt {
+        /// Joint of every `LookAtJoint`, in the same order
+        joints: Vec<usize>,
+        look_at: LookAt,
+    },
+}
+
+impl IkSolver {
+    #[inline]
+    fn target(&self) -> IkTarget {
+        match self {
+            IkSolver::TwoBone { ik, .. } => ik.target,
+            IkSolver::LookAt { look_at, .. } => look_at.target,
+        }
+    }
+}
+
+/// An IK chain or constraint resolved to joint indexes
+#[derive(Debug, Clone)]
+pub(crate) struct ActiveIkChain {
+    name: String,
+    solver: IkSolver,
+    /// Model space target, None until an entity target was first read
+    target: Option<Vec3>,
+    /// If the chain reached or faced the target on the last update
+    reached: bool,
+}
+
+impl AnimatorController {
+    /// Add a two bone IK chain, applied after blending and before bone
+    /// overrides. Chains and look-at constraints are solved in the order they
+    /// were added, replacing any with the same name. Fails if a joint doesn't
+    /// exist or the joints aren't a chain of ancestors.
+    pub fn add_two_bone_ik(
+        &mut self,
+        name: impl Into<String>,
+        ik: TwoBoneIk,
+    ) -> Result<(), AnimatorError> {
+        let name = name.into();
+        let joints =
+            self.resolve_ik_joints(&name, [&ik.start_joint, &ik.mid_joint, &ik.end_joint])?;
+        let joints = [joints[0], joints[1], joints[2]];
+        self.insert_ik_chain(name, IkSolver::TwoBone { joints, ik });
+        Ok(())
+    }
+
+    /// Add a look-at constraint, solved with the IK chains, see
+    /// [`Self::add_two_bone_ik`]. Fails if it has no joints, a joint doesn't
+    /// exist or the joints aren't a chain of ancestors.
+    pub fn add_look_at(
+        &mut self,
+        name: impl Into<String>,
+        look_at: LookAt,
+    ) -> Result<(), AnimatorError> {
+        let name = name.into();
+        if look_at.joints.is_empty() {
+            return Err(AnimatorError::InvalidIkChain(name));
+        }
+        let joints = self.resolve_ik_joints(&name, look_at.joints.iter().map(|j| &j.joint))?;
+        self.insert_ik_chain(name, IkSolver::LookAt { joints, look_at });
+        Ok(())
+    }
+
+    /// Remove an IK chain or look-at constraint, returns false if there is no
+    /// such chain
+    #[inline]
+    pub fn remove_ik(&mut self, name: &str) -> bool {
+        let count = self.ik_chains.len();
+        self.ik_chains.retain(|chain| chain.name != name);
+        self.ik_chains.len() != count
+    }
+
+    #[inline]
+    pub fn two_bone_ik(&self, name: &str) -> Option<&TwoBoneIk> {
+        match &self.ik_chain(name)?.solver {
+            IkSolver::TwoBone { ik, .. } => Some(ik),
+            IkSolver::LookAt { .. } => None,
+        }
+    }
+
+    #[inline]
+    pub fn look_at(&self, name: &str) -> Option<&LookAt> {
+        match &self.ik_chain(name)?.solver {
+            IkSolver::LookAt { look_at, .. } => Some(look_at),
+            IkSolver::TwoBone { .. } => None,
+        }
+    }
+
+    /// Move the target of a chain or constraint, returns false if there is no
+    /// such chain
+    #[inline]
+    pub fn set_ik_target(&mut self, name: &str, target: IkTarget) -> bool {
+        let Some(chain) = self.ik_chain_mut(name) else {
+            return false;
+        };
+        match &mut chain.solver {
+            IkSolver::TwoBone { ik, .. } => ik.target = target,
+            IkSolver::LookAt { look_at, .. } => look_at.target = target,
+        }
+        if let IkTarget::Model(position) = target {
+            chain.target = Some(position);
+        }
+        true
+    }
+
+    /// Blend a chain or constraint in or out, returns false if there is no
+    /// such chain
+    #[inline]
+    pub fn set_ik_weight(&mut self, name: &str, weight: f32) -> bool {
+        let Some(chain) = self.ik_chain_mut(name) else {
+            return false;
+        };
+        match &mut chain.solver {
+            IkSolver::TwoBone { ik, .. } => ik.weight = weight,
+            IkSolver::LookAt { look_at, .. } => look_at.weight = weight,
+        }
+        true
+    }
+
+    /// If the end of a chain reached its target, or a look-at constraint
+    /// faced it within its angle limits, on the last update
+    #[inline]
+    pub fn ik_reached(&self, name: &str) -> Option<bool> {
+        self.ik_chain(name).map(|chain| chain.reached)
+    }
+
+    #[inline]
+    fn ik_chain(&self, name: &str) -> Option<&ActiveIkChain> {
+        self.ik_chains.iter().find(|chain| chain.name == name)
+    }
+
+    #[inline]
+    fn ik_chain_mut(&mut self, name: &str) -> Option<&mut ActiveIkChain> {
+        self.ik_chains.iter_mut().find(|chain| chain.name == name)
+    }
+
+    fn insert_ik_chain(&mut self, name: String, solver: IkSolver) {
+        let target = match solver.target() {
+            IkTarget::Model(position) => Some(position),
+            IkTarget::Entity(_) => None,
+        };
+        let active = ActiveIkChain {
+            name,
+            solver,
+            target,
+            reached: false,
+        };
+        match self.ik_chains.iter_mut().find(|chain| chain.name == active.name) {
+            Some(chain) => *chain = active,
+            None => self.ik_chains.push(active),
+        }
+    }
+
+    /// Index of every joint of a chain, each an ancestor of the next
+    fn resolve_ik_joints<'a>(
+        &self,
+        name: &str,
+        joint_names: impl IntoIterator<Item = &'a String>,
+    ) -> Result<Vec<usize>, AnimatorError> {
+        let mut joints: Vec<usize> = Vec::new();
+        for joint_name in joint_names {
+            let joint = self
+                .find_joint(joint_name)
+                .ok_or_else(|| AnimatorError::UnknownJoint(joint_name.clone()))?;
+            if let Some(&previous) = joints.last()
+                && !self.is_ancestor(previous, joint)
+            {
+                return Err(AnimatorError::InvalidIkChain(name.to_string()));
+            }
+            joints.push(joint);
+        }
+        Ok(joints)
+    }
+
+    /// If `ancestor` is above `joint` in the hierarchy
+    fn is_ancestor(&self, ancestor: usize, joint: usize) -> bool {
+        let mut current = joint_parent(&self.skeleton, joint);
+        while let Some(parent) = current {
+            if parent == ancestor {
+                return true;
+            }
+            current = joint_parent(&self.skeleton, parent);
+        }
+        false
+    }
+
+    /// Bend every IK chain of the blended pose toward its target
+    pub(crate) fn apply_ik(&mut self) -> Result<(), AnimatorError> {
+        if self.ik_chains.is_empty() {
+            return Ok(());
+        }
+
+        let mut chains = std::mem::take(&mut self.ik_chains);
+        let mut result = Ok(());
+        for chain in &mut chains {
+            chain.reached = false;
+            let Some(target) = chain.target else {
+                continue;
+            };
+            let solved = match &chain.solver {
+                IkSolver::TwoBone { joints, ik } => self.solve_two_bone(*joints, ik, target),
+                IkSolver::LookAt { joints, look_at } => self.solve_look_at(joints, look_at, target),
+            };
+            match solved {
+                Ok(reached) => chain.reached = reached,
+                Err(source) => {
+                    result = Err(AnimatorError::Ik {
+                        chain: chain.name.clone(),
+                        source,
+                    });
+                    break;
+                }
+            }
+        }
+        self.ik_chains = chains;
+        result
+    }
+
+    /// Returns if the end joint reached the target
+    fn solve_two_bone(
+        &mut self,
+        [start, mid, end]: [usize; 3],
+        ik: &TwoBoneIk,
+        target: Vec3,
+    ) -> Result<bool, OzzError> {
+        let weight = ik.weight.clamp(0.0, 1.0);
+        if weight <= 0.0 || end >= self.bone_trans.len() {
+            return Ok(false);
+        }
+        // Earlier chains may have moved these joints
+        let (Some(start_model), Some(mid_model), Some(end_model)) = (
+            self.model_space_bone(start),
+            self.model_space_bone(mid),
+            self.model_space_bone(end),
+        ) else {
+            return Ok(false);
+        };
+
+        let mut job = IKTwoBoneJob::default();
+        job.set_target(to_ozz_vec3(target));
+        job.set_pole_vector(to_ozz_vec3(ik.pole_vector));
+        job.set_mid_axis(to_ozz_vec3(ik.mid_axis));
+        job.set_weight(weight);
+        job.set_soften(ik.soften);
+        job.set_twist_angle(ik.twist_angle);
+        job.set_start_joint(to_ozz_mat4(start_model));
+        job.set_mid_joint(to_ozz_mat4(mid_model));
+        job.set_end_joint(to_ozz_mat4(end_model));
+        job.run()?;
+
+        // Corrections apply on top of the joints' local rotations
+        self.correct_joint(start, from_ozz_quat(job.start_joint_correction()));
+        self.correct_joint(mid, from_ozz_quat(job.mid_joint_correction()));
+        Ok(job.reached())
+    }
+
+    /// Returns if the last joint faces the target
+    fn solve_look_at(
+        &mut self,
+        joints: &[usize],
+        look_at: &LookAt,
+        target: Vec3,
+    ) -> Result<bool, OzzError> {
+        let weight = look_at.weight.clamp(0.0, 1.0);
+        if weight <= 0.0 || joints.iter().any(|&joint| joint >= self.bone_trans.len()) {
+            return Ok(false);
+        }
+        // Model space of the pose before the constraint, the forward axis and
+        // offset are carried from each corrected joint into its ancestor
+        let models = self.model_space_bones();
+        let mut forward = look_at.forward;
+        let mut offset = look_at.offset;
+        let mut reached = true;
+
+        let mut job = IKAimJob::default();
+        job.set_target(to_ozz_vec3(target));
+        job.set_up(to_ozz_vec3(look_at.up));
+        job.set_pole_vector(to_ozz_vec3(look_at.pole_vector));
+        job.set_twist_angle(look_at.twist_angle);
+        // From the last joint up the chain
+        for (i, (&joint, settings)) in joints.iter().zip(&look_at.joints).enumerate().rev() {
+            job.set_joint(to_ozz_mat4(models[joint]));
+            job.set_forward(to_ozz_vec3(forward));
+            job.set_offset(to_ozz_vec3(offset));
+            job.set_weight(weight * settings.weight.clamp(0.0, 1.0));
+            job.run()?;
+
+            let (correction, clamped) =
+                clamp_rotation(from_ozz_quat(job.joint_correction()), settings.max_angle);
+            reached &= job.reached() && !clamped;
+            self.correct_joint(joint, correction);
+
+            if let Some(&parent) = i.checked_sub(1).and_then(|parent| joints.get(parent)) {
+                let corrected = models[joint] * Mat4::from_quat(correction);
+                let to_parent = models[parent].inverse() * corrected;
+                forward = to_parent.transform_vector3(forward);
+                offset = to_parent.transform_point3(offset);
+            }
+        }
+        Ok(reached)
+    }
+
+    #[inline]
+    fn correct_joint(&mut self, joint: usize, correction: Quat) {
+        let bone = &mut self.bone_trans[joint];
+        bone.rotation = (bone.rotation * correction).normalize();
+    }
+}
+
+/// Limit a rotation to `max_angle` radians around its own axis, returns if
+/// it had to be limited
+#[inline]
+fn clamp_rotation(rotation: Quat, max_angle: f32) -> (Quat, bool) {
+    // Take the short way around
+    let rotation = if rotation.w < 0.0 { -rotation } else { rotation };
+    let (axis, angle) = rotation.to_axis_angle();
+    let max_angle = max_angle.max(0.0);
+    if angle > max_angle {
+        (Quat::from_axis_angle(axis, max_angle), true)
+    } else {
+        (rotation, false)
+    }
+}
+
+/// Read the targets of IK chains following entities, in the controller's model
+/// space. Transforms are those propagated last frame.
+pub fn update_ik_targets(
+    mut controllers: Query<(&mut AnimatorController, &GlobalTransform)>,
+    targets: Query<&GlobalTransform>,
+) {
+    for (mut controller, root) in controllers.iter_mut() {
+        if !controller
+            .ik_chains
+            .iter()
+            .any(|chain| matches!(chain.solver.target(), IkTarget::Entity(_)))
+        {
+            continue;
+        }
+        let world_to_model = root.affine().inverse();
+        for chain in &mut controller.ik_chains {
+            if let IkTarget::Entity(entity) = chain.solver.target()
+                && let Ok(target) = targets.get(entity)
+            {
+                chain.target = Some(world_to_model.transform_point3(target.translation()));
+            }
+        }
+    }
+}
+
+// ozz builds on its own glam version
+
+#[inline]
+fn to_ozz_vec3(vector: Vec3) -> glam::Vec3A {
+    glam::Vec3A::from_array(vector.to_array())
+}
+
+#[inline]
+fn from_ozz_quat(rotation: glam::Quat) -> Quat {
+    Quat::from_array(rotation.to_array())
+}
+
+#[inline]
+fn to_ozz_mat4(matrix: Mat4) -> glam::Mat4 {
+    glam::Mat4::from_cols_array(&matrix.to_cols_array())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{AnimationLayer, ExternalPose, LayerBlendType, Parameters, test_chain};
+    use std::f32::consts::FRAC_PI_2;
+
+    /// A controller holding the rest pose of a leg hanging down from the
+    /// origin, `Hip` to `Knee` to `Ankle`, each bone one unit long
+    fn leg() -> AnimatorController {
+        let skeleton = test_chain(&[
+            ("Hip", Transform::IDENTITY),
+            ("Knee", Transform::from_translation(Vec3::NEG_Y)),
+            ("Ankle", Transform::from_translation(Vec3::NEG_Y)),
+        ]);
+        let mut layer = AnimationLayer::new(
+            "Base".to_string(),
+            LayerBlendType::Override,
+            1.0,
+            &skeleton,
+            "Rest".to_string(),
+        );
+        layer.add_state("Rest".to_string(), ExternalPose::new(&skeleton).into());
+        AnimatorController::new(skeleton.clone(), vec![layer], Parameters::new()).unwrap()
+    }
+
+    fn joint_position(controller: &AnimatorController, joint: usize) -> Vec3 {
+        controller.model_space_bone(joint).unwrap().w_axis.truncate()
+    }
+
+    #[test]
+    fn two_bone_ik_reaches_targets_within_the_chain_length() {
+        let mut controller = leg();
+        let target = Vec3::new(0.0, -1.2, 0.8);
+        controller
+            .add_two_bone_ik(
+                "Leg",
+                TwoBoneIk::new("Hip", "Knee", "Ankle", IkTarget::Model(target))
+                    .with_pole_vector(Vec3::Z),
+            )
+            .unwrap();
+        controller.update(&Time::default()).unwrap();
+        assert_eq!(controller.ik_reached("Leg"), Some(true));
+        assert!(joint_position(&controller, 2).abs_diff_eq(target, 1e-3));
+        // Bones keep their length
+        let knee = joint_position(&controller, 1);
+        assert!((knee.length() - 1.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn two_bone_ik_stretches_toward_unreachable_targets() {
+        let mut controller = leg();
+        controller
+            .add_two_bone_ik(
+                "Leg",
+                TwoBoneIk::new("Hip", "Knee", "Ankle", IkTarget::Model(Vec3::new(0.0, 0.0, 5.0))),
+            )
+            .unwrap();
+        controller.update(&Time::default()).unwrap();
+        assert_eq!(controller.ik_reached("Leg"), Some(false));
+        // Fully stretched along the direction to the target
+        assert!(joint_position(&controller, 2).abs_diff_eq(Vec3::new(0.0, 0.0, 2.0), 1e-3));
+    }
+
+    #[test]
+    fn two_bone_ik_without_weight_keeps_the_pose() {
+        let mut controller = leg();
+        controller
+            .add_two_bone_ik(
+                "Leg",
+                TwoBoneIk::new("Hip", "Knee", "Ankle", IkTarget::Model(Vec3::new(0.0, -1.0, 1.0)))
+                    .with_weight(0.0),
+            )
+            .unwrap();
+        controller.update(&Time::default()).unwrap();
+        assert_eq!(controller.ik_reached("Leg"), Some(false));
+        assert!(joint_position(&controller, 2).abs_diff_eq(Vec3::new(0.0, -2.0, 0.0), 1e-5));
+    }
+
+    #[test]
+    fn look_at_turns_up_to_its_angle_limit() {
+        // Looking along +Z at a target on +X takes a quarter turn
+        let target = IkTarget::Model(Vec3::new(10.0, 0.0, 0.0));
+        let mut controller = leg();
+        controller
+            .add_look_at("Hip", LookAt::new(target).with_joint("Hip", 1.0, FRAC_PI_2))
+            .unwrap();
+        controller.update(&Time::default()).unwrap();
+        assert_eq!(controller.ik_reached("Hip"), Some(true));
+        let rotation = controller.local_transform(0).unwrap().rotation;
+        assert!((rotation.angle_between(Quat::IDENTITY) - FRAC_PI_2).abs() < 1e-3);
+
+        let limit = 30f32.to_radians();
+        let mut controller = leg();
+        controller
+            .add_look_at("Hip", LookAt::new(target).with_joint("Hip", 1.0, limit))
+            .unwrap();
+        controller.update(&Time::default()).unwrap();
+        assert_eq!(controller.ik_reached("Hip"), Some(false));
+        let rotation = controller.local_transform(0).unwrap().rotation;
+        assert!((rotation.angle_between(Quat::IDENTITY) - limit).abs() < 1e-3);
+    }
+
+    #[test]
+    fn clamp_rotation_limits_the_angle_and_keeps_the_axis() {
+        let rotation = Quat::from_rotation_y(1.0);
+        assert_eq!(clamp_rotation(rotation, 2.0), (rotation, false));
+
+        let (clamped, limited) = clamp_rotation(rotation, 0.25);
+        assert!(limited);
+        assert!(clamped.angle_between(Quat::from_rotation_y(0.25)) < 1e-5);
+
+        // The same rotation the long way around is clamped the short way
+        let (clamped, limited) = clamp_rotation(-rotation, 0.25);
+        assert!(limited);
+        assert!(clamped.angle_between(Quat::from_rotation_y(0.25)) < 1e-5);
+    }
+}
@@ -1,28 +1,141 @@
-use super::{AnimationState, Parameters};
-use bevy::prelude::Time;
+use super::curve_output::CurveSample;
+use super::parameters::ParamBinding;
+use super::predicates::{MAX_PREDICATE_DEPTH, Predicates};
+use super::random::AnimatorRng;
+use super::pose::soa_weights;
+use super::{
+    AnimationMarker, AnimationState, AnimatorError, JointMask, LayerSnapshot, LayerTrace,
+    ParameterValue, Parameters, SimpleState, StateChange, StateLoop, StateStats, StateUsage,
+    TransitionOutcome, TransitionTrace,
+};
+use bevy::{
+    log::{debug, warn},
+    prelude::{Reflect, Time},
+};
 use ozz_animation_rs::{
     BlendingJob, BlendingJobArc, BlendingLayer, OzzError, Skeleton, SoaTransform,
 };
-use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fmt::Debug;
 use std::sync::{Arc, RwLock};
 
-/// Represents a layer in the animator
-#[derive(Debug)]
+/// A state added by `cross_fade_to_clip`, removed once the layer leaves it
+#[derive(Debug, Clone)]
+struct TransientClip {
+    state: String,
+    /// State to crossfade back to once a clamped clip finishes
+    return_to: String,
+    duration: f32,
+}
+
+/// Represents a layer in the animator. Its name, weight and playback state
+/// are reflected for inspectors, the graph itself is not.
+#[derive(Debug, Reflect)]
 pub struct AnimationLayer {
     pub name: String,
     pub layer_blend_type: LayerBlendType,
     pub layer_weight: f32,
+    /// Joints the layer affects, None for every joint
+    #[reflect(ignore)]
+    joint_mask: Option<JointMask>,
+    /// Conditions that must all hold for the layer to play, empty for always
+    #[reflect(ignore)]
+    activation: Vec<TransitionCondition>,
+    #[reflect(ignore)]
+    activation_bindings: Vec<ParamBinding>,
+    /// If the activation conditions held on the last update
+    active: bool,
+    #[reflect(ignore)]
     states: HashMap<String, AnimationState>,
+    #[reflect(ignore)]
     transitions: HashMap<String, Vec<Transition>>,
+    /// Parameter of every transition condition resolved to its id, in the
+    /// same order as `transitions`
+    #[reflect(ignore)]
+    condition_bindings: HashMap<String, Vec<Vec<ParamBinding>>>,
+    /// Layout of the parameters the layer was last bound to, 0 if never
+    #[reflect(ignore)]
+    bound_layout: u64,
+    /// State the layer starts in
+    default_state: String,
     current_state: String,
     next_state: Option<String>,
     transition_time: f32,
     transition_duration: f32,
+    /// If the source state is frozen for the current transition
+    #[reflect(ignore)]
+    freeze_source: bool,
+    /// Joints crossfaded by the current transition, the others switch instantly
+    #[reflect(ignore)]
+    transition_mask: Option<JointMask>,
+    /// If a transition started since the last update, its destination still
+    /// needs to be latched and pre-warmed
+    #[reflect(ignore)]
+    transition_started: bool,
+    /// If the last update didn't sample, e.g. while the layer weighed nothing,
+    /// so a frozen source state still holds an older pose
+    #[reflect(ignore)]
+    unsampled: bool,
+    /// Last condition results of the transitions leaving `condition_history_state`,
+    /// used by edge triggered transitions
+    #[reflect(ignore)]
+    condition_history: Vec<bool>,
+    #[reflect(ignore)]
+    condition_history_state: String,
+    /// Why each transition leaving the current state didn't fire on the last
+    /// check, kept so checks don't allocate
+    #[reflect(ignore)]
+    unmet: Vec<Option<Unmet>>,
+    /// Times the current state looped since the layer entered it
+    loop_count: u32,
+    /// If `loop_count` changed since the controller last read it
+    #[reflect(ignore)]
+    loop_count_changed: bool,
+    /// Loops since they were last drained by the controller
+    #[reflect(ignore)]
+    loops: Vec<StateLoop>,
+    /// Markers crossed since they were last drained by the controller
+    #[reflect(ignore)]
+    markers: Vec<AnimationMarker>,
+    /// State changes since they were last drained by the controller
+    #[reflect(ignore)]
+    state_changes: Vec<StateChange>,
+    /// Triggers of the transition fired by the last update
+    #[reflect(ignore)]
+    consumed_triggers: Vec<String>,
+    /// Transitions evaluated by the last update, if tracing is enabled
+    #[reflect(ignore)]
+    trace: Option<LayerTrace>,
+    /// Usage of every state, if usage tracking is enabled
+    #[reflect(ignore)]
+    usage: Option<HashMap<String, StateUsage>>,
+    /// Times every transition fired, keyed by source state, if usage tracking is enabled
+    #[reflect(ignore)]
+    transition_usage: Option<HashMap<String, Vec<u32>>>,
+    /// Random numbers of the layer, seeded by the controller
+    #[reflect(ignore)]
+    rng: AnimatorRng,
+    /// Random value rolled when the current state was entered
+    state_roll: f32,
+    /// States that start at a random time whenever they are entered
+    #[reflect(ignore)]
+    random_start: HashSet<String>,
+    /// States added by `cross_fade_to_clip` that are still playing
+    #[reflect(ignore)]
+    transient_clips: Vec<TransientClip>,
+    /// Transient states created so far, for unique names
+    transient_count: u32,
+    /// Gameplay tags of every tagged state
+    #[reflect(ignore)]
+    pub(crate) state_tags: HashMap<String, Vec<String>>,
     pub is_transitioning: bool,
+    #[reflect(ignore)]
     blending_job: BlendingJobArc,
+    #[reflect(ignore)]
     blend_job_output: Arc<RwLock<Vec<SoaTransform>>>,
     /// If the source of the output has changed
+    #[reflect(ignore)]
     output_source_changed: bool,
 }
 
@@ -47,12 +160,41 @@ impl AnimationLayer {
             name,
             layer_weight,
             layer_blend_type,
+            joint_mask: None,
+            activation: Vec::new(),
+            activation_bindings: Vec::new(),
+            active: true,
+            default_state: default_state_name.clone(),
             current_state: default_state_name,
             states: HashMap::new(),
             transitions: HashMap::new(),
+            condition_bindings: HashMap::new(),
+            bound_layout: 0,
             next_state: None,
             transition_time: 0.0,
             transition_duration: 0.0,
+            freeze_source: false,
+            transition_mask: None,
+            transition_started: false,
+            unsampled: false,
+            condition_history: Vec::new(),
+            condition_history_state: String::new(),
+            unmet: Vec::new(),
+            loop_count: 0,
+            loop_count_changed: true,
+            loops: Vec::new(),
+            markers: Vec::new(),
+            state_changes: Vec::new(),
+            consumed_triggers: Vec::new(),
+            trace: None,
+            usage: None,
+            transition_usage: None,
+            rng: AnimatorRng::default(),
+            state_roll: 0.0,
+            random_start: HashSet::new(),
+            transient_clips: Vec::new(),
+            transient_count: 0,
+            state_tags: HashMap::new(),
             is_transitioning: false,
             blending_job,
             blend_job_output,
@@ -61,17 +203,144 @@ impl AnimationLayer {
         }
     }
 
+    /// Only affect the joints in `mask`, see [`JointMask`]
+    #[inline]
+    pub fn with_joint_mask(mut self, mask: JointMask) -> Self {
+        self.joint_mask = Some(mask);
+        self
+    }
+
+    /// Change the joints the layer affects. On a layer already in a controller,
+    /// use [`AnimatorController::set_joint_mask`](super::AnimatorController::set_joint_mask)
+    #[inline]
+    pub fn set_joint_mask(&mut self, mask: Option<JointMask>) {
+        self.joint_mask = mask;
+    }
+
+    #[inline]
+    pub fn joint_mask(&self) -> Option<&JointMask> {
+        self.joint_mask.as_ref()
+    }
+
+    /// Only play the layer while all of `conditions` hold, e.g. a swimming
+    /// overlay gated on `Bool("in_water", true)`. An inactive layer isn't
+    /// updated or sampled and weighs 0 in the final blend, its states resume
+    /// where they were once it activates again.
+    /// ## Example
+    /// ```ignore
+    /// let swimming = AnimationLayer::new(
+    ///     "Swimming".to_string(),
+    ///     LayerBlendType::Additive,
+    ///     1.0,
+    ///     &skeleton,
+    ///     "Paddle".to_string(),
+    /// )
+    /// .with_activation(vec![TransitionCondition::Bool("in_water".to_string(), true)]);
+    /// ```
+    #[inline]
+    pub fn with_activation(mut self, conditions: Vec<TransitionCondition>) -> Self {
+        self.set_activation(conditions);
+        self
+    }
+
+    /// Change the activation conditions, empty to always play the layer
+    #[inline]
+    pub fn set_activation(&mut self, conditions: Vec<TransitionCondition>) {
+        self.activation = conditions;
+        self.bound_layout = 0;
+    }
+
+    #[inline]
+    pub fn activation(&self) -> &[TransitionCondition] {
+        &self.activation
+    }
+
+    /// If the activation conditions held on the last update
+    #[inline]
+    pub fn is_active(&self) -> bool {
+        self.active
+    }
+
+    /// Weight of the layer in the final blend, 0 while inactive
+    #[inline]
+    pub fn effective_weight(&self) -> f32 {
+        if self.active { self.layer_weight } else { 0.0 }
+    }
+
+    /// Start a clip state at a random time whenever the layer enters it, so
+    /// characters sharing the same idle don't move in lockstep. The time comes
+    /// from the controller's seeded random numbers, see
+    /// [`AnimatorController::set_seed`](super::AnimatorController::set_seed).
+    #[inline]
+    pub fn with_random_start(mut self, state: impl Into<String>) -> Self {
+        self.random_start.insert(state.into());
+        self
+    }
+
+    #[inline]
+    pub fn set_random_start(&mut self, state: &str, enabled: bool) {
+        if enabled {
+            self.random_start.insert(state.to_string());
+        } else {
+            self.random_start.remove(state);
+        }
+    }
+
+    /// Random value in `[0, 1)` rolled when the layer entered its current
+    /// state, read by `TransitionCondition::Random`
+    #[inline]
+    pub fn state_roll(&self) -> f32 {
+        self.state_roll
+    }
+
+    /// Restart the layer's random numbers
+    #[inline]
+    pub(crate) fn seed_rng(&mut self, seed: u64) {
+        self.rng = AnimatorRng::new(seed);
+        self.state_roll = self.rng.next_f32();
+    }
+
+    /// Move a state back to its start, or to a random time if it starts
+    /// randomly
+    #[inline]
+    fn restart_state(&mut self, state_name: &str) {
+        let random_start = self.random_start.contains(state_name);
+        let Some(state) = self.states.get_mut(state_name) else {
+            return;
+        };
+        let time = match state {
+            AnimationState::Simple(clip) if random_start => self.rng.next_f32() * clip.duration(),
+            _ => 0.0,
+        };
+        state.set_time(time);
+    }
+
     #[inline]
     pub fn add_state(&mut self, name: String, state: AnimationState) {
+        if let Some(usage) = &mut self.usage {
+            usage.entry(name.clone()).or_default();
+        }
         self.states.insert(name, state);
+        self.bound_layout = 0;
     }
 
     #[inline]
-    pub fn add_transition(&mut self, from_state: String, transition: Transition) {
+    pub fn add_transition(&mut self, from_state: String, mut transition: Transition) {
+        if let Some(mask) = &mut transition.mask
+            && let Some(skeleton) = self.blending_job.skeleton()
+            && let Err(error) = mask.bind(skeleton)
+        {
+            warn!(
+                "Transition mask from '{from_state}' to '{}' in layer '{}' ignored: {error}",
+                transition.to_state, self.name
+            );
+            transition.mask = None;
+        }
         self.transitions
             .entry(from_state)
             .or_default()
             .push(transition);
+        self.bound_layout = 0;
     }
 
     #[inline]
@@ -79,59 +348,600 @@ impl AnimationLayer {
         self.layer_weight = weight.clamp(0.0, 1.0);
     }
 
+    /// Move playback of the current state to a normalized time, cancelling any
+    /// transition in progress
     #[inline]
-    fn check_transitions(&mut self, parameters: &Parameters) -> bool {
-        let Some(transitions) = self.transitions.get(&self.current_state) else {
+    pub fn seek(&mut self, normalized_time: f32) {
+        self.flush_transition();
+        if let Some(state) = self.states.get_mut(&self.current_state) {
+            state.seek(normalized_time);
+        }
+    }
+
+    /// Jump to a state and move its playback to a time in seconds, cancelling
+    /// any transition in progress. Returns false if the state doesn't exist.
+    #[inline]
+    pub fn seek_state(&mut self, state_name: &str, time: f32) -> bool {
+        let Some(state) = self.states.get_mut(state_name) else {
             return false;
         };
+        state.set_time(time);
+        self.flush_transition();
+        if self.current_state != state_name {
+            self.enter_state(state_name.to_string());
+            self.record_play(state_name);
+        }
+        true
+    }
+
+    /// Sample every state once without advancing playback, see
+    /// [`AnimatorController::prewarm`](super::AnimatorController::prewarm)
+    pub fn prewarm(&mut self) -> Result<(), AnimatorError> {
+        for (name, state) in &mut self.states {
+            state
+                .prewarm()
+                .map_err(|error| error.in_state(&self.name, name))?;
+        }
+        Ok(())
+    }
+
+    /// Sample a state at a time in seconds into `output` without advancing the
+    /// layer. Returns false if the state doesn't exist.
+    #[inline]
+    pub fn evaluate_at(
+        &self,
+        state_name: &str,
+        parameters: &Parameters,
+        time: f32,
+        output: &mut [SoaTransform],
+    ) -> Result<bool, OzzError> {
+        let Some(state) = self.states.get(state_name) else {
+            return Ok(false);
+        };
+        state.evaluate_at(parameters, time, output)?;
+        Ok(true)
+    }
+
+    #[inline]
+    pub fn has_state(&self, state_name: &str) -> bool {
+        self.states.contains_key(state_name)
+    }
+
+    /// State the layer was created in
+    #[inline]
+    pub fn default_state(&self) -> &str {
+        &self.default_state
+    }
+
+    #[inline]
+    pub fn current_state(&self) -> &str {
+        &self.current_state
+    }
+
+    /// Name of every state in the layer, in no particular order
+    #[inline]
+    pub fn state_names(&self) -> impl Iterator<Item = &str> {
+        self.states.keys().map(String::as_str)
+    }
+
+    #[inline]
+    pub fn state(&self, state_name: &str) -> Option<&AnimationState> {
+        self.states.get(state_name)
+    }
+
+    #[inline]
+    pub(crate) fn state_mut(&mut self, state_name: &str) -> Option<&mut AnimationState> {
+        self.states.get_mut(state_name)
+    }
+
+    /// Transitions leaving a state, in evaluation order
+    #[inline]
+    pub fn transitions(&self, from_state: &str) -> &[Transition] {
+        self.transitions.get(from_state).map_or(&[], Vec::as_slice)
+    }
+
+    /// Duration of the current state in seconds, None for blend trees, external
+    /// poses and sub-controllers
+    #[inline]
+    pub fn current_state_duration(&self) -> Option<f32> {
+        match self.states.get(&self.current_state)? {
+            AnimationState::Simple(state) => Some(state.duration()),
+            AnimationState::Blend(_)
+            | AnimationState::External(_)
+            | AnimationState::Controller(_) => None,
+        }
+    }
+
+    /// Every parameter the states and transitions of the layer read, with its
+    /// default value
+    pub(crate) fn referenced_parameters(&self, out: &mut Vec<(String, ParameterValue)>) {
+        for state in self.states.values() {
+            state.referenced_parameters(out);
+        }
+        out.extend(
+            self.activation
+                .iter()
+                .filter_map(TransitionCondition::referenced_parameter),
+        );
+        for transition in self.transitions.values().flatten() {
+            if let Some(name) = &transition.duration_parameter {
+                out.push((name.clone(), ParameterValue::Float(1.0)));
+            }
+            out.extend(
+                transition
+                    .conditions
+                    .iter()
+                    .filter_map(TransitionCondition::referenced_parameter),
+            );
+        }
+    }
+
+    /// Resolve every parameter read by the transitions and states of the layer
+    /// to its id, so updates skip the name lookups. Controllers rebind their
+    /// layers whenever a parameter name is added or a state or transition is.
+    pub fn bind_parameters(&mut self, parameters: &Parameters) {
+        self.condition_bindings = self
+            .transitions
+            .iter()
+            .map(|(state, transitions)| {
+                let bindings = transitions
+                    .iter()
+                    .map(|transition| {
+                        transition
+                            .conditions
+                            .iter()
+                            .map(|condition| match condition.parameter() {
+                                Some(parameter) => ParamBinding::resolve(parameters, parameter),
+                                None => ParamBinding::default(),
+                            })
+                            .collect()
+                    })
+                    .collect();
+                (state.clone(), bindings)
+            })
+            .collect();
+        self.activation_bindings = self
+            .activation
+            .iter()
+            .map(|condition| match condition.parameter() {
+                Some(parameter) => ParamBinding::resolve(parameters, parameter),
+                None => ParamBinding::default(),
+            })
+            .collect();
+        for state in self.states.values_mut() {
+            state.bind_parameters(parameters);
+        }
+        self.bound_layout = parameters.layout();
+    }
+
+    /// Evaluate the activation conditions, returning if the layer is active
+    pub(crate) fn update_activation(
+        &mut self,
+        parameters: &Parameters,
+        layer_states: &LayerStates,
+        predicates: &Predicates,
+    ) -> bool {
+        let context = ConditionContext {
+            parameters,
+            layer_states,
+            predicates,
+        };
+        let active = self.activation.iter().enumerate().all(|(i, condition)| {
+            let binding = self.activation_bindings.get(i).copied().unwrap_or_default();
+            self.evaluate_condition(condition, binding, &context, 0)
+        });
+        self.active = active;
+        active
+    }
+
+    /// If the layer was bound to parameters with a different layout, or its
+    /// graph changed since
+    #[inline]
+    pub(crate) fn needs_binding(&self, parameters: &Parameters) -> bool {
+        self.bound_layout != parameters.layout()
+    }
+
+    /// Playback time of the current state from 0 at the start to 1 at the end,
+    /// counted from when the layer entered it
+    #[inline]
+    pub fn normalized_time(&self) -> f32 {
+        self.states
+            .get(&self.current_state)
+            .map_or(0.0, AnimationState::normalized_time)
+    }
+
+    /// Playback time of the state being transitioned to, if any
+    #[inline]
+    pub fn next_state_normalized_time(&self) -> Option<f32> {
+        let next_state = self.next_state.as_ref()?;
+        self.states.get(next_state).map(AnimationState::normalized_time)
+    }
+
+    /// Stats of the current state and the state being transitioned to, given
+    /// the layer's weight in the final blend
+    pub(crate) fn state_stats(&self, weight: f32, additive: bool, out: &mut Vec<StateStats>) {
+        let next_state = self
+            .next_state
+            .as_ref()
+            .filter(|next_state| **next_state != self.current_state);
+        let t = match next_state {
+            Some(_) if self.transition_duration > 0.0 => {
+                (self.transition_time / self.transition_duration).clamp(0.0, 1.0)
+            }
+            Some(_) => 1.0,
+            None => 0.0,
+        };
+        let mut push = |name: &String, state_weight: f32| {
+            let Some(state) = self.states.get(name) else {
+                return;
+            };
+            out.push(StateStats {
+                layer: self.name.clone(),
+                state: name.clone(),
+                ratio: state.normalized_time(),
+                state_weight,
+                weight: state_weight * weight,
+                additive,
+                motions: state.motion_stats(state_weight * weight),
+            });
+        };
+        push(&self.current_state, 1.0 - t);
+        if let Some(next_state) = next_state {
+            push(next_state, t);
+        }
+    }
+
+    #[inline]
+    fn flush_transition(&mut self) {
         if self.is_transitioning {
-            return false;
+            self.output_source_changed = true;
         }
-        for transition in transitions {
-            let next_state = &transition.to_state;
-            if self.evaluate_transition(transition, parameters) {
-                // Make sure the next state exists
-                if !self.states.contains_key(next_state) {
-                    return false;
-                }
+        if let Some(next_state) = self.next_state.as_ref().and_then(|n| self.states.get_mut(n)) {
+            next_state.release_latch();
+        }
+        self.next_state = None;
+        self.transition_time = 0.0;
+        self.transition_duration = 0.0;
+        self.freeze_source = false;
+        self.transition_mask = None;
+        self.transition_started = false;
+        self.is_transitioning = false;
+    }
 
-                self.next_state = Some(next_state.to_string());
-                self.transition_time = 0.0;
-                self.transition_duration = transition.duration;
-                self.is_transitioning = true;
+    /// Switch to a state directly, crossfading over `blend_duration` seconds.
+    /// A duration of 0 switches on the spot. Transition conditions are not checked.
+    /// ## Example
+    /// ```ignore
+    /// layer.force_state("stunned", 0.1)?;
+    /// ```
+    pub fn force_state(&mut self, state_name: &str, blend_duration: f32) -> Result<(), AnimatorError> {
+        if !self.states.contains_key(state_name) {
+            return Err(AnimatorError::UnknownState {
+                layer: self.name.clone(),
+                state: state_name.to_string(),
+            });
+        }
 
-                println!(
-                    "Transitioning from {} to {}",
-                    self.current_state, transition.to_state
-                );
-                return true;
+        if self.current_state == state_name {
+            self.flush_transition();
+        } else if blend_duration <= 0.0 {
+            self.flush_transition();
+            self.restart_state(state_name);
+            self.enter_state(state_name.to_string());
+            self.record_play(state_name);
+        } else if self.next_state.as_deref() != Some(state_name) {
+            if let Some(next_state) = self.next_state.as_ref().and_then(|n| self.states.get_mut(n)) {
+                next_state.release_latch();
             }
+            self.start_transition(state_name.to_string(), blend_duration, false, None);
         }
-        false
+        Ok(())
     }
 
+    /// Restart a state from the beginning and switch to it on the spot
     #[inline]
-    fn evaluate_transition(&self, transition: &Transition, parameters: &Parameters) -> bool {
-        if transition.has_exit_time {
-            // TODO: Check exit time logic here
-            // ...
+    pub fn play(&mut self, state_name: &str) -> Result<(), AnimatorError> {
+        if !self.seek_state(state_name, 0.0) {
+            return Err(AnimatorError::UnknownState {
+                layer: self.name.clone(),
+                state: state_name.to_string(),
+            });
         }
+        Ok(())
+    }
 
-        // Validate all conditions
+    /// Crossfade to a state over `duration` seconds, starting it from the
+    /// beginning. Crossfading to the current state restarts it instantly.
+    #[inline]
+    pub fn cross_fade(&mut self, state_name: &str, duration: f32) -> Result<(), AnimatorError> {
+        if duration <= 0.0 || self.current_state == state_name {
+            return self.play(state_name);
+        }
+        self.force_state(state_name, duration)
+    }
+
+    /// Crossfade to a clip state that isn't part of the graph over `duration`
+    /// seconds. The state is created on the fly, named after the clip with a
+    /// unique suffix, and removed once the layer leaves it. A clamped clip
+    /// crossfades back to the state the layer was heading for once it
+    /// finishes, a looping one plays until another state is forced. Returns
+    /// the name of the state, as seen in state change events.
+    pub fn cross_fade_to_clip(
+        &mut self,
+        name: &str,
+        clip: SimpleState,
+        duration: f32,
+    ) -> Result<String, AnimatorError> {
+        // Clips crossfaded to over another clip return to where that one would
+        // have, the other clip is removed once the layer leaves it
+        let playing = self.next_state.as_ref().unwrap_or(&self.current_state);
+        let return_to = self
+            .transient_clips
+            .iter()
+            .find(|clip| clip.state == *playing)
+            .map_or(playing, |clip| &clip.return_to)
+            .clone();
+        // Names never repeat, so a clip can play again while it fades out
+        let state = loop {
+            self.transient_count += 1;
+            let state = format!("{name} [{}]", self.transient_count);
+            if !self.has_state(&state) {
+                break state;
+            }
+        };
+        self.add_state(state.clone(), AnimationState::Simple(clip));
+        self.transient_clips.push(TransientClip {
+            state: state.clone(),
+            return_to,
+            duration,
+        });
+        self.cross_fade(&state, duration)?;
+        Ok(state)
+    }
+
+    /// If the state was created by [`Self::cross_fade_to_clip`]
+    #[inline]
+    pub fn is_transient_state(&self, state_name: &str) -> bool {
+        self.transient_clips.iter().any(|clip| clip.state == state_name)
+    }
+
+    /// Send finished clamped clips back and remove the ones the layer left
+    pub(crate) fn update_transient_clips(&mut self) {
+        if self.transient_clips.is_empty() {
+            return;
+        }
+        if self.next_state.is_none()
+            && let Some(clip) = self
+                .transient_clips
+                .iter()
+                .find(|clip| clip.state == self.current_state)
+            && self.states.get(&clip.state).is_some_and(|state| state.is_finished())
+            && self.states.contains_key(&clip.return_to)
+        {
+            let (return_to, duration) = (clip.return_to.clone(), clip.duration);
+            // The state exists, so this can't fail
+            let _ = self.cross_fade(&return_to, duration);
+        }
+
+        let mut clips = std::mem::take(&mut self.transient_clips);
+        clips.retain(|clip| {
+            let playing = clip.state == self.current_state
+                || self.next_state.as_ref() == Some(&clip.state);
+            if !playing {
+                self.states.remove(&clip.state);
+                if let Some(usage) = &mut self.usage {
+                    usage.remove(&clip.state);
+                }
+                self.bound_layout = 0;
+            }
+            playing
+        });
+        self.transient_clips = clips;
+    }
+
+    #[inline]
+    fn start_transition(
+        &mut self,
+        to_state: String,
+        duration: f32,
+        freeze_source: bool,
+        mask: Option<JointMask>,
+    ) {
+        debug!("Transitioning from {} to {}", self.current_state, to_state);
+        self.record_play(&to_state);
+        // States play from their own start, not from wherever they were left
+        self.restart_state(&to_state);
+        self.state_changes.push(StateChange::TransitionStarted {
+            layer: self.name.clone(),
+            from: self.current_state.clone(),
+            to: to_state.clone(),
+        });
+        self.next_state = Some(to_state);
+        self.transition_time = 0.0;
+        self.transition_duration = duration;
+        self.freeze_source = freeze_source;
+        self.transition_mask = mask;
+        self.transition_started = true;
+        self.is_transitioning = true;
+    }
+
+    #[inline]
+    fn check_transitions(
+        &mut self,
+        parameters: &Parameters,
+        layer_states: &LayerStates,
+        predicates: &Predicates,
+    ) {
+        if let Some(trace) = &mut self.trace {
+            trace.state.clone_from(&self.current_state);
+            trace.transitioning = self.is_transitioning;
+            trace.transitions.clear();
+        }
+        let Some(transitions) = self.transitions.get(&self.current_state) else {
+            return;
+        };
+
+        // Conditions are tracked every frame so edge triggered transitions
+        // only fire when their conditions change while in this state
+        let progress = self.state_progress();
+        let context = ConditionContext {
+            parameters,
+            layer_states,
+            predicates,
+        };
+        let mut failed = std::mem::take(&mut self.unmet);
+        failed.clear();
+        failed.extend(transitions.iter().enumerate().map(|(i, transition)| {
+            let bindings = self
+                .condition_bindings
+                .get(&self.current_state)
+                .and_then(|bindings| bindings.get(i))
+                .map_or(&[][..], Vec::as_slice);
+            self.unmet(transition, bindings, progress, &context)
+        }));
+        // Edge triggered transitions don't fire on the frame their state is entered
+        let entered = self.condition_history_state != self.current_state;
+        let history = &self.condition_history;
+        let fires = |i: usize| {
+            let met = failed[i].is_none();
+            match transitions[i].condition_mode {
+                ConditionMode::Level => met,
+                ConditionMode::Edge => {
+                    met && !entered && !history.get(i).copied().unwrap_or(false)
+                }
+            }
+        };
+        let fired = if self.is_transitioning {
+            None
+        } else {
+            (0..transitions.len()).find(|&i| fires(i))
+        };
+
+        if let Some(trace) = &mut self.trace {
+            trace.transitions.extend(transitions.iter().enumerate().map(|(i, transition)| {
+                let outcome = if let Some(unmet) = failed[i] {
+                    match unmet {
+                        Unmet::ExitTime => TransitionOutcome::WaitingForExitTime,
+                        Unmet::Condition(index) => TransitionOutcome::ConditionFailed {
+                            index,
+                            condition: transition.conditions[index].clone(),
+                        },
+                    }
+                } else if fired != Some(i) {
+                    if fires(i) {
+                        TransitionOutcome::Met
+                    } else {
+                        TransitionOutcome::WaitingForEdge
+                    }
+                } else if self.states.contains_key(&transition.to_state) {
+                    TransitionOutcome::Fired
+                } else {
+                    TransitionOutcome::UnknownState
+                };
+                TransitionTrace {
+                    to_state: transition.to_state.clone(),
+                    outcome,
+                }
+            }));
+        }
+
+        if entered {
+            self.condition_history_state.clone_from(&self.current_state);
+        }
+        self.condition_history.clear();
+        self.condition_history.extend(failed.iter().map(Option::is_none));
+        self.unmet = failed;
+
+        let Some((index, transition)) = fired.map(|i| (i, &transitions[i])) else {
+            return;
+        };
+
+        // Make sure the next state exists
+        if !self.states.contains_key(&transition.to_state) {
+            return;
+        }
+        if let Some(usage) = &mut self.transition_usage {
+            let fires = usage.entry(self.current_state.clone()).or_default();
+            if fires.len() <= index {
+                fires.resize(index + 1, 0);
+            }
+            fires[index] += 1;
+        }
         for condition in &transition.conditions {
-            if !self.evaluate_condition(condition, parameters) {
-                return false;
+            predicates.triggers(condition, &mut self.consumed_triggers);
+        }
+        let (to_state, duration, freeze_source, mask) = (
+            transition.to_state.clone(),
+            transition.resolved_duration(parameters),
+            transition.freeze_source,
+            transition.mask.clone(),
+        );
+        self.start_transition(to_state, duration, freeze_source, mask);
+    }
+
+    /// Normalized time of the current state since the layer entered it,
+    /// counting every loop, e.g. 1.5 halfway through the second loop. None once
+    /// a clamped state finished, as it won't play any further.
+    #[inline]
+    fn state_progress(&self) -> Option<f32> {
+        match self.states.get(&self.current_state) {
+            Some(state) if state.is_finished() => None,
+            Some(state) => Some(self.loop_count as f32 + state.normalized_time()),
+            None => Some(0.0),
+        }
+    }
+
+    /// What keeps a transition from firing, if anything
+    #[inline]
+    fn unmet(
+        &self,
+        transition: &Transition,
+        bindings: &[ParamBinding],
+        progress: Option<f32>,
+        context: &ConditionContext,
+    ) -> Option<Unmet> {
+        // Exit times below 1 are reached again on every loop, and a finished
+        // state has reached all of them
+        if transition.has_exit_time {
+            let reached = match progress {
+                None => true,
+                Some(progress) if transition.exit_time < 1.0 => {
+                    progress.fract() >= transition.exit_time
+                }
+                Some(progress) => progress >= transition.exit_time,
+            };
+            if !reached {
+                return Some(Unmet::ExitTime);
             }
         }
-        true
+
+        // Validate all conditions
+        transition
+            .conditions
+            .iter()
+            .enumerate()
+            .position(|(i, condition)| {
+                let binding = bindings.get(i).copied().unwrap_or_default();
+                !self.evaluate_condition(condition, binding, context, 0)
+            })
+            .map(Unmet::Condition)
     }
 
     #[inline]
-    fn evaluate_condition(&self, condition: &TransitionCondition, parameters: &Parameters) -> bool {
+    fn evaluate_condition(
+        &self,
+        condition: &TransitionCondition,
+        binding: ParamBinding,
+        context: &ConditionContext,
+        depth: usize,
+    ) -> bool {
+        let parameters = context.parameters;
         match condition {
-            TransitionCondition::Bool(name, value) => parameters.get_bool(name) == Some(*value),
+            TransitionCondition::Bool(name, value) => {
+                parameters.bound_bool(binding, name) == Some(*value)
+            }
             TransitionCondition::Float(name, value, compare_type) => {
-                if let Some(param_value) = parameters.get_float(name) {
+                if let Some(param_value) = parameters.bound_float(binding, name) {
                     match compare_type {
                         CompareType::Greater => param_value > *value,
                         CompareType::Less => param_value < *value,
@@ -143,7 +953,7 @@ impl AnimationLayer {
                 }
             }
             TransitionCondition::Int(name, value, compare_type) => {
-                if let Some(param_value) = parameters.get_int(name) {
+                if let Some(param_value) = parameters.bound_int(binding, name) {
                     match compare_type {
                         CompareType::Greater => param_value > *value,
                         CompareType::Less => param_value < *value,
@@ -154,74 +964,230 @@ impl AnimationLayer {
                     false
                 }
             }
-            TransitionCondition::Trigger(name) => parameters.get_trigger(name),
+            TransitionCondition::Trigger(name) => parameters.bound_trigger(binding, name),
+            TransitionCondition::StateFinished => self
+                .states
+                .get(&self.current_state)
+                .is_some_and(AnimationState::is_finished),
+            TransitionCondition::LayerState(layer, state, in_state) => {
+                let current = if *layer == self.name {
+                    Some(self.current_state.as_str())
+                } else {
+                    context.layer_states.get(layer)
+                };
+                current.is_some_and(|current| (current == state) == *in_state)
+            }
+            TransitionCondition::LayerTag(layer, tag, has_tag) => {
+                let tags = if *layer == self.name {
+                    Some(self.state_tags(&self.current_state))
+                } else {
+                    context.layer_states.tags(layer)
+                };
+                tags.is_some_and(|tags| tags.contains(tag) == *has_tag)
+            }
+            TransitionCondition::Predicate(name) => {
+                depth < MAX_PREDICATE_DEPTH
+                    && context.predicates.bound(name).is_some_and(|mut conditions| {
+                        conditions.all(|(condition, binding)| {
+                            self.evaluate_condition(condition, binding, context, depth + 1)
+                        })
+                    })
+            }
+            TransitionCondition::Random(min, max) => (*min..*max).contains(&self.state_roll),
         }
     }
 
     #[inline]
-    pub fn update(&mut self, time: &Time, parameters: &mut Parameters) -> Result<(), OzzError> {
+    pub fn update(&mut self, time: &Time, parameters: &mut Parameters) -> Result<(), AnimatorError> {
+        self.tick(
+            time,
+            parameters,
+            &LayerStates::default(),
+            &Predicates::default(),
+            true,
+        )
+    }
+
+    /// Run the state machine, only sampling and blending states if `sample` is set
+    #[inline]
+    pub(crate) fn tick(
+        &mut self,
+        time: &Time,
+        parameters: &mut Parameters,
+        layer_states: &LayerStates,
+        predicates: &Predicates,
+        sample: bool,
+    ) -> Result<(), AnimatorError> {
         let was_transitioning = self.is_transitioning;
 
-        self.check_transitions(parameters);
+        self.check_transitions(parameters, layer_states, predicates);
+        let transition_started = std::mem::take(&mut self.transition_started);
 
-        // Detect transition state changes
-        if was_transitioning != self.is_transitioning {
-            self.output_source_changed = true;
+        // Blend trees hold their weights while the crossfade runs
+        if transition_started
+            && let Some(next_state) = self.next_state.as_ref().and_then(|n| self.states.get_mut(n))
+        {
+            next_state.latch_weights(parameters);
         }
 
-        // Update current state
-        if let Some(current_state) = self.states.get_mut(&self.current_state) {
-            match current_state {
-                AnimationState::Simple(s) => {
-                    s.update(time)?;
-                }
-                AnimationState::Blend(b) => {
-                    b.update(time, parameters)?;
-                }
-            }
-        }
-
-        // Handle transition
-        if let Some(next_state_name) = &self.next_state {
+        // Advance the transition before sampling, so every state is sampled
+        // exactly once this frame with the same parameters
+        if self.next_state.is_some() {
             self.transition_time += time.delta_secs();
 
             if self.transition_time >= self.transition_duration {
                 // Transition complete
-                self.current_state = next_state_name.clone();
-                self.next_state = None;
+                if let Some(next_state_name) = self.next_state.take() {
+                    if let Some(state) = self.states.get_mut(&next_state_name) {
+                        state.release_latch();
+                    }
+                    let from = self.current_state.clone();
+                    self.enter_state(next_state_name.clone());
+                    self.state_changes.push(StateChange::TransitionCompleted {
+                        layer: self.name.clone(),
+                        from,
+                        to: next_state_name,
+                    });
+                }
+                self.freeze_source = false;
+                self.transition_mask = None;
                 self.is_transitioning = false;
                 self.output_source_changed = true;
-            } else {
-                // Blend between states
-                let t = self.transition_time / self.transition_duration;
+            }
+        }
 
-                // TODO: NEED TO CACHE POINTERS AND DONT RECONSTRUCT BLENDING LAYERS, JUST UPDATE THEM
-                let current_state_output = self.states.get(&self.current_state).map(|s| match s {
-                    AnimationState::Simple(state) => state.get_output_pointer(),
-                    AnimationState::Blend(state) => state.get_output_pointer(),
+        // Detect transition state changes
+        if was_transitioning != self.is_transitioning {
+            self.output_source_changed = true;
+        }
+
+        // Update current state, a frozen source keeps the pose it had when the transition started
+        let frozen = self.is_transitioning && self.freeze_source;
+        let unsampled = std::mem::replace(&mut self.unsampled, !sample);
+        if frozen
+            && sample
+            && unsampled
+            && let Some(current_state) = self.states.get_mut(&self.current_state)
+        {
+            current_state
+                .sample(parameters)
+                .map_err(|error| error.in_state(&self.name, &self.current_state))?;
+        }
+        if !frozen && let Some(current_state) = self.states.get_mut(&self.current_state) {
+            let loops_before = current_state.loop_count();
+            let finished_before = current_state.is_finished();
+            current_state
+                .tick(time, parameters, sample)
+                .map_err(|error| error.in_state(&self.name, &self.current_state))?;
+            let loops = current_state.loop_count() - loops_before;
+            if !finished_before && current_state.is_finished() {
+                self.state_changes.push(StateChange::Finished {
+                    layer: self.name.clone(),
+                    state: self.current_state.clone(),
                 });
-                let next_state_output = self.states.get_mut(next_state_name).map(|s| {
-                    // We need to update the next state to get the output
-                    match s {
-                        AnimationState::Simple(state) => {
-                            let _ = state.update(time);
-                            state.get_output_pointer()
-                        }
-                        AnimationState::Blend(state) => {
-                            let _ = state.update(time, parameters);
-                            state.get_output_pointer()
-                        }
-                    }
+            }
+            let mut markers = Vec::new();
+            current_state.drain_markers(&mut markers);
+            self.markers.extend(markers.into_iter().map(|name| AnimationMarker {
+                layer: self.name.clone(),
+                state: self.current_state.clone(),
+                name,
+            }));
+            if sample
+                && let Some(usage) = self.usage.as_mut().and_then(|u| u.get_mut(&self.current_state))
+            {
+                usage.time_sampled += time.delta_secs();
+            }
+            for _ in 0..loops {
+                self.loop_count += 1;
+                self.loop_count_changed = true;
+                self.loops.push(StateLoop {
+                    layer: self.name.clone(),
+                    state: self.current_state.clone(),
+                    loop_count: self.loop_count,
                 });
+            }
+        }
 
-                if let (Some(current_output), Some(next_output)) =
-                    (current_state_output, next_state_output)
-                {
-                    self.blend_states(current_output, next_output, t)?;
-                }
+        // Update the destination state, unless it is the state we just updated.
+        // On the first frame of a transition it is sampled at its entry time
+        // instead, so the blend doesn't start from a stale pose.
+        let Some(next_state_name) = &self.next_state else {
+            return Ok(());
+        };
+        if *next_state_name != self.current_state
+            && let Some(next_state) = self.states.get_mut(next_state_name)
+        {
+            next_state.set_latch_progress(self.transition_time / self.transition_duration);
+            let result = if !transition_started {
+                next_state.tick(time, parameters, sample)
+            } else if sample {
+                next_state.sample(parameters)
+            } else {
+                Ok(())
+            };
+            result.map_err(|error| error.in_state(&self.name, next_state_name))?;
+            let mut markers = Vec::new();
+            next_state.drain_markers(&mut markers);
+            self.markers.extend(markers.into_iter().map(|name| AnimationMarker {
+                layer: self.name.clone(),
+                state: next_state_name.clone(),
+                name,
+            }));
+            if sample
+                && let Some(usage) = self.usage.as_mut().and_then(|u| u.get_mut(next_state_name))
+            {
+                usage.time_sampled += time.delta_secs();
             }
         }
 
+        // Blend between states
+        // TODO: NEED TO CACHE POINTERS AND DONT RECONSTRUCT BLENDING LAYERS, JUST UPDATE THEM
+        let current_state_output = self
+            .states
+            .get(&self.current_state)
+            .and_then(|s| s.get_output_pointer());
+        let next_state_output = self
+            .states
+            .get(next_state_name)
+            .and_then(|s| s.get_output_pointer());
+        if sample
+            && let (Some(current_output), Some(next_output)) =
+                (current_state_output, next_state_output)
+        {
+            let t = self.transition_time / self.transition_duration;
+            self.blend_states(current_output, next_output, t)
+                .map_err(|source| AnimatorError::TransitionBlending {
+                    layer: self.name.clone(),
+                    source,
+                })?;
+        }
+
+        Ok(())
+    }
+
+    /// Sample the current and destination states at their playback time and
+    /// blend them like the last update did, without advancing anything
+    pub(crate) fn sample(&mut self, parameters: &Parameters) -> Result<(), AnimatorError> {
+        for name in [Some(&self.current_state), self.next_state.as_ref()].into_iter().flatten() {
+            if let Some(state) = self.states.get_mut(name) {
+                state
+                    .sample(parameters)
+                    .map_err(|error| error.in_state(&self.name, name))?;
+            }
+        }
+        let outputs = self.next_state.as_ref().and_then(|next_state| {
+            let current = self.states.get(&self.current_state)?.get_output_pointer()?;
+            Some((current, self.states.get(next_state)?.get_output_pointer()?))
+        });
+        if let Some((current, next)) = outputs {
+            let t = self.transition_time / self.transition_duration;
+            self.blend_states(current, next, t)
+                .map_err(|source| AnimatorError::TransitionBlending {
+                    layer: self.name.clone(),
+                    source,
+                })?;
+        }
         Ok(())
     }
 
@@ -234,22 +1200,278 @@ impl AnimationLayer {
     ) -> Result<(), OzzError> {
         self.blending_job.layers_mut().clear();
 
+        // With a mask, joints outside of it are fully in the next state already
+        // and the per-joint weights always sum to 1
+        let (current_weight, next_weight, current_joints, next_joints) =
+            match &self.transition_mask {
+                None => (1.0 - t, t, vec![], vec![]),
+                Some(mask) => {
+                    let num_soa_joints = mask.weights().len().div_ceil(4);
+                    let current: Vec<f32> =
+                        mask.weights().iter().map(|m| m * (1.0 - t)).collect();
+                    let next: Vec<f32> = current.iter().map(|w| 1.0 - w).collect();
+                    (
+                        1.0,
+                        1.0,
+                        soa_weights(&current, num_soa_joints),
+                        soa_weights(&next, num_soa_joints),
+                    )
+                }
+            };
+
         // Construct blending layers
         self.blending_job.layers_mut().push(BlendingLayer {
             transform: current,
-            weight: 1.0 - t,
-            joint_weights: vec![],
+            weight: current_weight,
+            joint_weights: current_joints,
         });
         self.blending_job.layers_mut().push(BlendingLayer {
             transform: next,
-            weight: t,
-            joint_weights: vec![],
+            weight: next_weight,
+            joint_weights: next_joints,
         });
 
         self.blending_job.run()?;
         Ok(())
     }
 
+    /// Capture the runtime state of this layer
+    pub fn snapshot(&self) -> LayerSnapshot {
+        let state_times = self
+            .states
+            .iter()
+            .map(|(name, state)| {
+                let mut times = Vec::new();
+                state.collect_times(&mut times);
+                (name.clone(), times)
+            })
+            .collect::<BTreeMap<_, _>>();
+
+        LayerSnapshot {
+            name: self.name.clone(),
+            weight: self.layer_weight,
+            current_state: self.current_state.clone(),
+            next_state: self.next_state.clone(),
+            transition_time: self.transition_time,
+            transition_duration: self.transition_duration,
+            freeze_source: self.freeze_source,
+            state_times,
+            random: Some((self.state_roll, self.rng.state())),
+            loop_count: self.loop_count,
+            transition_mask: self.transition_mask.clone(),
+        }
+    }
+
+    /// Restore the runtime state of this layer from a snapshot
+    pub fn restore(&mut self, snapshot: &LayerSnapshot) -> Result<(), AnimatorError> {
+        self.check_snapshot(snapshot)?;
+        for (name, times) in &snapshot.state_times {
+            if let Some(state) = self.states.get_mut(name) {
+                state.restore_times(&mut times.iter().copied());
+            }
+        }
+
+        self.set_weight(snapshot.weight);
+        self.enter_state(snapshot.current_state.clone());
+        self.next_state = snapshot.next_state.clone();
+        self.transition_time = snapshot.transition_time;
+        self.transition_duration = snapshot.transition_duration;
+        self.freeze_source = snapshot.freeze_source;
+        self.transition_mask = self.bound_mask(snapshot.transition_mask.as_ref())?;
+        self.loop_count = snapshot.loop_count;
+        if let Some((roll, rng)) = snapshot.random {
+            self.state_roll = roll;
+            self.rng = AnimatorRng::new(rng);
+        }
+        self.is_transitioning = self.next_state.is_some();
+        self.output_source_changed = true;
+        Ok(())
+    }
+
+    /// The error `restore` would fail with, without changing the layer
+    pub(crate) fn check_snapshot(&self, snapshot: &LayerSnapshot) -> Result<(), AnimatorError> {
+        let unknown_state = |state: &str| AnimatorError::UnknownState {
+            layer: self.name.clone(),
+            state: state.to_string(),
+        };
+        if !self.states.contains_key(&snapshot.current_state) {
+            return Err(unknown_state(&snapshot.current_state));
+        }
+        if let Some(next_state) = &snapshot.next_state
+            && !self.states.contains_key(next_state)
+        {
+            return Err(unknown_state(next_state));
+        }
+        if let Some(state) = snapshot
+            .state_times
+            .keys()
+            .find(|state| !self.states.contains_key(*state))
+        {
+            return Err(unknown_state(state));
+        }
+        self.bound_mask(snapshot.transition_mask.as_ref())?;
+        Ok(())
+    }
+
+    /// A copy of `mask` bound to the layer's skeleton
+    #[inline]
+    fn bound_mask(&self, mask: Option<&JointMask>) -> Result<Option<JointMask>, AnimatorError> {
+        let (Some(mask), Some(skeleton)) = (mask, self.blending_job.skeleton()) else {
+            return Ok(mask.cloned());
+        };
+        let mut mask = mask.clone();
+        mask.bind(skeleton)?;
+        Ok(Some(mask))
+    }
+
+    /// Make a state current without a transition, restarting its loop count
+    #[inline]
+    fn enter_state(&mut self, state_name: String) {
+        if state_name != self.current_state {
+            self.state_changes.push(StateChange::Exited {
+                layer: self.name.clone(),
+                state: self.current_state.clone(),
+            });
+            self.state_changes.push(StateChange::Entered {
+                layer: self.name.clone(),
+                state: state_name.clone(),
+            });
+        }
+        self.current_state = state_name;
+        self.state_roll = self.rng.next_f32();
+        self.output_source_changed = true;
+        self.loop_count = 0;
+        self.loop_count_changed = true;
+    }
+
+    /// Times the current state looped since the layer entered it, also
+    /// available to transition conditions as the `loop_count_parameter()` int
+    #[inline]
+    pub fn loop_count(&self) -> u32 {
+        self.loop_count
+    }
+
+    /// Name of the int parameter tracking `loop_count()`, `"<layer name>.loop_count"`
+    #[inline]
+    pub fn loop_count_parameter(&self) -> String {
+        format!("{}.loop_count", self.name)
+    }
+
+    /// Record which transitions every update evaluates and why they didn't fire
+    #[inline]
+    pub fn set_tracing(&mut self, enabled: bool) {
+        match (enabled, self.trace.is_some()) {
+            (true, false) => {
+                self.trace = Some(LayerTrace {
+                    layer: self.name.clone(),
+                    ..Default::default()
+                });
+            }
+            (false, true) => self.trace = None,
+            _ => {}
+        }
+    }
+
+    /// Transitions evaluated by the last update, `None` unless tracing is enabled
+    #[inline]
+    pub fn trace(&self) -> Option<&LayerTrace> {
+        self.trace.as_ref()
+    }
+
+    /// Count how often every state plays and how long it is sampled, see
+    /// [`AnimatorController::state_usage`](super::AnimatorController::state_usage).
+    /// The current state counts as played when enabling.
+    pub fn set_usage_tracking(&mut self, enabled: bool) {
+        if !enabled {
+            self.usage = None;
+            self.transition_usage = None;
+            return;
+        }
+        if self.usage.is_none() {
+            self.usage = Some(
+                self.states
+                    .keys()
+                    .map(|name| (name.clone(), StateUsage::default()))
+                    .collect(),
+            );
+            self.transition_usage = Some(HashMap::new());
+            let current_state = self.current_state.clone();
+            self.record_play(&current_state);
+        }
+    }
+
+    /// Times a transition fired while usage tracking was enabled, by source
+    /// state and index in evaluation order. Forced states don't count.
+    #[inline]
+    pub fn transition_fires(&self, from_state: &str, index: usize) -> Option<u32> {
+        let usage = self.transition_usage.as_ref()?;
+        Some(
+            usage
+                .get(from_state)
+                .and_then(|fires| fires.get(index))
+                .copied()
+                .unwrap_or(0),
+        )
+    }
+
+    /// Usage of every state of the layer, `None` unless usage tracking is enabled
+    #[inline]
+    pub fn usage(&self) -> Option<&HashMap<String, StateUsage>> {
+        self.usage.as_ref()
+    }
+
+    #[inline]
+    fn record_play(&mut self, state_name: &str) {
+        if let Some(usage) = self.usage.as_mut().and_then(|u| u.get_mut(state_name)) {
+            usage.plays += 1;
+        }
+    }
+
+    #[inline]
+    pub(crate) fn take_loop_count_changed(&mut self) -> bool {
+        std::mem::take(&mut self.loop_count_changed)
+    }
+
+    #[inline]
+    pub(crate) fn drain_loops(&mut self, loops: &mut Vec<StateLoop>) {
+        loops.append(&mut self.loops);
+    }
+
+    #[inline]
+    pub(crate) fn drain_markers(&mut self, markers: &mut Vec<AnimationMarker>) {
+        markers.append(&mut self.markers);
+    }
+
+    /// Sample the float tracks of the current state, and of the next one
+    /// weighted by the crossfade while transitioning
+    pub(crate) fn sample_curves(&mut self, weight: f32, out: &mut Vec<CurveSample>) {
+        let t = match &self.next_state {
+            Some(_) if self.transition_duration > 0.0 => {
+                (self.transition_time / self.transition_duration).clamp(0.0, 1.0)
+            }
+            _ => 0.0,
+        };
+        if let Some(state) = self.states.get_mut(&self.current_state) {
+            state.sample_curves(weight * (1.0 - t), out);
+        }
+        if let Some(next_state) = &self.next_state
+            && *next_state != self.current_state
+            && let Some(state) = self.states.get_mut(next_state)
+        {
+            state.sample_curves(weight * t, out);
+        }
+    }
+
+    #[inline]
+    pub(crate) fn drain_consumed_triggers(&mut self, triggers: &mut Vec<String>) {
+        triggers.append(&mut self.consumed_triggers);
+    }
+
+    #[inline]
+    pub(crate) fn drain_state_changes(&mut self, state_changes: &mut Vec<StateChange>) {
+        state_changes.append(&mut self.state_changes);
+    }
+
     pub fn has_output_changed(&self) -> bool {
         self.output_source_changed
     }
@@ -266,45 +1488,470 @@ impl AnimationLayer {
             // TODO: WE NEED TO BE IN "T POSE" or DEFAULT POSE IF NO STATE IS FOUND
             self.states
                 .get(&self.current_state)
-                .map(|s| match s {
-                    AnimationState::Simple(state) => state.get_output_pointer(),
-                    AnimationState::Blend(state) => state.get_output_pointer(),
-                })
+                .and_then(|s| s.get_output_pointer())
                 .unwrap_or(self.blend_job_output.clone())
         }
     }
 }
 
 /// The type of blending to use for a layer
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, Reflect)]
 pub enum LayerBlendType {
     Override,
     Additive,
 }
 
+/// How the final blend treats override layer weights that don't sum to 1
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Reflect)]
+pub enum OverrideNormalization {
+    /// Weights are scaled so they sum to 1, the rest pose only fills in while
+    /// the total is below the rest pose threshold
+    #[default]
+    Normalized,
+    /// Weights are used as they are, the rest pose makes up whatever the total
+    /// is missing from 1. Totals above 1 are still scaled down.
+    PassThrough,
+}
+
+/// How the weights of several additive layers accumulate in the final pose
+#[derive(Debug, Clone, Copy, PartialEq, Default, Reflect)]
+pub enum AdditiveAccumulation {
+    /// Every additive layer is applied with its own weight
+    #[default]
+    Sum,
+    /// Weights are scaled down together when their sum exceeds the given maximum
+    ClampedSum(f32),
+    /// Weights are scaled down so they sum to 1 when their sum exceeds it,
+    /// a single layer fading in keeps its own weight
+    Normalized,
+    /// Later layers take priority, each layer only gets the weight left over
+    /// by the layers above it, out of a total of 1
+    Priority,
+}
+
+impl AdditiveAccumulation {
+    /// Effective weight of every additive layer, in layer order
+    pub fn accumulate(&self, weights: &[f32]) -> Vec<f32> {
+        let total: f32 = weights.iter().sum();
+        match *self {
+            AdditiveAccumulation::Sum => weights.to_vec(),
+            AdditiveAccumulation::ClampedSum(max) if total > max && total > 0.0 => {
+                weights.iter().map(|w| w * max / total).collect()
+            }
+            AdditiveAccumulation::ClampedSum(_) => weights.to_vec(),
+            AdditiveAccumulation::Normalized if total > 1.0 => {
+                weights.iter().map(|w| w / total).collect()
+            }
+            AdditiveAccumulation::Normalized => weights.to_vec(),
+            AdditiveAccumulation::Priority => {
+                let mut remaining = 1.0_f32;
+                let mut effective = vec![0.0; weights.len()];
+                for (effective, weight) in effective.iter_mut().zip(weights).rev() {
+                    *effective = weight.min(remaining);
+                    remaining -= *effective;
+                }
+                effective
+            }
+        }
+    }
+}
+
 /// Represents a transition to a state
-#[derive(Debug)]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
 pub struct Transition {
     pub to_state: String,
     pub duration: f32,
+    /// Float parameter `duration` is multiplied by when the transition fires,
+    /// e.g. a blend scale lowered at high gameplay speeds. With a `duration` of
+    /// 1 the parameter holds the duration itself.
+    pub duration_parameter: Option<String>,
     pub conditions: Vec<TransitionCondition>,
+    /// Only fire once the source state has played up to `exit_time`, in
+    /// addition to the conditions
     pub has_exit_time: bool,
+    /// Normalized time of the source state, counted from when the layer entered
+    /// it. Below 1 it is reached again on every loop, 2.5 fires halfway through
+    /// the third loop. A clamped state that finished has reached all of them.
     pub exit_time: f32,
+    /// Hold the source state's pose from the start of the transition instead of
+    /// continuing to play it, useful when interrupting one-shots like attacks
+    pub freeze_source: bool,
+    /// Only crossfade these joints, weighted by the mask, while the others
+    /// switch to the destination instantly, e.g. the upper body blending into
+    /// an aim while the legs snap. None crossfades every joint. Serialized by
+    /// joint name, and bound to the layer's skeleton when the transition is
+    /// added.
+    pub mask: Option<JointMask>,
+    /// Whether the transition fires whenever its conditions hold, or only on the
+    /// frame they start holding
+    pub condition_mode: ConditionMode,
+}
+
+impl Transition {
+    /// Blend duration in seconds with the current parameters, a missing
+    /// duration parameter counts as 1
+    #[inline]
+    pub fn resolved_duration(&self, parameters: &Parameters) -> f32 {
+        let scale = self
+            .duration_parameter
+            .as_deref()
+            .and_then(|name| parameters.get_float(name))
+            .unwrap_or(1.0);
+        (self.duration * scale).max(0.0)
+    }
+}
+
+/// When a transition fires
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum ConditionMode {
+    /// Whenever the conditions are met
+    #[default]
+    Level,
+    /// Only on the frame the conditions become met, e.g. "jump was pressed this
+    /// frame" from a plain bool parameter. Conditions already met when the
+    /// source state is entered don't count as a change.
+    Edge,
 }
 
 /// Condition for state transitions
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum TransitionCondition {
     Bool(String, bool),
     Float(String, f32, CompareType),
     Int(String, i32, CompareType),
     Trigger(String),
+    /// The current state of the layer played to the end of a clamped clip, see
+    /// [`PlaybackMode::Clamp`](super::PlaybackMode::Clamp)
+    StateFinished,
+    /// Whether another layer of the controller is (true) or isn't (false) in
+    /// a state, as `(layer, state, in_state)`. A layer stays in its source
+    /// state until a transition completes. Fails if the layer doesn't exist.
+    LayerState(String, String, bool),
+    /// Whether the current state of another layer of the controller has (true)
+    /// or doesn't have (false) a tag, as `(layer, tag, has_tag)`, see
+    /// [`AnimationLayer::with_state_tag`]. Fails if the layer doesn't exist.
+    LayerTag(String, String, bool),
+    /// Every condition of a named predicate of the controller holds, see
+    /// [`AnimatorController::with_predicate`](super::AnimatorController::with_predicate).
+    /// Fails if the predicate doesn't exist.
+    Predicate(String),
+    /// The layer's random roll for its current state lies in `[min, max)`, as
+    /// `(min, max)`. The roll is drawn once each time the state is entered, so
+    /// transitions out of a state covering adjacent ranges pick one of them at
+    /// random, e.g. one of several idle variations.
+    Random(f32, f32),
 }
 
+impl TransitionCondition {
+    /// Parameter the condition reads
+    #[inline]
+    pub(crate) fn parameter(&self) -> Option<&str> {
+        match self {
+            TransitionCondition::Bool(name, _)
+            | TransitionCondition::Float(name, ..)
+            | TransitionCondition::Int(name, ..)
+            | TransitionCondition::Trigger(name) => Some(name),
+            TransitionCondition::StateFinished
+            | TransitionCondition::LayerState(..)
+            | TransitionCondition::LayerTag(..)
+            | TransitionCondition::Predicate(_)
+            | TransitionCondition::Random(..) => None,
+        }
+    }
+
+    /// Parameter the condition reads with its default value, triggers left out
+    #[inline]
+    pub(crate) fn referenced_parameter(&self) -> Option<(String, ParameterValue)> {
+        let (name, default) = match self {
+            TransitionCondition::Bool(name, _) => (name, ParameterValue::Bool(false)),
+            TransitionCondition::Float(name, ..) => (name, ParameterValue::Float(0.0)),
+            TransitionCondition::Int(name, ..) => (name, ParameterValue::Int(0)),
+            TransitionCondition::Trigger(_)
+            | TransitionCondition::StateFinished
+            | TransitionCondition::LayerState(..)
+            | TransitionCondition::LayerTag(..)
+            | TransitionCondition::Predicate(_)
+            | TransitionCondition::Random(..) => return None,
+        };
+        Some((name.clone(), default))
+    }
+}
+
+/// What transition conditions are evaluated against
+struct ConditionContext<'a> {
+    parameters: &'a Parameters,
+    layer_states: &'a LayerStates,
+    predicates: &'a Predicates,
+}
+
+/// Why a transition didn't fire
+#[derive(Debug, Clone, Copy)]
+enum Unmet {
+    /// The source state hasn't played up to the exit time yet
+    ExitTime,
+    /// Index of the first condition that doesn't hold
+    Condition(usize),
+}
+
+/// Current state of a layer and its tags
 #[derive(Debug, Clone)]
+struct CurrentState {
+    layer: String,
+    state: String,
+    tags: Vec<String>,
+}
+
+impl CurrentState {
+    #[inline]
+    fn new(layer: &AnimationLayer) -> Self {
+        Self {
+            layer: layer.name.clone(),
+            state: layer.current_state.clone(),
+            tags: layer.state_tags(&layer.current_state).to_vec(),
+        }
+    }
+}
+
+/// Current state of every layer of a controller, in layer order
+#[derive(Debug, Clone, Default)]
+pub(crate) struct LayerStates(Vec<CurrentState>);
+
+impl LayerStates {
+    #[inline]
+    fn find(&self, layer: &str) -> Option<&CurrentState> {
+        self.0.iter().find(|current| current.layer == layer)
+    }
+
+    #[inline]
+    fn get(&self, layer: &str) -> Option<&str> {
+        self.find(layer).map(|current| current.state.as_str())
+    }
+
+    #[inline]
+    fn tags(&self, layer: &str) -> Option<&[String]> {
+        self.find(layer).map(|current| current.tags.as_slice())
+    }
+
+    #[inline]
+    pub(crate) fn rebuild(&mut self, layers: &[AnimationLayer]) {
+        self.0.clear();
+        self.0.extend(layers.iter().map(CurrentState::new));
+    }
+
+    /// Update the current state of the layer at `index`
+    #[inline]
+    pub(crate) fn set(&mut self, index: usize, layer: &AnimationLayer) {
+        let Some(current) = self.0.get_mut(index) else {
+            return;
+        };
+        current.state.clone_from(&layer.current_state);
+        // Runs every update, only copy the tags once they change
+        let tags = layer.state_tags(&layer.current_state);
+        if current.tags != tags {
+            current.tags = tags.to_vec();
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum CompareType {
     Greater,
     Less,
     Equals,
     NotEqual,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        AnimatorController, PlaybackMode, Transition, synthetic_clip, synthetic_skeleton,
+        test_layer, test_rig,
+    };
+    use bevy::prelude::default;
+    use std::time::Duration;
+
+    /// A controller playing a clamped one second clip, leaving it for `Idle`
+    /// at `exit_time` once `done` is set
+    fn clamped_controller(exit_time: f32) -> AnimatorController {
+        let skeleton = Arc::new(synthetic_skeleton(4, 1).unwrap());
+        let clip = Arc::new(synthetic_clip(&skeleton, 1.0, 30.0, 0));
+        let state = |clip: &Arc<_>| {
+            SimpleState::from_recording(Arc::clone(clip), skeleton.num_soa_joints())
+                .with_playback_mode(PlaybackMode::Clamp)
+        };
+        let mut layer = AnimationLayer::new(
+            "Base".to_string(),
+            LayerBlendType::Override,
+            1.0,
+            &skeleton,
+            "Attack".to_string(),
+        );
+        layer.add_state("Attack".to_string(), state(&clip).into());
+        layer.add_state("Idle".to_string(), state(&clip).into());
+        layer.add_transition(
+            "Attack".to_string(),
+            Transition {
+                to_state: "Idle".to_string(),
+                duration: 0.25,
+                has_exit_time: true,
+                exit_time,
+                conditions: vec![TransitionCondition::Bool("done".to_string(), true)],
+                ..default()
+            },
+        );
+        let mut parameters = Parameters::new();
+        parameters.set_bool("done", false);
+        AnimatorController::new(skeleton.clone(), vec![layer], parameters).unwrap()
+    }
+
+    fn advance(controller: &mut AnimatorController, time: &mut Time, seconds: f32) {
+        time.advance_by(Duration::from_secs_f32(seconds));
+        controller.update(time).unwrap();
+    }
+
+    #[test]
+    fn finished_clamped_state_reaches_every_exit_time() {
+        for exit_time in [0.9, 1.0, 2.5] {
+            let mut controller = clamped_controller(exit_time);
+            let mut time = Time::default();
+            advance(&mut controller, &mut time, 1.5);
+            let layer = &controller.layers[0];
+            assert!(layer.states["Attack"].is_finished());
+            assert!(!layer.is_transitioning);
+
+            controller.parameters_mut().set_bool("done", true);
+            advance(&mut controller, &mut time, 1.0 / 30.0);
+            let layer = &controller.layers[0];
+            assert!(layer.is_transitioning, "exit time {exit_time}");
+            assert_eq!(layer.next_state.as_deref(), Some("Idle"));
+        }
+    }
+
+    #[test]
+    fn layer_tags_gate_transitions_of_other_layers() {
+        let base = clamped_controller(1.0);
+        let skeleton = base.skeleton.clone();
+        let mut layers = base.layers;
+        layers[0].add_state_tag("Attack", "busy");
+        let clip = Arc::new(synthetic_clip(&skeleton, 1.0, 30.0, 1));
+        let mut upper = AnimationLayer::new(
+            "Upper".to_string(),
+            LayerBlendType::Override,
+            1.0,
+            &skeleton,
+            "Lowered".to_string(),
+        );
+        for state in ["Lowered", "Raised"] {
+            let clip = SimpleState::from_recording(clip.clone(), skeleton.num_soa_joints());
+            upper.add_state(state.to_string(), clip.into());
+        }
+        upper.add_transition(
+            "Lowered".to_string(),
+            Transition {
+                to_state: "Raised".to_string(),
+                conditions: vec![TransitionCondition::LayerTag(
+                    "Base".to_string(),
+                    "busy".to_string(),
+                    false,
+                )],
+                ..default()
+            },
+        );
+        layers.push(upper);
+        let mut parameters = Parameters::new();
+        parameters.set_bool("done", false);
+        let mut controller = AnimatorController::new(skeleton, layers, parameters).unwrap();
+        let mut time = Time::default();
+
+        advance(&mut controller, &mut time, 1.5);
+        assert_eq!(controller.layers[1].current_state(), "Lowered");
+        controller.parameters_mut().set_bool("done", true);
+        advance(&mut controller, &mut time, 0.1);
+        // Still in the tagged state until the crossfade completes
+        assert_eq!(controller.layers[1].current_state(), "Lowered");
+        advance(&mut controller, &mut time, 0.3);
+        advance(&mut controller, &mut time, 0.1);
+        assert_eq!(controller.layers[0].current_state(), "Idle");
+        assert_eq!(controller.layers[1].current_state(), "Raised");
+    }
+
+    #[test]
+    fn stacked_clips_return_to_the_graph() {
+        let mut controller = clamped_controller(1.0);
+        let skeleton = controller.skeleton.clone();
+        let clip = Arc::new(synthetic_clip(&skeleton, 0.5, 30.0, 1));
+        let one_shot = || {
+            SimpleState::from_recording(clip.clone(), skeleton.num_soa_joints())
+                .with_playback_mode(PlaybackMode::Clamp)
+        };
+        let mut time = Time::default();
+        let layer = &mut controller.layers[0];
+        let wave = layer.cross_fade_to_clip("Wave", one_shot(), 0.1).unwrap();
+        advance(&mut controller, &mut time, 0.2);
+        // Interrupt the first clip with a second one
+        let layer = &mut controller.layers[0];
+        let nod = layer.cross_fade_to_clip("Nod", one_shot(), 0.1).unwrap();
+        for _ in 0..20 {
+            advance(&mut controller, &mut time, 0.1);
+        }
+        let layer = &controller.layers[0];
+        assert_eq!(layer.current_state(), "Attack");
+        assert!(!layer.has_state(&wave) && !layer.has_state(&nod));
+    }
+
+    #[test]
+    fn exit_time_waits_for_playing_state() {
+        let mut controller = clamped_controller(0.9);
+        controller.parameters_mut().set_bool("done", true);
+        let mut time = Time::default();
+        advance(&mut controller, &mut time, 0.5);
+        assert!(!controller.layers[0].is_transitioning);
+        // Transitions are checked before the states advance, so this one
+        // fires on the update after playback passed its exit time
+        advance(&mut controller, &mut time, 0.45);
+        advance(&mut controller, &mut time, 1.0 / 30.0);
+        assert!(controller.layers[0].is_transitioning);
+    }
+
+    #[test]
+    fn normalized_additive_weights_only_scale_down() {
+        let normalized = AdditiveAccumulation::Normalized;
+        assert_eq!(normalized.accumulate(&[0.3]), vec![0.3]);
+        assert_eq!(normalized.accumulate(&[0.25, 0.5]), vec![0.25, 0.5]);
+        assert_eq!(normalized.accumulate(&[1.0, 1.0]), vec![0.5, 0.5]);
+    }
+
+    #[test]
+    fn edge_transitions_fire_when_their_conditions_start_holding() {
+        let (skeleton, clip) = test_rig();
+        let mut layer = test_layer(&skeleton, &clip, &["Idle", "Walk"]);
+        layer.add_transition(
+            "Idle".to_string(),
+            Transition {
+                to_state: "Walk".to_string(),
+                conditions: vec![TransitionCondition::Bool("go".to_string(), true)],
+                condition_mode: ConditionMode::Edge,
+                duration: 0.0,
+                ..default()
+            },
+        );
+        let mut parameters = Parameters::new();
+        parameters.set_bool("go", true);
+        let mut controller =
+            AnimatorController::new(skeleton.clone(), vec![layer], parameters).unwrap();
+        let mut time = Time::default();
+
+        // Already holding when the state is entered, then still holding
+        for _ in 0..2 {
+            advance(&mut controller, &mut time, 0.1);
+            assert_eq!(controller.layers[0].current_state(), "Idle");
+        }
+        controller.parameters_mut().set_bool("go", false);
+        advance(&mut controller, &mut time, 0.1);
+        controller.parameters_mut().set_bool("go", true);
+        advance(&mut controller, &mut time, 0.1);
+        assert_eq!(controller.layers[0].current_state(), "Walk");
+    }
+}
@@ -1,29 +1,227 @@
+mod additive_shot;
 mod asset_loader;
+mod async_animation;
+mod attachment;
+mod audio;
 mod base;
 mod blend_tree;
+mod blend_tree_player;
+mod budget;
+mod builder;
+#[cfg(feature = "render")]
+mod clip_baker;
+mod clip_player;
 mod controller;
+mod coverage;
+mod crowd;
+mod curve;
+mod curve_output;
+#[cfg(feature = "render")]
+mod debug;
+mod definition;
+mod dependencies;
+mod diff;
+mod error;
+mod events;
+mod external;
+mod graph_export;
+mod hot_reload;
+mod ik;
 mod layer;
+mod mask;
+mod missing_parameters;
+mod overrides;
 mod parameters;
+mod pose;
+mod pose_copy;
+mod pose_modifier;
+mod predicates;
+mod ragdoll;
+mod random;
+mod recording;
+mod retarget;
+mod skeleton;
+mod snapshot;
 mod state;
+mod stats;
+mod sub_controller;
+#[cfg(any(test, feature = "bench"))]
+mod synthetic;
+mod tags;
+mod time_source;
+mod trace;
+mod triggers;
+mod usage;
+mod validation;
 
 pub mod prelude;
 pub use prelude::*;
 
 use bevy::{app::Animation, prelude::*};
+use std::time::Duration;
 
-pub struct OzzAnimationPlugin;
+/// Adds Ozz asset loading and drives every `AnimatorController`
+/// ## Example
+/// ```ignore
+/// App::new().add_plugins((
+///     DefaultPlugins,
+///     OzzAnimationPlugin {
+///         update_mode: AnimatorUpdateMode::Fixed,
+///         ..default()
+///     },
+/// ));
+/// ```
+#[derive(Debug, Clone)]
+pub struct OzzAnimationPlugin {
+    /// Which schedule drives the controllers
+    pub update_mode: AnimatorUpdateMode,
+    /// Longest time a single controller update may advance, so a long hitch
+    /// doesn't skip ahead. None never clamps.
+    pub max_delta: Option<Duration>,
+    /// Split updates longer than this into several steps, so exit times,
+    /// transitions and events within a long frame happen in order. Applied
+    /// after `max_delta`. None runs every update as a single step.
+    pub sub_step: Option<Duration>,
+    /// Step every `AnimationClipBaker`, needs the `render` feature and Bevy's
+    /// `AnimationPlugin` for the clip and graph assets
+    pub bake_clips: bool,
+}
+
+impl Default for OzzAnimationPlugin {
+    #[inline]
+    fn default() -> Self {
+        Self {
+            update_mode: AnimatorUpdateMode::default(),
+            max_delta: Some(Duration::from_millis(100)),
+            sub_step: None,
+            bake_clips: false,
+        }
+    }
+}
 
 impl Plugin for OzzAnimationPlugin {
     fn build(&self, app: &mut App) {
         app.add_plugins(OzzAssetPlugin::new(&["ozz"]))
-            .add_systems(Update, add_bone_indexes)
-            .add_systems(
+            .add_event::<AnimatorErrorEvent>()
+            .add_event::<StateLoopEvent>()
+            .add_event::<AnimationMarkerEvent>()
+            .add_event::<AnimatorStateEntered>()
+            .add_event::<AnimatorStateExited>()
+            .add_event::<AnimatorStateFinished>()
+            .add_event::<AnimatorTransitionStarted>()
+            .add_event::<AnimatorTransitionCompleted>()
+            .init_resource::<AnimationPriorities>()
+            .init_resource::<AnimationTime>()
+            .init_resource::<AnimationAudioHooks>()
+            .init_resource::<AnimatorTags>()
+            .register_type::<AnimatorController>()
+            .register_type::<AnimationLayer>()
+            .register_type::<Parameters>()
+            .register_type::<OzzTransform>()
+            .init_asset::<CurveAsset>()
+            .init_asset_loader::<CurveAssetLoader>()
+            .init_asset::<AnimatorControllerAsset>()
+            .init_asset_loader::<AnimatorControllerAssetLoader>()
+            .init_asset::<BoneMap>()
+            .init_asset_loader::<BoneMapLoader>()
+            .insert_resource(self.update_mode)
+            .insert_resource(AnimatorMaxDelta(self.max_delta))
+            .insert_resource(AnimatorSubStep(self.sub_step));
+
+        app.add_systems(Update, sync_controller_assets)
+            .add_systems(PreUpdate, finish_async_animations)
+            .add_systems(Last, start_async_animations);
+        // Attachments follow the freshly propagated controller transforms
+        app.add_systems(
+            PostUpdate,
+            update_bone_attachments.after(TransformSystem::TransformPropagate),
+        );
+
+        // Curves write parameters and IK targets follow their entities before
+        // the controllers read them, audio hooks hear the markers of the new
+        // pose, tag components follow the new states and curve outputs the
+        // new curve values
+        let inputs = (drive_curves, update_ik_targets).before(animate_bones);
+        let outputs = (dispatch_animation_audio, sync_animator_tags, sync_curve_outputs)
+            .after(animate_bones);
+        match self.update_mode {
+            AnimatorUpdateMode::Frame => app.add_systems(PostUpdate, (inputs, outputs)),
+            AnimatorUpdateMode::Fixed => app.add_systems(FixedUpdate, (inputs, outputs)),
+        };
+
+        if self.update_mode == AnimatorUpdateMode::Fixed {
+            app.add_systems(
+                FixedUpdate,
+                (animate_bones, record_crowd_sources.after(animate_bones)),
+            );
+        }
+
+        #[cfg(feature = "render")]
+        {
+            if self.bake_clips {
+                app.add_systems(Update, bake_animation_clips);
+            }
+            app.add_systems(Update, (add_bone_indexes, update_pose_copies))
+                .add_systems(
+                    PostUpdate,
+                    // Apps without Bevy's GizmoPlugin have nothing to draw with
+                    draw_debug_skeletons
+                        .run_if(
+                            resource_exists::<GizmoConfigStore>
+                                .and(any_with_component::<DebugSkeleton>),
+                        )
+                        .after(TransformSystem::TransformPropagate),
+                );
+            match self.update_mode {
+                AnimatorUpdateMode::Frame => app.add_systems(
+                    PostUpdate,
+                    ((
+                        animate_bones,
+                        record_crowd_sources.after(animate_bones),
+                        update_blend_tree_players,
+                        update_clip_players,
+                        update_bone_transforms
+                            .after(record_crowd_sources)
+                            .after(update_blend_tree_players)
+                            .after(update_clip_players),
+                    )
+                        .before(bevy::render::mesh::inherit_weights)
+                        .ambiguous_with_all())
+                    .in_set(Animation)
+                    .before(TransformSystem::TransformPropagate),
+                ),
+                // Controllers already updated in FixedUpdate, only write out the
+                // interpolated pose
+                AnimatorUpdateMode::Fixed => app.add_systems(
+                    PostUpdate,
+                    (
+                        (update_blend_tree_players, update_clip_players),
+                        update_bone_transforms,
+                    )
+                        .chain()
+                        .before(bevy::render::mesh::inherit_weights)
+                        .ambiguous_with_all()
+                        .in_set(Animation)
+                        .before(TransformSystem::TransformPropagate),
+                ),
+            };
+        }
+
+        // Without rendering only the state machines are evaluated
+        #[cfg(not(feature = "render"))]
+        if self.update_mode == AnimatorUpdateMode::Frame {
+            app.add_systems(
                 PostUpdate,
-                ((animate_bones, update_bone_transforms)
-                    .before(bevy::render::mesh::inherit_weights)
-                    .ambiguous_with_all())
-                .in_set(Animation)
-                .before(TransformSystem::TransformPropagate),
+                (
+                    animate_bones,
+                    record_crowd_sources.after(animate_bones),
+                    update_blend_tree_players,
+                    update_clip_players,
+                )
+                    .ambiguous_with_all()
+                    .in_set(Animation)
+                    .before(TransformSystem::TransformPropagate),
             );
+        }
     }
 }
@@ -0,0 +1,207 @@
+use super::skeleton::{find_joint, joint_info, joint_subtree};
+use super::{AnimatorController, AnimatorError};
+use ozz_animation_rs::Skeleton;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+/// Weight of every joint of a skeleton on a layer, from 0 (untouched by the
+/// layer) to 1, e.g. to only play a layer on the upper body
+/// ## Example
+/// ```ignore
+/// let upper_body = JointMask::subtree(&skeleton, "Spine")?
+///     .with_subtree(&skeleton, "Neck", 0.5)?;
+/// let layer = AnimationLayer::new(
+///     "UpperBody".to_string(),
+///     LayerBlendType::Override,
+///     1.0,
+///     &skeleton,
+///     "aim".to_string(),
+/// )
+/// .with_joint_mask(upper_body);
+/// ```
+///
+/// Masks serialize as a map from joint name to weight, leaving out joints
+/// weighing 0, so they can be written in controller assets. A deserialized
+/// mask is matched to the skeleton by name with [`JointMask::bind`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct JointMask {
+    weights: Vec<f32>,
+    /// Joint of every weight, in skeleton order once bound
+    names: Arc<[String]>,
+}
+
+impl JointMask {
+    #[inline]
+    fn filled(skeleton: &Skeleton, weight: f32) -> Self {
+        Self {
+            weights: vec![weight; skeleton.num_joints()],
+            names: (0..skeleton.num_joints())
+                .map(|index| {
+                    joint_info(skeleton, index).map_or_else(String::new, |joint| joint.name.into())
+                })
+                .collect(),
+        }
+    }
+
+    /// A mask where no joint is affected
+    #[inline]
+    pub fn empty(skeleton: &Skeleton) -> Self {
+        Self::filled(skeleton, 0.0)
+    }
+
+    /// A mask where every joint is fully affected
+    #[inline]
+    pub fn full(skeleton: &Skeleton) -> Self {
+        Self::filled(skeleton, 1.0)
+    }
+
+    /// A mask affecting only a joint and everything below it
+    #[inline]
+    pub fn subtree(skeleton: &Skeleton, joint: &str) -> Result<Self, AnimatorError> {
+        Self::empty(skeleton).with_subtree(skeleton, joint, 1.0)
+    }
+
+    /// Set the weight of a joint and everything below it
+    pub fn with_subtree(
+        mut self,
+        skeleton: &Skeleton,
+        joint: &str,
+        weight: f32,
+    ) -> Result<Self, AnimatorError> {
+        let Some(root) = find_joint(skeleton, joint, false) else {
+            return Err(AnimatorError::UnknownJoint(joint.to_string()));
+        };
+        for index in joint_subtree(skeleton, root) {
+            self.set_weight(index, weight);
+        }
+        Ok(self)
+    }
+
+    /// Set the weight of a single joint, does nothing for unknown joints
+    #[inline]
+    pub fn set_weight(&mut self, joint: usize, weight: f32) {
+        if let Some(joint_weight) = self.weights.get_mut(joint) {
+            *joint_weight = weight.clamp(0.0, 1.0);
+        }
+    }
+
+    #[inline]
+    pub fn weight(&self, joint: usize) -> f32 {
+        self.weights.get(joint).copied().unwrap_or(0.0)
+    }
+
+    /// Weight of every joint, in skeleton order
+    #[inline]
+    pub fn weights(&self) -> &[f32] {
+        &self.weights
+    }
+
+    /// If the weights are in the joint order of `skeleton`
+    #[inline]
+    pub fn is_bound(&self, skeleton: &Skeleton) -> bool {
+        self.names.len() == skeleton.num_joints()
+            && self
+                .names
+                .iter()
+                .enumerate()
+                .all(|(index, name)| find_joint(skeleton, name, false) == Some(index))
+    }
+
+    /// Match the weights to the joints of `skeleton` by name, e.g. after
+    /// deserializing the mask. Joints the mask doesn't name weigh 0, and
+    /// masks built from the same skeleton are left as they are.
+    pub fn bind(&mut self, skeleton: &Skeleton) -> Result<(), AnimatorError> {
+        if self.is_bound(skeleton) {
+            return Ok(());
+        }
+        let mut bound = Self::empty(skeleton);
+        for (name, weight) in self.names.iter().zip(&self.weights) {
+            let Some(index) = find_joint(skeleton, name, false) else {
+                return Err(AnimatorError::UnknownJoint(name.clone()));
+            };
+            bound.set_weight(index, *weight);
+        }
+        *self = bound;
+        Ok(())
+    }
+}
+
+impl Serialize for JointMask {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_map(
+            self.names
+                .iter()
+                .zip(&self.weights)
+                .filter(|(_, weight)| **weight > 0.0),
+        )
+    }
+}
+
+impl<'de> Deserialize<'de> for JointMask {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let joints = BTreeMap::<String, f32>::deserialize(deserializer)?;
+        let (names, weights): (Vec<_>, Vec<_>) = joints
+            .into_iter()
+            .map(|(name, weight)| (name, weight.clamp(0.0, 1.0)))
+            .unzip();
+        Ok(Self {
+            weights,
+            names: names.into(),
+        })
+    }
+}
+
+impl AnimatorController {
+    /// Change which joints a layer affects, None affects every joint
+    #[inline]
+    pub fn set_joint_mask(
+        &mut self,
+        layer: &str,
+        mask: Option<JointMask>,
+    ) -> Result<(), AnimatorError> {
+        let Some(layer) = self.layers.iter_mut().find(|l| l.name == layer) else {
+            return Err(AnimatorError::UnknownLayer(layer.to_string()));
+        };
+        layer.set_joint_mask(mask);
+        self.apply_joint_weights();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Transition, synthetic_skeleton};
+
+    #[test]
+    fn masks_serialize_by_joint_name() {
+        let skeleton = synthetic_skeleton(6, 2).unwrap();
+        let mask = JointMask::subtree(&skeleton, "Joint1")
+            .unwrap()
+            .with_subtree(&skeleton, "Joint2", 0.5)
+            .unwrap();
+        let transition = Transition {
+            to_state: "Aim".to_string(),
+            mask: Some(mask.clone()),
+            ..Default::default()
+        };
+
+        let text = ron::to_string(&transition).unwrap();
+        assert!(text.contains("\"Joint3\":1.0"), "{text}");
+        assert!(!text.contains("Joint0"), "{text}");
+        let read: Transition = ron::from_str(&text).unwrap();
+        let mut read_mask = read.mask.unwrap();
+        assert!(!read_mask.is_bound(&skeleton));
+        read_mask.bind(&skeleton).unwrap();
+        assert_eq!(read_mask, mask);
+    }
+
+    #[test]
+    fn binding_fails_on_unknown_joints() {
+        let skeleton = synthetic_skeleton(3, 1).unwrap();
+        let mut mask: JointMask = ron::from_str("{\"Spine\": 1.0}").unwrap();
+        let error = mask.bind(&skeleton).unwrap_err();
+        assert!(matches!(error, AnimatorError::UnknownJoint(joint) if joint == "Spine"));
+    }
+}
@@ -0,0 +1,140 @@
+use super::{AnimatorController, AnimatorError, ParameterValue, Parameters};
+use bevy::log::warn;
+use std::collections::HashSet;
+
+/// What a controller does about parameters its graph reads but that were never
+/// set, checked on the first update and whenever a layer is added
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MissingParameterPolicy {
+    /// Conditions on the parameter never hold, blend trees hold their weights
+    /// and speed or duration scales count as 1
+    Ignore,
+    /// Log a warning once per parameter, otherwise like `Ignore`
+    #[default]
+    Warn,
+    /// Add the parameter with its default value: false, 0, or 1 for speed and
+    /// duration scales
+    UseDefault,
+    /// Send an `AnimatorErrorEvent` once per parameter, otherwise like `Ignore`
+    Error,
+}
+
+/// A parameter read by the graph of a controller that was never set
+#[derive(Debug, Clone, PartialEq)]
+pub struct MissingParameter {
+    pub name: String,
+    /// Value the parameter gets with `MissingParameterPolicy::UseDefault`, also
+    /// telling its type
+    pub default: ParameterValue,
+}
+
+/// Per-controller state of the missing parameter check
+#[derive(Debug, Default)]
+pub(crate) struct ParameterCheck {
+    pub(crate) policy: MissingParameterPolicy,
+    /// If the graph was checked since it last changed
+    pub(crate) checked: bool,
+    /// Parameters already warned about or reported
+    pub(crate) reported: HashSet<String>,
+    /// Errors waiting to be sent as events
+    pub(crate) errors: Vec<AnimatorError>,
+}
+
+/// If the parameters hold a value of the same type under this name
+#[inline]
+pub(crate) fn is_set(parameters: &Parameters, name: &str, value: ParameterValue) -> bool {
+    match value {
+        ParameterValue::Bool(_) => parameters.get_bool(name).is_some(),
+        ParameterValue::Float(_) => parameters.get_float(name).is_some(),
+        ParameterValue::Int(_) => parameters.get_int(name).is_some(),
+        ParameterValue::Trigger => true,
+    }
+}
+
+impl AnimatorController {
+    /// Choose what happens when the graph reads a parameter that was never set,
+    /// see [`MissingParameterPolicy`]
+    /// ## Example
+    /// ```ignore
+    /// let controller = AnimatorController::new(skeleton, layers, parameters)?
+    ///     .with_missing_parameter_policy(MissingParameterPolicy::Error);
+    /// ```
+    #[inline]
+    pub fn with_missing_parameter_policy(mut self, policy: MissingParameterPolicy) -> Self {
+        self.set_missing_parameter_policy(policy);
+        self
+    }
+
+    #[inline]
+    pub fn set_missing_parameter_policy(&mut self, policy: MissingParameterPolicy) {
+        self.parameter_check.policy = policy;
+        self.parameter_check.checked = false;
+    }
+
+    #[inline]
+    pub fn missing_parameter_policy(&self) -> MissingParameterPolicy {
+        self.parameter_check.policy
+    }
+
+    /// Every parameter read by a transition condition, predicate, blend tree,
+    /// speed or duration scale that isn't set. Triggers are left out, they usually only
+    /// exist once first fired.
+    /// ## Example
+    /// ```ignore
+    /// // In a test over every character
+    /// assert_eq!(controller.missing_parameters(), vec![]);
+    /// ```
+    pub fn missing_parameters(&self) -> Vec<MissingParameter> {
+        let mut referenced = Vec::new();
+        for layer in &self.layers {
+            layer.referenced_parameters(&mut referenced);
+        }
+        self.predicate_parameters(&mut referenced);
+
+        let mut missing: Vec<MissingParameter> = Vec::new();
+        for (name, default) in referenced {
+            // Loop counts are written by the controller itself
+            if self.layers.iter().any(|l| l.loop_count_parameter() == name)
+                || is_set(self.parameters(), &name, default)
+                || missing.iter().any(|m| m.name == name)
+            {
+                continue;
+            }
+            missing.push(MissingParameter { name, default });
+        }
+        missing
+    }
+
+    /// Apply the missing parameter policy to parameters not reported yet
+    pub(crate) fn check_parameters(&mut self) {
+        self.parameter_check.checked = true;
+        let policy = self.parameter_check.policy;
+        if policy == MissingParameterPolicy::Ignore {
+            return;
+        }
+        for missing in self.missing_parameters() {
+            if policy == MissingParameterPolicy::UseDefault {
+                self.parameters_mut().set(&missing.name, missing.default);
+                continue;
+            }
+            if !self.parameter_check.reported.insert(missing.name.clone()) {
+                continue;
+            }
+            if policy == MissingParameterPolicy::Warn {
+                warn!(
+                    "Parameter '{}' is read by the animator graph but was never set",
+                    missing.name
+                );
+            } else {
+                let error = AnimatorError::MissingParameter(missing.name);
+                self.parameter_check.errors.push(error);
+            }
+        }
+    }
+
+    /// Missing parameter errors queued since the last drain
+    #[inline]
+    pub(crate) fn drain_parameter_errors(&mut self) -> std::vec::Drain<'_, AnimatorError> {
+        self.parameter_check.errors.drain(..)
+    }
+}
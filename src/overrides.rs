@@ -0,0 +1,114 @@
+use super::{AnimatorController, AnimatorError, OzzTransform};
+use bevy::prelude::*;
+
+/// Space the transform of a [`BoneOverride`] is given in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BoneOverrideSpace {
+    /// Relative to the parent joint
+    #[default]
+    Local,
+    /// Relative to the skeleton root, e.g. a weapon grip position
+    Model,
+}
+
+/// Replaces a bone's transform after blending, for precise alignment like
+/// hands on a weapon grip or feet on ladder rungs
+/// ## Example
+/// ```ignore
+/// controller.add_bone_override(BoneOverride {
+///     joint: "LeftHand".to_string(),
+///     transform: grip_transform,
+///     space: BoneOverrideSpace::Model,
+///     weight: 1.0,
+///     duration: None,
+/// })?;
+/// ```
+#[derive(Debug, Clone)]
+pub struct BoneOverride {
+    /// Name of the joint to override
+    pub joint: String,
+    pub transform: Transform,
+    pub space: BoneOverrideSpace,
+    /// How much of the override to apply, from 0 (animated pose) to 1
+    pub weight: f32,
+    /// Seconds until the override is removed, None keeps it until cleared
+    pub duration: Option<f32>,
+}
+
+/// A bone override resolved to a joint index
+#[derive(Debug, Clone)]
+pub(crate) struct ActiveBoneOverride {
+    joint: usize,
+    bone_override: BoneOverride,
+}
+
+impl AnimatorController {
+    /// Override a bone after blending, applied in the order they were added
+    #[inline]
+    pub fn add_bone_override(&mut self, bone_override: BoneOverride) -> Result<(), AnimatorError> {
+        let Some(joint) = self.find_joint(&bone_override.joint) else {
+            return Err(AnimatorError::UnknownJoint(bone_override.joint));
+        };
+        self.bone_overrides.push(ActiveBoneOverride {
+            joint,
+            bone_override,
+        });
+        Ok(())
+    }
+
+    /// Remove every override of a joint
+    #[inline]
+    pub fn remove_bone_overrides(&mut self, joint: &str) {
+        self.bone_overrides
+            .retain(|active| active.bone_override.joint != joint);
+    }
+
+    #[inline]
+    pub fn clear_bone_overrides(&mut self) {
+        self.bone_overrides.clear();
+    }
+
+    /// Apply the bone overrides to the blended pose and expire finished ones
+    pub(crate) fn apply_bone_overrides(&mut self, delta_secs: f32) {
+        if self.bone_overrides.is_empty() {
+            return;
+        }
+
+        let mut overrides = std::mem::take(&mut self.bone_overrides);
+        for active in &overrides {
+            let Some(bone) = self.bone_trans.get(active.joint).copied() else {
+                continue;
+            };
+            let bone_override = &active.bone_override;
+            let target = match bone_override.space {
+                BoneOverrideSpace::Local => bone_override.transform,
+                BoneOverrideSpace::Model => {
+                    let parent = self
+                        .joint(active.joint)
+                        .and_then(|joint| joint.parent)
+                        .and_then(|parent| self.model_space_bone(parent))
+                        .unwrap_or(Mat4::IDENTITY);
+                    let local = parent.inverse() * bone_override.transform.compute_matrix();
+                    Transform::from_matrix(local)
+                }
+            };
+
+            let weight = bone_override.weight.clamp(0.0, 1.0);
+            self.bone_trans[active.joint] = OzzTransform {
+                scale: bone.scale.lerp(target.scale, weight),
+                rotation: bone.rotation.slerp(target.rotation, weight),
+                position: bone.position.lerp(target.translation, weight),
+            };
+        }
+
+        // Expire timed overrides
+        overrides.retain_mut(|active| match &mut active.bone_override.duration {
+            Some(remaining) => {
+                *remaining -= delta_secs;
+                *remaining > 0.0
+            }
+            None => true,
+        });
+        self.bone_overrides = overrides;
+    }
+}
@@ -1,62 +1,397 @@
+use bevy::prelude::{Reflect, ReflectDefault, ReflectDeserialize, ReflectSerialize};
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
 
-/// Parameter storage for the animator
-#[derive(Debug, Default)]
+/// A single parameter value, used to set many parameters in one call
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum ParameterValue {
+    Bool(bool),
+    Float(f32),
+    Int(i32),
+    Trigger,
+}
+
+impl From<bool> for ParameterValue {
+    fn from(value: bool) -> Self {
+        ParameterValue::Bool(value)
+    }
+}
+
+impl From<f32> for ParameterValue {
+    fn from(value: f32) -> Self {
+        ParameterValue::Float(value)
+    }
+}
+
+impl From<i32> for ParameterValue {
+    fn from(value: i32) -> Self {
+        ParameterValue::Int(value)
+    }
+}
+
+/// Handle to a parameter, obtained once with [`Parameters::id_of`] so hot
+/// paths skip hashing the name on every read. Ids stay valid for the
+/// `Parameters` that handed them out and its clones.
+/// ## Example
+/// ```ignore
+/// let speed = controller.parameters_mut().id_of("speed");
+///
+/// // Every frame
+/// controller.parameters_mut().set_float_by_id(speed, velocity.length());
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Reflect)]
+pub struct ParamId(u32);
+
+impl ParamId {
+    #[inline]
+    fn index(self) -> usize {
+        self.0 as usize
+    }
+}
+
+/// Values stored under one parameter name, one per type
+#[derive(Debug, Clone, Copy, Default, Reflect)]
+struct ParamSlot {
+    bool: Option<bool>,
+    float: Option<f32>,
+    int: Option<i32>,
+    trigger: bool,
+}
+
+/// Source of `Parameters::layout` tokens, 0 is never handed out
+static NEXT_LAYOUT: AtomicU64 = AtomicU64::new(1);
+
+#[inline]
+fn next_layout() -> u64 {
+    NEXT_LAYOUT.fetch_add(1, Ordering::Relaxed)
+}
+
+/// Parameter storage for the animator, reflected so inspectors can show and
+/// edit the values. Scenes store it in its serialized form.
+#[derive(Debug, Clone, Serialize, Deserialize, Reflect)]
+#[serde(from = "ParameterMaps", into = "ParameterMaps")]
+#[reflect(Debug, Default, Serialize, Deserialize)]
 pub struct Parameters {
+    /// Name of every parameter, indexed by id
+    names: Vec<String>,
+    ids: HashMap<String, ParamId>,
+    /// Values of every parameter, indexed by id
+    slots: Vec<ParamSlot>,
+    /// Changes whenever a name is added, see `layout`. Not reflected, copies
+    /// built from reflection get a layout of their own.
+    #[reflect(ignore, default = "next_layout")]
+    layout: u64,
+}
+
+/// Serialized form of `Parameters`, one map per type
+#[derive(Default, Serialize, Deserialize)]
+#[serde(default)]
+struct ParameterMaps {
     bools: HashMap<String, bool>,
     floats: HashMap<String, f32>,
     ints: HashMap<String, i32>,
     triggers: HashMap<String, bool>,
 }
 
+impl Default for Parameters {
+    #[inline]
+    fn default() -> Self {
+        Self {
+            names: Vec::new(),
+            ids: HashMap::new(),
+            slots: Vec::new(),
+            layout: next_layout(),
+        }
+    }
+}
+
+impl From<ParameterMaps> for Parameters {
+    fn from(maps: ParameterMaps) -> Self {
+        let mut parameters = Parameters::default();
+        for (name, value) in maps.bools {
+            parameters.set_bool(&name, value);
+        }
+        for (name, value) in maps.floats {
+            parameters.set_float(&name, value);
+        }
+        for (name, value) in maps.ints {
+            parameters.set_int(&name, value);
+        }
+        for (name, set) in maps.triggers {
+            if set {
+                parameters.set_trigger(&name);
+            }
+        }
+        parameters
+    }
+}
+
+impl From<Parameters> for ParameterMaps {
+    fn from(parameters: Parameters) -> Self {
+        let mut maps = ParameterMaps::default();
+        for (name, value) in parameters.iter() {
+            let name = name.to_string();
+            match value {
+                ParameterValue::Bool(value) => {
+                    maps.bools.insert(name, value);
+                }
+                ParameterValue::Float(value) => {
+                    maps.floats.insert(name, value);
+                }
+                ParameterValue::Int(value) => {
+                    maps.ints.insert(name, value);
+                }
+                ParameterValue::Trigger => {
+                    maps.triggers.insert(name, true);
+                }
+            }
+        }
+        maps
+    }
+}
+
 impl Parameters {
     #[inline]
     pub fn new() -> Self {
         Self::default()
     }
 
+    /// Id of a parameter, adding the name without a value if it is new
+    #[inline]
+    pub fn id_of(&mut self, name: &str) -> ParamId {
+        if let Some(&id) = self.ids.get(name) {
+            return id;
+        }
+        let id = ParamId(self.names.len() as u32);
+        self.names.push(name.to_string());
+        self.ids.insert(name.to_string(), id);
+        self.slots.push(ParamSlot::default());
+        self.layout = next_layout();
+        id
+    }
+
+    /// Id of a parameter that was already set or looked up with `id_of`
+    #[inline]
+    pub fn find_id(&self, name: &str) -> Option<ParamId> {
+        self.ids.get(name).copied()
+    }
+
+    /// Name of a parameter by id
+    #[inline]
+    pub fn name_of(&self, id: ParamId) -> Option<&str> {
+        self.names.get(id.index()).map(String::as_str)
+    }
+
+    /// Token that changes whenever a parameter name is added. Graphs bound to
+    /// these parameters read them by id while it stays the same, and rebind on
+    /// their next update once it changes.
+    #[inline]
+    pub fn layout(&self) -> u64 {
+        self.layout
+    }
+
+    #[inline]
+    fn slot(&self, name: &str) -> Option<&ParamSlot> {
+        self.slots.get(self.find_id(name)?.index())
+    }
+
+    #[inline]
+    fn slot_mut(&mut self, name: &str) -> &mut ParamSlot {
+        let id = self.id_of(name);
+        &mut self.slots[id.index()]
+    }
+
     #[inline]
     pub fn get_bool(&self, name: &str) -> Option<bool> {
-        self.bools.get(name).copied()
+        self.slot(name)?.bool
     }
 
     #[inline]
     pub fn get_float(&self, name: &str) -> Option<f32> {
-        self.floats.get(name).copied()
+        self.slot(name)?.float
     }
 
     #[inline]
     pub fn get_int(&self, name: &str) -> Option<i32> {
-        self.ints.get(name).copied()
+        self.slot(name)?.int
     }
 
     #[inline]
     pub fn get_trigger(&self, name: &str) -> bool {
-        self.triggers.get(name).copied().unwrap_or(false)
+        self.slot(name).is_some_and(|slot| slot.trigger)
+    }
+
+    #[inline]
+    pub fn get_bool_by_id(&self, id: ParamId) -> Option<bool> {
+        self.slots.get(id.index())?.bool
+    }
+
+    #[inline]
+    pub fn get_float_by_id(&self, id: ParamId) -> Option<f32> {
+        self.slots.get(id.index())?.float
+    }
+
+    #[inline]
+    pub fn get_int_by_id(&self, id: ParamId) -> Option<i32> {
+        self.slots.get(id.index())?.int
+    }
+
+    #[inline]
+    pub fn get_trigger_by_id(&self, id: ParamId) -> bool {
+        self.slots.get(id.index()).is_some_and(|slot| slot.trigger)
     }
 
     #[inline]
     pub fn set_bool(&mut self, name: &str, value: bool) {
-        self.bools.insert(name.to_string(), value);
+        self.slot_mut(name).bool = Some(value);
     }
 
     #[inline]
     pub fn set_float(&mut self, name: &str, value: f32) {
-        self.floats.insert(name.to_string(), value);
+        self.slot_mut(name).float = Some(value);
     }
 
     #[inline]
     pub fn set_int(&mut self, name: &str, value: i32) {
-        self.ints.insert(name.to_string(), value);
+        self.slot_mut(name).int = Some(value);
     }
 
     #[inline]
     pub fn set_trigger(&mut self, name: &str) {
-        self.triggers.insert(name.to_string(), true);
+        self.slot_mut(name).trigger = true;
+    }
+
+    /// Set a bool by id, does nothing for ids of other `Parameters`
+    #[inline]
+    pub fn set_bool_by_id(&mut self, id: ParamId, value: bool) {
+        if let Some(slot) = self.slots.get_mut(id.index()) {
+            slot.bool = Some(value);
+        }
+    }
+
+    #[inline]
+    pub fn set_float_by_id(&mut self, id: ParamId, value: f32) {
+        if let Some(slot) = self.slots.get_mut(id.index()) {
+            slot.float = Some(value);
+        }
+    }
+
+    #[inline]
+    pub fn set_int_by_id(&mut self, id: ParamId, value: i32) {
+        if let Some(slot) = self.slots.get_mut(id.index()) {
+            slot.int = Some(value);
+        }
+    }
+
+    #[inline]
+    pub fn set_trigger_by_id(&mut self, id: ParamId) {
+        if let Some(slot) = self.slots.get_mut(id.index()) {
+            slot.trigger = true;
+        }
+    }
+
+    #[inline]
+    pub fn set(&mut self, name: &str, value: ParameterValue) {
+        match value {
+            ParameterValue::Bool(value) => self.set_bool(name, value),
+            ParameterValue::Float(value) => self.set_float(name, value),
+            ParameterValue::Int(value) => self.set_int(name, value),
+            ParameterValue::Trigger => self.set_trigger(name),
+        }
+    }
+
+    /// Set many parameters at once, e.g. from a network snapshot or an input system
+    /// ## Example
+    /// ```ignore
+    /// parameters.apply([
+    ///     ("speed", 0.5.into()),
+    ///     ("is_grounded", true.into()),
+    ///     ("jump", ParameterValue::Trigger),
+    /// ]);
+    /// ```
+    #[inline]
+    pub fn apply<'a>(&mut self, batch: impl IntoIterator<Item = (&'a str, ParameterValue)>) {
+        for (name, value) in batch {
+            self.set(name, value);
+        }
+    }
+
+    /// Every parameter and its value, triggers only while they are set
+    pub fn iter(&self) -> impl Iterator<Item = (&str, ParameterValue)> {
+        self.names.iter().zip(&self.slots).flat_map(|(name, slot)| {
+            let name = name.as_str();
+            let bool = slot.bool.map(|v| (name, ParameterValue::Bool(v)));
+            let float = slot.float.map(|v| (name, ParameterValue::Float(v)));
+            let int = slot.int.map(|v| (name, ParameterValue::Int(v)));
+            let trigger = slot.trigger.then_some((name, ParameterValue::Trigger));
+            [bool, float, int, trigger].into_iter().flatten()
+        })
+    }
+
+    /// Unset a single trigger
+    #[inline]
+    pub fn reset_trigger(&mut self, name: &str) {
+        if let Some(id) = self.find_id(name) {
+            self.slots[id.index()].trigger = false;
+        }
     }
 
     #[inline]
     pub fn reset_triggers(&mut self) {
-        self.triggers.clear();
+        for slot in &mut self.slots {
+            slot.trigger = false;
+        }
+    }
+
+    /// Float read through a binding, by id while the binding is current
+    #[inline]
+    pub(crate) fn bound_float(&self, binding: ParamBinding, name: &str) -> Option<f32> {
+        match binding.id {
+            Some(id) if binding.layout == self.layout => self.get_float_by_id(id),
+            _ => self.get_float(name),
+        }
+    }
+
+    #[inline]
+    pub(crate) fn bound_bool(&self, binding: ParamBinding, name: &str) -> Option<bool> {
+        match binding.id {
+            Some(id) if binding.layout == self.layout => self.get_bool_by_id(id),
+            _ => self.get_bool(name),
+        }
+    }
+
+    #[inline]
+    pub(crate) fn bound_int(&self, binding: ParamBinding, name: &str) -> Option<i32> {
+        match binding.id {
+            Some(id) if binding.layout == self.layout => self.get_int_by_id(id),
+            _ => self.get_int(name),
+        }
+    }
+
+    #[inline]
+    pub(crate) fn bound_trigger(&self, binding: ParamBinding, name: &str) -> bool {
+        match binding.id {
+            Some(id) if binding.layout == self.layout => self.get_trigger_by_id(id),
+            _ => self.get_trigger(name),
+        }
+    }
+}
+
+/// A parameter name of the graph resolved to its id, falling back to the name
+/// once the parameters it was resolved against change layout
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct ParamBinding {
+    id: Option<ParamId>,
+    layout: u64,
+}
+
+impl ParamBinding {
+    #[inline]
+    pub(crate) fn resolve(parameters: &Parameters, name: &str) -> Self {
+        Self {
+            id: parameters.find_id(name),
+            layout: parameters.layout,
+        }
     }
 }
@@ -0,0 +1,248 @@
+use super::skeleton::find_joint;
+use super::{AnimatorError, OzzTransform, RecordedClip};
+use bevy::prelude::*;
+use ozz_animation_rs::{Skeleton, SoaTransform};
+
+/// A static pose, baked from a controller with `AnimatorController::bake_pose`.
+/// Useful as an additive reference, a death pose or a menu pose.
+#[derive(Debug, Clone)]
+pub struct BakedPose {
+    pose: Vec<SoaTransform>,
+    num_joints: usize,
+}
+
+impl BakedPose {
+    #[inline]
+    pub(crate) fn new(pose: Vec<SoaTransform>, num_joints: usize) -> Self {
+        Self { pose, num_joints }
+    }
+
+    /// The pose in ozz's SoA layout, for blending jobs
+    #[inline]
+    pub fn soa(&self) -> &[SoaTransform] {
+        &self.pose
+    }
+
+    /// Local transform of every joint, in joint order
+    #[inline]
+    pub fn transforms(&self) -> Vec<Transform> {
+        soa_to_transforms(&self.pose, self.num_joints)
+    }
+
+    /// Additive difference between this pose and a reference pose, for use on
+    /// additive layers (recoil, flinches)
+    #[inline]
+    pub fn difference(&self, reference: &BakedPose) -> BakedPose {
+        BakedPose::new(
+            pose_difference(&self.pose, &reference.pose),
+            self.num_joints.min(reference.num_joints),
+        )
+    }
+
+    /// A single frame clip holding this pose, playable with `SimpleState::from_recording`
+    #[inline]
+    pub fn to_clip(&self) -> RecordedClip {
+        RecordedClip::from_pose(self.pose.clone())
+    }
+}
+
+/// A SoA transform with every lane set to identity
+#[inline]
+pub fn identity_soa_transform() -> SoaTransform {
+    SoaTransform::IDENTITY
+}
+
+/// Read the transform of a single joint out of a SoA pose, panics if the joint
+/// is past the end of the pose
+#[inline]
+pub fn get_joint(pose: &[SoaTransform], joint: usize) -> Transform {
+    let soa = &pose[joint / 4];
+    let lane = joint % 4;
+    Transform {
+        translation: Vec3::new(
+            soa.translation.x[lane],
+            soa.translation.y[lane],
+            soa.translation.z[lane],
+        ),
+        rotation: Quat::from_xyzw(
+            soa.rotation.x[lane],
+            soa.rotation.y[lane],
+            soa.rotation.z[lane],
+            soa.rotation.w[lane],
+        ),
+        scale: Vec3::new(soa.scale.x[lane], soa.scale.y[lane], soa.scale.z[lane]),
+    }
+}
+
+/// Replace `bones` with the transform of every joint of a SoA pose, up to
+/// `num_joints`
+#[inline]
+pub(crate) fn pose_to_bones(
+    pose: &[SoaTransform],
+    num_joints: usize,
+    bones: &mut Vec<OzzTransform>,
+) {
+    let num_joints = num_joints.min(pose.len() * 4);
+    bones.clear();
+    bones.extend((0..num_joints).map(|joint| OzzTransform::from(get_joint(pose, joint))));
+}
+
+/// Write the transform of a single joint into a SoA pose, panics if the joint
+/// is past the end of the pose
+#[inline]
+pub fn set_joint(pose: &mut [SoaTransform], joint: usize, transform: &Transform) {
+    let soa = &mut pose[joint / 4];
+    let lane = joint % 4;
+    soa.translation.x[lane] = transform.translation.x;
+    soa.translation.y[lane] = transform.translation.y;
+    soa.translation.z[lane] = transform.translation.z;
+    soa.rotation.x[lane] = transform.rotation.x;
+    soa.rotation.y[lane] = transform.rotation.y;
+    soa.rotation.z[lane] = transform.rotation.z;
+    soa.rotation.w[lane] = transform.rotation.w;
+    soa.scale.x[lane] = transform.scale.x;
+    soa.scale.y[lane] = transform.scale.y;
+    soa.scale.z[lane] = transform.scale.z;
+}
+
+/// Index of a named joint that fits in the pose
+#[inline]
+fn named_joint(
+    skeleton: &Skeleton,
+    pose: &[SoaTransform],
+    name: &str,
+) -> Result<usize, AnimatorError> {
+    find_joint(skeleton, name, false)
+        .filter(|joint| *joint < pose.len() * 4)
+        .ok_or_else(|| AnimatorError::UnknownJoint(name.to_string()))
+}
+
+/// Read the local transform of a joint by name
+/// ## Example
+/// ```ignore
+/// let head = joint_transform(&skeleton, &pose, "head")?;
+/// ```
+#[inline]
+pub fn joint_transform(
+    skeleton: &Skeleton,
+    pose: &[SoaTransform],
+    name: &str,
+) -> Result<Transform, AnimatorError> {
+    Ok(get_joint(pose, named_joint(skeleton, pose, name)?))
+}
+
+/// Write the local transform of a joint by name
+#[inline]
+pub fn set_joint_transform(
+    skeleton: &Skeleton,
+    pose: &mut [SoaTransform],
+    name: &str,
+    transform: &Transform,
+) -> Result<(), AnimatorError> {
+    set_joint(pose, named_joint(skeleton, pose, name)?, transform);
+    Ok(())
+}
+
+/// Change only the local translation of a joint by name
+#[inline]
+pub fn set_joint_translation(
+    skeleton: &Skeleton,
+    pose: &mut [SoaTransform],
+    name: &str,
+    translation: Vec3,
+) -> Result<(), AnimatorError> {
+    let joint = named_joint(skeleton, pose, name)?;
+    let transform = get_joint(pose, joint).with_translation(translation);
+    set_joint(pose, joint, &transform);
+    Ok(())
+}
+
+/// Change only the local rotation of a joint by name
+/// ## Example
+/// ```ignore
+/// let mut pose = skeleton.joint_rest_poses().to_vec();
+/// set_joint_rotation(&skeleton, &mut pose, "head", Quat::from_rotation_y(0.5))?;
+/// ```
+#[inline]
+pub fn set_joint_rotation(
+    skeleton: &Skeleton,
+    pose: &mut [SoaTransform],
+    name: &str,
+    rotation: Quat,
+) -> Result<(), AnimatorError> {
+    let joint = named_joint(skeleton, pose, name)?;
+    let transform = get_joint(pose, joint).with_rotation(rotation);
+    set_joint(pose, joint, &transform);
+    Ok(())
+}
+
+/// Change only the local scale of a joint by name
+#[inline]
+pub fn set_joint_scale(
+    skeleton: &Skeleton,
+    pose: &mut [SoaTransform],
+    name: &str,
+    scale: Vec3,
+) -> Result<(), AnimatorError> {
+    let joint = named_joint(skeleton, pose, name)?;
+    let transform = get_joint(pose, joint).with_scale(scale);
+    set_joint(pose, joint, &transform);
+    Ok(())
+}
+
+/// Pack per-joint transforms into a SoA pose, unused lanes are identity
+/// ## Example
+/// ```ignore
+/// let transforms = soa_to_transforms(skeleton.joint_rest_poses(), skeleton.num_joints());
+/// let pose = transforms_to_soa(&transforms);
+/// ```
+#[inline]
+pub fn transforms_to_soa(transforms: &[Transform]) -> Vec<SoaTransform> {
+    let mut pose = vec![identity_soa_transform(); transforms.len().div_ceil(4)];
+    for (joint, transform) in transforms.iter().enumerate() {
+        set_joint(&mut pose, joint, transform);
+    }
+    pose
+}
+
+/// Unpack the first `num_joints` joints of a SoA pose into per-joint transforms,
+/// joints past the end of the pose are skipped
+#[inline]
+pub fn soa_to_transforms(pose: &[SoaTransform], num_joints: usize) -> Vec<Transform> {
+    (0..num_joints.min(pose.len() * 4))
+        .map(|joint| get_joint(pose, joint))
+        .collect()
+}
+
+/// Additive difference between a pose and a reference pose, the inverse of
+/// what an additive blending layer applies
+pub(crate) fn pose_difference(pose: &[SoaTransform], reference: &[SoaTransform]) -> Vec<SoaTransform> {
+    let len = pose.len().min(reference.len());
+    let mut difference = vec![identity_soa_transform(); len];
+    for joint in 0..len * 4 {
+        let current = get_joint(pose, joint);
+        let base = get_joint(reference, joint);
+        let scale = Vec3::select(
+            base.scale.cmpeq(Vec3::ZERO),
+            Vec3::ONE,
+            current.scale / base.scale,
+        );
+        let delta = Transform {
+            translation: current.translation - base.translation,
+            rotation: (base.rotation.conjugate() * current.rotation).normalize(),
+            scale,
+        };
+        set_joint(&mut difference, joint, &delta);
+    }
+    difference
+}
+
+/// Pack per-joint weights into SoA lanes, joints past the end weigh 1
+#[inline]
+pub(crate) fn soa_weights(weights: &[f32], num_soa_joints: usize) -> Vec<glam::Vec4> {
+    let mut soa = vec![glam::Vec4::ONE; num_soa_joints];
+    for (joint, weight) in weights.iter().enumerate().take(num_soa_joints * 4) {
+        soa[joint / 4][joint % 4] = *weight;
+    }
+    soa
+}
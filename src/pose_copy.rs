@@ -0,0 +1,78 @@
+use super::crowd::{mirror_map, mirror_transform};
+use super::{AnimatorController, OzzTransform};
+use bevy::prelude::*;
+
+/// Shows the latest pose of another entity's `AnimatorController` without
+/// running a second state machine, for holograms, mirrors and kill-cam doubles.
+/// Put it where an `AnimatorTarget` would go, joints are matched to the source
+/// skeleton by name so the copy can use a different skeleton instance.
+/// ## Example
+/// ```ignore
+/// let player = commands.spawn((SceneRoot(mesh.clone()), controller)).id();
+/// commands.spawn((
+///     SceneRoot(hologram_mesh),
+///     Transform::from_xyz(2.0, 0.0, 0.0),
+///     PoseCopy::new(player).mirrored(true),
+/// ));
+/// ```
+#[derive(Component, Debug, Clone)]
+pub struct PoseCopy {
+    /// Entity with the `AnimatorController` to copy
+    pub source: Entity,
+    /// Swap left and right, like a reflection
+    pub mirrored: bool,
+    /// Index of the mirrored counterpart of every joint of the source
+    mirror_map: Vec<usize>,
+}
+
+impl PoseCopy {
+    #[inline]
+    pub fn new(source: Entity) -> Self {
+        Self {
+            source,
+            mirrored: false,
+            mirror_map: Vec::new(),
+        }
+    }
+
+    #[inline]
+    pub fn mirrored(mut self, mirrored: bool) -> Self {
+        self.mirrored = mirrored;
+        self
+    }
+
+    /// Transform of a joint of the source pose, `alpha` interpolates between
+    /// the last two updates like `AnimatorController::interpolated_bone`
+    #[inline]
+    pub fn joint(
+        &self,
+        source: &AnimatorController,
+        joint: usize,
+        alpha: f32,
+    ) -> Option<OzzTransform> {
+        if !self.mirrored {
+            return source.interpolated_bone(joint, alpha);
+        }
+        let mirrored = self.mirror_map.get(joint).copied().unwrap_or(joint);
+        source
+            .interpolated_bone(mirrored, alpha)
+            .map(|bone| mirror_transform(&bone))
+    }
+}
+
+/// Pair up the left and right joints of every mirrored copy's source
+pub fn update_pose_copies(
+    mut copies: Query<&mut PoseCopy>,
+    controllers: Query<&AnimatorController>,
+) {
+    for mut copy in &mut copies {
+        if !copy.mirrored {
+            continue;
+        }
+        if let Ok(controller) = controllers.get(copy.source)
+            && copy.mirror_map.len() != controller.joint_count()
+        {
+            copy.mirror_map = mirror_map(controller);
+        }
+    }
+}
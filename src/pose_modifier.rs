@@ -0,0 +1,178 @@
+use super::pose::get_joint;
+use super::skeleton::joint_parent;
+use super::{AnimatorController, AnimatorError, Parameters};
+use bevy::prelude::*;
+use ozz_animation_rs::{Skeleton, SoaTransform};
+use std::fmt;
+
+/// The blended pose handed to a [`PoseModifier`]
+pub struct PoseModifierContext<'a> {
+    /// Local transform of every joint in ozz's SoA layout, 4 joints per
+    /// transform. Edit it with `set_joint_rotation` and friends.
+    pub local: &'a mut [SoaTransform],
+    /// Model space transform of every joint, relative to the skeleton root,
+    /// as left by the previous modifier
+    pub model: &'a [Mat4],
+    pub skeleton: &'a Skeleton,
+    pub parameters: &'a Parameters,
+    /// Seconds the update advanced
+    pub delta_secs: f32,
+}
+
+/// A procedural adjustment of the blended pose, like a lean into turns,
+/// breathing or weapon recoil, run every sampled update after the layers are
+/// blended and before IK and bone overrides
+/// ## Example
+/// ```ignore
+/// struct Breathing {
+///     time: f32,
+/// }
+///
+/// impl PoseModifier for Breathing {
+///     fn modify(&mut self, pose: &mut PoseModifierContext) -> Result<(), AnimatorError> {
+///         self.time += pose.delta_secs;
+///         let joint = joint_transform(pose.skeleton, pose.local, "Spine2")?;
+///         let breath = Quat::from_rotation_x((self.time * 1.5).sin() * 0.03);
+///         set_joint_rotation(pose.skeleton, pose.local, "Spine2", joint.rotation * breath)
+///     }
+/// }
+///
+/// controller.add_pose_modifier("breathing", Breathing { time: 0.0 });
+/// ```
+pub trait PoseModifier: Send + Sync + 'static {
+    fn modify(&mut self, pose: &mut PoseModifierContext) -> Result<(), AnimatorError>;
+}
+
+/// A named entry of a controller's pose modifier stack
+pub(crate) struct ActivePoseModifier {
+    name: String,
+    enabled: bool,
+    modifier: Box<dyn PoseModifier>,
+}
+
+impl fmt::Debug for ActivePoseModifier {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ActivePoseModifier")
+            .field("name", &self.name)
+            .field("enabled", &self.enabled)
+            .finish_non_exhaustive()
+    }
+}
+
+impl AnimatorController {
+    /// Add a modifier to the end of the stack, modifiers run in stack order.
+    /// A modifier with the same name is replaced in place.
+    #[inline]
+    pub fn add_pose_modifier(&mut self, name: impl Into<String>, modifier: impl PoseModifier) {
+        let active = ActivePoseModifier {
+            name: name.into(),
+            enabled: true,
+            modifier: Box::new(modifier),
+        };
+        match self.pose_modifiers.iter_mut().find(|m| m.name == active.name) {
+            Some(existing) => *existing = active,
+            None => self.pose_modifiers.push(active),
+        }
+    }
+
+    /// Insert a modifier at a position of the stack, the end if past it,
+    /// replacing any with the same name
+    #[inline]
+    pub fn insert_pose_modifier(
+        &mut self,
+        index: usize,
+        name: impl Into<String>,
+        modifier: impl PoseModifier,
+    ) {
+        let name = name.into();
+        self.remove_pose_modifier(&name);
+        let index = index.min(self.pose_modifiers.len());
+        self.pose_modifiers.insert(
+            index,
+            ActivePoseModifier {
+                name,
+                enabled: true,
+                modifier: Box::new(modifier),
+            },
+        );
+    }
+
+    /// Remove a modifier, returns false if there is no such modifier
+    #[inline]
+    pub fn remove_pose_modifier(&mut self, name: &str) -> bool {
+        let count = self.pose_modifiers.len();
+        self.pose_modifiers.retain(|m| m.name != name);
+        self.pose_modifiers.len() != count
+    }
+
+    /// Skip a modifier without losing its place in the stack, returns false if
+    /// there is no such modifier
+    #[inline]
+    pub fn set_pose_modifier_enabled(&mut self, name: &str, enabled: bool) -> bool {
+        let Some(active) = self.pose_modifiers.iter_mut().find(|m| m.name == name) else {
+            return false;
+        };
+        active.enabled = enabled;
+        true
+    }
+
+    #[inline]
+    pub fn is_pose_modifier_enabled(&self, name: &str) -> bool {
+        self.pose_modifiers
+            .iter()
+            .any(|m| m.name == name && m.enabled)
+    }
+
+    /// Names of the modifiers in stack order
+    #[inline]
+    pub fn pose_modifiers(&self) -> impl Iterator<Item = &str> {
+        self.pose_modifiers.iter().map(|m| m.name.as_str())
+    }
+
+    #[inline]
+    pub fn clear_pose_modifiers(&mut self) {
+        self.pose_modifiers.clear();
+    }
+
+    /// Run every enabled modifier on the final blended pose
+    pub(crate) fn apply_pose_modifiers(&mut self, delta_secs: f32) -> Result<(), AnimatorError> {
+        if !self.pose_modifiers.iter().any(|m| m.enabled) {
+            return Ok(());
+        }
+        let Some(output) = self.final_blending_job.output() else {
+            return Ok(());
+        };
+        let mut local = output.write().map_err(|_| {
+            output.clear_poison();
+            AnimatorError::PoisonedLock("final pose")
+        })?;
+
+        let mut model = Vec::with_capacity(self.skeleton.num_joints());
+        for active in self.pose_modifiers.iter_mut().filter(|m| m.enabled) {
+            // Earlier modifiers may have moved any joint
+            model_matrices(&self.skeleton, &local, &mut model);
+            active.modifier.modify(&mut PoseModifierContext {
+                local: &mut local,
+                model: &model,
+                skeleton: &self.skeleton,
+                parameters: &self.parameters,
+                delta_secs,
+            })?;
+        }
+        Ok(())
+    }
+}
+
+/// Model space transform of every joint of a SoA pose
+fn model_matrices(skeleton: &Skeleton, local: &[SoaTransform], model: &mut Vec<Mat4>) {
+    model.clear();
+    for joint in 0..skeleton.num_joints().min(local.len() * 4) {
+        let matrix = get_joint(local, joint).compute_matrix();
+        // Parents are always stored before their children
+        let matrix = match joint_parent(skeleton, joint) {
+            Some(parent) if parent < model.len() => model[parent] * matrix,
+            _ => matrix,
+        };
+        model.push(matrix);
+    }
+}
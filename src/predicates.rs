@@ -0,0 +1,165 @@
+use super::parameters::ParamBinding;
+use super::{AnimatorController, ParameterValue, Parameters, TransitionCondition};
+use std::collections::HashMap;
+
+/// Deepest nesting of predicates referencing other predicates, deeper ones and
+/// cycles never hold
+pub(crate) const MAX_PREDICATE_DEPTH: usize = 8;
+
+/// Named condition sets of a controller, referenced from transitions with
+/// `TransitionCondition::Predicate`
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Predicates {
+    conditions: HashMap<String, Vec<TransitionCondition>>,
+    /// Parameter of every condition resolved to its id, keyed like `conditions`
+    bindings: HashMap<String, Vec<ParamBinding>>,
+    /// Layout of the parameters the predicates were last bound to, 0 if never
+    bound_layout: u64,
+}
+
+impl Predicates {
+    #[inline]
+    pub(crate) fn get(&self, name: &str) -> Option<&[TransitionCondition]> {
+        self.conditions.get(name).map(Vec::as_slice)
+    }
+
+    /// Conditions of a predicate with the binding of each
+    #[inline]
+    pub(crate) fn bound(
+        &self,
+        name: &str,
+    ) -> Option<impl Iterator<Item = (&TransitionCondition, ParamBinding)>> {
+        let conditions = self.conditions.get(name)?;
+        let bindings = self.bindings.get(name).map_or(&[][..], Vec::as_slice);
+        Some(
+            conditions
+                .iter()
+                .enumerate()
+                .map(|(i, condition)| (condition, bindings.get(i).copied().unwrap_or_default())),
+        )
+    }
+
+    #[inline]
+    pub(crate) fn needs_binding(&self, parameters: &Parameters) -> bool {
+        self.bound_layout != parameters.layout()
+    }
+
+    pub(crate) fn bind_parameters(&mut self, parameters: &Parameters) {
+        self.bindings = self
+            .conditions
+            .iter()
+            .map(|(name, conditions)| {
+                let bindings = conditions
+                    .iter()
+                    .map(|condition| match condition.parameter() {
+                        Some(parameter) => ParamBinding::resolve(parameters, parameter),
+                        None => ParamBinding::default(),
+                    })
+                    .collect();
+                (name.clone(), bindings)
+            })
+            .collect();
+        self.bound_layout = parameters.layout();
+    }
+
+    /// Every trigger a condition reads, following predicates
+    pub(crate) fn triggers(&self, condition: &TransitionCondition, out: &mut Vec<String>) {
+        self.collect_triggers(condition, 0, out);
+    }
+
+    fn collect_triggers(
+        &self,
+        condition: &TransitionCondition,
+        depth: usize,
+        out: &mut Vec<String>,
+    ) {
+        match condition {
+            TransitionCondition::Trigger(name) => out.push(name.clone()),
+            TransitionCondition::Predicate(name) if depth < MAX_PREDICATE_DEPTH => {
+                for condition in self.get(name).unwrap_or_default() {
+                    self.collect_triggers(condition, depth + 1, out);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+impl AnimatorController {
+    /// Define a named set of conditions that transitions of every layer can
+    /// reference with `TransitionCondition::Predicate`, so common gating like
+    /// "IsGrounded" lives in one place. The predicate holds when all of its
+    /// conditions do, and may itself reference other predicates.
+    /// ## Example
+    /// ```ignore
+    /// layer.add_transition(
+    ///     "Idle".to_string(),
+    ///     Transition {
+    ///         to_state: "Attack".to_string(),
+    ///         duration: 0.1,
+    ///         conditions: vec![
+    ///             TransitionCondition::Predicate("CanAttack".to_string()),
+    ///             TransitionCondition::Trigger("attack".to_string()),
+    ///         ],
+    ///         ..default()
+    ///     },
+    /// );
+    ///
+    /// let controller = AnimatorController::new(skeleton, layers, parameters)?
+    ///     .with_predicate(
+    ///         "CanAttack",
+    ///         vec![
+    ///             TransitionCondition::Bool("is_grounded".to_string(), true),
+    ///             TransitionCondition::Float("stamina".to_string(), 10.0, CompareType::Greater),
+    ///         ],
+    ///     );
+    /// ```
+    #[inline]
+    pub fn with_predicate(
+        mut self,
+        name: impl Into<String>,
+        conditions: Vec<TransitionCondition>,
+    ) -> Self {
+        self.set_predicate(name, conditions);
+        self
+    }
+
+    /// Define or replace a predicate, see [`Self::with_predicate`]
+    #[inline]
+    pub fn set_predicate(&mut self, name: impl Into<String>, conditions: Vec<TransitionCondition>) {
+        self.predicates.conditions.insert(name.into(), conditions);
+        self.predicates.bound_layout = 0;
+        self.parameter_check.checked = false;
+    }
+
+    /// Remove a predicate, transitions referencing it stop firing
+    #[inline]
+    pub fn remove_predicate(&mut self, name: &str) -> Option<Vec<TransitionCondition>> {
+        self.predicates.bound_layout = 0;
+        self.predicates.conditions.remove(name)
+    }
+
+    /// Conditions of a predicate
+    #[inline]
+    pub fn predicate(&self, name: &str) -> Option<&[TransitionCondition]> {
+        self.predicates.get(name)
+    }
+
+    /// Every predicate with its conditions, in no particular order
+    #[inline]
+    pub fn predicates(&self) -> impl Iterator<Item = (&str, &[TransitionCondition])> {
+        self.predicates
+            .conditions
+            .iter()
+            .map(|(name, conditions)| (name.as_str(), conditions.as_slice()))
+    }
+
+    /// Every parameter the predicates read, with its default value
+    pub(crate) fn predicate_parameters(&self, out: &mut Vec<(String, ParameterValue)>) {
+        for condition in self.predicates.conditions.values().flatten() {
+            if let Some(referenced) = condition.referenced_parameter() {
+                out.push(referenced);
+            }
+        }
+    }
+}
@@ -1,7 +1,49 @@
+pub use crate::additive_shot::*;
 pub use crate::asset_loader::*;
+pub use crate::async_animation::*;
+pub use crate::attachment::*;
+pub use crate::audio::*;
 pub use crate::base::*;
 pub use crate::blend_tree::*;
+pub use crate::blend_tree_player::*;
+pub use crate::budget::*;
+pub use crate::builder::*;
+#[cfg(feature = "render")]
+pub use crate::clip_baker::*;
+pub use crate::clip_player::*;
 pub use crate::controller::*;
+pub use crate::coverage::*;
+pub use crate::crowd::*;
+pub use crate::curve::*;
+pub use crate::curve_output::*;
+#[cfg(feature = "render")]
+pub use crate::debug::*;
+pub use crate::definition::*;
+pub use crate::diff::*;
+pub use crate::error::*;
+pub use crate::events::*;
+pub use crate::external::*;
+pub use crate::hot_reload::*;
+pub use crate::ik::*;
 pub use crate::layer::*;
+pub use crate::mask::*;
+pub use crate::missing_parameters::*;
+pub use crate::overrides::*;
 pub use crate::parameters::*;
+pub use crate::pose::*;
+pub use crate::pose_copy::*;
+pub use crate::pose_modifier::*;
+pub use crate::recording::*;
+pub use crate::retarget::*;
+pub use crate::skeleton::*;
+pub use crate::snapshot::*;
 pub use crate::state::*;
+pub use crate::stats::*;
+#[cfg(any(test, feature = "bench"))]
+pub use crate::synthetic::*;
+pub use crate::tags::*;
+pub use crate::time_source::*;
+pub use crate::trace::*;
+pub use crate::triggers::*;
+pub use crate::usage::*;
+pub use crate::validation::*;
@@ -0,0 +1,137 @@
+use super::pose::{get_joint, set_joint};
+use super::{AnimatorController, AnimatorError, ExternalPose, JointMask};
+use bevy::prelude::*;
+
+/// The physics pose of a controller and how much of it shows
+#[derive(Debug, Clone, Default)]
+pub(crate) struct PhysicsBlend {
+    pose: Option<ExternalPose>,
+    weight: f32,
+    target: f32,
+    /// Weight per second toward the target
+    speed: f32,
+    mask: Option<JointMask>,
+}
+
+impl AnimatorController {
+    /// Blend an externally computed pose, like a physics ragdoll's, over the
+    /// animated pose once the layers are blended. Unlike an external layer it
+    /// isn't normalized with the other layers, at a weight of 1 it replaces
+    /// the animation entirely. Pose modifiers, IK and bone overrides still
+    /// apply on top. Starts at a weight of 0.
+    /// ## Example
+    /// ```ignore
+    /// let ragdoll = ExternalPose::new(&skeleton);
+    /// controller.set_physics_pose(ragdoll.clone());
+    ///
+    /// // On death
+    /// controller.fade_physics_weight(1.0, 0.2);
+    /// // Every frame, from the ragdoll's bodies
+    /// ragdoll.set_model_transforms(&skeleton, &body_transforms);
+    ///
+    /// // On getup stop writing the ragdoll and fade back to the animation
+    /// controller.force_state("Base Layer", "Get Up", 0.0)?;
+    /// controller.fade_physics_weight(0.0, 0.5);
+    /// ```
+    #[inline]
+    pub fn set_physics_pose(&mut self, pose: ExternalPose) {
+        self.physics.pose = Some(pose);
+    }
+
+    /// Remove the physics pose, the animated pose shows again right away
+    #[inline]
+    pub fn clear_physics_pose(&mut self) {
+        self.physics = PhysicsBlend::default();
+    }
+
+    #[inline]
+    pub fn physics_pose(&self) -> Option<&ExternalPose> {
+        self.physics.pose.as_ref()
+    }
+
+    /// Show this much of the physics pose right away, from 0 to 1
+    #[inline]
+    pub fn set_physics_weight(&mut self, weight: f32) {
+        let weight = weight.clamp(0.0, 1.0);
+        self.physics.weight = weight;
+        self.physics.target = weight;
+    }
+
+    /// Move the weight of the physics pose to `weight` over `duration` seconds
+    #[inline]
+    pub fn fade_physics_weight(&mut self, weight: f32, duration: f32) {
+        if duration <= 0.0 {
+            self.set_physics_weight(weight);
+            return;
+        }
+        self.physics.target = weight.clamp(0.0, 1.0);
+        self.physics.speed = (self.physics.target - self.physics.weight).abs() / duration;
+    }
+
+    /// Current weight of the physics pose
+    #[inline]
+    pub fn physics_weight(&self) -> f32 {
+        self.physics.weight
+    }
+
+    /// Only blend the physics pose on these joints, weighted by the mask, e.g.
+    /// an arm going limp after a hit. None blends every joint.
+    #[inline]
+    pub fn set_physics_mask(&mut self, mask: Option<JointMask>) {
+        self.physics.mask = mask;
+    }
+
+    /// Advance a weight fade, even on updates that aren't sampled
+    #[inline]
+    pub(crate) fn update_physics_weight(&mut self, delta_secs: f32) {
+        let physics = &mut self.physics;
+        if physics.weight != physics.target {
+            let step = physics.speed * delta_secs;
+            physics.weight = if physics.weight < physics.target {
+                (physics.weight + step).min(physics.target)
+            } else {
+                (physics.weight - step).max(physics.target)
+            };
+        }
+    }
+
+    /// Blend the physics pose into the final blended pose
+    pub(crate) fn apply_physics_pose(&mut self) -> Result<(), AnimatorError> {
+        let Some(pose) = &self.physics.pose else {
+            return Ok(());
+        };
+        if self.physics.weight <= 0.0 {
+            return Ok(());
+        }
+        let Some(output) = self.final_blending_job.output() else {
+            return Ok(());
+        };
+        let mut local = output.write().map_err(|_| {
+            output.clear_poison();
+            AnimatorError::PoisonedLock("final pose")
+        })?;
+        let physics = pose.read();
+
+        let num_joints = self
+            .skeleton
+            .num_joints()
+            .min(local.len() * 4)
+            .min(physics.len() * 4);
+        for joint in 0..num_joints {
+            let mask = self.physics.mask.as_ref().map_or(1.0, |mask| mask.weight(joint));
+            let weight = self.physics.weight * mask;
+            if weight <= 0.0 {
+                continue;
+            }
+            let animated = get_joint(&local, joint);
+            let simulated = get_joint(&physics, joint);
+            let blended = Transform {
+                translation: animated.translation.lerp(simulated.translation, weight),
+                rotation: animated.rotation.slerp(simulated.rotation, weight),
+                scale: animated.scale.lerp(simulated.scale, weight),
+            };
+            set_joint(&mut local, joint, &blended);
+        }
+        Ok(())
+    }
+}
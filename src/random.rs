@@ -0,0 +1,139 @@
+use super::AnimatorController;
+
+/// Small deterministic random number generator (SplitMix64), the same seed
+/// always produces the same numbers on every platform
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub(crate) struct AnimatorRng(u64);
+
+impl AnimatorRng {
+    #[inline]
+    pub(crate) fn new(seed: u64) -> Self {
+        Self(seed)
+    }
+
+    /// Current state, restoring it with `new` continues the same sequence
+    #[inline]
+    pub(crate) fn state(&self) -> u64 {
+        self.0
+    }
+
+    #[inline]
+    pub(crate) fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform in `[0, 1)`
+    #[inline]
+    pub(crate) fn next_f32(&mut self) -> f32 {
+        (self.next_u64() >> 40) as f32 / (1u64 << 24) as f32
+    }
+}
+
+/// Seed of the layer at `index` of a controller seeded with `seed`, so every
+/// layer draws its own numbers however often the others do
+#[inline]
+pub(crate) fn layer_seed(seed: u64, index: usize) -> u64 {
+    AnimatorRng::new(seed ^ (index as u64).wrapping_mul(0xD1B5_4A32_D192_ED03)).next_u64()
+}
+
+impl AnimatorController {
+    /// Seed the random numbers behind `TransitionCondition::Random` and random
+    /// start times, restarting every layer's sequence. Controllers with the
+    /// same seed and inputs play the same randomized idles, so replays and
+    /// deterministic simulations stay reproducible. Every controller starts
+    /// with seed 0, give each instance its own seed so they don't all make
+    /// the same choices.
+    /// ## Example
+    /// ```ignore
+    /// // Derive the seed from something stable across runs, like a spawn index
+    /// controller.set_seed(spawn_index as u64);
+    /// ```
+    #[inline]
+    pub fn set_seed(&mut self, seed: u64) {
+        self.seed = seed;
+        for (index, layer) in self.layers.iter_mut().enumerate() {
+            layer.seed_rng(layer_seed(seed, index));
+        }
+    }
+
+    #[inline]
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.set_seed(seed);
+        self
+    }
+
+    /// Seed last set with [`Self::set_seed`]
+    #[inline]
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Parameters, test_layer, test_rig};
+
+    /// Normalized time `Walk` randomly starts at when a controller seeded
+    /// with `seed`, if any, crossfades to it
+    fn walk_start(seed: Option<u64>) -> f32 {
+        let (skeleton, clip) = test_rig();
+        let layer = test_layer(&skeleton, &clip, &["Idle", "Walk"]).with_random_start("Walk");
+        let mut controller =
+            AnimatorController::new(skeleton.clone(), vec![layer], Parameters::new()).unwrap();
+        if let Some(seed) = seed {
+            controller.set_seed(seed);
+        }
+        controller.cross_fade("Walk", 0.5, "Base").unwrap();
+        controller.layers[0].next_state_normalized_time().unwrap()
+    }
+
+    #[test]
+    fn controllers_with_the_same_seed_draw_the_same_numbers() {
+        assert_eq!(walk_start(Some(11)), walk_start(Some(11)));
+        assert_ne!(walk_start(Some(11)), walk_start(Some(12)));
+    }
+
+    #[test]
+    fn unseeded_controllers_start_from_the_same_seed() {
+        assert_eq!(walk_start(None), walk_start(None));
+        assert_eq!(walk_start(None), walk_start(Some(0)));
+    }
+
+    #[test]
+    fn splitmix_matches_the_reference_sequence() {
+        let mut rng = AnimatorRng::new(1_234_567);
+        assert_eq!(rng.next_u64(), 6_457_827_717_110_365_317);
+        assert_eq!(rng.next_u64(), 3_203_168_211_198_807_973);
+        assert_eq!(rng.next_u64(), 9_817_491_932_198_370_423);
+    }
+
+    #[test]
+    fn restoring_the_state_continues_the_sequence() {
+        let mut rng = AnimatorRng::new(42);
+        rng.next_u64();
+        let mut restored = AnimatorRng::new(rng.state());
+        for _ in 0..8 {
+            assert_eq!(rng.next_u64(), restored.next_u64());
+        }
+    }
+
+    #[test]
+    fn floats_are_in_the_unit_range() {
+        let mut rng = AnimatorRng::new(7);
+        for _ in 0..1000 {
+            assert!((0.0..1.0).contains(&rng.next_f32()));
+        }
+    }
+
+    #[test]
+    fn layers_draw_their_own_numbers() {
+        assert_eq!(layer_seed(3, 1), layer_seed(3, 1));
+        assert_ne!(layer_seed(3, 0), layer_seed(3, 1));
+        assert_ne!(layer_seed(3, 0), layer_seed(4, 0));
+    }
+}
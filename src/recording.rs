@@ -0,0 +1,197 @@
+use super::pose::{get_joint, pose_difference, set_joint, transforms_to_soa};
+use super::AnimatorController;
+use bevy::prelude::*;
+use ozz_animation_rs::SoaTransform;
+
+/// An in-memory animation made of recorded poses, playable with
+/// [`SimpleState::from_recording`](super::SimpleState::from_recording)
+#[derive(Debug, Clone, Default)]
+pub struct RecordedClip {
+    frames: Vec<RecordedFrame>,
+}
+
+#[derive(Debug, Clone)]
+struct RecordedFrame {
+    /// Time of the frame in seconds from the start of the clip
+    time: f32,
+    pose: Vec<SoaTransform>,
+}
+
+impl RecordedClip {
+    /// A clip holding a single static pose
+    #[inline]
+    pub fn from_pose(pose: Vec<SoaTransform>) -> Self {
+        Self {
+            frames: vec![RecordedFrame { time: 0.0, pose }],
+        }
+    }
+
+    /// Duration in seconds
+    #[inline]
+    pub fn duration(&self) -> f32 {
+        self.frames.last().map(|frame| frame.time).unwrap_or(0.0)
+    }
+
+    /// Number of recorded frames
+    #[inline]
+    pub fn frame_count(&self) -> usize {
+        self.frames.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    /// Additive difference of every frame against a reference pose, playable
+    /// on an additive layer
+    pub fn difference(&self, reference: &[SoaTransform]) -> RecordedClip {
+        RecordedClip {
+            frames: self
+                .frames
+                .iter()
+                .map(|frame| RecordedFrame {
+                    time: frame.time,
+                    pose: pose_difference(&frame.pose, reference),
+                })
+                .collect(),
+        }
+    }
+
+    /// Sample the clip at a time in seconds into `output`, interpolating
+    /// between the closest frames
+    pub fn sample(&self, time: f32, output: &mut [SoaTransform]) {
+        let Some(last) = self.frames.last() else {
+            return;
+        };
+        let time = time.clamp(0.0, last.time);
+        let next = self
+            .frames
+            .partition_point(|frame| frame.time < time)
+            .min(self.frames.len() - 1);
+        let from = &self.frames[next.saturating_sub(1)];
+        let to = &self.frames[next];
+
+        let span = to.time - from.time;
+        if span <= f32::EPSILON {
+            let len = output.len().min(to.pose.len());
+            output[..len].clone_from_slice(&to.pose[..len]);
+            return;
+        }
+
+        let t = (time - from.time) / span;
+        let joints = output.len().min(from.pose.len()).min(to.pose.len()) * 4;
+        for joint in 0..joints {
+            let a = get_joint(&from.pose, joint);
+            let b = get_joint(&to.pose, joint);
+            let blended = Transform {
+                translation: a.translation.lerp(b.translation, t),
+                rotation: a.rotation.slerp(b.rotation, t),
+                scale: a.scale.lerp(b.scale, t),
+            };
+            set_joint(output, joint, &blended);
+        }
+    }
+}
+
+/// Records poses over time into a [`RecordedClip`], for replay ghosts or
+/// quick in-engine clip authoring
+/// ## Example
+/// ```ignore
+/// fn record(mut recorder: ResMut<GhostRecorder>, controllers: Query<&AnimatorController>, time: Res<Time>) {
+///     for controller in &controllers {
+///         recorder.0.record_controller(controller, time.delta_secs());
+///     }
+/// }
+///
+/// let clip = Arc::new(recorder.finish());
+/// let ghost_state = SimpleState::from_recording(clip, skeleton.num_soa_joints());
+/// ```
+#[derive(Debug, Clone)]
+pub struct PoseRecorder {
+    /// Minimum time in seconds between two recorded frames
+    frame_interval: f32,
+    /// Time since recording started
+    elapsed: f32,
+    clip: RecordedClip,
+}
+
+impl PoseRecorder {
+    /// Create a recorder that keeps at most `frame_rate` frames per second,
+    /// 0 records every call
+    #[inline]
+    pub fn new(frame_rate: f32) -> Self {
+        Self {
+            frame_interval: if frame_rate > 0.0 { 1.0 / frame_rate } else { 0.0 },
+            elapsed: 0.0,
+            clip: RecordedClip::default(),
+        }
+    }
+
+    /// Record the blended pose of a controller, `delta_secs` after the previous call
+    #[inline]
+    pub fn record_controller(&mut self, controller: &AnimatorController, delta_secs: f32) {
+        self.record_with(delta_secs, || {
+            let transforms: Vec<Transform> = controller
+                .bone_trans
+                .iter()
+                .map(|bone| Transform {
+                    translation: bone.position,
+                    rotation: bone.rotation,
+                    scale: bone.scale,
+                })
+                .collect();
+            transforms_to_soa(&transforms)
+        });
+    }
+
+    /// Record a pose given as local transforms in joint order, for example a
+    /// hand-posed hierarchy
+    #[inline]
+    pub fn record_transforms(&mut self, transforms: &[Transform], delta_secs: f32) {
+        self.record_with(delta_secs, || transforms_to_soa(transforms));
+    }
+
+    /// Record a SoA pose as is
+    #[inline]
+    pub fn record_pose(&mut self, pose: &[SoaTransform], delta_secs: f32) {
+        self.record_with(delta_secs, || pose.to_vec());
+    }
+
+    #[inline]
+    fn record_with(&mut self, delta_secs: f32, pose: impl FnOnce() -> Vec<SoaTransform>) {
+        if !self.clip.is_empty() {
+            self.elapsed += delta_secs.max(0.0);
+        }
+        let due = self
+            .clip
+            .frames
+            .last()
+            .is_none_or(|last| self.elapsed - last.time >= self.frame_interval);
+        if due {
+            self.clip.frames.push(RecordedFrame {
+                time: self.elapsed,
+                pose: pose(),
+            });
+        }
+    }
+
+    /// Time recorded so far in seconds
+    #[inline]
+    pub fn duration(&self) -> f32 {
+        self.elapsed
+    }
+
+    /// Discard everything recorded so far
+    #[inline]
+    pub fn clear(&mut self) {
+        self.elapsed = 0.0;
+        self.clip.frames.clear();
+    }
+
+    /// Stop recording and return the clip
+    #[inline]
+    pub fn finish(self) -> RecordedClip {
+        self.clip
+    }
+}
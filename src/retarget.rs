@@ -0,0 +1,440 @@
+use super::pose::{get_joint, set_joint};
+use super::skeleton::{find_joint, joint_info, joint_parent};
+use super::{AnimatorController, AnimatorError, PoseRecorder, RecordedClip, SimpleState};
+use bevy::{
+    asset::{AssetLoader, LoadContext, io::Reader},
+    prelude::*,
+};
+use ozz_animation_rs::{OzzError, Skeleton, SoaTransform};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use thiserror::Error;
+
+/// Which joint of a source skeleton drives which joint of a target skeleton,
+/// loaded from `.bonemap.ron` files
+/// ## Example
+/// ```ron
+/// (
+///     bones: [
+///         ("mixamorig:Hips", "pelvis"),
+///         ("mixamorig:Spine", "spine_01"),
+///         ("mixamorig:Head", "head"),
+///     ],
+///     root: Some("mixamorig:Hips"),
+/// )
+/// ```
+#[derive(Asset, TypePath, Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct BoneMap {
+    /// Pairs of source and target joint names
+    pub bones: Vec<(String, String)>,
+    /// Source joint whose translation carries the motion, usually the hips.
+    /// Every other target joint keeps the translations of its rest pose, so
+    /// the target keeps its own proportions.
+    #[serde(default)]
+    pub root: Option<String>,
+    /// Scale of the root translation, None uses the ratio between the rest
+    /// heights of the target and source roots
+    #[serde(default)]
+    pub root_scale: Option<f32>,
+}
+
+impl BoneMap {
+    /// Map every source joint to the target joint with the same name, ignoring
+    /// case, separators and namespace prefixes
+    pub fn from_matching_names(source: &Skeleton, target: &Skeleton) -> Self {
+        let bones = (0..source.num_joints())
+            .filter_map(|joint| joint_info(source, joint))
+            .filter_map(|joint| {
+                let target_joint = find_joint(target, joint.name, true)?;
+                let target_name = joint_info(target, target_joint)?.name;
+                Some((joint.name.to_string(), target_name.to_string()))
+            })
+            .collect();
+        Self {
+            bones,
+            root: None,
+            root_scale: None,
+        }
+    }
+
+    #[inline]
+    pub fn with_root(mut self, root: impl Into<String>) -> Self {
+        self.root = Some(root.into());
+        self
+    }
+}
+
+/// Rest pose of a skeleton, locally and in model space
+#[derive(Debug, Clone)]
+struct RestPose {
+    local: Vec<Transform>,
+    model_rotations: Vec<Quat>,
+    model_translations: Vec<Vec3>,
+}
+
+impl RestPose {
+    fn new(skeleton: &Skeleton) -> Self {
+        let num_joints = skeleton.num_joints();
+        let mut rest = Self {
+            local: Vec::with_capacity(num_joints),
+            model_rotations: Vec::with_capacity(num_joints),
+            model_translations: Vec::with_capacity(num_joints),
+        };
+        // Parents are always stored before their children
+        for joint in 0..num_joints {
+            let local = get_joint(skeleton.joint_rest_poses(), joint);
+            let (rotation, translation) = match joint_parent(skeleton, joint) {
+                Some(parent) => (
+                    rest.model_rotations[parent] * local.rotation,
+                    rest.model_translations[parent]
+                        + rest.model_rotations[parent] * local.translation,
+                ),
+                None => (local.rotation, local.translation),
+            };
+            rest.local.push(local);
+            rest.model_rotations.push(rotation);
+            rest.model_translations.push(translation);
+        }
+        rest
+    }
+}
+
+/// Plays poses of one skeleton on another, e.g. to share a mocap library
+/// across several character rigs. Every mapped joint follows the model space
+/// rotation of its source relative to the source's rest pose, so the two rigs
+/// may have different bind orientations as long as their rest poses face the
+/// same way (both in T-pose, say). Bone lengths stay those of the target,
+/// only the root translation is transferred, scaled to the target's size.
+/// Unmapped target joints keep their rest pose.
+/// ## Example
+/// ```ignore
+/// let map = bone_maps.get(&mocap_to_knight).unwrap();
+/// let retargeter = Retargeter::new(mocap_skeleton.clone(), knight_skeleton.clone(), map)?;
+///
+/// // Bake a clip of the source skeleton for the target
+/// let walk = SimpleState::new(mocap_walk.clone(), mocap_skeleton.num_soa_joints());
+/// let walk = Arc::new(retargeter.retarget_clip(&walk, 30.0)?);
+/// let knight_walk = SimpleState::from_recording(walk, knight_skeleton.num_soa_joints());
+///
+/// // Or follow a live controller every frame
+/// retargeter.retarget_controller(&mocap_controller, &mut knight_pose.write());
+/// ```
+#[derive(Debug, Clone)]
+pub struct Retargeter {
+    source: Arc<Skeleton>,
+    target: Arc<Skeleton>,
+    /// Source joint driving every target joint
+    sources: Vec<Option<usize>>,
+    source_rest: RestPose,
+    target_rest: RestPose,
+    /// Source and target joint of the root
+    root: Option<(usize, usize)>,
+    root_scale: f32,
+}
+
+impl Retargeter {
+    /// Resolve a bone map against two skeletons, failing on joint names
+    /// missing from either
+    pub fn new(
+        source: Arc<Skeleton>,
+        target: Arc<Skeleton>,
+        map: &BoneMap,
+    ) -> Result<Self, AnimatorError> {
+        let mut sources = vec![None; target.num_joints()];
+        let mut mapped = Vec::with_capacity(map.bones.len());
+        for (source_name, target_name) in &map.bones {
+            let source_joint = find_joint(&source, source_name, false)
+                .ok_or_else(|| AnimatorError::UnknownJoint(source_name.clone()))?;
+            let target_joint = find_joint(&target, target_name, false)
+                .ok_or_else(|| AnimatorError::UnknownJoint(target_name.clone()))?;
+            sources[target_joint] = Some(source_joint);
+            mapped.push((source_joint, target_joint));
+        }
+
+        let root = match &map.root {
+            Some(name) => {
+                let source_joint = find_joint(&source, name, false)
+                    .ok_or_else(|| AnimatorError::UnknownJoint(name.clone()))?;
+                let root = mapped
+                    .iter()
+                    .find(|(mapped_source, _)| *mapped_source == source_joint)
+                    .copied()
+                    .ok_or_else(|| AnimatorError::UnknownJoint(name.clone()))?;
+                Some(root)
+            }
+            None => None,
+        };
+
+        let source_rest = RestPose::new(&source);
+        let target_rest = RestPose::new(&target);
+        let root_scale = map.root_scale.unwrap_or_else(|| {
+            let Some((source_root, target_root)) = root else {
+                return 1.0;
+            };
+            let source_height = source_rest.model_translations[source_root].length();
+            let target_height = target_rest.model_translations[target_root].length();
+            if source_height > f32::EPSILON {
+                target_height / source_height
+            } else {
+                1.0
+            }
+        });
+
+        Ok(Self {
+            source,
+            target,
+            sources,
+            source_rest,
+            target_rest,
+            root,
+            root_scale,
+        })
+    }
+
+    #[inline]
+    pub fn source(&self) -> &Arc<Skeleton> {
+        &self.source
+    }
+
+    #[inline]
+    pub fn target(&self) -> &Arc<Skeleton> {
+        &self.target
+    }
+
+    /// Scale applied to the root translation
+    #[inline]
+    pub fn root_scale(&self) -> f32 {
+        self.root_scale
+    }
+
+    /// Retarget a local pose of the source skeleton into a local pose of the
+    /// target skeleton
+    #[inline]
+    pub fn retarget(&self, source_pose: &[SoaTransform], output: &mut [SoaTransform]) {
+        let num_joints = self.source.num_joints().min(source_pose.len() * 4);
+        self.retarget_with(num_joints, |joint| get_joint(source_pose, joint), output);
+    }
+
+    /// Retarget the latest pose of a controller driving the source skeleton
+    #[inline]
+    pub fn retarget_controller(
+        &self,
+        controller: &AnimatorController,
+        output: &mut [SoaTransform],
+    ) {
+        let pose = controller.local_pose();
+        let num_joints = self.source.num_joints().min(pose.len());
+        self.retarget_with(num_joints, |joint| pose[joint].into(), output);
+    }
+
+    /// Bake a clip of the source skeleton into a clip of the target skeleton,
+    /// sampled `frame_rate` times per second
+    pub fn retarget_clip(
+        &self,
+        clip: &SimpleState,
+        frame_rate: f32,
+    ) -> Result<RecordedClip, OzzError> {
+        let duration = clip.duration();
+        let frame_interval = if frame_rate > 0.0 { 1.0 / frame_rate } else { duration };
+        let frame_count = if frame_interval > 0.0 {
+            (duration / frame_interval).ceil() as usize
+        } else {
+            0
+        };
+
+        let mut recorder = PoseRecorder::new(0.0);
+        let mut source_pose = self.source.joint_rest_poses().to_vec();
+        let mut target_pose = self.target.joint_rest_poses().to_vec();
+        let mut previous_time = 0.0;
+        for frame in 0..=frame_count {
+            let time = (frame as f32 * frame_interval).min(duration);
+            clip.evaluate_clip_at(time, &mut source_pose)?;
+            self.retarget(&source_pose, &mut target_pose);
+            recorder.record_pose(&target_pose, time - previous_time);
+            previous_time = time;
+        }
+        Ok(recorder.finish())
+    }
+
+    fn retarget_with(
+        &self,
+        num_source_joints: usize,
+        source_joint: impl Fn(usize) -> Transform,
+        output: &mut [SoaTransform],
+    ) {
+        // Model space rotations of the source pose, parents come first
+        let mut source_rotations: Vec<Quat> = Vec::with_capacity(num_source_joints);
+        for joint in 0..num_source_joints {
+            let local = source_joint(joint).rotation;
+            let rotation = match joint_parent(&self.source, joint) {
+                Some(parent) if parent < source_rotations.len() => source_rotations[parent] * local,
+                _ => local,
+            };
+            source_rotations.push(rotation);
+        }
+
+        let num_target_joints = self.target.num_joints().min(output.len() * 4);
+        let mut target_rotations: Vec<Quat> = Vec::with_capacity(num_target_joints);
+        for joint in 0..num_target_joints {
+            let parent = joint_parent(&self.target, joint);
+            let parent_rotation = parent.map_or(Quat::IDENTITY, |parent| target_rotations[parent]);
+            let mut local = self.target_rest.local[joint];
+
+            if let Some(source) = self.sources[joint]
+                && source < num_source_joints
+            {
+                // Apply the source's model space change from its rest pose on
+                // top of the target's rest pose
+                let change = source_rotations[source]
+                    * self.source_rest.model_rotations[source].inverse();
+                let rotation = change * self.target_rest.model_rotations[joint];
+                local.rotation = (parent_rotation.inverse() * rotation).normalize();
+
+                let source_local = source_joint(source);
+                let source_rest = self.source_rest.local[source];
+                local.scale *=
+                    source_local.scale / source_rest.scale.max(Vec3::splat(f32::EPSILON));
+
+                if self.root == Some((source, joint)) {
+                    // Move the root by the source's offset from its rest
+                    // position, converted between the parents' spaces
+                    let source_parent = joint_parent(&self.source, source)
+                        .map_or(Quat::IDENTITY, |parent| source_rotations[parent]);
+                    let offset =
+                        source_parent * (source_local.translation - source_rest.translation);
+                    local.translation += parent_rotation.inverse() * offset * self.root_scale;
+                }
+            }
+
+            target_rotations.push(parent_rotation * local.rotation);
+            set_joint(output, joint, &local);
+        }
+    }
+}
+
+/// Loads `.bonemap.ron` files into [`BoneMap`]s
+#[derive(Default)]
+pub struct BoneMapLoader;
+
+/// Possible errors that can be produced by BoneMapLoader
+#[non_exhaustive]
+#[derive(Debug, Error)]
+pub enum BoneMapLoaderError {
+    /// An IO Error
+    #[error("Could not read the file: {0}")]
+    Io(#[from] std::io::Error),
+    /// A RON Error
+    #[error("Could not parse the bone map: {0}")]
+    Ron(#[from] ron::error::SpannedError),
+}
+
+impl AssetLoader for BoneMapLoader {
+    type Asset = BoneMap;
+    type Settings = ();
+    type Error = BoneMapLoaderError;
+
+    async fn load(
+        &self,
+        reader: &mut dyn Reader,
+        _settings: &(),
+        _load_context: &mut LoadContext<'_>,
+    ) -> Result<Self::Asset, Self::Error> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+        Ok(ron::de::from_bytes(&bytes)?)
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["bonemap.ron"]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_chain;
+    use std::f32::consts::FRAC_PI_2;
+
+    /// A source rig and a target rig twice its size, whose hips are turned a
+    /// quarter around Y in the rest pose and its spine turned back
+    fn retargeter() -> Retargeter {
+        let source = test_chain(&[
+            ("Hips", Transform::from_xyz(0.0, 1.0, 0.0)),
+            ("Spine", Transform::from_xyz(0.0, 0.5, 0.0)),
+        ]);
+        let target = test_chain(&[
+            (
+                "pelvis",
+                Transform::from_xyz(0.0, 2.0, 0.0).with_rotation(Quat::from_rotation_y(FRAC_PI_2)),
+            ),
+            (
+                "spine",
+                Transform::from_xyz(0.0, 1.0, 0.0).with_rotation(Quat::from_rotation_y(-FRAC_PI_2)),
+            ),
+        ]);
+        let map = BoneMap {
+            bones: vec![
+                ("Hips".to_string(), "pelvis".to_string()),
+                ("Spine".to_string(), "spine".to_string()),
+            ],
+            root: Some("Hips".to_string()),
+            root_scale: None,
+        };
+        Retargeter::new(source, target, &map).unwrap()
+    }
+
+    fn retarget(retargeter: &Retargeter, source_pose: &[SoaTransform]) -> Vec<SoaTransform> {
+        let mut output = retargeter.target().joint_rest_poses().to_vec();
+        retargeter.retarget(source_pose, &mut output);
+        output
+    }
+
+    fn assert_rotation(actual: Quat, expected: Quat) {
+        assert!(actual.angle_between(expected) < 1e-4, "{actual} != {expected}");
+    }
+
+    #[test]
+    fn source_rest_pose_plays_the_target_rest_pose() {
+        let retargeter = retargeter();
+        let output = retarget(&retargeter, retargeter.source().joint_rest_poses());
+        for joint in 0..2 {
+            let expected = get_joint(retargeter.target().joint_rest_poses(), joint);
+            let actual = get_joint(&output, joint);
+            assert_rotation(actual.rotation, expected.rotation);
+            assert!(actual.translation.abs_diff_eq(expected.translation, 1e-5));
+        }
+    }
+
+    #[test]
+    fn rotations_follow_the_source_in_model_space() {
+        let retargeter = retargeter();
+        let mut source_pose = retargeter.source().joint_rest_poses().to_vec();
+        let bend = Quat::from_rotation_x(0.5);
+        set_joint(
+            &mut source_pose,
+            1,
+            &Transform::from_xyz(0.0, 0.5, 0.0).with_rotation(bend),
+        );
+
+        let output = retarget(&retargeter, &source_pose);
+        let pelvis = get_joint(&output, 0);
+        let spine = get_joint(&output, 1);
+        // The target spine's rest model rotation is the identity, the bend
+        // applies as is despite the turned hips
+        assert_rotation(pelvis.rotation, Quat::from_rotation_y(FRAC_PI_2));
+        assert_rotation(pelvis.rotation * spine.rotation, bend);
+        // Bone lengths stay those of the target
+        assert!(spine.translation.abs_diff_eq(Vec3::new(0.0, 1.0, 0.0), 1e-5));
+    }
+
+    #[test]
+    fn root_translation_is_scaled_to_the_target() {
+        let retargeter = retargeter();
+        assert!((retargeter.root_scale() - 2.0).abs() < 1e-5);
+
+        let mut source_pose = retargeter.source().joint_rest_poses().to_vec();
+        set_joint(&mut source_pose, 0, &Transform::from_xyz(0.1, 1.2, 0.0));
+        let pelvis = get_joint(&retarget(&retargeter, &source_pose), 0);
+        assert!(pelvis.translation.abs_diff_eq(Vec3::new(0.2, 2.4, 0.0), 1e-5));
+    }
+}
@@ -0,0 +1,342 @@
+use super::pose::set_joint;
+use super::{AnimatorController, AnimatorError, OzzTransform};
+use bevy::prelude::*;
+use ozz_animation_rs::{Archive, Skeleton, SoaTransform};
+use std::collections::HashSet;
+
+/// A joint of the skeleton
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct JointInfo<'a> {
+    /// Index of the joint, as used by `BoneIndex` and the bone transforms
+    pub index: usize,
+    pub name: &'a str,
+    /// Index of the parent joint, None for roots
+    pub parent: Option<usize>,
+}
+
+impl AnimatorController {
+    /// Number of joints in the skeleton
+    #[inline]
+    pub fn joint_count(&self) -> usize {
+        self.skeleton.num_joints()
+    }
+
+    /// Iterate over every joint of the skeleton, parents always come before
+    /// their children
+    #[inline]
+    pub fn joints(&self) -> impl Iterator<Item = JointInfo<'_>> {
+        (0..self.joint_count()).filter_map(|index| joint_info(&self.skeleton, index))
+    }
+
+    /// Information about a single joint
+    #[inline]
+    pub fn joint(&self, index: usize) -> Option<JointInfo<'_>> {
+        joint_info(&self.skeleton, index)
+    }
+
+    /// Index of the joint with exactly this name
+    #[inline]
+    pub fn find_joint(&self, name: &str) -> Option<usize> {
+        find_joint(&self.skeleton, name, false)
+    }
+
+    /// Index of a joint by name, ignoring case, separators and namespace
+    /// prefixes, so "spine_01" finds "mixamorig:Spine01"
+    #[inline]
+    pub fn find_joint_fuzzy(&self, name: &str) -> Option<usize> {
+        find_joint(&self.skeleton, name, true)
+    }
+
+    /// Local transform of every joint in the latest pose, after blending and
+    /// bone overrides, in joint order. Empty until the first update.
+    /// ## Example
+    /// ```ignore
+    /// for (joint, transform) in controller.joints().zip(controller.local_pose()) {
+    ///     info!("{}: {:?}", joint.name, transform.rotation);
+    /// }
+    /// ```
+    #[inline]
+    pub fn local_pose(&self) -> &[OzzTransform] {
+        &self.bone_trans
+    }
+
+    /// Local transform of a single joint in the latest pose, see [`local_pose`](Self::local_pose)
+    #[inline]
+    pub fn local_transform(&self, index: usize) -> Option<Transform> {
+        self.bone_trans.get(index).map(|bone| (*bone).into())
+    }
+
+    /// Model space transform of every bone in the latest pose, relative to the
+    /// skeleton root
+    pub fn model_space_bones(&self) -> Vec<Mat4> {
+        let mut model: Vec<Mat4> = Vec::with_capacity(self.bone_trans.len());
+        for (index, bone) in self.bone_trans.iter().enumerate() {
+            let local =
+                Mat4::from_scale_rotation_translation(bone.scale, bone.rotation, bone.position);
+            // Parents are always stored before their children
+            let transform = match joint_parent(&self.skeleton, index) {
+                Some(parent) if parent < model.len() => model[parent] * local,
+                _ => local,
+            };
+            model.push(transform);
+        }
+        model
+    }
+
+    /// Model space transform of a single bone in the latest pose
+    pub fn model_space_bone(&self, index: usize) -> Option<Mat4> {
+        let mut joint = Some(index);
+        let mut model = Mat4::IDENTITY;
+        while let Some(current) = joint {
+            let bone = self.bone_trans.get(current)?;
+            model = Mat4::from_scale_rotation_translation(bone.scale, bone.rotation, bone.position)
+                * model;
+            joint = joint_parent(&self.skeleton, current);
+        }
+        Some(model)
+    }
+
+    /// Iterate over a joint and every joint below it in the hierarchy
+    #[inline]
+    pub fn joint_subtree(&self, root: usize) -> impl Iterator<Item = JointInfo<'_>> {
+        joint_subtree(&self.skeleton, root)
+            .into_iter()
+            .filter_map(|index| joint_info(&self.skeleton, index))
+    }
+}
+
+/// Index of the parent of a joint, None for roots
+#[inline]
+pub(crate) fn joint_parent(skeleton: &Skeleton, index: usize) -> Option<usize> {
+    let parent = *skeleton.joint_parents().get(index)?;
+    (parent >= 0).then_some(parent as usize)
+}
+
+#[inline]
+pub(crate) fn joint_info(skeleton: &Skeleton, index: usize) -> Option<JointInfo<'_>> {
+    if index >= skeleton.num_joints() {
+        return None;
+    }
+    let name = skeleton.joint_names().get_by_right(&(index as i16))?;
+    Some(JointInfo {
+        index,
+        name: name.as_str(),
+        parent: joint_parent(skeleton, index),
+    })
+}
+
+pub(crate) fn find_joint(skeleton: &Skeleton, name: &str, fuzzy: bool) -> Option<usize> {
+    if let Some(index) = skeleton.joint_names().get_by_left(name) {
+        return Some(*index as usize);
+    }
+    if !fuzzy {
+        return None;
+    }
+
+    // Compare without case or separators, then allow namespace prefixes,
+    // preferring the shortest joint name
+    let wanted = normalize_joint_name(name);
+    if wanted.is_empty() {
+        return None;
+    }
+    let mut best: Option<(usize, usize)> = None;
+    for (joint_name, index) in skeleton.joint_names().iter() {
+        let normalized = normalize_joint_name(joint_name);
+        if normalized == wanted {
+            return Some(*index as usize);
+        }
+        if normalized.ends_with(&wanted)
+            && best.is_none_or(|(_, length)| normalized.len() < length)
+        {
+            best = Some((*index as usize, normalized.len()));
+        }
+    }
+    best.map(|(index, _)| index)
+}
+
+/// Every joint below `root` including itself, in skeleton order
+pub(crate) fn joint_subtree(skeleton: &Skeleton, root: usize) -> Vec<usize> {
+    let num_joints = skeleton.num_joints();
+    if root >= num_joints {
+        return Vec::new();
+    }
+
+    // Parents are always stored before their children
+    let mut in_subtree = vec![false; num_joints];
+    in_subtree[root] = true;
+    let mut joints = vec![root];
+    for index in root + 1..num_joints {
+        if let Some(parent) = joint_parent(skeleton, index)
+            && in_subtree[parent]
+        {
+            in_subtree[index] = true;
+            joints.push(index);
+        }
+    }
+    joints
+}
+
+#[inline]
+fn normalize_joint_name(name: &str) -> String {
+    name.chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+/// A joint used to build a runtime skeleton
+#[derive(Debug, Clone)]
+pub struct SkeletonJoint {
+    pub name: String,
+    /// Index of the parent in the joint list, None for roots
+    pub parent: Option<usize>,
+    /// Local rest pose, relative to the parent
+    pub rest_pose: Transform,
+}
+
+/// Build an ozz skeleton from a joint list, so rigs without an offline ozz
+/// skeleton can still be driven by a controller. Joints may be in any order,
+/// they are renumbered so parents come first, use `find_joint` on the
+/// controller to look them up by name.
+pub fn skeleton_from_joints(joints: &[SkeletonJoint]) -> Result<Skeleton, AnimatorError> {
+    if joints.is_empty() {
+        return Err(AnimatorError::InvalidSkeleton("no joints".to_string()));
+    }
+    if joints.len() > i16::MAX as usize {
+        return Err(AnimatorError::InvalidSkeleton(format!(
+            "{} joints, at most {} are supported",
+            joints.len(),
+            i16::MAX
+        )));
+    }
+    if let Some(joint) = joints
+        .iter()
+        .find(|joint| joint.parent.is_some_and(|parent| parent >= joints.len()))
+    {
+        return Err(AnimatorError::InvalidSkeleton(format!(
+            "joint '{}' has a parent out of range",
+            joint.name
+        )));
+    }
+
+    // Depth-first order, parents before children
+    let mut children = vec![Vec::new(); joints.len()];
+    let mut stack = Vec::new();
+    for (index, joint) in joints.iter().enumerate().rev() {
+        match joint.parent {
+            Some(parent) => children[parent].insert(0, index),
+            None => stack.push(index),
+        }
+    }
+    let mut order = Vec::with_capacity(joints.len());
+    while let Some(index) = stack.pop() {
+        order.push(index);
+        stack.extend(children[index].iter().rev());
+    }
+    if order.len() != joints.len() {
+        return Err(AnimatorError::InvalidSkeleton(
+            "the joint hierarchy contains a cycle".to_string(),
+        ));
+    }
+
+    let mut new_index = vec![0; joints.len()];
+    for (new, &old) in order.iter().enumerate() {
+        new_index[old] = new;
+    }
+
+    let mut names = HashSet::with_capacity(joints.len());
+    let mut parents = Vec::with_capacity(joints.len());
+    let mut rest_poses = vec![SoaTransform::IDENTITY; joints.len().div_ceil(4)];
+    for (new, &old) in order.iter().enumerate() {
+        let joint = &joints[old];
+        if joint.name.contains('\0') {
+            return Err(AnimatorError::InvalidSkeleton(format!(
+                "joint '{}' has a nul character in its name",
+                joint.name
+            )));
+        }
+        if !names.insert(joint.name.as_str()) {
+            return Err(AnimatorError::InvalidSkeleton(format!(
+                "more than one joint is named '{}'",
+                joint.name
+            )));
+        }
+        parents.push(joint.parent.map_or(-1, |parent| new_index[parent] as i16));
+        set_joint(&mut rest_poses, new, &joint.rest_pose);
+    }
+
+    // ozz only builds skeletons from archives, so write one in memory
+    let mut bytes = Vec::new();
+    bytes.push(u8::from(cfg!(target_endian = "little")));
+    bytes.extend_from_slice(Skeleton::tag().as_bytes());
+    bytes.push(0);
+    bytes.extend_from_slice(&Skeleton::version().to_ne_bytes());
+    bytes.extend_from_slice(&(joints.len() as u32).to_ne_bytes());
+    let char_count: usize = order.iter().map(|&old| joints[old].name.len() + 1).sum();
+    bytes.extend_from_slice(&(char_count as u32).to_ne_bytes());
+    for &old in &order {
+        bytes.extend_from_slice(joints[old].name.as_bytes());
+        bytes.push(0);
+    }
+    for parent in &parents {
+        bytes.extend_from_slice(&parent.to_ne_bytes());
+    }
+    for soa in &rest_poses {
+        let lanes = [
+            soa.translation.x,
+            soa.translation.y,
+            soa.translation.z,
+            soa.rotation.x,
+            soa.rotation.y,
+            soa.rotation.z,
+            soa.rotation.w,
+            soa.scale.x,
+            soa.scale.y,
+            soa.scale.z,
+        ];
+        for lane in &lanes {
+            for index in 0..4 {
+                bytes.extend_from_slice(&lane[index].to_ne_bytes());
+            }
+        }
+    }
+    let mut archive = Archive::from_vec(bytes)?;
+    Skeleton::from_archive(&mut archive)
+        .map_err(|error| AnimatorError::InvalidSkeleton(error.to_string()))
+}
+
+/// Build an ozz skeleton from the joints of a skinned mesh, using their names
+/// and current local transforms as the rest pose. A joint's parent is its
+/// closest ancestor that is also a joint of the mesh.
+#[cfg(feature = "render")]
+pub fn skeleton_from_skinned_mesh(
+    skinned_mesh: &bevy::render::mesh::skinning::SkinnedMesh,
+    joints: &Query<(&Name, &Transform)>,
+    parents: &Query<&ChildOf>,
+) -> Result<Skeleton, AnimatorError> {
+    let mut skeleton_joints = Vec::with_capacity(skinned_mesh.joints.len());
+    for &entity in &skinned_mesh.joints {
+        let Ok((name, transform)) = joints.get(entity) else {
+            return Err(AnimatorError::InvalidSkeleton(format!(
+                "joint {entity} has no Name or Transform"
+            )));
+        };
+
+        let mut parent = None;
+        let mut current = entity;
+        while let Ok(child_of) = parents.get(current) {
+            current = child_of.parent();
+            if let Some(index) = skinned_mesh.joints.iter().position(|&j| j == current) {
+                parent = Some(index);
+                break;
+            }
+        }
+
+        skeleton_joints.push(SkeletonJoint {
+            name: name.to_string(),
+            parent,
+            rest_pose: *transform,
+        });
+    }
+    skeleton_from_joints(&skeleton_joints)
+}
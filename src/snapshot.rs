@@ -0,0 +1,219 @@
+use super::missing_parameters::is_set;
+use super::{AnimatorError, JointMask, ParameterValue, Parameters};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Serializable runtime state of an `AnimatorController`, used for savegames.
+///
+/// Snapshots are keyed by the controller's graph version, a snapshot taken from
+/// one version of a graph will refuse to load into another.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AnimatorSnapshot {
+    /// Graph version of the controller the snapshot was taken from
+    pub graph_version: u32,
+    /// Parameter values at the time of the snapshot
+    pub parameters: Parameters,
+    /// Runtime state of every layer, in layer order
+    pub layers: Vec<LayerSnapshot>,
+}
+
+/// Serializable runtime state of an `AnimationLayer`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LayerSnapshot {
+    pub name: String,
+    pub weight: f32,
+    pub current_state: String,
+    pub next_state: Option<String>,
+    pub transition_time: f32,
+    pub transition_duration: f32,
+    #[serde(default)]
+    pub freeze_source: bool,
+    /// Playback times of every clip, keyed by state name
+    pub state_times: BTreeMap<String, Vec<f32>>,
+    /// Random roll of the current state and state of the layer's random
+    /// numbers, None keeps the layer's own
+    #[serde(default)]
+    pub random: Option<(f32, u64)>,
+    /// Loops the current state played since the layer entered it, which exit
+    /// times past 1 count from
+    #[serde(default)]
+    pub loop_count: u32,
+    /// Mask of the transition in progress, by joint name
+    #[serde(default)]
+    pub transition_mask: Option<JointMask>,
+}
+
+impl Parameters {
+    /// Fail if `snapshot` has a parameter these don't, or one of another type.
+    /// Loop count ints are written by the controller, so they may not exist yet.
+    pub(crate) fn check_snapshot(
+        &self,
+        snapshot: &Parameters,
+        is_loop_count: impl Fn(&str) -> bool,
+    ) -> Result<(), AnimatorError> {
+        for (name, value) in snapshot.iter() {
+            let known = match value {
+                ParameterValue::Trigger => self.find_id(name).is_some(),
+                ParameterValue::Int(_) if is_loop_count(name) => true,
+                value => is_set(self, name, value),
+            };
+            if !known {
+                return Err(AnimatorError::SnapshotParameter(name.to_string()));
+            }
+        }
+        Ok(())
+    }
+
+    /// Copy the values of a snapshot by name, so every `ParamId` handed out
+    /// keeps pointing at the same parameter
+    pub(crate) fn restore_snapshot(&mut self, snapshot: &Parameters) {
+        self.reset_triggers();
+        self.apply(snapshot.iter());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        AnimationLayer, AnimatorController, LayerBlendType, SimpleState, SyntheticAnimators,
+        Transition, TransitionCondition, synthetic_clip, synthetic_skeleton,
+    };
+    use bevy::prelude::Time;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    fn controller() -> AnimatorController {
+        let animators = SyntheticAnimators::new(1, 4);
+        let (skeleton, clips) = animators.rig().unwrap();
+        animators.controller(&skeleton, &clips, 0).unwrap()
+    }
+
+    /// A snapshot after a round trip through its serialized form
+    fn saved(controller: &AnimatorController) -> AnimatorSnapshot {
+        ron::from_str(&ron::to_string(&controller.snapshot()).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn parameters_are_restored_by_name() {
+        let mut controller = controller();
+        let parameters = controller.parameters_mut();
+        for index in 0..8 {
+            parameters.set_float(&format!("float {index}"), index as f32);
+        }
+        parameters.set_bool("grounded", true);
+        let grounded = parameters.find_id("grounded").unwrap();
+        let speed = parameters.find_id("float 3").unwrap();
+        let snapshot = saved(&controller);
+
+        let parameters = controller.parameters_mut();
+        parameters.set_bool("grounded", false);
+        parameters.set_float("float 3", 10.0);
+        parameters.set_trigger("float 3");
+        controller.restore_snapshot(&snapshot).unwrap();
+        let parameters = controller.parameters();
+        assert_eq!(parameters.get_bool_by_id(grounded), Some(true));
+        assert_eq!(parameters.get_float_by_id(speed), Some(3.0));
+        assert!(!parameters.get_trigger("float 3"));
+    }
+
+    #[test]
+    fn unknown_and_mistyped_parameters_are_rejected() {
+        let mut controller = controller();
+        controller.parameters_mut().set_bool("grounded", true);
+        let mut snapshot = controller.snapshot();
+        snapshot.parameters.set_float("missing", 1.0);
+        let error = controller.restore_snapshot(&snapshot).unwrap_err();
+        assert!(matches!(error, AnimatorError::SnapshotParameter(name) if name == "missing"));
+
+        let mut snapshot = controller.snapshot();
+        snapshot.parameters = Parameters::new();
+        snapshot.parameters.set_int("grounded", 1);
+        let error = controller.restore_snapshot(&snapshot).unwrap_err();
+        assert!(matches!(error, AnimatorError::SnapshotParameter(name) if name == "grounded"));
+        assert_eq!(controller.parameters().get_bool("grounded"), Some(true));
+    }
+
+    /// Walks to Run once Walk played two and a half loops, and crossfades back
+    /// over the second chain only once `stop` is set
+    fn walk_and_run() -> AnimatorController {
+        let skeleton = Arc::new(synthetic_skeleton(6, 2).unwrap());
+        let clip = Arc::new(synthetic_clip(&skeleton, 1.0, 30.0, 0));
+        let mut layer = AnimationLayer::new(
+            "Base".to_string(),
+            LayerBlendType::Override,
+            1.0,
+            &skeleton,
+            "Walk".to_string(),
+        );
+        for state in ["Walk", "Run"] {
+            let clip = SimpleState::from_recording(clip.clone(), skeleton.num_soa_joints());
+            layer.add_state(state.to_string(), clip.into());
+        }
+        layer.add_transition(
+            "Walk".to_string(),
+            Transition {
+                to_state: "Run".to_string(),
+                has_exit_time: true,
+                exit_time: 2.5,
+                ..Default::default()
+            },
+        );
+        layer.add_transition(
+            "Run".to_string(),
+            Transition {
+                to_state: "Walk".to_string(),
+                duration: 1.0,
+                conditions: vec![TransitionCondition::Bool("stop".to_string(), true)],
+                mask: Some(JointMask::subtree(&skeleton, "Joint2").unwrap()),
+                ..Default::default()
+            },
+        );
+        let mut parameters = Parameters::new();
+        parameters.set_bool("stop", false);
+        AnimatorController::new(skeleton, vec![layer], parameters).unwrap()
+    }
+
+    fn advance(controller: &mut AnimatorController, time: &mut Time, seconds: f32) {
+        time.advance_by(Duration::from_secs_f32(seconds));
+        controller.update(time).unwrap();
+    }
+
+    #[test]
+    fn exit_times_past_one_loop_survive_a_load() {
+        let mut controller = walk_and_run();
+        let mut time = Time::default();
+        advance(&mut controller, &mut time, 0.75);
+        advance(&mut controller, &mut time, 0.75);
+        assert_eq!(controller.layers[0].loop_count(), 1);
+
+        let mut loaded = walk_and_run();
+        loaded.restore_snapshot(&saved(&controller)).unwrap();
+        assert_eq!(loaded.layers[0].loop_count(), 1);
+        // 2.6 loops in, the exit time is checked on the next update
+        advance(&mut loaded, &mut time, 1.1);
+        assert_eq!(loaded.layers[0].current_state(), "Walk");
+        advance(&mut loaded, &mut time, 0.01);
+        assert_eq!(loaded.layers[0].current_state(), "Run");
+    }
+
+    #[test]
+    fn masked_crossfades_survive_a_load() {
+        let mut controller = walk_and_run();
+        let mut time = Time::default();
+        advance(&mut controller, &mut time, 2.6);
+        advance(&mut controller, &mut time, 0.01);
+        controller.parameters_mut().set_bool("stop", true);
+        advance(&mut controller, &mut time, 0.25);
+        let snapshot = saved(&controller);
+        assert!(snapshot.layers[0].transition_mask.is_some());
+
+        let mut loaded = walk_and_run();
+        loaded.restore_snapshot(&snapshot).unwrap();
+        let mask = JointMask::subtree(&loaded.skeleton, "Joint2").unwrap();
+        assert_eq!(loaded.snapshot().layers[0].transition_mask, Some(mask));
+        advance(&mut controller, &mut time, 0.1);
+        advance(&mut loaded, &mut Time::default(), 0.1);
+        assert_eq!(loaded.local_pose(), controller.local_pose());
+    }
+}
@@ -1,11 +1,19 @@
 use super::blend_tree::BlendTree;
-use super::Parameters;
-use ozz_animation_rs::{Animation, SamplingContext, SamplingJob, SamplingJobArc, SoaTransform, OzzError};
+use super::curve_output::CurveSample;
+use super::parameters::ParamBinding;
+use super::{
+    AnimatorController, AnimatorError, ExternalPose, MotionStats, ParameterValue, Parameters,
+    PoseRecorder, RecordedClip, read_lock, write_lock,
+};
+use ozz_animation_rs::{
+    Animation, OzzError, SamplingContext, SamplingJob, SamplingJobArc, SoaTransform, Track,
+    TrackSamplingJobArc,
+};
 use std::fmt::Debug;
 use std::sync::{Arc, RwLock};
 use bevy::prelude::Time;
+use serde::{Deserialize, Serialize};
 
-// TODO: TIME SHOULDNT BE USING ELAPSED_SECS, WE SHOULD BE ABLE TO CONTROL IT
 /// Base trait for animation states
 // pub trait AnimationState: Send + Sync + Debug {
 //     fn update(&mut self, time: &Time) -> Result<(), OzzError>;
@@ -17,22 +25,171 @@ use bevy::prelude::Time;
 pub enum AnimationState {
     Simple(SimpleState),
     Blend(BlendState),
+    /// A pose filled in by gameplay code, see [`ExternalPose`]
+    External(ExternalPose),
+    /// The final pose of another controller, see
+    /// [`AnimatorController::add_sub_controller`]
+    Controller(Box<AnimatorController>),
+}
+
+impl From<SimpleState> for AnimationState {
+    fn from(state: SimpleState) -> Self {
+        AnimationState::Simple(state)
+    }
+}
+
+impl From<BlendState> for AnimationState {
+    fn from(state: BlendState) -> Self {
+        AnimationState::Blend(state)
+    }
+}
+
+impl From<ExternalPose> for AnimationState {
+    fn from(pose: ExternalPose) -> Self {
+        AnimationState::External(pose)
+    }
+}
+
+/// Playback speed of a state, optionally scaled by a float parameter
+#[derive(Debug, Clone)]
+struct PlaybackSpeed {
+    speed: f32,
+    parameter: Option<String>,
+    binding: ParamBinding,
+}
+
+impl Default for PlaybackSpeed {
+    #[inline]
+    fn default() -> Self {
+        Self {
+            speed: 1.0,
+            parameter: None,
+            binding: ParamBinding::default(),
+        }
+    }
+}
+
+impl PlaybackSpeed {
+    /// Speed for this update, a missing parameter counts as 1 and playback
+    /// never runs backwards
+    #[inline]
+    fn resolve(&self, params: &Parameters) -> f32 {
+        let multiplier = self
+            .parameter
+            .as_deref()
+            .and_then(|name| params.bound_float(self.binding, name))
+            .unwrap_or(1.0);
+        (self.speed * multiplier).max(0.0)
+    }
+
+    #[inline]
+    fn bind_parameters(&mut self, params: &Parameters) {
+        if let Some(parameter) = &self.parameter {
+            self.binding = ParamBinding::resolve(params, parameter);
+        }
+    }
+
+    #[inline]
+    fn referenced_parameters(&self, out: &mut Vec<(String, ParameterValue)>) {
+        if let Some(parameter) = &self.parameter {
+            out.push((parameter.clone(), ParameterValue::Float(1.0)));
+        }
+    }
+
+    #[inline]
+    fn same_graph(&self, other: &PlaybackSpeed) -> bool {
+        self.speed == other.speed && self.parameter == other.parameter
+    }
+}
+
+/// What a simple state does at the end of its clip
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum PlaybackMode {
+    /// Wrap around to the start
+    #[default]
+    Loop,
+    /// Hold the last frame and report the state as finished, for one-shots
+    /// like attacks and deaths
+    Clamp,
+}
+
+/// What a simple state does when one update moves playback further than the
+/// length of its clip, e.g. after a loading hitch or alt-tab
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SpikePolicy {
+    /// Wrap around as many times as needed, firing every marker crossed
+    #[default]
+    FireAll,
+    /// Wrap around as many times as needed, but fire no markers for the update
+    SkipEvents,
+    /// Advance by less than one clip length, so at most one loop's worth of
+    /// playback and markers happens in the update
+    Clamp,
+}
+
+/// Where the poses of a simple state come from
+#[derive(Debug)]
+enum ClipSource {
+    /// An ozz animation
+    Ozz(SamplingJobArc),
+    /// Poses recorded at runtime
+    Recorded(Arc<RecordedClip>),
+}
+
+/// A named float track of a state, with the job sampling it every update
+#[derive(Debug)]
+struct FloatTrack {
+    name: Arc<str>,
+    job: TrackSamplingJobArc<f32>,
+}
+
+impl FloatTrack {
+    #[inline]
+    fn track(&self) -> Option<&Arc<Track<f32>>> {
+        self.job.track()
+    }
+
+    /// If both sample the same track under the same name
+    #[inline]
+    fn same_graph(&self, other: &FloatTrack) -> bool {
+        self.name == other.name
+            && match (self.track(), other.track()) {
+                (Some(old), Some(new)) => Arc::ptr_eq(old, new),
+                (old, new) => old.is_none() && new.is_none(),
+            }
+    }
 }
 
 /// Simple state containing a single animation
 #[derive(Debug)]
 pub struct SimpleState {
-    sampling_job: SamplingJobArc,
+    source: ClipSource,
     output: Arc<RwLock<Vec<SoaTransform>>>,
+    /// Local playback time in seconds
+    time: f32,
+    /// Number of times playback wrapped around the end of the clip
+    loop_count: u32,
+    /// Named markers at normalized times
+    markers: Vec<(f32, String)>,
+    /// Markers crossed since they were last drained
+    fired_markers: Vec<String>,
+    /// Named float tracks sampled with the clip
+    float_tracks: Vec<FloatTrack>,
+    speed: PlaybackSpeed,
+    spike_policy: SpikePolicy,
+    playback_mode: PlaybackMode,
+    /// If a clamped clip reached its end
+    finished: bool,
 }
 
+// SAFETY: see AnimatorController
 unsafe impl Send for SimpleState {}
 unsafe impl Sync for SimpleState {}
 
 impl SimpleState {
     /// Create a new simple state that holds a single animation.
     /// ## Example
-    /// ```
+    /// ```ignore
     /// use bevy::tasks::futures_lite::future::try_zip;
     ///
     /// let (mut skeleton, mut animation) = try_zip(
@@ -55,24 +212,460 @@ impl SimpleState {
         sampling_job.set_output(sample_out.clone());
 
         Self {
-            sampling_job,
+            source: ClipSource::Ozz(sampling_job),
             output: sample_out,
+            time: 0.0,
+            loop_count: 0,
+            markers: Vec::new(),
+            fired_markers: Vec::new(),
+            float_tracks: Vec::new(),
+            speed: PlaybackSpeed::default(),
+            spike_policy: SpikePolicy::default(),
+            playback_mode: PlaybackMode::default(),
+            finished: false,
+        }
+    }
+
+    /// Create a new simple state that plays back a recorded clip, see [`PoseRecorder`](super::PoseRecorder)
+    #[inline]
+    pub fn from_recording(clip: Arc<RecordedClip>, joint_count: usize) -> Self {
+        Self {
+            source: ClipSource::Recorded(clip),
+            output: Arc::new(RwLock::new(vec![SoaTransform::default(); joint_count])),
+            time: 0.0,
+            loop_count: 0,
+            markers: Vec::new(),
+            fired_markers: Vec::new(),
+            float_tracks: Vec::new(),
+            speed: PlaybackSpeed::default(),
+            spike_policy: SpikePolicy::default(),
+            playback_mode: PlaybackMode::default(),
+            finished: false,
         }
     }
+
+    /// Play the clip `speed` times faster than real time
+    #[inline]
+    pub fn with_speed(mut self, speed: f32) -> Self {
+        self.set_speed(speed);
+        self
+    }
+
+    /// Play the clip once and hold its last frame, see [`PlaybackMode::Clamp`]
+    /// ## Example
+    /// ```ignore
+    /// let death = SimpleState::new(death_animation, skeleton.num_soa_joints())
+    ///     .with_playback_mode(PlaybackMode::Clamp);
+    /// ```
+    #[inline]
+    pub fn with_playback_mode(mut self, playback_mode: PlaybackMode) -> Self {
+        self.set_playback_mode(playback_mode);
+        self
+    }
+
+    /// Choose how an update longer than the clip is played, see [`SpikePolicy`]
+    /// ## Example
+    /// ```ignore
+    /// // A short footstep loop shouldn't play a burst of footsteps after a hitch
+    /// let walk = SimpleState::new(walk_animation, skeleton.num_soa_joints())
+    ///     .with_marker("footstep", 0.5)
+    ///     .with_spike_policy(SpikePolicy::SkipEvents);
+    /// ```
+    #[inline]
+    pub fn with_spike_policy(mut self, spike_policy: SpikePolicy) -> Self {
+        self.spike_policy = spike_policy;
+        self
+    }
+
+    /// Also multiply the playback speed by a float parameter every update
+    /// ## Example
+    /// ```ignore
+    /// let attack = SimpleState::new(attack_animation, skeleton.num_soa_joints())
+    ///     .with_speed_parameter("attack_speed");
+    /// ```
+    #[inline]
+    pub fn with_speed_parameter(mut self, parameter: impl Into<String>) -> Self {
+        self.speed.parameter = Some(parameter.into());
+        self.speed.binding = ParamBinding::default();
+        self
+    }
+
+    /// Resolve the speed parameter to its id, see
+    /// [`AnimationLayer::bind_parameters`](super::AnimationLayer::bind_parameters)
+    #[inline]
+    pub fn bind_parameters(&mut self, params: &Parameters) {
+        self.speed.bind_parameters(params);
+    }
 }
 
 impl SimpleState {
     #[inline]
     pub fn update(&mut self, time: &Time) -> Result<(), OzzError> {
-        let Some(animation) = self.sampling_job.animation() else {
-            return Ok(());
+        self.tick(time, &Parameters::new(), true)
+    }
+
+    /// Advance playback, only sampling the animation if `sample` is set
+    #[inline]
+    pub(crate) fn tick(
+        &mut self,
+        time: &Time,
+        params: &Parameters,
+        sample: bool,
+    ) -> Result<(), OzzError> {
+        let duration = self.duration();
+        self.time = if duration > 0.0 {
+            let mut delta = time.delta_secs() * self.speed.resolve(params);
+            let spike = delta >= duration;
+            if spike && self.spike_policy == SpikePolicy::Clamp {
+                delta = duration * (1.0 - f32::EPSILON);
+            }
+            let mut advanced = self.time + delta;
+            if self.playback_mode == PlaybackMode::Clamp {
+                advanced = advanced.min(duration);
+            }
+            if !(spike && self.spike_policy == SpikePolicy::SkipEvents) {
+                self.fire_markers(self.time / duration, advanced / duration);
+            }
+            match self.playback_mode {
+                PlaybackMode::Loop => {
+                    if advanced >= duration {
+                        self.loop_count += (advanced / duration) as u32;
+                    }
+                    advanced % duration
+                }
+                PlaybackMode::Clamp => {
+                    self.finished = advanced >= duration;
+                    advanced
+                }
+            }
+        } else {
+            0.0
+        };
+        if sample {
+            self.sample()?;
+        }
+        Ok(())
+    }
+
+    /// Sample the animation at the current playback time without advancing it
+    #[inline]
+    pub(crate) fn sample(&mut self) -> Result<(), OzzError> {
+        let duration = self.duration();
+        match &mut self.source {
+            ClipSource::Ozz(sampling_job) => {
+                if sampling_job.animation().is_none() {
+                    return Ok(());
+                }
+                let ratio = if duration > 0.0 { self.time / duration } else { 0.0 };
+                sampling_job.set_ratio(ratio);
+                sampling_job.run()
+            }
+            ClipSource::Recorded(clip) => {
+                clip.sample(self.time, &mut write_lock(&self.output));
+                Ok(())
+            }
+        }
+    }
+
+    /// Local playback time in seconds
+    #[inline]
+    pub fn time(&self) -> f32 {
+        self.time
+    }
+
+    /// Set the local playback time in seconds, applied on the next update
+    #[inline]
+    pub fn set_time(&mut self, time: f32) {
+        self.time = time.max(0.0);
+        if self.playback_mode == PlaybackMode::Clamp {
+            let duration = self.duration();
+            self.time = self.time.min(duration);
+            self.finished = self.time >= duration;
+        }
+    }
+
+    /// Whether the clip loops or stops on its last frame
+    #[inline]
+    pub fn playback_mode(&self) -> PlaybackMode {
+        self.playback_mode
+    }
+
+    #[inline]
+    pub fn set_playback_mode(&mut self, playback_mode: PlaybackMode) {
+        self.playback_mode = playback_mode;
+        self.finished = false;
+    }
+
+    /// If a `PlaybackMode::Clamp` state reached the end of its clip
+    #[inline]
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    /// Number of times playback wrapped around the end of the clip
+    #[inline]
+    pub fn loop_count(&self) -> u32 {
+        self.loop_count
+    }
+
+    /// Playback speed multiplier, before any speed parameter
+    #[inline]
+    pub fn speed(&self) -> f32 {
+        self.speed.speed
+    }
+
+    #[inline]
+    pub fn set_speed(&mut self, speed: f32) {
+        self.speed.speed = speed.max(0.0);
+    }
+
+    /// Float parameter the speed is multiplied by
+    #[inline]
+    pub fn speed_parameter(&self) -> Option<&str> {
+        self.speed.parameter.as_deref()
+    }
+
+    #[inline]
+    pub fn spike_policy(&self) -> SpikePolicy {
+        self.spike_policy
+    }
+
+    #[inline]
+    pub fn set_spike_policy(&mut self, spike_policy: SpikePolicy) {
+        self.spike_policy = spike_policy;
+    }
+
+    /// Playback time from 0 at the start to 1 at the end of the clip
+    #[inline]
+    pub fn normalized_time(&self) -> f32 {
+        let duration = self.duration();
+        if duration > 0.0 { self.time / duration } else { 0.0 }
+    }
+
+    /// Fire a named marker every time playback crosses a normalized time,
+    /// e.g. footsteps. Sent as an `AnimationMarkerEvent`.
+    /// ## Example
+    /// ```ignore
+    /// let walk = SimpleState::new(walk_animation, skeleton.num_soa_joints())
+    ///     .with_marker("footstep", 0.3)
+    ///     .with_marker("footstep", 0.8);
+    /// ```
+    #[inline]
+    pub fn with_marker(mut self, name: impl Into<String>, normalized_time: f32) -> Self {
+        self.add_marker(name, normalized_time);
+        self
+    }
+
+    #[inline]
+    pub fn add_marker(&mut self, name: impl Into<String>, normalized_time: f32) {
+        self.markers
+            .push((normalized_time.clamp(0.0, 1.0), name.into()));
+    }
+
+    /// Named markers and their normalized times, in the order they were added
+    #[inline]
+    pub fn markers(&self) -> impl Iterator<Item = (&str, f32)> {
+        self.markers.iter().map(|(time, name)| (name.as_str(), *time))
+    }
+
+    /// Sample a float track at the same normalized time as the clip every
+    /// update, driving a named curve like a weapon trail's intensity. Read
+    /// the blended value with [`AnimatorController::curve_value`].
+    /// ## Example
+    /// ```ignore
+    /// let trail = Arc::new(Track::<f32>::from_path("/slash_trail.ozz").unwrap());
+    /// let slash = SimpleState::new(slash_animation, skeleton.num_soa_joints())
+    ///     .with_float_track("weapon_trail_intensity", trail);
+    /// ```
+    #[inline]
+    pub fn with_float_track(mut self, name: impl Into<String>, track: Arc<Track<f32>>) -> Self {
+        self.add_float_track(name, track);
+        self
+    }
+
+    /// Add a float track, replacing any with the same name
+    #[inline]
+    pub fn add_float_track(&mut self, name: impl Into<String>, track: Arc<Track<f32>>) {
+        let name = name.into();
+        match self.float_tracks.iter_mut().find(|existing| *existing.name == name) {
+            Some(existing) => existing.job.set_track(track),
+            None => {
+                let mut job = TrackSamplingJobArc::default();
+                job.set_track(track);
+                self.float_tracks.push(FloatTrack {
+                    name: name.into(),
+                    job,
+                });
+            }
+        }
+    }
+
+    /// Names of the float tracks, in the order they were added
+    #[inline]
+    pub fn float_tracks(&self) -> impl Iterator<Item = &str> {
+        self.float_tracks.iter().map(|track| &*track.name)
+    }
+
+    /// Value of a float track at the current playback time
+    pub fn sample_float_track(&self, name: &str) -> Option<f32> {
+        let track = self.float_tracks.iter().find(|track| *track.name == *name)?;
+        sample_track(track.track()?, self.normalized_time())
+    }
+
+    /// Sample every float track into `out` as name, value and weight
+    pub(crate) fn sample_curves(&mut self, weight: f32, out: &mut Vec<CurveSample>) {
+        let ratio = self.normalized_time();
+        for track in &mut self.float_tracks {
+            track.job.set_ratio(ratio);
+            if track.job.run().is_ok() {
+                out.push((track.name.clone(), track.job.result(), weight));
+            }
+        }
+    }
+
+    /// Queue every marker crossed moving from `from` to `to`, in normalized
+    /// time that keeps counting past 1 on loops
+    #[inline]
+    fn fire_markers(&mut self, from: f32, to: f32) {
+        for (time, name) in &self.markers {
+            let crossings = (to - time).floor() - (from - time).floor();
+            for _ in 0..crossings.max(0.0) as u32 {
+                self.fired_markers.push(name.clone());
+            }
+        }
+    }
+
+    /// Move the markers crossed by the last update into `out`, or drop them if it is None
+    #[inline]
+    pub(crate) fn drain_markers(&mut self, out: Option<&mut Vec<String>>) {
+        match out {
+            Some(out) => out.append(&mut self.fired_markers),
+            None => self.fired_markers.clear(),
+        }
+    }
+
+    /// Sample the animation at a time in seconds into `output`, without
+    /// advancing or otherwise touching this state. Looping states wrap the
+    /// time around, clamped ones hold their first and last frame.
+    pub fn evaluate_at(&self, time: f32, output: &mut [SoaTransform]) -> Result<(), OzzError> {
+        let duration = self.duration();
+        let time = match self.playback_mode {
+            PlaybackMode::Loop if duration > 0.0 => time.max(0.0) % duration,
+            PlaybackMode::Loop => 0.0,
+            PlaybackMode::Clamp => time.clamp(0.0, duration),
+        };
+        self.evaluate_clip_at(time, output)
+    }
+
+    /// Sample the clip at a time in seconds between 0 and its duration,
+    /// whatever the playback mode
+    pub(crate) fn evaluate_clip_at(
+        &self,
+        time: f32,
+        output: &mut [SoaTransform],
+    ) -> Result<(), OzzError> {
+        let duration = self.duration();
+        let time = time.clamp(0.0, duration);
+        let animation = match &self.source {
+            ClipSource::Ozz(sampling_job) => match sampling_job.animation() {
+                Some(animation) => animation,
+                None => return Ok(()),
+            },
+            ClipSource::Recorded(clip) => {
+                clip.sample(time, output);
+                return Ok(());
+            }
         };
-        let duration = animation.duration();
-        self.sampling_job.set_ratio((time.elapsed_secs() % duration) / duration);
-        self.sampling_job.run()?;
+        let sample_out = Arc::new(RwLock::new(vec![SoaTransform::default(); output.len()]));
+        let mut sampling_job: SamplingJobArc = SamplingJob::default();
+        sampling_job.set_context(SamplingContext::new(animation.num_tracks()));
+        sampling_job.set_animation(animation.clone());
+        sampling_job.set_output(sample_out.clone());
+
+        sampling_job.set_ratio(if duration > 0.0 { time / duration } else { 0.0 });
+        sampling_job.run()?;
+        output.clone_from_slice(&read_lock(&sample_out)[..output.len()]);
         Ok(())
     }
 
+    /// Bake the additive difference between this animation and a reference
+    /// pose into a clip, sampled `frame_rate` times per second. The result
+    /// plays on an additive layer like an additive animation authored offline.
+    /// ## Example
+    /// ```ignore
+    /// let reference = controller.bake_pose(&parameters, 0.0)?;
+    /// let recoil = Arc::new(recoil_state.bake_additive(reference.soa(), 30.0)?);
+    /// let additive_state = SimpleState::from_recording(recoil, skeleton.num_soa_joints());
+    /// ```
+    pub fn bake_additive(
+        &self,
+        reference: &[SoaTransform],
+        frame_rate: f32,
+    ) -> Result<RecordedClip, OzzError> {
+        let duration = self.duration();
+        let frame_interval = if frame_rate > 0.0 { 1.0 / frame_rate } else { duration };
+        let frame_count = if frame_interval > 0.0 {
+            (duration / frame_interval).ceil() as usize
+        } else {
+            0
+        };
+
+        let mut recorder = PoseRecorder::new(0.0);
+        let mut pose = vec![SoaTransform::default(); read_lock(&self.output).len()];
+        let mut previous_time = 0.0;
+        for frame in 0..=frame_count {
+            let time = (frame as f32 * frame_interval).min(duration);
+            self.evaluate_clip_at(time, &mut pose)?;
+            recorder.record_pose(&pose, time - previous_time);
+            previous_time = time;
+        }
+        Ok(recorder.finish().difference(reference))
+    }
+
+    /// Duration of the animation in seconds
+    #[inline]
+    pub fn duration(&self) -> f32 {
+        match &self.source {
+            ClipSource::Ozz(sampling_job) => sampling_job
+                .animation()
+                .map(|animation| animation.duration())
+                .unwrap_or(0.0),
+            ClipSource::Recorded(clip) => clip.duration(),
+        }
+    }
+
+    /// If both states play the same clip the same way, ignoring their
+    /// runtime state
+    pub(crate) fn same_graph(&self, other: &SimpleState) -> bool {
+        let same_clip = match (&self.source, &other.source) {
+            (ClipSource::Ozz(old), ClipSource::Ozz(new)) => {
+                match (old.animation(), new.animation()) {
+                    (Some(old), Some(new)) => Arc::ptr_eq(old, new),
+                    (old, new) => old.is_none() && new.is_none(),
+                }
+            }
+            (ClipSource::Recorded(old), ClipSource::Recorded(new)) => Arc::ptr_eq(old, new),
+            _ => false,
+        };
+        same_clip
+            && self.playback_mode == other.playback_mode
+            && self.speed.same_graph(&other.speed)
+            && self.spike_policy == other.spike_policy
+            && self.markers == other.markers
+            && self.float_tracks.len() == other.float_tracks.len()
+            && self
+                .float_tracks
+                .iter()
+                .zip(&other.float_tracks)
+                .all(|(old, new)| old.same_graph(new))
+    }
+
+    /// Move playback to a normalized time, where 0 is the start and 1 the end of the clip
+    #[inline]
+    pub fn seek(&mut self, normalized_time: f32) {
+        self.set_time(normalized_time.clamp(0.0, 1.0) * self.duration());
+    }
+
     #[inline]
     pub fn get_output_pointer(&self) -> Arc<RwLock<Vec<SoaTransform>>> {
         self.output.clone()
@@ -82,9 +675,11 @@ impl SimpleState {
 /// State containing a blend tree
 #[derive(Debug)]
 pub struct BlendState {
-    blend_tree: BlendTree,
+    blend_tree: Box<BlendTree>,
+    speed: PlaybackSpeed,
 }
 
+// SAFETY: see AnimatorController
 unsafe impl Send for BlendState {}
 unsafe impl Sync for BlendState {}
 
@@ -92,20 +687,561 @@ impl BlendState {
     #[inline]
     pub fn new(blend_tree: BlendTree) -> Self {
         Self {
-            blend_tree,
+            blend_tree: Box::new(blend_tree),
+            speed: PlaybackSpeed::default(),
         }
     }
+
+    /// Play every motion of the tree `speed` times faster than real time
+    #[inline]
+    pub fn with_speed(mut self, speed: f32) -> Self {
+        self.set_speed(speed);
+        self
+    }
+
+    /// Also multiply the playback speed by a float parameter every update, see
+    /// [`SimpleState::with_speed_parameter`]
+    #[inline]
+    pub fn with_speed_parameter(mut self, parameter: impl Into<String>) -> Self {
+        self.speed.parameter = Some(parameter.into());
+        self.speed.binding = ParamBinding::default();
+        self
+    }
+
+    /// Playback speed multiplier, before any speed parameter
+    #[inline]
+    pub fn speed(&self) -> f32 {
+        self.speed.speed
+    }
+
+    #[inline]
+    pub fn set_speed(&mut self, speed: f32) {
+        self.speed.speed = speed.max(0.0);
+    }
+
+    /// Float parameter the speed is multiplied by
+    #[inline]
+    pub fn speed_parameter(&self) -> Option<&str> {
+        self.speed.parameter.as_deref()
+    }
+
+    /// Every parameter the tree reads, with its default value
+    #[inline]
+    pub(crate) fn referenced_parameters(&self, out: &mut Vec<(String, ParameterValue)>) {
+        self.speed.referenced_parameters(out);
+        self.blend_tree.referenced_parameters(out);
+    }
+
+    /// If both states blend the same motions the same way, ignoring their
+    /// runtime state
+    #[inline]
+    pub(crate) fn same_graph(&self, other: &BlendState) -> bool {
+        self.speed.same_graph(&other.speed) && self.blend_tree.same_graph(&other.blend_tree)
+    }
+
+    /// See [`BlendTree::thresholds_sorted`]
+    #[inline]
+    pub(crate) fn thresholds_sorted(&self) -> bool {
+        self.blend_tree.thresholds_sorted()
+    }
+
+    /// Resolve the speed parameter and every parameter of the tree to its id,
+    /// see [`BlendTree::bind_parameters`]
+    #[inline]
+    pub fn bind_parameters(&mut self, params: &Parameters) {
+        self.speed.bind_parameters(params);
+        self.blend_tree.bind_parameters(params);
+    }
 }
 
 impl BlendState {
     #[inline]
-    pub fn update(&mut self, time: &Time, params: &mut Parameters) -> Result<(), OzzError> {
-        self.blend_tree.update(time, params)?;
-        Ok(())
+    pub fn update(&mut self, time: &Time, params: &mut Parameters) -> Result<(), AnimatorError> {
+        self.tick(time, params, true)
+    }
+
+    /// Advance playback, only sampling and blending if `sample` is set
+    #[inline]
+    pub(crate) fn tick(
+        &mut self,
+        time: &Time,
+        params: &mut Parameters,
+        sample: bool,
+    ) -> Result<(), AnimatorError> {
+        let speed = self.speed.resolve(params);
+        if speed == 1.0 {
+            return self.blend_tree.tick(time, params, sample);
+        }
+        let mut scaled = Time::<()>::default();
+        scaled.advance_by(time.delta().mul_f32(speed));
+        self.blend_tree.tick(&scaled, params, sample)
+    }
+
+    /// Sample the blend tree at the current playback time without advancing it
+    #[inline]
+    pub(crate) fn sample(&mut self, params: &Parameters) -> Result<(), AnimatorError> {
+        self.blend_tree.sample(params)
+    }
+
+    /// Sample every motion of the tree once, see [`AnimatorController::prewarm`](super::AnimatorController::prewarm)
+    #[inline]
+    pub(crate) fn prewarm(&mut self) -> Result<(), AnimatorError> {
+        self.blend_tree.prewarm()
+    }
+
+    #[inline]
+    pub(crate) fn latch_weights(&mut self, params: &Parameters) {
+        self.blend_tree.latch_weights(params);
+    }
+
+    #[inline]
+    pub(crate) fn set_latch_progress(&mut self, progress: f32) {
+        self.blend_tree.set_latch_progress(progress);
+    }
+
+    #[inline]
+    pub(crate) fn release_latch(&mut self) {
+        self.blend_tree.release_latch();
     }
 
     #[inline]
     pub fn get_output_pointer(&self) -> Arc<RwLock<Vec<SoaTransform>>> {
         self.blend_tree.get_output_pointer()
     }
+
+    /// Number of times the dominant motion of the tree wrapped, see [`BlendTree::loop_count`]
+    #[inline]
+    pub fn loop_count(&self) -> u32 {
+        self.blend_tree.loop_count()
+    }
+
+    /// Normalized time of the dominant motion of the tree
+    #[inline]
+    pub fn normalized_time(&self) -> f32 {
+        self.blend_tree.normalized_time()
+    }
+
+    /// If the dominant motion of the tree finished, see [`BlendTree::is_finished`]
+    #[inline]
+    pub fn is_finished(&self) -> bool {
+        self.blend_tree.is_finished()
+    }
+
+    #[inline]
+    pub(crate) fn drain_markers(&mut self, out: Option<&mut Vec<String>>) {
+        self.blend_tree.drain_markers(out);
+    }
+
+    #[inline]
+    pub(crate) fn motion_stats(&self, weight: f32) -> Vec<MotionStats> {
+        self.blend_tree.motion_stats(weight)
+    }
+
+    /// See [`BlendTree::sample_curves`]
+    #[inline]
+    pub(crate) fn sample_curves(&self, weight: f32, out: &mut Vec<CurveSample>) {
+        self.blend_tree.sample_curves(weight, out);
+    }
+
+    /// Sample the blend tree at a time in seconds into `output`, without
+    /// advancing or otherwise touching this state
+    #[inline]
+    pub fn evaluate_at(
+        &self,
+        params: &Parameters,
+        time: f32,
+        output: &mut [SoaTransform],
+    ) -> Result<(), OzzError> {
+        self.blend_tree.evaluate(params, time, output)
+    }
+
+    /// Move every clip in the tree to a normalized time
+    #[inline]
+    pub fn seek(&mut self, normalized_time: f32) {
+        self.blend_tree.seek(normalized_time);
+    }
+
+    /// Move every clip in the tree to a time in seconds
+    #[inline]
+    pub fn set_time(&mut self, time: f32) {
+        self.blend_tree.set_time(time);
+    }
+
+    #[inline]
+    pub(crate) fn collect_times(&self, times: &mut Vec<f32>) {
+        self.blend_tree.collect_times(times);
+    }
+
+    #[inline]
+    pub(crate) fn restore_times(&mut self, times: &mut impl Iterator<Item = f32>) {
+        self.blend_tree.restore_times(times);
+    }
+}
+
+impl AnimationState {
+    /// Every parameter the state reads, with its default value
+    #[inline]
+    pub(crate) fn referenced_parameters(&self, out: &mut Vec<(String, ParameterValue)>) {
+        match self {
+            AnimationState::Simple(state) => state.speed.referenced_parameters(out),
+            AnimationState::Blend(state) => state.referenced_parameters(out),
+            // Sub-controllers check their own parameters
+            AnimationState::External(_) | AnimationState::Controller(_) => {}
+        }
+    }
+
+    /// Resolve every parameter the state reads to its id
+    #[inline]
+    pub fn bind_parameters(&mut self, params: &Parameters) {
+        match self {
+            AnimationState::Simple(state) => state.bind_parameters(params),
+            AnimationState::Blend(state) => state.bind_parameters(params),
+            // Sub-controllers bind to their own parameters
+            AnimationState::External(_) | AnimationState::Controller(_) => {}
+        }
+    }
+
+    /// Advance playback, only sampling if `sample` is set
+    #[inline]
+    pub(crate) fn tick(
+        &mut self,
+        time: &Time,
+        params: &mut Parameters,
+        sample: bool,
+    ) -> Result<(), AnimatorError> {
+        match self {
+            AnimationState::Simple(state) => Ok(state.tick(time, params, sample)?),
+            AnimationState::Blend(state) => state.tick(time, params, sample),
+            AnimationState::External(_) => Ok(()),
+            AnimationState::Controller(controller) => {
+                controller.tick(time, sample)?;
+                // Only markers are forwarded to the parent layer
+                controller.state_loops.clear();
+                controller.state_changes.clear();
+                Ok(())
+            }
+        }
+    }
+
+    /// Sample the state at its current playback time without advancing it
+    #[inline]
+    pub(crate) fn sample(&mut self, params: &Parameters) -> Result<(), AnimatorError> {
+        match self {
+            AnimationState::Simple(state) => Ok(state.sample()?),
+            AnimationState::Blend(state) => state.sample(params),
+            AnimationState::External(_) => Ok(()),
+            AnimationState::Controller(controller) => controller.sample_pose(),
+        }
+    }
+
+    /// Sample the state and everything in it once without advancing playback
+    #[inline]
+    pub(crate) fn prewarm(&mut self) -> Result<(), AnimatorError> {
+        match self {
+            AnimationState::Simple(state) => Ok(state.sample()?),
+            AnimationState::Blend(state) => state.prewarm(),
+            AnimationState::External(_) => Ok(()),
+            AnimationState::Controller(controller) => controller.prewarm(),
+        }
+    }
+
+    /// Hold a blend tree's weights while transitioning into it, see
+    /// [`BlendState::latch_weights`]. Does nothing for simple states.
+    #[inline]
+    pub(crate) fn latch_weights(&mut self, params: &Parameters) {
+        if let AnimationState::Blend(state) = self {
+            state.latch_weights(params);
+        }
+    }
+
+    #[inline]
+    pub(crate) fn set_latch_progress(&mut self, progress: f32) {
+        if let AnimationState::Blend(state) = self {
+            state.set_latch_progress(progress);
+        }
+    }
+
+    #[inline]
+    pub(crate) fn release_latch(&mut self) {
+        if let AnimationState::Blend(state) = self {
+            state.release_latch();
+        }
+    }
+
+    /// Pose the state samples into, None for a sub-controller without one
+    #[inline]
+    pub fn get_output_pointer(&self) -> Option<Arc<RwLock<Vec<SoaTransform>>>> {
+        match self {
+            AnimationState::Simple(state) => Some(state.get_output_pointer()),
+            AnimationState::Blend(state) => Some(state.get_output_pointer()),
+            AnimationState::External(pose) => Some(pose.output()),
+            AnimationState::Controller(controller) => controller.output_pointer(),
+        }
+    }
+
+    /// Number of times playback of the state wrapped around
+    #[inline]
+    pub fn loop_count(&self) -> u32 {
+        match self {
+            AnimationState::Simple(state) => state.loop_count(),
+            AnimationState::Blend(state) => state.loop_count(),
+            AnimationState::External(_) | AnimationState::Controller(_) => 0,
+        }
+    }
+
+    /// Move the markers crossed by the last update into `out`
+    #[inline]
+    pub(crate) fn drain_markers(&mut self, out: &mut Vec<String>) {
+        match self {
+            AnimationState::Simple(state) => state.drain_markers(Some(out)),
+            AnimationState::Blend(state) => state.drain_markers(Some(out)),
+            AnimationState::External(_) => {}
+            AnimationState::Controller(controller) => {
+                out.extend(controller.markers.drain(..).map(|marker| marker.name));
+            }
+        }
+    }
+
+    /// Sample the float tracks of the state into `out` as name, value and
+    /// weight. Blend trees pass on those of their motions weighted by the
+    /// blend, a sub controller its blended curves.
+    #[inline]
+    pub(crate) fn sample_curves(&mut self, weight: f32, out: &mut Vec<CurveSample>) {
+        match self {
+            AnimationState::Simple(state) => state.sample_curves(weight, out),
+            AnimationState::Blend(state) => state.sample_curves(weight, out),
+            AnimationState::Controller(controller) => out.extend(
+                controller
+                    .curve_values
+                    .iter()
+                    .map(|(name, value)| (name.clone(), *value, weight)),
+            ),
+            AnimationState::External(_) => {}
+        }
+    }
+
+    /// If the state played to the end of a clamped clip, see [`PlaybackMode::Clamp`]
+    #[inline]
+    pub fn is_finished(&self) -> bool {
+        match self {
+            AnimationState::Simple(state) => state.is_finished(),
+            AnimationState::Blend(state) => state.is_finished(),
+            AnimationState::External(_) | AnimationState::Controller(_) => false,
+        }
+    }
+
+    /// Stats of every motion of a blend tree state given the state's share of
+    /// the final pose, empty for other states
+    #[inline]
+    pub(crate) fn motion_stats(&self, weight: f32) -> Vec<MotionStats> {
+        match self {
+            AnimationState::Blend(state) => state.motion_stats(weight),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Playback time from 0 at the start to 1 at the end of the state
+    #[inline]
+    pub fn normalized_time(&self) -> f32 {
+        match self {
+            AnimationState::Simple(state) => state.normalized_time(),
+            AnimationState::Blend(state) => state.normalized_time(),
+            AnimationState::External(_) | AnimationState::Controller(_) => 0.0,
+        }
+    }
+
+    /// Sample the state at a time in seconds into `output` without side effects
+    #[inline]
+    pub fn evaluate_at(
+        &self,
+        params: &Parameters,
+        time: f32,
+        output: &mut [SoaTransform],
+    ) -> Result<(), OzzError> {
+        match self {
+            AnimationState::Simple(state) => state.evaluate_at(time, output),
+            AnimationState::Blend(state) => state.evaluate_at(params, time, output),
+            // External poses have no timeline, use what was last written
+            AnimationState::External(pose) => {
+                let pose = pose.read();
+                let len = output.len().min(pose.len());
+                output[..len].clone_from_slice(&pose[..len]);
+                Ok(())
+            }
+            // Sub-controllers can't be evaluated without advancing them
+            AnimationState::Controller(controller) => {
+                let output_pointer = controller.output_pointer().ok_or(OzzError::InvalidJob)?;
+                let pose = read_lock(&output_pointer);
+                let len = output.len().min(pose.len());
+                output[..len].clone_from_slice(&pose[..len]);
+                Ok(())
+            }
+        }
+    }
+
+    /// Move playback to a normalized time
+    #[inline]
+    pub fn seek(&mut self, normalized_time: f32) {
+        match self {
+            AnimationState::Simple(state) => state.seek(normalized_time),
+            AnimationState::Blend(state) => state.seek(normalized_time),
+            AnimationState::External(_) | AnimationState::Controller(_) => {}
+        }
+    }
+
+    /// Move playback to a time in seconds
+    #[inline]
+    pub fn set_time(&mut self, time: f32) {
+        match self {
+            AnimationState::Simple(state) => state.set_time(time),
+            AnimationState::Blend(state) => state.set_time(time),
+            AnimationState::External(_) | AnimationState::Controller(_) => {}
+        }
+    }
+
+    /// Playback times of every clip in this state, in depth-first order
+    #[inline]
+    pub(crate) fn collect_times(&self, times: &mut Vec<f32>) {
+        match self {
+            AnimationState::Simple(state) => times.push(state.time()),
+            AnimationState::Blend(state) => state.collect_times(times),
+            AnimationState::External(_) | AnimationState::Controller(_) => {}
+        }
+    }
+
+    /// Restore playback times previously produced by `collect_times`
+    #[inline]
+    pub(crate) fn restore_times(&mut self, times: &mut impl Iterator<Item = f32>) {
+        match self {
+            AnimationState::Simple(state) => {
+                if let Some(time) = times.next() {
+                    state.set_time(time);
+                }
+            }
+            AnimationState::Blend(state) => state.restore_times(times),
+            AnimationState::External(_) | AnimationState::Controller(_) => {}
+        }
+    }
+}
+
+/// Value of a float track at a ratio from 0 to 1 of its duration
+#[inline]
+fn sample_track(track: &Arc<Track<f32>>, ratio: f32) -> Option<f32> {
+    let mut job: TrackSamplingJobArc<f32> = TrackSamplingJobArc::default();
+    job.set_track(track.clone());
+    job.set_ratio(ratio);
+    job.run().ok()?;
+    Some(job.result())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{PoseRecorder, SyntheticAnimators, test_rig, test_state, test_track};
+    use bevy::prelude::Transform;
+    use std::time::Duration;
+
+    fn one_second_state() -> SimpleState {
+        let (skeleton, clip) = test_rig();
+        test_state(&skeleton, &clip)
+    }
+
+    fn advance(state: &mut SimpleState, time: &mut Time, seconds: f32) {
+        time.advance_by(Duration::from_secs_f32(seconds));
+        state.update(time).unwrap();
+    }
+
+    #[test]
+    fn evaluating_at_the_end_follows_the_playback_mode() {
+        // Rises from the first frame to the last, unlike the seamless synthetic clips
+        let mut recorder = PoseRecorder::new(0.0);
+        for (height, delta) in [(0.0, 0.0), (1.0, 1.0)] {
+            recorder.record_transforms(&[Transform::from_xyz(0.0, height, 0.0); 4], delta);
+        }
+        let clip = Arc::new(recorder.finish());
+        let looping = SimpleState::from_recording(clip.clone(), 1);
+        let clamped = SimpleState::from_recording(clip, 1).with_playback_mode(PlaybackMode::Clamp);
+        let pose = |state: &SimpleState, time: f32| {
+            let mut pose = vec![SoaTransform::default(); 1];
+            state.evaluate_at(time, &mut pose).unwrap();
+            pose
+        };
+        let mut last_frame = vec![SoaTransform::default(); 1];
+        looping.evaluate_clip_at(1.0, &mut last_frame).unwrap();
+        let first_frame = pose(&looping, 0.0);
+        assert_ne!(first_frame, last_frame);
+
+        assert_eq!(pose(&looping, 1.0), first_frame);
+        assert_eq!(pose(&clamped, 1.0), last_frame);
+        assert_eq!(pose(&clamped, 5.0), last_frame);
+        assert_eq!(pose(&clamped, -1.0), first_frame);
+    }
+
+    #[test]
+    fn sampling_a_sub_controller_does_not_advance_it() {
+        let animators = SyntheticAnimators::new(1, 4);
+        let (skeleton, clips) = animators.rig().unwrap();
+        let mut state = AnimationState::Controller(Box::new(
+            animators.controller(&skeleton, &clips, 0).unwrap(),
+        ));
+        let mut time = Time::default();
+        time.advance_by(Duration::from_secs_f32(0.1));
+        state.tick(&time, &mut Parameters::new(), true).unwrap();
+        let AnimationState::Controller(controller) = &mut state else {
+            unreachable!()
+        };
+        controller.parameters_mut().set_trigger("wave");
+        let before = controller.layers[0].normalized_time();
+
+        state.sample(&Parameters::new()).unwrap();
+        let AnimationState::Controller(controller) = &state else {
+            unreachable!()
+        };
+        assert!(controller.parameters().get_trigger("wave"));
+        assert_eq!(controller.layers[0].normalized_time(), before);
+    }
+
+    #[test]
+    fn looping_state_wraps_and_counts_loops() {
+        let mut state = one_second_state();
+        let mut time = Time::default();
+        advance(&mut state, &mut time, 0.75);
+        advance(&mut state, &mut time, 0.5);
+        assert!((state.normalized_time() - 0.25).abs() < 1e-4);
+        assert_eq!(state.loop_count(), 1);
+        assert!(!state.is_finished());
+    }
+
+    #[test]
+    fn clamped_state_holds_its_last_frame() {
+        let mut state = one_second_state().with_playback_mode(PlaybackMode::Clamp);
+        let mut time = Time::default();
+        advance(&mut state, &mut time, 0.75);
+        assert!(!state.is_finished());
+        advance(&mut state, &mut time, 0.5);
+        assert_eq!(state.normalized_time(), 1.0);
+        assert_eq!(state.loop_count(), 0);
+        assert!(state.is_finished());
+
+        // Seeking back plays it again
+        state.seek(0.0);
+        advance(&mut state, &mut time, 0.1);
+        assert!(!state.is_finished());
+    }
+
+    #[test]
+    fn spike_policies_and_float_tracks_are_part_of_the_graph() {
+        let (skeleton, clip) = test_rig();
+        let trail = test_track(1.0);
+        let untracked = || test_state(&skeleton, &clip);
+        let state = || untracked().with_float_track("trail", trail.clone());
+        assert!(state().same_graph(&state()));
+        let clamped = state().with_spike_policy(SpikePolicy::Clamp);
+        assert!(!state().same_graph(&clamped));
+        let other_track = untracked().with_float_track("trail", test_track(1.0));
+        assert!(!state().same_graph(&other_track));
+        let renamed = untracked().with_float_track("glow", trail.clone());
+        assert!(!state().same_graph(&renamed));
+        assert!(!state().same_graph(&untracked()));
+    }
 }
@@ -0,0 +1,207 @@
+use super::{AnimatorController, LayerBlendType, OverrideNormalization};
+
+/// Runtime info about a state that contributes to the final pose, as returned
+/// by [`AnimatorController::state_stats`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct StateStats {
+    pub layer: String,
+    pub state: String,
+    /// Playback ratio the state was last sampled at, from 0 at the start to 1
+    /// at the end of the state
+    pub ratio: f32,
+    /// Weight of the state within its layer, below 1 while transitioning
+    pub state_weight: f32,
+    /// Share of the final pose, the state weight scaled by the layer's weight
+    /// in the final blend. Additive layers apply on top of the override
+    /// layers, so their shares don't add up with the others.
+    pub weight: f32,
+    /// If the state is on an additive layer
+    pub additive: bool,
+    /// Every motion of a blend tree state, in motion order, empty for other states
+    pub motions: Vec<MotionStats>,
+}
+
+/// Runtime info about a motion of a blend tree, see [`StateStats`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct MotionStats {
+    /// Index of the motion in its tree
+    pub index: usize,
+    /// Playback ratio the motion was last sampled at
+    pub ratio: f32,
+    /// Share of the final pose, like [`StateStats::weight`]
+    pub weight: f32,
+    /// Every motion of a nested tree, empty for clips
+    pub motions: Vec<MotionStats>,
+}
+
+impl AnimatorController {
+    /// Every state playing on every layer with its last sampled ratio and its
+    /// share of the final pose, for inspectors and debug overlays. Joint masks
+    /// are not taken into account, the weights are those of an unmasked joint.
+    /// ## Example
+    /// ```ignore
+    /// for stats in controller.state_stats() {
+    ///     ui.label(format!("{}/{}: {:.0}%", stats.layer, stats.state, stats.weight * 100.0));
+    ///     for motion in &stats.motions {
+    ///         ui.label(format!("  motion {}: {:.0}%", motion.index, motion.weight * 100.0));
+    ///     }
+    /// }
+    /// ui.label(format!("Rest pose: {:.0}%", controller.rest_pose_weight() * 100.0));
+    /// ```
+    pub fn state_stats(&self) -> Vec<StateStats> {
+        let divisor = self.override_divisor();
+        let additive_weights: Vec<f32> = self
+            .layers
+            .iter()
+            .filter(|l| l.layer_blend_type == LayerBlendType::Additive)
+            .map(|l| l.effective_weight())
+            .collect();
+        let mut additive_weights = self
+            .additive_accumulation()
+            .accumulate(&additive_weights)
+            .into_iter();
+
+        let mut stats = Vec::new();
+        for layer in &self.layers {
+            match layer.layer_blend_type {
+                LayerBlendType::Override => {
+                    let weight = if divisor > 0.0 {
+                        layer.effective_weight() / divisor
+                    } else {
+                        0.0
+                    };
+                    layer.state_stats(weight, false, &mut stats);
+                }
+                LayerBlendType::Additive => {
+                    let weight = additive_weights.next().unwrap_or(0.0);
+                    layer.state_stats(weight, true, &mut stats);
+                }
+            }
+        }
+        stats
+    }
+
+    /// Share of the final pose filled in by the rest pose, because the override
+    /// layer weights add up to less than the rest pose threshold
+    #[inline]
+    pub fn rest_pose_weight(&self) -> f32 {
+        let divisor = self.override_divisor();
+        if divisor > 0.0 {
+            (divisor - self.override_weight()).max(0.0) / divisor
+        } else {
+            1.0
+        }
+    }
+
+    /// Total weight of the override layers
+    #[inline]
+    fn override_weight(&self) -> f32 {
+        self.layers
+            .iter()
+            .filter(|l| l.layer_blend_type == LayerBlendType::Override)
+            .map(|l| l.effective_weight())
+            .sum()
+    }
+
+    /// What ozz divides the override layer weights by, the rest pose making up
+    /// the difference below the threshold
+    #[inline]
+    fn override_divisor(&self) -> f32 {
+        let threshold = match self.override_normalization() {
+            OverrideNormalization::Normalized => self.rest_pose_threshold(),
+            OverrideNormalization::PassThrough => 1.0,
+        };
+        self.override_weight().max(threshold)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Parameters, test_layer, test_rig};
+    use bevy::prelude::Time;
+    use std::time::Duration;
+
+    /// A controller with a layer per `(blend type, weight)`, each playing
+    /// `Idle` and able to crossfade to `Walk`
+    fn animator(layers: &[(LayerBlendType, f32)]) -> AnimatorController {
+        let (skeleton, clip) = test_rig();
+        let layers = layers
+            .iter()
+            .enumerate()
+            .map(|(index, &(blend_type, weight))| {
+                let mut layer = test_layer(&skeleton, &clip, &["Idle", "Walk"]);
+                layer.name = format!("Layer {index}");
+                layer.layer_blend_type = blend_type;
+                layer.layer_weight = weight;
+                layer
+            })
+            .collect();
+        let mut controller =
+            AnimatorController::new(skeleton.clone(), layers, Parameters::new()).unwrap();
+        controller.update(&Time::default()).unwrap();
+        controller
+    }
+
+    fn weights(controller: &AnimatorController) -> Vec<f32> {
+        controller.state_stats().iter().map(|stats| stats.weight).collect()
+    }
+
+    fn assert_close(actual: f32, expected: f32) {
+        assert!((actual - expected).abs() < 1e-5, "{actual} != {expected}");
+    }
+
+    #[test]
+    fn override_shares_are_normalized() {
+        let controller =
+            animator(&[(LayerBlendType::Override, 3.0), (LayerBlendType::Override, 1.0)]);
+        let weights = weights(&controller);
+        assert_close(weights[0], 0.75);
+        assert_close(weights[1], 0.25);
+        assert_close(controller.rest_pose_weight(), 0.0);
+    }
+
+    #[test]
+    fn rest_pose_fills_in_light_layers() {
+        // Normalized weights only leave room for the rest pose below the threshold
+        let mut controller = animator(&[(LayerBlendType::Override, 0.05)]);
+        controller.set_rest_pose_threshold(0.1);
+        assert_close(weights(&controller)[0], 0.5);
+        assert_close(controller.rest_pose_weight(), 0.5);
+
+        let mut controller = animator(&[(LayerBlendType::Override, 0.25)]);
+        controller.set_override_normalization(OverrideNormalization::PassThrough);
+        assert_close(weights(&controller)[0], 0.25);
+        assert_close(controller.rest_pose_weight(), 0.75);
+    }
+
+    #[test]
+    fn additive_shares_are_their_layer_weight() {
+        let controller =
+            animator(&[(LayerBlendType::Override, 1.0), (LayerBlendType::Additive, 0.5)]);
+        let stats = controller.state_stats();
+        assert!(!stats[0].additive);
+        assert_close(stats[0].weight, 1.0);
+        assert!(stats[1].additive);
+        assert_close(stats[1].weight, 0.5);
+    }
+
+    #[test]
+    fn crossfades_split_the_layer_share() {
+        let mut controller =
+            animator(&[(LayerBlendType::Override, 1.0), (LayerBlendType::Override, 1.0)]);
+        controller.cross_fade("Walk", 1.0, "Layer 0").unwrap();
+        let mut time = Time::default();
+        time.advance_by(Duration::from_secs_f32(0.5));
+        controller.update(&time).unwrap();
+
+        let stats = controller.state_stats();
+        let states: Vec<&str> = stats.iter().map(|stats| stats.state.as_str()).collect();
+        assert_eq!(states, ["Idle", "Walk", "Idle"]);
+        assert_close(stats[0].state_weight, 0.5);
+        assert_close(stats[1].state_weight, 0.5);
+        assert_close(stats[0].weight, 0.25);
+        assert_close(stats[1].weight, 0.25);
+        assert_close(stats[2].weight, 0.5);
+    }
+}
@@ -0,0 +1,88 @@
+use super::{AnimationLayer, AnimationState, AnimatorController, AnimatorError, LayerBlendType};
+use ozz_animation_rs::SoaTransform;
+use std::sync::{Arc, RwLock};
+
+/// Name of the single state of a sub-controller layer
+const SUB_CONTROLLER_STATE: &str = "Controller";
+
+impl AnimatorController {
+    /// Add another controller on the same skeleton as a layer of this one, e.g.
+    /// an emote controller shipped by a plugin. The sub-controller runs its own
+    /// state machines and parameters, and its final pose blends in like any
+    /// other layer. Layers blend in the order they were added, so later
+    /// sub-controllers play over earlier ones.
+    /// ## Example
+    /// ```ignore
+    /// let emotes = build_emote_controller(&skeleton)?;
+    /// controller.add_sub_controller("Emotes", LayerBlendType::Override, 1.0, emotes)?;
+    /// if let Some(emotes) = controller.sub_controller_mut("Emotes") {
+    ///     emotes.parameters_mut().set_trigger("wave");
+    /// }
+    /// ```
+    pub fn add_sub_controller(
+        &mut self,
+        name: &str,
+        layer_blend_type: LayerBlendType,
+        layer_weight: f32,
+        controller: AnimatorController,
+    ) -> Result<(), AnimatorError> {
+        if controller.skeleton.num_joints() != self.skeleton.num_joints() {
+            return Err(AnimatorError::InvalidSkeleton(format!(
+                "sub-controller '{name}' has {} joints, expected {}",
+                controller.skeleton.num_joints(),
+                self.skeleton.num_joints()
+            )));
+        }
+        let mut layer = AnimationLayer::new(
+            name.to_string(),
+            layer_blend_type,
+            layer_weight,
+            &self.skeleton,
+            SUB_CONTROLLER_STATE.to_string(),
+        );
+        layer.add_state(
+            SUB_CONTROLLER_STATE.to_string(),
+            AnimationState::Controller(Box::new(controller)),
+        );
+        self.add_layer(layer)
+    }
+
+    /// A sub-controller added with `add_sub_controller`
+    #[inline]
+    pub fn sub_controller(&self, name: &str) -> Option<&AnimatorController> {
+        let layer = self.layers.iter().find(|l| l.name == name)?;
+        match layer.state(SUB_CONTROLLER_STATE)? {
+            AnimationState::Controller(controller) => Some(controller),
+            _ => None,
+        }
+    }
+
+    /// Mutable access to a sub-controller, to drive its parameters
+    #[inline]
+    pub fn sub_controller_mut(&mut self, name: &str) -> Option<&mut AnimatorController> {
+        let layer = self.layers.iter_mut().find(|l| l.name == name)?;
+        match layer.state_mut(SUB_CONTROLLER_STATE)? {
+            AnimationState::Controller(controller) => Some(controller),
+            _ => None,
+        }
+    }
+
+    /// Blend the pose of every layer again at their current playback time,
+    /// without running the state machines, consuming triggers or firing
+    /// markers. Sub-controller states are advanced by their layer's update.
+    pub(crate) fn sample_pose(&mut self) -> Result<(), AnimatorError> {
+        for layer in self.layers.iter_mut().filter(|layer| layer.is_active()) {
+            layer.sample(&self.parameters)?;
+        }
+        self.final_blending_job
+            .run()
+            .map_err(AnimatorError::FinalBlending)
+    }
+
+    /// Final blended pose of the controller, before bone overrides, None if
+    /// its blending job has no output to blend into
+    #[inline]
+    pub(crate) fn output_pointer(&self) -> Option<Arc<RwLock<Vec<SoaTransform>>>> {
+        self.final_blending_job.output().cloned()
+    }
+}
@@ -0,0 +1,318 @@
+use super::{
+    AnimationLayer, AnimatorController, AnimatorError, LayerBlendType, Parameters, PoseRecorder,
+    RecordedClip, SimpleState, SkeletonJoint, Transition, skeleton_from_joints,
+};
+#[cfg(feature = "render")]
+use super::{BoneIndex, BonePoseSource};
+use bevy::prelude::*;
+use ozz_animation_rs::Skeleton;
+#[cfg(test)]
+use ozz_animation_rs::{Archive, Track};
+use std::sync::Arc;
+
+/// A skeleton of `joint_count` joints named `Joint0`, `Joint1`.. split into
+/// `chains` chains hanging off the root, for benchmarks and tests that
+/// shouldn't depend on binary assets
+/// ## Example
+/// ```ignore
+/// let skeleton = Arc::new(synthetic_skeleton(80, 5)?);
+/// ```
+pub fn synthetic_skeleton(joint_count: usize, chains: usize) -> Result<Skeleton, AnimatorError> {
+    let chains = chains.max(1);
+    let joints: Vec<SkeletonJoint> = (0..joint_count)
+        .map(|index| SkeletonJoint {
+            name: format!("Joint{index}"),
+            // Joints 1 to `chains` start a chain each, the others continue
+            // the chain of joint i - chains
+            parent: (index > 0).then(|| index.saturating_sub(chains)),
+            rest_pose: Transform::from_xyz(0.0, 0.1, 0.0),
+        })
+        .collect();
+    skeleton_from_joints(&joints)
+}
+
+/// A looping clip that swings every joint of the skeleton around its rest
+/// pose, recorded at `frame_rate`. Clips of different `variation`s move at
+/// different phases and speeds, so blending them does real work.
+/// ## Example
+/// ```ignore
+/// let walk = Arc::new(synthetic_clip(&skeleton, 1.0, 30.0, 0));
+/// let run = Arc::new(synthetic_clip(&skeleton, 0.6, 30.0, 1));
+/// ```
+pub fn synthetic_clip(
+    skeleton: &Skeleton,
+    duration: f32,
+    frame_rate: f32,
+    variation: u32,
+) -> RecordedClip {
+    let duration = duration.max(0.0);
+    let frame_count = (duration * frame_rate.max(1.0)).ceil().max(1.0) as usize;
+    let frame_time = duration / frame_count as f32;
+    let swings = (1 + variation / 2) as f32;
+
+    let mut recorder = PoseRecorder::new(0.0);
+    let mut transforms = vec![Transform::from_xyz(0.0, 0.1, 0.0); skeleton.num_joints()];
+    for frame in 0..=frame_count {
+        // A full number of swings per clip so it loops without a seam
+        let phase = frame as f32 / frame_count as f32 * std::f32::consts::TAU * swings;
+        for (joint, transform) in transforms.iter_mut().enumerate() {
+            let offset = joint as f32 * 0.37 + variation as f32 * 1.3;
+            transform.rotation = Quat::from_euler(
+                EulerRot::XYZ,
+                (phase + offset).sin() * 0.4,
+                (phase * 2.0 + offset).cos() * 0.2,
+                0.0,
+            );
+        }
+        // The first frame is recorded at 0 whatever the delta
+        recorder.record_transforms(&transforms, frame_time);
+    }
+    recorder.finish()
+}
+
+/// Generates a skeleton and clips of a configurable size and spawns any number
+/// of controllers playing them, to measure sampling, blending and bone
+/// write-out without shipping large assets. Every layer cycles through the
+/// clips, crossfading at the end of each loop, and instances start at
+/// different times. Clips are recorded in memory, so they are sampled with
+/// [`SimpleState::from_recording`] rather than an ozz sampling job.
+/// ## Example
+/// ```ignore
+/// fn setup(mut commands: Commands) {
+///     SyntheticAnimators::new(500, 60)
+///         .with_clips(4)
+///         .with_layers(2)
+///         .spawn(&mut commands)
+///         .unwrap();
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct SyntheticAnimators {
+    /// Number of controllers
+    pub count: usize,
+    pub joint_count: usize,
+    /// Chains of joints hanging off the root, see [`synthetic_skeleton`]
+    pub chains: usize,
+    /// Clips every layer cycles through
+    pub clip_count: usize,
+    /// Override layers blended on top of each other
+    pub layer_count: usize,
+    /// Duration of each clip in seconds
+    pub clip_duration: f32,
+    /// Seconds every crossfade between clips takes
+    pub crossfade: f32,
+    /// Spawn an entity with a `BoneIndex` per joint of every controller, so
+    /// bone write-out is measured too. Only used with the `render` feature.
+    pub bones: bool,
+}
+
+impl SyntheticAnimators {
+    #[inline]
+    pub fn new(count: usize, joint_count: usize) -> Self {
+        Self {
+            count,
+            joint_count,
+            chains: 5,
+            clip_count: 2,
+            layer_count: 1,
+            clip_duration: 1.0,
+            crossfade: 0.25,
+            bones: true,
+        }
+    }
+
+    #[inline]
+    pub fn with_chains(mut self, chains: usize) -> Self {
+        self.chains = chains;
+        self
+    }
+
+    #[inline]
+    pub fn with_clips(mut self, clip_count: usize) -> Self {
+        self.clip_count = clip_count;
+        self
+    }
+
+    #[inline]
+    pub fn with_layers(mut self, layer_count: usize) -> Self {
+        self.layer_count = layer_count;
+        self
+    }
+
+    #[inline]
+    pub fn with_clip_duration(mut self, clip_duration: f32) -> Self {
+        self.clip_duration = clip_duration;
+        self
+    }
+
+    #[inline]
+    pub fn with_crossfade(mut self, crossfade: f32) -> Self {
+        self.crossfade = crossfade;
+        self
+    }
+
+    /// Skip the bone entities, to measure the controllers alone
+    #[inline]
+    pub fn without_bones(mut self) -> Self {
+        self.bones = false;
+        self
+    }
+
+    /// The skeleton and clips every controller shares
+    pub fn rig(&self) -> Result<(Arc<Skeleton>, Vec<Arc<RecordedClip>>), AnimatorError> {
+        let skeleton = Arc::new(synthetic_skeleton(self.joint_count, self.chains)?);
+        let clips = (0..self.clip_count.max(1) as u32)
+            .map(|variation| {
+                Arc::new(synthetic_clip(&skeleton, self.clip_duration, 30.0, variation))
+            })
+            .collect();
+        Ok((skeleton, clips))
+    }
+
+    /// Build one controller of the rig, `index` picks where its clips start
+    pub fn controller(
+        &self,
+        skeleton: &Arc<Skeleton>,
+        clips: &[Arc<RecordedClip>],
+        index: usize,
+    ) -> Result<AnimatorController, AnimatorError> {
+        // Spread the instances over the clip so they don't all sample the same frame
+        let start = (index as f32 * 0.618_034).fract();
+        let layers = (0..self.layer_count.max(1))
+            .map(|layer_index| {
+                let state_name = |clip: usize| format!("Clip {clip}");
+                let mut layer = AnimationLayer::new(
+                    format!("Layer {layer_index}"),
+                    LayerBlendType::Override,
+                    1.0,
+                    skeleton,
+                    state_name(layer_index % clips.len().max(1)),
+                );
+                for (clip_index, clip) in clips.iter().enumerate() {
+                    let mut state =
+                        SimpleState::from_recording(clip.clone(), skeleton.num_soa_joints());
+                    state.seek(start);
+                    layer.add_state(state_name(clip_index), state.into());
+                    if clips.len() > 1 {
+                        layer.add_transition(
+                            state_name(clip_index),
+                            Transition {
+                                to_state: state_name((clip_index + 1) % clips.len()),
+                                duration: self.crossfade,
+                                has_exit_time: true,
+                                exit_time: 1.0,
+                                ..default()
+                            },
+                        );
+                    }
+                }
+                layer
+            })
+            .collect();
+        AnimatorController::new(skeleton.clone(), layers, Parameters::new())
+    }
+
+    /// Every controller of the rig, for benchmarks that update them directly
+    pub fn controllers(&self) -> Result<Vec<AnimatorController>, AnimatorError> {
+        let (skeleton, clips) = self.rig()?;
+        (0..self.count)
+            .map(|index| self.controller(&skeleton, &clips, index))
+            .collect()
+    }
+
+    /// Spawn every controller on a grid, returning their entities
+    pub fn spawn(&self, commands: &mut Commands) -> Result<Vec<Entity>, AnimatorError> {
+        let (skeleton, clips) = self.rig()?;
+        let columns = (self.count as f32).sqrt().ceil().max(1.0) as usize;
+        let mut entities = Vec::with_capacity(self.count);
+        for index in 0..self.count {
+            let controller = self.controller(&skeleton, &clips, index)?;
+            let position = Vec3::new((index % columns) as f32, 0.0, (index / columns) as f32);
+            let entity = commands
+                .spawn((controller, Transform::from_translation(position * 2.0)))
+                .id();
+            #[cfg(feature = "render")]
+            if self.bones {
+                commands.spawn_batch((0..skeleton.num_joints()).map(move |joint| {
+                    (
+                        Transform::default(),
+                        BoneIndex(joint),
+                        BonePoseSource(entity),
+                        ChildOf(entity),
+                    )
+                }));
+            }
+            entities.push(entity);
+        }
+        Ok(entities)
+    }
+}
+
+/// The rig most unit tests play, a four joint skeleton and a one second clip
+#[cfg(test)]
+pub(crate) fn test_rig() -> (Arc<Skeleton>, Arc<RecordedClip>) {
+    let skeleton = Arc::new(synthetic_skeleton(4, 1).expect("synthetic skeleton"));
+    let clip = Arc::new(synthetic_clip(&skeleton, 1.0, 30.0, 0));
+    (skeleton, clip)
+}
+
+/// A state playing `clip` on `skeleton`
+#[cfg(test)]
+pub(crate) fn test_state(skeleton: &Skeleton, clip: &Arc<RecordedClip>) -> SimpleState {
+    SimpleState::from_recording(clip.clone(), skeleton.num_soa_joints())
+}
+
+/// An override layer named `Base` with a state playing `clip` for every name
+/// of `states`, starting in the first one
+#[cfg(test)]
+pub(crate) fn test_layer(
+    skeleton: &Arc<Skeleton>,
+    clip: &Arc<RecordedClip>,
+    states: &[&str],
+) -> AnimationLayer {
+    let mut layer = AnimationLayer::new(
+        "Base".to_string(),
+        LayerBlendType::Override,
+        1.0,
+        skeleton,
+        states.first().copied().unwrap_or_default().to_string(),
+    );
+    for state in states {
+        layer.add_state(state.to_string(), test_state(skeleton, clip).into());
+    }
+    layer
+}
+
+/// A skeleton of a single chain, every joint parented to the one before it
+#[cfg(test)]
+pub(crate) fn test_chain(joints: &[(&str, Transform)]) -> Arc<Skeleton> {
+    let joints: Vec<SkeletonJoint> = joints
+        .iter()
+        .enumerate()
+        .map(|(index, &(name, rest_pose))| SkeletonJoint {
+            name: name.to_string(),
+            parent: index.checked_sub(1),
+            rest_pose,
+        })
+        .collect();
+    Arc::new(skeleton_from_joints(&joints).expect("chain skeleton"))
+}
+
+/// A float track holding `value` from start to end
+#[cfg(test)]
+pub(crate) fn test_track(value: f32) -> Arc<Track<f32>> {
+    let mut bytes = vec![u8::from(cfg!(target_endian = "little"))];
+    bytes.extend_from_slice(Track::<f32>::tag().as_bytes());
+    bytes.push(0);
+    bytes.extend_from_slice(&Track::<f32>::version().to_ne_bytes());
+    // Two keys, no name
+    bytes.extend_from_slice(&2u32.to_ne_bytes());
+    bytes.extend_from_slice(&0u32.to_ne_bytes());
+    for float in [0.0, 1.0, value, value] {
+        bytes.extend_from_slice(&f32::to_ne_bytes(float));
+    }
+    // Both keys interpolate linearly
+    bytes.push(0);
+    let mut archive = Archive::from_vec(bytes).expect("track archive");
+    Arc::new(Track::from_archive(&mut archive).expect("float track"))
+}
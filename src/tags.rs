@@ -0,0 +1,217 @@
+use super::{AnimationLayer, AnimatorController};
+use bevy::prelude::*;
+use std::collections::{BTreeSet, HashMap};
+
+impl AnimationLayer {
+    /// Tag a state, the tag is active while the state is the layer's current
+    /// state. See [`AnimatorTags`] to mirror tags as components.
+    /// ## Example
+    /// ```ignore
+    /// let layer = AnimationLayer::new(
+    ///     "Base Layer".to_string(),
+    ///     LayerBlendType::Override,
+    ///     1.0,
+    ///     &skeleton,
+    ///     "Idle".to_string(),
+    /// )
+    /// .with_state_tag("Jump", "airborne")
+    /// .with_state_tag("Fall", "airborne");
+    /// ```
+    #[inline]
+    pub fn with_state_tag(mut self, state: impl Into<String>, tag: impl Into<String>) -> Self {
+        self.add_state_tag(state, tag);
+        self
+    }
+
+    #[inline]
+    pub fn add_state_tag(&mut self, state: impl Into<String>, tag: impl Into<String>) {
+        let tags = self.state_tags.entry(state.into()).or_default();
+        let tag = tag.into();
+        if !tags.contains(&tag) {
+            tags.push(tag);
+        }
+    }
+
+    /// Remove a tag from a state, returns false if the state didn't have it
+    #[inline]
+    pub fn remove_state_tag(&mut self, state: &str, tag: &str) -> bool {
+        let Some(tags) = self.state_tags.get_mut(state) else {
+            return false;
+        };
+        let count = tags.len();
+        tags.retain(|t| t != tag);
+        tags.len() != count
+    }
+
+    /// Tags of a state, in the order they were added
+    #[inline]
+    pub fn state_tags(&self, state: &str) -> &[String] {
+        self.state_tags.get(state).map_or(&[], Vec::as_slice)
+    }
+}
+
+impl AnimatorController {
+    /// Tags of the current state of every active layer, in name order without
+    /// duplicates
+    #[inline]
+    pub fn active_tags(&self) -> BTreeSet<&str> {
+        self.layers
+            .iter()
+            .filter(|layer| layer.is_active())
+            .flat_map(|layer| layer.state_tags(layer.current_state()))
+            .map(String::as_str)
+            .collect()
+    }
+
+    /// If the current state of an active layer has the tag
+    #[inline]
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.layers
+            .iter()
+            .filter(|layer| layer.is_active())
+            .any(|layer| layer.state_tags(layer.current_state()).iter().any(|t| t == tag))
+    }
+}
+
+/// How a tag is mirrored on the controller entity
+#[derive(Debug, Clone, Copy)]
+struct TagComponent {
+    insert: fn(&mut EntityCommands),
+    remove: fn(&mut EntityCommands),
+}
+
+/// Marker components inserted on a controller's entity while a state with the
+/// matching tag is current, and removed once none is, so other systems filter
+/// with `With<Airborne>` instead of reading the controller. Added by the plugin.
+/// ## Example
+/// ```ignore
+/// #[derive(Component, Default)]
+/// struct Airborne;
+///
+/// app.world_mut()
+///     .resource_mut::<AnimatorTags>()
+///     .register::<Airborne>("airborne");
+///
+/// fn air_control(players: Query<&mut Velocity, With<Airborne>>) {}
+/// ```
+#[derive(Resource, Debug, Default)]
+pub struct AnimatorTags {
+    components: HashMap<String, TagComponent>,
+}
+
+impl AnimatorTags {
+    /// Insert a `T` on the entity of every controller with the tag active,
+    /// replacing the component registered for the tag before
+    #[inline]
+    pub fn register<T: Component + Default>(&mut self, tag: impl Into<String>) {
+        self.components.insert(
+            tag.into(),
+            TagComponent {
+                insert: |entity| {
+                    entity.insert(T::default());
+                },
+                remove: |entity| {
+                    entity.remove::<T>();
+                },
+            },
+        );
+    }
+
+    /// Stop mirroring a tag, components already inserted stay
+    #[inline]
+    pub fn unregister(&mut self, tag: &str) -> bool {
+        self.components.remove(tag).is_some()
+    }
+
+    #[inline]
+    pub fn is_registered(&self, tag: &str) -> bool {
+        self.components.contains_key(tag)
+    }
+}
+
+/// Insert and remove the components of registered tags as states change
+pub fn sync_animator_tags(
+    tags: Res<AnimatorTags>,
+    mut controllers: Query<(Entity, &mut AnimatorController)>,
+    mut commands: Commands,
+    mut active: Local<Vec<String>>,
+) {
+    if tags.components.is_empty() {
+        return;
+    }
+    for (entity, mut controller) in &mut controllers {
+        // Compare in place, the tags only need copying on the frames they change
+        let registered = || {
+            controller
+                .layers
+                .iter()
+                .filter(|layer| layer.is_active())
+                .flat_map(|layer| layer.state_tags(layer.current_state()))
+                .filter(|tag| tags.is_registered(tag))
+        };
+        let applied = &controller.applied_tags;
+        if registered().all(|tag| applied.contains(tag))
+            && applied.iter().all(|tag| registered().any(|t| t == tag))
+        {
+            continue;
+        }
+        active.clear();
+        for tag in registered() {
+            if !active.contains(tag) {
+                active.push(tag.clone());
+            }
+        }
+
+        let mut entity = commands.entity(entity);
+        for tag in applied.iter().filter(|tag| !active.contains(tag)) {
+            if let Some(component) = tags.components.get(tag) {
+                (component.remove)(&mut entity);
+            }
+        }
+        for tag in active.iter().filter(|tag| !applied.contains(tag)) {
+            if let Some(component) = tags.components.get(tag) {
+                (component.insert)(&mut entity);
+            }
+        }
+        // Bookkeeping only, the controller itself didn't change. The previous
+        // tags are kept as the buffer for the next change.
+        std::mem::swap(&mut controller.bypass_change_detection().applied_tags, &mut *active);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::SyntheticAnimators;
+    use bevy::ecs::system::RunSystemOnce;
+
+    #[derive(Component, Default)]
+    struct Busy;
+
+    #[test]
+    fn tag_components_follow_the_current_state() {
+        let animators = SyntheticAnimators::new(1, 4);
+        let (skeleton, clips) = animators.rig().unwrap();
+        let mut controller = animators.controller(&skeleton, &clips, 0).unwrap();
+        controller.layers[0].add_state_tag("Clip 0", "busy");
+        controller.layers[0].add_state_tag("Clip 0", "unregistered");
+        let mut world = World::new();
+        world.init_resource::<AnimatorTags>();
+        world.resource_mut::<AnimatorTags>().register::<Busy>("busy");
+        let entity = world.spawn(controller).id();
+        let sync = |world: &mut World| world.run_system_once(sync_animator_tags).unwrap();
+
+        sync(&mut world);
+        assert!(world.entity(entity).contains::<Busy>());
+        let controller = world.get::<AnimatorController>(entity).unwrap();
+        assert_eq!(controller.applied_tags, vec!["busy".to_string()]);
+        sync(&mut world);
+        assert!(world.entity(entity).contains::<Busy>());
+
+        let mut controller = world.get_mut::<AnimatorController>(entity).unwrap();
+        controller.force_state("Layer 0", "Clip 1", 0.0).unwrap();
+        sync(&mut world);
+        assert!(!world.entity(entity).contains::<Busy>());
+        assert!(world.get::<AnimatorController>(entity).unwrap().applied_tags.is_empty());
+    }
+}
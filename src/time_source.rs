@@ -0,0 +1,187 @@
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+use std::any::Any;
+use std::time::Duration;
+
+/// Global pause and time scale of every controller, added by the plugin. For
+/// hit-stop and slow motion without touching the app's `Time`. Controllers
+/// driven by an [`AnimatorClock`] own their time and ignore it.
+/// ## Example
+/// ```ignore
+/// fn hit_stop(mut animation_time: ResMut<AnimationTime>) {
+///     animation_time.time_scale = 0.1;
+/// }
+/// ```
+#[derive(Resource, Debug, Clone, Copy, PartialEq)]
+pub struct AnimationTime {
+    /// Freeze every controller, they keep updating without advancing
+    pub paused: bool,
+    /// Multiplies the time every controller advances by
+    pub time_scale: f32,
+}
+
+impl Default for AnimationTime {
+    fn default() -> Self {
+        Self {
+            paused: false,
+            time_scale: 1.0,
+        }
+    }
+}
+
+impl AnimationTime {
+    #[inline]
+    pub fn pause(&mut self) {
+        self.paused = true;
+    }
+
+    #[inline]
+    pub fn resume(&mut self) {
+        self.paused = false;
+    }
+
+    #[inline]
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    /// Time controllers advance by for `delta` of the app's time
+    #[inline]
+    pub fn scale(&self, delta: Duration) -> Duration {
+        match self.paused {
+            true => Duration::ZERO,
+            false => scale_duration(delta, self.time_scale),
+        }
+    }
+
+    /// A time advanced by the scaled delta of `time`
+    #[inline]
+    pub(crate) fn scale_time(&self, time: &Time) -> Time {
+        let mut scaled = Time::<()>::default();
+        scaled.advance_by(self.scale(time.delta()));
+        scaled
+    }
+}
+
+/// The app's `Time` together with the global `AnimationTime`, for systems
+/// advancing controllers
+#[derive(SystemParam)]
+pub struct ScaledTime<'w> {
+    time: Res<'w, Time>,
+    animation_time: Option<Res<'w, AnimationTime>>,
+}
+
+impl ScaledTime<'_> {
+    /// Unscaled time of the app, as read by clocks
+    #[inline]
+    pub fn time(&self) -> &Time {
+        &self.time
+    }
+
+    /// Delta of the app's time scaled by `AnimationTime`
+    #[inline]
+    pub fn delta(&self) -> Duration {
+        let animation_time = self.animation_time.as_deref().copied().unwrap_or_default();
+        animation_time.scale(self.time.delta())
+    }
+}
+
+/// `delta` times a scale, zero for scales that aren't positive
+#[inline]
+pub(crate) fn scale_duration(delta: Duration, scale: f32) -> Duration {
+    if scale > 0.0 { delta.mul_f32(scale) } else { Duration::ZERO }
+}
+
+/// A clock that decides how far a controller advances every update, in place
+/// of the app's `Time`. Cutscene timelines, replays and network-smoothed clocks
+/// implement it to own the animation time of their characters.
+/// ## Example
+/// ```ignore
+/// struct Timeline {
+///     playing: bool,
+///     rate: f32,
+/// }
+///
+/// impl TimeSource for Timeline {
+///     fn delta(&mut self, time: &Time) -> Duration {
+///         match self.playing {
+///             true => time.delta().mul_f32(self.rate),
+///             false => Duration::ZERO,
+///         }
+///     }
+/// }
+///
+/// commands.spawn((controller, AnimatorClock::new(Timeline { playing: true, rate: 0.5 })));
+/// ```
+pub trait TimeSource: Any + Send + Sync {
+    /// Time to advance by this update, given the time of the schedule the
+    /// controller updates in. Called exactly once per update.
+    fn delta(&mut self, time: &Time) -> Duration;
+}
+
+/// Drives the `AnimatorController` of its entity with a [`TimeSource`]
+/// instead of the app's `Time`. Throttling, the update budget, max delta and
+/// sub-stepping all apply to the time it returns.
+#[derive(Component)]
+pub struct AnimatorClock(pub Box<dyn TimeSource>);
+
+impl AnimatorClock {
+    #[inline]
+    pub fn new(source: impl TimeSource) -> Self {
+        Self(Box::new(source))
+    }
+
+    /// The time source, if it is a `T`
+    #[inline]
+    pub fn get<T: TimeSource>(&self) -> Option<&T> {
+        (self.0.as_ref() as &dyn Any).downcast_ref()
+    }
+
+    #[inline]
+    pub fn get_mut<T: TimeSource>(&mut self) -> Option<&mut T> {
+        (self.0.as_mut() as &mut dyn Any).downcast_mut()
+    }
+}
+
+impl std::fmt::Debug for AnimatorClock {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("AnimatorClock").finish_non_exhaustive()
+    }
+}
+
+/// A [`TimeSource`] that only advances when told to, e.g. by a network
+/// snapshot or a replay, ignoring the app's `Time`
+/// ## Example
+/// ```ignore
+/// commands.spawn((controller, AnimatorClock::new(ManualClock::default())));
+///
+/// // When a server snapshot arrives
+/// if let Some(clock) = clock.get_mut::<ManualClock>() {
+///     clock.advance(snapshot.server_time - last_server_time);
+/// }
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ManualClock {
+    pending: Duration,
+}
+
+impl ManualClock {
+    /// Queue time for the next update
+    #[inline]
+    pub fn advance(&mut self, delta: Duration) {
+        self.pending += delta;
+    }
+
+    /// Time queued for the next update
+    #[inline]
+    pub fn pending(&self) -> Duration {
+        self.pending
+    }
+}
+
+impl TimeSource for ManualClock {
+    #[inline]
+    fn delta(&mut self, _time: &Time) -> Duration {
+        std::mem::take(&mut self.pending)
+    }
+}
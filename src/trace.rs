@@ -0,0 +1,100 @@
+use super::{AnimatorController, TransitionCondition};
+use std::fmt;
+
+/// Transitions a layer evaluated during its last update, see
+/// [`AnimatorController::set_tracing`]
+#[derive(Debug, Clone, Default)]
+pub struct LayerTrace {
+    pub layer: String,
+    /// State the transitions left from
+    pub state: String,
+    /// If a transition was already running, in which case none could fire
+    pub transitioning: bool,
+    /// Every transition leaving `state`, in evaluation order
+    pub transitions: Vec<TransitionTrace>,
+}
+
+/// How a single transition fared during an update
+#[derive(Debug, Clone)]
+pub struct TransitionTrace {
+    pub to_state: String,
+    pub outcome: TransitionOutcome,
+}
+
+#[derive(Debug, Clone)]
+pub enum TransitionOutcome {
+    /// The transition started
+    Fired,
+    /// The conditions held, but the layer was already transitioning or an
+    /// earlier transition fired first
+    Met,
+    /// The conditions held, but already did last update, see [`ConditionMode::Edge`](super::ConditionMode::Edge)
+    WaitingForEdge,
+    /// The conditions held, but the destination state doesn't exist
+    UnknownState,
+    /// The source state hasn't played up to the exit time yet
+    WaitingForExitTime,
+    /// The first condition that doesn't hold
+    ConditionFailed {
+        index: usize,
+        condition: TransitionCondition,
+    },
+}
+
+impl fmt::Display for LayerTrace {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} in {}", self.layer, self.state)?;
+        if self.transitioning {
+            write!(f, " (transitioning)")?;
+        }
+        if self.transitions.is_empty() {
+            write!(f, ": no transitions")?;
+        }
+        for transition in &self.transitions {
+            write!(f, "\n  -> {}: ", transition.to_state)?;
+            match &transition.outcome {
+                TransitionOutcome::Fired => write!(f, "fired")?,
+                TransitionOutcome::Met => write!(f, "met")?,
+                TransitionOutcome::WaitingForEdge => write!(f, "met, waiting for a change")?,
+                TransitionOutcome::UnknownState => write!(f, "met, unknown state")?,
+                TransitionOutcome::WaitingForExitTime => write!(f, "waiting for exit time")?,
+                TransitionOutcome::ConditionFailed { index, condition } => {
+                    write!(f, "condition {index} failed: {condition:?}")?
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl AnimatorController {
+    /// Record which transitions every layer evaluates each update and which
+    /// condition failed first. Off by default, it clones every evaluated
+    /// condition.
+    #[inline]
+    pub fn set_tracing(&mut self, enabled: bool) {
+        for layer in &mut self.layers {
+            layer.set_tracing(enabled);
+        }
+    }
+
+    /// Traces of every layer with tracing enabled, from the last update
+    #[inline]
+    pub fn traces(&self) -> impl Iterator<Item = &LayerTrace> {
+        self.layers.iter().filter_map(|layer| layer.trace())
+    }
+
+    /// Human readable dump of `traces()`
+    /// ## Example
+    /// ```ignore
+    /// controller.set_tracing(true);
+    /// // ... after an update
+    /// info!("{}", controller.trace_report());
+    /// ```
+    pub fn trace_report(&self) -> String {
+        self.traces()
+            .map(|trace| trace.to_string())
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
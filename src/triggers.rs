@@ -0,0 +1,71 @@
+use super::{AnimatorController, AnimatorError, Parameters};
+use std::collections::HashMap;
+
+/// Which layers see a trigger when several have transitions waiting on it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TriggerPolicy {
+    /// Every layer sees the trigger for the whole update, it is only reset
+    /// once all layers ran
+    #[default]
+    AllLayers,
+    /// The first layer, in layer order, whose transition fires on the trigger
+    /// consumes it, later layers see it unset
+    FirstLayer,
+}
+
+impl AnimatorController {
+    /// Choose which layers see a trigger, see [`TriggerPolicy`]. Routes set
+    /// with `route_trigger` take precedence.
+    #[inline]
+    pub fn set_trigger_policy(&mut self, trigger_policy: TriggerPolicy) {
+        self.trigger_policy = trigger_policy;
+    }
+
+    #[inline]
+    pub fn trigger_policy(&self) -> TriggerPolicy {
+        self.trigger_policy
+    }
+
+    /// Only let `layer` see a trigger, every other layer sees it unset
+    /// ## Example
+    /// ```ignore
+    /// controller.route_trigger("attack", "Upper Body")?;
+    /// ```
+    pub fn route_trigger(&mut self, trigger: &str, layer: &str) -> Result<(), AnimatorError> {
+        if !self.layers.iter().any(|l| l.name == layer) {
+            return Err(AnimatorError::UnknownLayer(layer.to_string()));
+        }
+        self.trigger_routes.insert(trigger.to_string(), layer.to_string());
+        Ok(())
+    }
+
+    /// Let every layer see a routed trigger again
+    #[inline]
+    pub fn clear_trigger_route(&mut self, trigger: &str) {
+        self.trigger_routes.remove(trigger);
+    }
+
+    /// Layer a trigger is routed to, if any
+    #[inline]
+    pub fn trigger_route(&self, trigger: &str) -> Option<&str> {
+        self.trigger_routes.get(trigger).map(String::as_str)
+    }
+}
+
+/// Unset the triggers routed to other layers while `layer` updates, keeping
+/// them in `hidden` so they can be set again afterwards
+#[inline]
+pub(crate) fn hide_routed_triggers(
+    parameters: &mut Parameters,
+    routes: &HashMap<String, String>,
+    layer: &str,
+    hidden: &mut Vec<String>,
+) {
+    hidden.clear();
+    for (trigger, route) in routes {
+        if route != layer && parameters.get_trigger(trigger) {
+            parameters.reset_trigger(trigger);
+            hidden.push(trigger.clone());
+        }
+    }
+}
@@ -0,0 +1,75 @@
+use super::AnimatorController;
+use std::fmt::Write;
+
+/// How much a state was used while usage tracking was enabled, see
+/// [`AnimatorController::set_usage_tracking`]
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct StateUsage {
+    /// Times the state was entered, directly or through a transition
+    pub plays: u32,
+    /// Seconds the state was sampled for, including while blending in or out
+    pub time_sampled: f32,
+}
+
+/// Usage of a single state, as returned by [`AnimatorController::state_usage`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct StateUsageEntry {
+    pub layer: String,
+    pub state: String,
+    pub usage: StateUsage,
+}
+
+impl AnimatorController {
+    /// Count how often every state plays and how long it is sampled, to find
+    /// states and clips that are never used. Disabling clears the counts.
+    #[inline]
+    pub fn set_usage_tracking(&mut self, enabled: bool) {
+        for layer in &mut self.layers {
+            layer.set_usage_tracking(enabled);
+        }
+    }
+
+    /// Usage of every state of every tracked layer, sorted by layer then state
+    /// name. States that never played are included with zero usage.
+    pub fn state_usage(&self) -> Vec<StateUsageEntry> {
+        let mut entries: Vec<StateUsageEntry> = self
+            .layers
+            .iter()
+            .filter_map(|layer| Some((layer, layer.usage()?)))
+            .flat_map(|(layer, usage)| {
+                usage.iter().map(|(state, usage)| StateUsageEntry {
+                    layer: layer.name.clone(),
+                    state: state.clone(),
+                    usage: *usage,
+                })
+            })
+            .collect();
+        entries.sort_by(|a, b| (&a.layer, &a.state).cmp(&(&b.layer, &b.state)));
+        entries
+    }
+
+    /// `state_usage()` as CSV with a `layer,state,plays,time_sampled` header
+    pub fn state_usage_csv(&self) -> String {
+        let mut csv = String::from("layer,state,plays,time_sampled\n");
+        for entry in self.state_usage() {
+            let _ = writeln!(
+                csv,
+                "{},{},{},{}",
+                csv_field(&entry.layer),
+                csv_field(&entry.state),
+                entry.usage.plays,
+                entry.usage.time_sampled
+            );
+        }
+        csv
+    }
+}
+
+/// Quote a CSV field if it needs it
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
@@ -0,0 +1,242 @@
+use super::{AnimationLayer, AnimationState, AnimatorController, TransitionCondition};
+use std::collections::{BTreeSet, VecDeque};
+use std::fmt;
+
+/// A likely mistake in an animator graph, found by [`AnimatorController::validate`]
+#[non_exhaustive]
+#[derive(Debug, Clone, PartialEq)]
+pub enum GraphIssue {
+    /// No chain of transitions leads from the layer's default state to the
+    /// state, it only plays if forced from code
+    UnreachableState { layer: String, state: String },
+    /// A transition leads to a state the layer doesn't have, it never fires
+    MissingTransitionTarget {
+        layer: String,
+        from: String,
+        to: String,
+    },
+    /// A condition, blend tree, speed or duration scale reads a parameter
+    /// that was never set
+    UndeclaredParameter { parameter: String },
+    /// A condition references a predicate that was never defined
+    UnknownPredicate { predicate: String },
+    /// The thresholds of a 1D blend tree, or of one nested in it, don't rise
+    /// in motion order
+    UnsortedThresholds { layer: String, state: String },
+}
+
+impl fmt::Display for GraphIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GraphIssue::UnreachableState { layer, state } => {
+                write!(f, "State {layer}/{state} can't be reached from the default state")
+            }
+            GraphIssue::MissingTransitionTarget { layer, from, to } => {
+                write!(f, "Transition {layer}/{from} -> {to} leads to a missing state")
+            }
+            GraphIssue::UndeclaredParameter { parameter } => {
+                write!(f, "Parameter '{parameter}' is read but never set")
+            }
+            GraphIssue::UnknownPredicate { predicate } => {
+                write!(f, "Predicate '{predicate}' is referenced but never defined")
+            }
+            GraphIssue::UnsortedThresholds { layer, state } => {
+                write!(f, "Blend tree {layer}/{state} has thresholds out of order")
+            }
+        }
+    }
+}
+
+impl AnimatorController {
+    /// Check the graph for likely mistakes: unreachable states, transitions to
+    /// missing states, conditions on undeclared parameters or undefined
+    /// predicates and 1D blend trees with thresholds out of order. Issues are
+    /// reported per layer in name order, an empty list means none were found.
+    /// ## Example
+    /// ```ignore
+    /// for issue in controller.validate() {
+    ///     warn!("{issue}");
+    /// }
+    /// ```
+    pub fn validate(&self) -> Vec<GraphIssue> {
+        let mut issues = Vec::new();
+        for layer in &self.layers {
+            validate_layer(layer, &mut issues);
+        }
+
+        let mut predicates = BTreeSet::new();
+        let conditions = self
+            .layers
+            .iter()
+            .flat_map(|layer| layer.state_names().flat_map(|state| layer.transitions(state)))
+            .flat_map(|transition| &transition.conditions)
+            .chain(self.layers.iter().flat_map(|layer| layer.activation()))
+            .chain(self.predicates().flat_map(|(_, conditions)| conditions));
+        for condition in conditions {
+            if let TransitionCondition::Predicate(name) = condition
+                && self.predicate(name).is_none()
+            {
+                predicates.insert(name.clone());
+            }
+        }
+        issues.extend(
+            predicates
+                .into_iter()
+                .map(|predicate| GraphIssue::UnknownPredicate { predicate }),
+        );
+
+        issues.extend(
+            self.missing_parameters()
+                .into_iter()
+                .map(|missing| GraphIssue::UndeclaredParameter {
+                    parameter: missing.name,
+                }),
+        );
+        issues
+    }
+}
+
+fn validate_layer(layer: &AnimationLayer, issues: &mut Vec<GraphIssue>) {
+    let states: BTreeSet<&str> = layer.state_names().collect();
+
+    // Walk every transition from the default state, ignoring conditions
+    let mut reached = BTreeSet::new();
+    let mut queue = VecDeque::from([layer.default_state()]);
+    while let Some(state) = queue.pop_front() {
+        if !states.contains(state) || !reached.insert(state) {
+            continue;
+        }
+        queue.extend(layer.transitions(state).iter().map(|t| t.to_state.as_str()));
+    }
+
+    for &state in &states {
+        // Clips crossfaded to from code are never meant to be reached
+        if !reached.contains(state) && !layer.is_transient_state(state) {
+            issues.push(GraphIssue::UnreachableState {
+                layer: layer.name.clone(),
+                state: state.to_string(),
+            });
+        }
+        for transition in layer.transitions(state) {
+            if !states.contains(transition.to_state.as_str()) {
+                issues.push(GraphIssue::MissingTransitionTarget {
+                    layer: layer.name.clone(),
+                    from: state.to_string(),
+                    to: transition.to_state.clone(),
+                });
+            }
+        }
+        if let Some(AnimationState::Blend(blend)) = layer.state(state)
+            && !blend.thresholds_sorted()
+        {
+            issues.push(GraphIssue::UnsortedThresholds {
+                layer: layer.name.clone(),
+                state: state.to_string(),
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        BlendMotionState, BlendState, BlendTree, BlendTreeType, CompareType, MotionData,
+        MotionThreshold, Parameters, Transition, test_layer, test_rig, test_state,
+    };
+    use bevy::prelude::default;
+    use std::sync::{Arc, RwLock};
+
+    fn transition(to_state: &str, conditions: Vec<TransitionCondition>) -> Transition {
+        Transition {
+            to_state: to_state.to_string(),
+            conditions,
+            ..default()
+        }
+    }
+
+    /// `Idle -> Walk -> Lean`, with `Hidden` never reached and `Idle` also
+    /// leading to a missing state. `Lean` is a 1D tree with its thresholds
+    /// in `thresholds` order.
+    fn controller(thresholds: [f32; 2], parameters: Parameters) -> AnimatorController {
+        let (skeleton, clip) = test_rig();
+        let motions = thresholds
+            .into_iter()
+            .map(|threshold| MotionData {
+                motion: BlendMotionState::Animation(Arc::new(RwLock::new(test_state(
+                    &skeleton, &clip,
+                )))),
+                threshold: MotionThreshold::Simple1D(threshold),
+            })
+            .collect();
+        let tree = BlendTree::new(&skeleton, BlendTreeType::Simple1D("lean".to_string()), motions);
+
+        let mut layer = test_layer(&skeleton, &clip, &["Idle", "Walk", "Hidden"]);
+        layer.add_state("Lean".to_string(), BlendState::new(tree).into());
+        layer.add_transition(
+            "Idle".to_string(),
+            transition(
+                "Walk",
+                vec![TransitionCondition::Float("speed".to_string(), 0.1, CompareType::Greater)],
+            ),
+        );
+        layer.add_transition("Idle".to_string(), transition("Run", Vec::new()));
+        layer.add_transition(
+            "Walk".to_string(),
+            transition("Lean", vec![TransitionCondition::Predicate("grounded".to_string())]),
+        );
+        AnimatorController::new(skeleton.clone(), vec![layer], parameters).unwrap()
+    }
+
+    #[test]
+    fn reports_every_kind_of_issue() {
+        let issues = controller([1.0, 0.0], Parameters::new()).validate();
+        let layer = "Base".to_string();
+        for expected in [
+            GraphIssue::UnreachableState {
+                layer: layer.clone(),
+                state: "Hidden".to_string(),
+            },
+            GraphIssue::MissingTransitionTarget {
+                layer: layer.clone(),
+                from: "Idle".to_string(),
+                to: "Run".to_string(),
+            },
+            GraphIssue::UnsortedThresholds {
+                layer: layer.clone(),
+                state: "Lean".to_string(),
+            },
+            GraphIssue::UnknownPredicate {
+                predicate: "grounded".to_string(),
+            },
+            GraphIssue::UndeclaredParameter {
+                parameter: "speed".to_string(),
+            },
+            GraphIssue::UndeclaredParameter {
+                parameter: "lean".to_string(),
+            },
+        ] {
+            assert!(issues.contains(&expected), "{expected} missing from {issues:?}");
+        }
+        assert_eq!(issues.len(), 6, "{issues:?}");
+    }
+
+    #[test]
+    fn fixed_graph_has_only_structural_issues() {
+        let mut parameters = Parameters::new();
+        parameters.set_float("speed", 0.0);
+        parameters.set_float("lean", 0.0);
+        let controller = controller([0.0, 1.0], parameters)
+            .with_predicate("grounded", vec![TransitionCondition::Bool("air".to_string(), false)]);
+        let issues = controller.validate();
+        // The predicate reads a parameter of its own
+        assert!(issues.contains(&GraphIssue::UndeclaredParameter {
+            parameter: "air".to_string()
+        }));
+        assert!(!issues.iter().any(|issue| matches!(
+            issue,
+            GraphIssue::UnsortedThresholds { .. } | GraphIssue::UnknownPredicate { .. }
+        )));
+        assert_eq!(issues.len(), 3, "{issues:?}");
+    }
+}